@@ -24,6 +24,14 @@ pub trait Asset: Send + Sync + 'static {
 
     /// The `Data` type the asset can be created from.
     type Data: Send + Sync + 'static;
+
+    /// Returns the approximate memory used by the asset in bytes, including memory on the GPU.
+    ///
+    /// This is used for the `AssetUsage` statistics and the memory budget of `Cache`. The
+    /// default implementation returns `0`, meaning the size is unknown.
+    fn byte_size(&self) -> usize {
+        0
+    }
 }
 
 /// Defines a way to process asset's data into the asset. This allows
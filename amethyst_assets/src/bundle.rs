@@ -1,16 +1,19 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
-use amethyst_core::ecs::{DispatcherBuilder, Resources, SystemBundle, World};
-use amethyst_error::Error;
-use atelier_assets::daemon::{
-    default_importer_contexts, default_importers, AssetDaemon, ImporterMap,
+use amethyst_core::{
+    ecs::{DispatcherBuilder, Resources, SystemBundle, World},
+    ArcThreadPool,
 };
-use log::info;
+use amethyst_error::{format_err, Error, ResultExt};
+use atelier_assets::loader::{io::LoaderIO, RpcIO};
+use rayon::ThreadPoolBuilder;
 
 use crate::{
-    prefab::{prefab_spawning_tick, ComponentRegistryBuilder, PrefabImporter},
-    simple_importer::get_source_importers,
-    DefaultLoader, Loader,
+    daemon::{AssetDaemonConfig, AssetDaemonHandle, DEFAULT_DAEMON_ADDRESS},
+    loading::LoadDependencies,
+    prefab::{prefab_spawning_tick, ComponentRegistryBuilder},
+    source::SourceIO,
+    AssetSources, AssetUsage, DefaultLoader, Loader, PackfileIO, ProcessingBudget,
 };
 
 fn asset_loading_tick(_: &mut World, resources: &mut Resources) {
@@ -20,36 +23,35 @@ fn asset_loading_tick(_: &mut World, resources: &mut Resources) {
     loader
         .process(resources)
         .expect("Error in Loader processing");
+    if let Some(mut usage) = resources.get_mut::<AssetUsage>() {
+        loader.update_usage(resources, &mut usage);
+    }
+    loader.flush_events(resources);
 }
 
-/// starts the asset thread with atelier_daemon
+/// Starts the asset daemon with the default configuration, watching the given directories.
+///
+/// The daemon keeps running until the process exits, use
+/// [`AssetDaemonConfig`](struct.AssetDaemonConfig.html) to control its address, database and
+/// lifetime.
 pub fn start_asset_daemon(asset_dirs: Vec<PathBuf>) {
-    let db_path = ".assets_db";
-    let address = "127.0.0.1:9999";
-
-    let mut importer_map = ImporterMap::default();
-    let mut importers = default_importers();
-
-    importers.push(("prefab", Box::new(PrefabImporter::default())));
-
-    importers.extend(get_source_importers());
-
-    for (ext, importer) in importers {
-        info!("Adding importer for ext {}", ext);
-        importer_map.insert(ext, importer);
-    }
-
-    AssetDaemon {
-        db_dir: PathBuf::from(db_path),
-        address: address.parse().unwrap(),
-        importers: importer_map,
-        importer_contexts: default_importer_contexts(),
-        asset_dirs,
-    }
-    .run();
+    AssetDaemonConfig::default()
+        .with_asset_dirs(asset_dirs)
+        .start()
+        .detach();
 }
 
 /// Bundle that initializes Loader as well as related processing systems and resources
+///
+/// The loader reads assets from the `PackfileIO` resource if there is one. Otherwise it connects
+/// to the daemon of the `AssetDaemonHandle` resource if there is one, and to the default daemon
+/// address otherwise. The files of the `AssetSources` resource, if any, are served in addition.
+///
+/// The memory usage of the asset storages is kept up to date in the `AssetUsage` resource.
+/// The `AssetEvent`s of each asset type are written to its `EventChannel<AssetEvent<A>>`.
+///
+/// Asset data is processed on the `ArcThreadPool` resource, which is created if it does not
+/// exist, within the limits of the `ProcessingBudget` resource.
 pub struct LoaderBundle;
 
 impl SystemBundle for LoaderBundle {
@@ -63,10 +65,35 @@ impl SystemBundle for LoaderBundle {
             .auto_register_components()
             .build();
         resources.insert(component_registry);
-        let mut loader = DefaultLoader::default();
+        let dependencies = LoadDependencies::default();
+        let io: Box<dyn LoaderIO> = if let Some(mut pack) = resources.remove::<PackfileIO>() {
+            pack.set_dependencies(dependencies.clone());
+            Box::new(pack)
+        } else {
+            let address = match resources.get::<AssetDaemonHandle>() {
+                Some(daemon) => daemon.address(),
+                None => DEFAULT_DAEMON_ADDRESS.parse().unwrap(),
+            };
+            Box::new(RpcIO::new(address.to_string()).with_context(|_| {
+                format_err!("Could not connect to the asset daemon at {}", address)
+            })?)
+        };
+        let io: Box<dyn LoaderIO> = match resources.remove::<AssetSources>() {
+            Some(sources) => Box::new(SourceIO::new(io, sources, dependencies.clone())),
+            None => io,
+        };
+        let mut loader = DefaultLoader::with_dependencies(io, dependencies);
         loader.init_world(resources);
         loader.init_dispatcher(builder);
         resources.insert(loader);
+        resources.insert(AssetUsage::default());
+        resources.get_or_insert_with(ProcessingBudget::default);
+        if !resources.contains::<ArcThreadPool>() {
+            let pool = ThreadPoolBuilder::new()
+                .build()
+                .with_context(|_| format_err!("Could not create the asset processing pool"))?;
+            resources.insert(Arc::new(pool));
+        }
         builder.add_thread_local_fn(asset_loading_tick);
         builder.add_thread_local_fn(prefab_spawning_tick);
         Ok(())
@@ -1,17 +1,45 @@
-use std::{borrow::Borrow, hash::Hash, marker::PhantomData};
+use std::{
+    borrow::Borrow,
+    hash::Hash,
+    marker::PhantomData,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
+use amethyst_core::{
+    dispatcher::System,
+    ecs::{systems::ParallelRunnable, SystemBuilder},
+};
 use atelier_assets::loader::{
     crossbeam_channel::Sender,
     handle::{AssetHandle, Handle, RefOp, WeakHandle},
 };
+use derivative::Derivative;
 use fnv::FnvHashMap;
 
+use crate::{asset::Asset, storage::AssetStorage};
+
+/// Handle kept by a cache with a memory budget, with the time it was last used.
+struct Resident<A> {
+    handle: Handle<A>,
+    last_used: AtomicU64,
+}
+
 /// A simple cache for asset handles of type `A`.
 /// This stores `WeakHandle`, so it doesn't keep the assets alive.
+///
+/// With a memory budget set by `with_budget`, the cache also keeps the inserted assets alive
+/// while their total size fits in the budget. `evict` then releases the least recently used
+/// assets until the budget is met again, and `CacheEvictionSystem` does so every frame. Evicted
+/// assets are unloaded once no other handle to them exists. `get` does not load them again, so
+/// check the handle it returns with `AssetStorage::contains` and load evicted assets with the
+/// `Loader` before inserting them again.
 // #[derive(Derivative)]
 // #[derivative(Default(bound = ""))]
 pub struct Cache<A> {
     map: FnvHashMap<String, WeakHandle>,
+    resident: FnvHashMap<String, Resident<A>>,
+    budget: Option<usize>,
+    clock: AtomicU64,
     tx: Sender<RefOp>,
     marker: PhantomData<A>,
 }
@@ -24,14 +52,40 @@ where
     pub fn new(tx: Sender<RefOp>) -> Self {
         Self {
             map: Default::default(),
+            resident: Default::default(),
+            budget: None,
+            clock: AtomicU64::new(0),
             tx,
             marker: Default::default(),
         }
     }
 
+    /// Keeps the cached assets loaded while their total size, as reported by
+    /// `Asset::byte_size`, is at most `bytes`.
+    pub fn with_budget(mut self, bytes: usize) -> Self {
+        self.budget = Some(bytes);
+        self
+    }
+
+    /// Returns the memory budget of the cache, if any.
+    pub fn budget(&self) -> Option<usize> {
+        self.budget
+    }
+
     /// Inserts an asset with a given `key` and returns the old value (if any).
     pub fn insert<K: Into<String>>(&mut self, key: K, asset: &Handle<A>) -> Option<WeakHandle> {
-        self.map.insert(key.into(), asset.downgrade())
+        let key = key.into();
+        if self.budget.is_some() {
+            let last_used = AtomicU64::new(self.tick());
+            self.resident.insert(
+                key.clone(),
+                Resident {
+                    handle: asset.clone(),
+                    last_used,
+                },
+            );
+        }
+        self.map.insert(key, asset.downgrade())
     }
 
     /// Retrieves an asset handle using a given `key`.
@@ -40,13 +94,130 @@ where
         K: ?Sized + Hash + Eq,
         String: Borrow<K>,
     {
+        if let Some(resident) = self.resident.get(key) {
+            resident.last_used.store(self.tick(), Ordering::Relaxed);
+        }
         self.map.get(key).map(|weak_handle: &WeakHandle| {
             Handle::<A>::new(self.tx.clone(), weak_handle.load_handle())
         })
     }
 
+    /// Returns the total size in bytes of the loaded assets kept alive by the cache.
+    pub fn resident_bytes(&self, storage: &AssetStorage<A>) -> usize {
+        self.resident
+            .values()
+            .filter_map(|resident| storage.get_byte_size(&resident.handle))
+            .sum()
+    }
+
+    /// Stops keeping the least recently used assets alive until the size of the remaining ones
+    /// fits in the budget, and returns the number of evicted assets.
+    ///
+    /// Assets that have not finished loading are not counted. Does nothing without a budget.
+    pub fn evict(&mut self, storage: &AssetStorage<A>) -> usize {
+        let budget = match self.budget {
+            Some(budget) => budget,
+            None => return 0,
+        };
+        let entries = self
+            .resident
+            .iter()
+            .map(|(key, resident)| {
+                (
+                    key.as_str(),
+                    resident.last_used.load(Ordering::Relaxed),
+                    storage.get_byte_size(&resident.handle).unwrap_or(0),
+                )
+            })
+            .collect();
+        let evicted: Vec<String> = least_recently_used_over_budget(entries, budget)
+            .into_iter()
+            .map(str::to_owned)
+            .collect();
+        for key in &evicted {
+            self.resident.remove(key);
+        }
+        evicted.len()
+    }
+
     /// Clears all values.
     pub fn clear_all(&mut self) {
         self.map.clear();
+        self.resident.clear();
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+/// Returns the keys of the least recently used entries to remove so that the size of the
+/// remaining entries is at most `budget`.
+fn least_recently_used_over_budget(
+    mut entries: Vec<(&str, u64, usize)>,
+    budget: usize,
+) -> Vec<&str> {
+    let mut total: usize = entries.iter().map(|(_, _, size)| size).sum();
+    entries.sort_by_key(|(_, last_used, _)| *last_used);
+    entries
+        .into_iter()
+        .take_while(|(_, _, size)| {
+            let over = total > budget;
+            total -= size;
+            over
+        })
+        .map(|(key, _, _)| key)
+        .collect()
+}
+
+/// System evicting the least recently used assets of the `Cache<A>` resource when it exceeds
+/// its memory budget.
+///
+/// The `LoaderBundle` does not know the asset types that are cached, so add the system for each
+/// `Cache<A>` resource with a budget:
+///
+/// ```rust,ignore
+/// resources.insert(Cache::<Texture>::new(ref_sender).with_budget(256 * 1024 * 1024));
+/// dispatcher_builder.add_system(Box::new(CacheEvictionSystem::<Texture>::default()));
+/// ```
+#[derive(Derivative)]
+#[derivative(Default(bound = ""))]
+pub struct CacheEvictionSystem<A> {
+    _marker: PhantomData<A>,
+}
+
+impl<A> System<'_> for CacheEvictionSystem<A>
+where
+    A: Asset + Clone,
+{
+    fn build(&mut self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new(format!("Cache Eviction: {}", A::name()))
+                .write_resource::<Cache<A>>()
+                .read_resource::<AssetStorage<A>>()
+                .build(|_, _, (cache, storage), _| {
+                    cache.evict(storage);
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::least_recently_used_over_budget;
+
+    #[test]
+    fn evicts_least_recently_used_until_within_budget() {
+        let entries = vec![("new", 3, 100), ("old", 1, 100), ("mid", 2, 100)];
+        assert_eq!(
+            vec!["old", "mid"],
+            least_recently_used_over_budget(entries, 150)
+        );
+    }
+
+    #[test]
+    fn evicts_nothing_within_budget() {
+        let entries = vec![("a", 0, 100), ("b", 1, 50)];
+        assert!(least_recently_used_over_budget(entries, 150).is_empty());
     }
 }
@@ -0,0 +1,234 @@
+use std::{
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    thread::JoinHandle,
+};
+
+use atelier_assets::{
+    daemon::{default_importer_contexts, default_importers, AssetDaemon, ImporterMap},
+    importer::BoxedImporter,
+};
+use futures_channel::oneshot;
+use log::{info, warn};
+
+use crate::{prefab::PrefabImporter, simple_importer::get_source_importers};
+
+/// Address the asset daemon listens on, unless configured otherwise.
+pub const DEFAULT_DAEMON_ADDRESS: &str = "127.0.0.1:9999";
+
+/// Directory of the asset metadata database, unless configured otherwise.
+pub const DEFAULT_DAEMON_DB_DIR: &str = ".assets_db";
+
+/// Configuration of the asset daemon, which watches the asset directories, imports the source
+/// files and serves the imported assets to the loader.
+///
+/// Running several applications on the same machine, for example a game and its tests, requires
+/// giving each of them its own address and database directory.
+///
+/// # Examples
+///
+/// ```no_run
+/// use amethyst_assets::AssetDaemonConfig;
+///
+/// let daemon = AssetDaemonConfig::default()
+///     .with_address("127.0.0.1:9998".parse().unwrap())
+///     .with_db_dir(std::env::temp_dir().join("my_game_assets_db"))
+///     .with_asset_dir("assets")
+///     .start();
+/// ```
+pub struct AssetDaemonConfig {
+    address: SocketAddr,
+    db_dir: PathBuf,
+    asset_dirs: Vec<PathBuf>,
+    importers: Vec<(&'static str, Box<dyn BoxedImporter>)>,
+}
+
+impl fmt::Debug for AssetDaemonConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AssetDaemonConfig")
+            .field("address", &self.address)
+            .field("db_dir", &self.db_dir)
+            .field("asset_dirs", &self.asset_dirs)
+            .field(
+                "importers",
+                &self
+                    .importers
+                    .iter()
+                    .map(|(ext, _)| ext)
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl Default for AssetDaemonConfig {
+    fn default() -> Self {
+        AssetDaemonConfig {
+            address: DEFAULT_DAEMON_ADDRESS.parse().unwrap(),
+            db_dir: PathBuf::from(DEFAULT_DAEMON_DB_DIR),
+            asset_dirs: Vec::new(),
+            importers: Vec::new(),
+        }
+    }
+}
+
+impl AssetDaemonConfig {
+    /// Sets the address the daemon listens on, and the loader connects to.
+    pub fn with_address(mut self, address: SocketAddr) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// Sets the directory of the asset metadata database.
+    pub fn with_db_dir<P: Into<PathBuf>>(mut self, db_dir: P) -> Self {
+        self.db_dir = db_dir.into();
+        self
+    }
+
+    /// Adds a directory to watch for source files.
+    pub fn with_asset_dir<P: Into<PathBuf>>(mut self, asset_dir: P) -> Self {
+        self.asset_dirs.push(asset_dir.into());
+        self
+    }
+
+    /// Adds several directories to watch for source files.
+    pub fn with_asset_dirs<I>(mut self, asset_dirs: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<PathBuf>,
+    {
+        self.asset_dirs
+            .extend(asset_dirs.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds an importer for source files with the given extension, in addition to the default
+    /// importers and the ones registered with `register_importer!`.
+    ///
+    /// An importer added here replaces any other importer for the same extension.
+    pub fn with_importer(mut self, ext: &'static str, importer: Box<dyn BoxedImporter>) -> Self {
+        self.importers.push((ext, importer));
+        self
+    }
+
+    /// Returns the address the daemon listens on.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Returns the directory of the asset metadata database.
+    pub fn db_dir(&self) -> &Path {
+        &self.db_dir
+    }
+
+    /// Returns the directories watched for source files.
+    pub fn asset_dirs(&self) -> &[PathBuf] {
+        &self.asset_dirs
+    }
+
+    pub(crate) fn into_asset_dirs_and_importers(
+        self,
+    ) -> (Vec<PathBuf>, Vec<(&'static str, Box<dyn BoxedImporter>)>) {
+        (self.asset_dirs, self.importers)
+    }
+
+    /// Starts the daemon on its own thread.
+    ///
+    /// The daemon is shut down when the returned handle is dropped.
+    pub fn start(self) -> AssetDaemonHandle {
+        let mut importer_map = ImporterMap::default();
+        for (ext, importer) in all_importers(self.importers) {
+            info!("Adding importer for ext {}", ext);
+            importer_map.insert(ext, importer);
+        }
+
+        info!(
+            "Starting asset daemon on {} with database {:?}",
+            self.address, self.db_dir
+        );
+        let (thread, shutdown) = AssetDaemon {
+            db_dir: self.db_dir,
+            address: self.address,
+            importers: importer_map,
+            importer_contexts: default_importer_contexts(),
+            asset_dirs: self.asset_dirs,
+        }
+        .run();
+
+        AssetDaemonHandle {
+            address: self.address,
+            thread: Some(thread),
+            shutdown: Some(shutdown),
+        }
+    }
+}
+
+/// Returns the importers of the daemon, with the given importers added last so that they take
+/// precedence.
+pub(crate) fn all_importers(
+    extra: Vec<(&'static str, Box<dyn BoxedImporter>)>,
+) -> Vec<(&'static str, Box<dyn BoxedImporter>)> {
+    let mut importers = default_importers();
+    importers.push(("prefab", Box::new(PrefabImporter::default())));
+    importers.extend(get_source_importers());
+    importers.extend(extra);
+    importers
+}
+
+/// Handle to a running asset daemon, returned by
+/// [`AssetDaemonConfig::start`](struct.AssetDaemonConfig.html#method.start).
+///
+/// Dropping the handle shuts the daemon down and waits for its thread to finish.
+pub struct AssetDaemonHandle {
+    address: SocketAddr,
+    thread: Option<JoinHandle<()>>,
+    shutdown: Option<oneshot::Sender<bool>>,
+}
+
+impl fmt::Debug for AssetDaemonHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AssetDaemonHandle")
+            .field("address", &self.address)
+            .field("running", &self.thread.is_some())
+            .finish()
+    }
+}
+
+impl AssetDaemonHandle {
+    /// Returns the address the daemon listens on.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Shuts the daemon down and waits for its thread to finish.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    /// Lets the daemon keep running until the process exits.
+    pub fn detach(mut self) {
+        self.thread.take();
+        if let Some(shutdown) = self.shutdown.take() {
+            std::mem::forget(shutdown);
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            // An error means the daemon already stopped.
+            let _ = shutdown.send(true);
+        }
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                warn!("Asset daemon thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for AssetDaemonHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
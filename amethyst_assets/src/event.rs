@@ -0,0 +1,91 @@
+use std::marker::PhantomData;
+
+use atelier_assets::loader::{handle::AssetHandle, LoadHandle};
+
+use crate::storage::AssetStorage;
+
+/// What happened to an asset, see `AssetEvent`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AssetEventKind {
+    /// The asset was loaded for the first time.
+    Loaded {
+        /// Version of the loaded asset.
+        version: u32,
+    },
+    /// A new version of the asset replaced the previous one, such as after its source file was
+    /// modified.
+    Reloaded {
+        /// Version of the loaded asset.
+        version: u32,
+    },
+    /// The asset was unloaded, because it is no longer referenced.
+    Unloaded,
+    /// The asset could not be loaded.
+    Failed {
+        /// Description of the error.
+        error: String,
+    },
+}
+
+/// Event written to the `EventChannel<AssetEvent<A>>` resource when an asset of type `A` is
+/// loaded, reloaded, unloaded or fails to load.
+///
+/// The events are written by the `LoaderBundle` once per frame. Systems that derive data from
+/// assets can read them to only rebuild that data when the assets change.
+///
+/// # Examples
+///
+/// ```
+/// use amethyst_assets::{AssetEvent, AssetEventKind, AssetStorage, Handle};
+/// use amethyst_core::shrev::{EventChannel, ReaderId};
+/// # struct Mesh;
+///
+/// fn meshes_to_rebuild(
+///     events: &EventChannel<AssetEvent<Mesh>>,
+///     reader: &mut ReaderId<AssetEvent<Mesh>>,
+///     storage: &AssetStorage<Mesh>,
+///     handles: &[Handle<Mesh>],
+/// ) -> Vec<Handle<Mesh>> {
+///     let mut changed = Vec::new();
+///     for event in events.read(reader) {
+///         if let AssetEventKind::Reloaded { .. } = event.kind {
+///             changed.extend(handles.iter().filter(|h| event.is_for(*h, storage)).cloned());
+///         }
+///     }
+///     changed
+/// }
+/// ```
+pub struct AssetEvent<A> {
+    /// Load handle of the asset.
+    pub handle: LoadHandle,
+    /// What happened to the asset.
+    pub kind: AssetEventKind,
+    marker: PhantomData<fn() -> A>,
+}
+
+impl<A> AssetEvent<A> {
+    pub(crate) fn new(handle: LoadHandle, kind: AssetEventKind) -> Self {
+        AssetEvent {
+            handle,
+            kind,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns `true` if the event is about the asset of the given handle.
+    ///
+    /// The storage resolves the handles of assets loaded by path.
+    pub fn is_for<T: AssetHandle>(&self, handle: &T, storage: &AssetStorage<A>) -> bool {
+        storage.resolve(handle.load_handle()) == Some(self.handle)
+    }
+}
+
+impl<A> std::fmt::Debug for AssetEvent<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AssetEvent")
+            .field("asset_type", &std::any::type_name::<A>())
+            .field("handle", &self.handle)
+            .field("kind", &self.kind)
+            .finish()
+    }
+}
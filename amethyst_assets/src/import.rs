@@ -0,0 +1,299 @@
+//! Importing source files outside of the asset daemon.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt,
+    fs::{self, File},
+    hash::Hasher,
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use amethyst_error::{format_err, Error, ResultExt};
+use atelier_assets::{
+    core::{
+        ArtifactId, ArtifactMetadata, AssetMetadata, AssetRef, AssetTypeId, AssetUuid,
+        CompressionType,
+    },
+    daemon::default_importer_contexts,
+    importer::{BoxedImporter, ImportOp, ImporterContext, ImporterContextHandle},
+};
+use fnv::FnvHasher;
+use type_uuid::TypeUuidDynamic;
+
+use crate::daemon::all_importers;
+
+/// Reads the files next to a source file, given their path relative to the source file.
+pub(crate) type ReadFile = Box<dyn Fn(&str) -> io::Result<Vec<u8>>>;
+
+thread_local! {
+    /// Reads the files next to the source file imported on this thread.
+    static IMPORT_FILES: RefCell<Option<ReadFile>> = RefCell::new(None);
+}
+
+/// Reads a file next to the source file being imported, such as an external buffer of a glTF
+/// file, given its path relative to the source file.
+///
+/// Returns `None` when the importer does not run for `AssetSources`, an `AssetPacker` or an
+/// `AssetValidator`, as the asset daemon does not tell importers where the source file is.
+pub fn read_import_file(path: &str) -> Option<io::Result<Vec<u8>>> {
+    IMPORT_FILES.with(|files| files.borrow().as_ref().map(|read| read(path)))
+}
+
+/// An asset imported from a source file, together with its serialized artifact.
+pub(crate) struct ImportedArtifact {
+    pub(crate) metadata: AssetMetadata,
+    pub(crate) data: Vec<u8>,
+}
+
+/// The importers of the asset daemon, keyed by file extension.
+pub(crate) struct ImporterSet {
+    importers: HashMap<&'static str, Box<dyn BoxedImporter>>,
+    contexts: Vec<Box<dyn ImporterContext>>,
+}
+
+impl fmt::Debug for ImporterSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.importers.keys()).finish()
+    }
+}
+
+impl ImporterSet {
+    /// Creates the importer set of the daemon, with the given importers taking precedence.
+    pub(crate) fn new(extra: Vec<(&'static str, Box<dyn BoxedImporter>)>) -> Self {
+        ImporterSet {
+            importers: all_importers(extra).into_iter().collect(),
+            contexts: default_importer_contexts(),
+        }
+    }
+
+    /// Returns the importer for the extension of the given path, if there is one.
+    ///
+    /// `.meta` files never have an importer.
+    pub(crate) fn importer_for(&self, path: &str) -> Option<&dyn BoxedImporter> {
+        let ext = Path::new(path).extension()?.to_str()?.to_lowercase();
+        if ext == "meta" {
+            return None;
+        }
+        self.importers.get(ext.as_str()).map(|importer| &**importer)
+    }
+
+    /// Imports the source file at `file`, with the `.meta` file next to it if there is one.
+    ///
+    /// Returns `None` if there is no importer for the file.
+    pub(crate) fn import_file(&self, file: &Path) -> Result<Option<Vec<ImportedArtifact>>, Error> {
+        let path = file.to_string_lossy();
+        if self.importer_for(&path).is_none() {
+            return Ok(None);
+        }
+
+        let mut meta_path = file.as_os_str().to_owned();
+        meta_path.push(".meta");
+        let meta = fs::read(&meta_path).ok();
+        let mut source = BufReader::new(
+            File::open(file).with_context(|_| format_err!("Failed to open {:?}", file))?,
+        );
+        let dir = file.parent().map(Path::to_path_buf).unwrap_or_default();
+        let files: ReadFile = Box::new(move |path| fs::read(dir.join(path)));
+        self.import(&path, &mut source, files, meta.as_deref())
+    }
+
+    /// Imports a source file.
+    ///
+    /// `meta` is the content of the `.meta` file the daemon wrote next to the source file, if any.
+    /// The importer options and state are restored from it, so that assets keep their UUIDs.
+    ///
+    /// Like in the daemon, the file is imported and its assets are serialized within the importer
+    /// contexts, so that importers can create handles with `make_handle`. The assets referenced by
+    /// the serialized handles are added to the load dependencies.
+    ///
+    /// The importer reads the files next to the source file with `files`, see `read_import_file`.
+    ///
+    /// Returns `None` if there is no importer for the file.
+    pub(crate) fn import(
+        &self,
+        path: &str,
+        source: &mut dyn Read,
+        files: ReadFile,
+        meta: Option<&[u8]>,
+    ) -> Result<Option<Vec<ImportedArtifact>>, Error> {
+        let importer = match self.importer_for(path) {
+            Some(importer) => importer,
+            None => return Ok(None),
+        };
+
+        let (options, state) = match meta {
+            Some(bytes) => {
+                let metadata = importer.deserialize_metadata(bytes).with_context(|_| {
+                    format_err!("Failed to read the import metadata of {:?}", path)
+                })?;
+                (metadata.importer_options, metadata.importer_state)
+            }
+            None => (importer.default_options(), importer.default_state()),
+        };
+
+        let _files = FilesScope::enter(files);
+        let mut scope = ContextScope::enter(&self.contexts);
+        let imported = importer
+            .import_boxed(&mut ImportOp::default(), source, options, state)
+            .with_context(|_| format_err!("Failed to import {:?}", path))?;
+
+        imported
+            .value
+            .assets
+            .into_iter()
+            .map(|asset| {
+                let (data, refs) =
+                    scope.serialize(asset.id, || bincode::serialize(&asset.asset_data));
+                let data = data?;
+                let mut load_deps = asset.load_deps;
+                for asset_ref in refs {
+                    if !load_deps.contains(&asset_ref) {
+                        load_deps.push(asset_ref);
+                    }
+                }
+                let length = data.len() as u64;
+                Ok(ImportedArtifact {
+                    metadata: AssetMetadata {
+                        id: asset.id,
+                        search_tags: asset.search_tags,
+                        build_pipeline: asset.build_pipeline,
+                        artifact: Some(ArtifactMetadata {
+                            id: artifact_id(&asset.id, &data),
+                            asset_id: asset.id,
+                            build_deps: asset.build_deps,
+                            load_deps,
+                            compression: CompressionType::None,
+                            compressed_size: None,
+                            uncompressed_size: Some(length),
+                            type_id: AssetTypeId(asset.asset_data.uuid()),
+                        }),
+                    },
+                    data,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()
+            .map(Some)
+    }
+}
+
+/// Handles of the importer contexts entered for the import of a source file, which are exited
+/// when the scope is dropped.
+struct ContextScope(Vec<Box<dyn ImporterContextHandle>>);
+
+impl ContextScope {
+    fn enter(contexts: &[Box<dyn ImporterContext>]) -> Self {
+        let handles = contexts
+            .iter()
+            .map(|context| context.handle())
+            .collect::<Vec<_>>();
+        for handle in &handles {
+            handle.enter();
+        }
+        ContextScope(handles)
+    }
+
+    /// Serializes the data of an asset with `serialize`, and returns the assets referenced by
+    /// the handles in the data.
+    fn serialize<T>(
+        &mut self,
+        asset: AssetUuid,
+        serialize: impl FnOnce() -> T,
+    ) -> (T, HashSet<AssetRef>) {
+        for handle in &mut self.0 {
+            handle.begin_serialize_asset(asset);
+        }
+        let result = serialize();
+        let mut refs = HashSet::new();
+        for handle in &mut self.0 {
+            refs.extend(handle.end_serialize_asset(asset));
+        }
+        (result, refs)
+    }
+}
+
+impl Drop for ContextScope {
+    fn drop(&mut self) {
+        for handle in self.0.iter().rev() {
+            handle.exit();
+        }
+    }
+}
+
+/// Makes the files next to a source file readable with `read_import_file` until it is dropped.
+struct FilesScope(Option<ReadFile>);
+
+impl FilesScope {
+    fn enter(files: ReadFile) -> Self {
+        FilesScope(IMPORT_FILES.with(|current| current.replace(Some(files))))
+    }
+}
+
+impl Drop for FilesScope {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        IMPORT_FILES.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Returns the id of the artifact of an asset, which only depends on the asset's UUID and data,
+/// so that it is the same with every build of the tools.
+pub(crate) fn artifact_id(asset: &AssetUuid, data: &[u8]) -> ArtifactId {
+    let mut hasher = FnvHasher::default();
+    hasher.write(&asset.0);
+    hasher.write(data);
+    ArtifactId(hasher.finish())
+}
+
+/// Returns the files in the directory and its subdirectories, sorted by path.
+pub(crate) fn source_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    collect_files(dir, &mut files)
+        .with_context(|_| format_err!("Failed to read asset directory {:?}", dir))?;
+    files.sort();
+    Ok(files)
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn artifact_ids_are_stable() {
+        // FNV-1a does not change between Rust versions, unlike `DefaultHasher`
+        assert_eq!(
+            ArtifactId(0x84be_c6e6_cec5_a235),
+            artifact_id(&AssetUuid([1; 16]), b"data")
+        );
+        assert_ne!(
+            artifact_id(&AssetUuid([1; 16]), b"data"),
+            artifact_id(&AssetUuid([2; 16]), b"data")
+        );
+    }
+
+    #[test]
+    fn import_files_are_readable_while_importing() {
+        assert!(read_import_file("buffer.bin").is_none());
+        {
+            let _files = FilesScope::enter(Box::new(|path| Ok(path.as_bytes().to_vec())));
+            assert_eq!(
+                b"buffer.bin".to_vec(),
+                read_import_file("buffer.bin").unwrap().unwrap()
+            );
+        }
+        assert!(read_import_file("buffer.bin").is_none());
+    }
+}
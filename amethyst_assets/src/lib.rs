@@ -18,20 +18,30 @@ pub use rayon::ThreadPool;
 mod asset;
 mod bundle;
 mod cache;
+mod daemon;
 /// asset loading specific errors
 pub mod error;
+mod event;
+mod import;
 #[cfg(feature = "json")]
 mod json;
 mod loader;
+mod loading;
+mod pack;
 /// helpers for registering prefab components
 pub mod prefab;
+mod priority;
 mod processor;
 mod progress;
 mod simple_importer;
 mod source;
 mod storage;
+mod usage;
+mod validate;
+mod variant;
 
 pub use atelier_assets::{
+    core::AssetRef,
     importer as atelier_importer,
     loader::{
         handle::{AssetHandle, GenericHandle, Handle, WeakHandle},
@@ -45,14 +55,28 @@ pub use {erased_serde, inventory, lazy_static};
 
 #[cfg(feature = "json")]
 pub use crate::json::JsonFormat;
+#[cfg(feature = "zip")]
+pub use crate::source::ZipSource;
 pub use crate::{
     asset::{Asset, Format, FormatValue, ProcessableAsset, SerializableFormat},
     bundle::{start_asset_daemon, LoaderBundle},
-    cache::Cache,
+    cache::{Cache, CacheEvictionSystem},
+    daemon::{AssetDaemonConfig, AssetDaemonHandle, DEFAULT_DAEMON_ADDRESS, DEFAULT_DAEMON_DB_DIR},
+    event::{AssetEvent, AssetEventKind},
+    import::read_import_file,
     loader::{create_asset_type, AssetUuid, DefaultLoader, LoadStatus, Loader},
-    processor::{AssetProcessorSystem, ProcessingQueue, ProcessingState},
-    progress::{Completion, Progress, ProgressCounter, Tracker},
-    simple_importer::{SimpleImporter, SourceFileImporter},
-    source::{Directory, Source},
+    loading::LoadingTracker,
+    pack::{AssetPacker, PackfileIO},
+    priority::LoadPriority,
+    processor::{AssetProcessorSystem, ProcessingBudget, ProcessingQueue, ProcessingState},
+    progress::{AssetErrorMeta, Completion, Progress, ProgressCounter, Tracker},
+    simple_importer::{
+        make_handle, make_handle_from_path, SimpleImporter, SimpleImporterOptions,
+        SourceFileImporter,
+    },
+    source::{AssetSources, Directory, InMemorySource, OverlaySource, Source, SOURCE_SEPARATOR},
     storage::AssetStorage,
+    usage::{AssetUsage, StorageUsage},
+    validate::{AssetValidator, ValidationIssue, ValidationReport},
+    variant::LOCALE_PLACEHOLDER,
 };
@@ -2,6 +2,7 @@ use std::{
     cell::RefCell,
     collections::HashMap,
     error::Error,
+    net::SocketAddr,
     ops::{Deref, DerefMut},
     sync::Arc,
 };
@@ -9,6 +10,7 @@ use std::{
 use amethyst_core::{
     dispatcher::System,
     ecs::{DispatcherBuilder, Resources},
+    shrev::EventChannel,
 };
 use amethyst_error::Error as AmethystError;
 use atelier_assets::loader as atelier_loader;
@@ -16,6 +18,7 @@ pub(crate) use atelier_loader::LoadHandle;
 use atelier_loader::{
     crossbeam_channel::{unbounded, Receiver, Sender, TryRecvError},
     handle::{AssetHandle, GenericHandle, Handle, RefOp, SerdeContext, WeakHandle},
+    io::LoaderIO,
     storage::{
         AssetLoadOp, AtomicHandleAllocator, DefaultIndirectionResolver, HandleAllocator,
         IndirectIdentifier, IndirectionTable, LoaderInfoProvider,
@@ -27,7 +30,17 @@ use log::debug;
 use serde::de::Deserialize;
 pub use type_uuid::TypeUuid;
 
-use crate::{processor::ProcessingQueue, progress::Progress, storage::AssetStorage, Asset};
+use crate::{
+    event::{AssetEvent, AssetEventKind},
+    loading::{LoadDependencies, RecordingInfoProvider},
+    priority::{LoadPriorities, LoadPriority, PrioritizedIO},
+    processor::ProcessingQueue,
+    progress::Progress,
+    storage::AssetStorage,
+    usage::{AssetUsage, StorageUsage},
+    variant::{path_identifier, AssetVariants, LOCALE_PLACEHOLDER},
+    Asset,
+};
 
 /// Manages asset loading and storage for an application.
 pub trait Loader: Send + Sync {
@@ -72,11 +85,71 @@ pub trait Loader: Send + Sync {
     ///
     /// This is useful when loading an asset whose Rust type is known.
     ///
+    /// # Parameters
+    ///
+    /// * `path`: Path of the source file, relative to the asset directory. The path can be
+    ///   prefixed with the name of a source of the `AssetSources` and `://` to load the file from
+    ///   that source, such as `"mods://sprites/logo.png"`. The `{lang}` placeholder in the path
+    ///   is replaced by the locale set with `set_locale`, see `LOCALE_PLACEHOLDER`.
+    ///
+    /// When the source file produces several assets, such as a glTF file, the asset of type `T`
+    /// is loaded.
+    ///
     /// # Type Parameters
     ///
     /// * `T`: Asset `TypeUuid`.
     fn load<T: TypeUuid>(&self, path: &str) -> Handle<T>;
 
+    /// Sets the locale that replaces the `{lang}` placeholder in the paths of loaded assets.
+    ///
+    /// The assets loaded with the placeholder are reloaded for the new locale, and their handles
+    /// keep referring to the previous variant until the new one is loaded. A path is looked up
+    /// with the full locale, such as `fr-CA`, then with its language, such as `fr`, and finally
+    /// without the placeholder, so `ui/title.{lang}.png` falls back to `ui/title.png`.
+    ///
+    /// # Parameters
+    ///
+    /// * `locale`: Language tag of the locale, or `None` to load the variants without locale.
+    ///
+    /// Loaders without localized variants ignore the locale.
+    fn set_locale(&self, _locale: Option<&str>) {}
+
+    /// Returns the locale set with `set_locale`, or `None` for loaders without localized
+    /// variants.
+    fn locale(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns an asset handle and loads the asset for the given path asynchronously, before
+    /// the pending assets with a lower priority.
+    ///
+    /// The load is cancelled if all handles to the asset are dropped before it is loaded.
+    ///
+    /// # Parameters
+    ///
+    /// * `path`: Path of the source file, see `load`.
+    /// * `priority`: Priority of the load, which can be changed with `set_priority`.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `T`: Asset `TypeUuid`.
+    fn load_with_priority<T: TypeUuid>(&self, path: &str, priority: LoadPriority) -> Handle<T> {
+        let handle = self.load(path);
+        self.set_priority(handle.load_handle(), priority);
+        handle
+    }
+
+    /// Changes the priority of the load of the asset with the given load handle, if it is still
+    /// pending.
+    ///
+    /// # Parameters
+    ///
+    /// * `handle`: `LoadHandle` of the asset.
+    /// * `priority`: New priority of the load.
+    ///
+    /// Loaders without priorities load the assets in the order they are requested.
+    fn set_priority(&self, _handle: LoadHandle, _priority: LoadPriority) {}
+
     /// Returns a weak handle to the asset of the given UUID, if any.
     ///
     /// # Parameters
@@ -102,6 +175,20 @@ pub trait Loader: Send + Sync {
     /// * `handle`: `LoadHandle` of the asset.
     fn get_load_status_handle(&self, handle: LoadHandle) -> LoadStatus;
 
+    /// Returns the load handles of the assets the asset with the given load handle depends on, as
+    /// far as the loader knows them.
+    ///
+    /// The dependencies of assets served by the asset daemon are only known once the asset is
+    /// loaded, the ones of assets from `AssetSources` and a `PackfileIO` once their metadata is
+    /// read.
+    ///
+    /// # Parameters
+    ///
+    /// * `handle`: `LoadHandle` of the asset.
+    fn get_load_dependencies(&self, _handle: LoadHandle) -> Vec<LoadHandle> {
+        Vec::new()
+    }
+
     /// Returns an immutable reference to the asset if it is committed.
     ///
     /// # Parameters
@@ -167,17 +254,32 @@ pub struct LoaderWithStorage {
     ref_sender: Sender<RefOp>,
     ref_receiver: Receiver<RefOp>,
     handle_allocator: Arc<AtomicHandleAllocator>,
+    priorities: LoadPriorities,
+    variants: AssetVariants,
+    dependencies: LoadDependencies,
     pub indirection_table: IndirectionTable,
 }
 
 impl Default for LoaderWithStorage {
     fn default() -> Self {
+        Self::new(Box::new(RpcIO::default()))
+    }
+}
+
+impl LoaderWithStorage {
+    /// Creates a loader that reads assets through the given IO.
+    pub fn new(io: Box<dyn LoaderIO>) -> Self {
+        Self::with_dependencies(io, LoadDependencies::default())
+    }
+
+    /// Creates a loader that reads assets through the given IO, which records the dependencies
+    /// in the metadata it serves in `dependencies`.
+    pub(crate) fn with_dependencies(io: Box<dyn LoaderIO>, dependencies: LoadDependencies) -> Self {
         let (tx, rx) = unbounded();
         let handle_allocator = Arc::new(AtomicHandleAllocator::default());
-        let loader = AtelierLoader::new_with_handle_allocator(
-            Box::new(RpcIO::default()),
-            handle_allocator.clone(),
-        );
+        let priorities = LoadPriorities::default();
+        let io = Box::new(PrioritizedIO::new(io, priorities.clone()));
+        let loader = AtelierLoader::new_with_handle_allocator(io, handle_allocator.clone());
         Self {
             indirection_table: loader.indirection_table(),
             loader,
@@ -185,6 +287,48 @@ impl Default for LoaderWithStorage {
             ref_sender: tx,
             ref_receiver: rx,
             handle_allocator,
+            priorities,
+            variants: AssetVariants::default(),
+            dependencies,
+        }
+    }
+
+    /// Returns the identifier `Loader::load` resolves the path with.
+    ///
+    /// Source files producing several assets are resolved to the asset of type `A` if it is a
+    /// registered asset type, and to their first asset otherwise.
+    fn path_identifier<A: TypeUuid>(&self, path: &str) -> IndirectIdentifier {
+        path_identifier(path, self.asset_data_type::<A>())
+    }
+
+    /// Returns the type of the data the storage of the asset type `A` is loaded from, if `A` is
+    /// a registered asset type.
+    fn asset_data_type<A: TypeUuid>(&self) -> Option<AssetTypeId> {
+        self.storage_map
+            .storages_by_asset_uuid
+            .get(&AssetTypeId(A::UUID))
+            .map(|asset_type| asset_type.data_uuid)
+    }
+
+    /// Creates a loader connected to the asset daemon listening on the given address.
+    pub fn connect(address: SocketAddr) -> std::io::Result<Self> {
+        Ok(Self::new(Box::new(RpcIO::new(address.to_string())?)))
+    }
+
+    /// Writes the events queued by the storages of all registered asset types to their
+    /// `EventChannel<AssetEvent<A>>`.
+    pub(crate) fn flush_events(&self, resources: &Resources) {
+        for storage in self.storage_map.storages_by_asset_uuid.values() {
+            (storage.flush_events)(resources);
+        }
+    }
+
+    /// Updates `usage` with the memory usage of the storages of all registered asset types.
+    pub fn update_usage(&self, resources: &Resources, usage: &mut AssetUsage) {
+        for (asset_type, storage) in self.storage_map.storages_by_asset_uuid.iter() {
+            if let Some(storage_usage) = (storage.usage)(resources) {
+                usage.set(*asset_type, storage_usage);
+            }
         }
     }
 }
@@ -197,17 +341,57 @@ impl Loader for LoaderWithStorage {
         Handle::new(self.ref_sender.clone(), self.loader.add_ref(id))
     }
     fn load<A: TypeUuid>(&self, path: &str) -> Handle<A> {
-        Handle::new(
-            self.ref_sender.clone(),
-            self.loader
-                .add_ref_indirect(IndirectIdentifier::Path(path.to_string())),
-        )
+        if path.contains(LOCALE_PLACEHOLDER) {
+            let handle = self.handle_allocator.alloc();
+            self.variants
+                .insert(handle, path, self.asset_data_type::<A>());
+            return Handle::new(self.ref_sender.clone(), handle);
+        }
+        let id = self.path_identifier::<A>(path);
+        Handle::new(self.ref_sender.clone(), self.loader.add_ref_indirect(id))
+    }
+    fn set_locale(&self, locale: Option<&str>) {
+        self.variants.set_locale(locale);
+    }
+    fn locale(&self) -> Option<String> {
+        self.variants.locale()
     }
     fn get_load(&self, id: AssetUuid) -> Option<WeakHandle> {
         self.loader.get_load(id).map(WeakHandle::new)
     }
     fn get_load_status_handle(&self, handle: LoadHandle) -> LoadStatus {
-        self.loader.get_load_status(handle)
+        self.variants
+            .get_load_status(&self.loader, handle)
+            .unwrap_or_else(|| self.loader.get_load_status(handle))
+    }
+    fn load_with_priority<A: TypeUuid>(&self, path: &str, priority: LoadPriority) -> Handle<A> {
+        if !path.contains(LOCALE_PLACEHOLDER) {
+            self.priorities.set_path(path, priority);
+        }
+        let handle = self.load(path);
+        self.set_priority(handle.load_handle(), priority);
+        handle
+    }
+    fn set_priority(&self, handle: LoadHandle, priority: LoadPriority) {
+        self.priorities.set(handle, priority);
+    }
+    fn get_load_dependencies(&self, handle: LoadHandle) -> Vec<LoadHandle> {
+        let handle = self.variants.resolve(handle).unwrap_or(handle);
+        let handle = if handle.is_indirect() {
+            match self.indirection_table.resolve(handle) {
+                Some(handle) => handle,
+                None => return Vec::new(),
+            }
+        } else {
+            handle
+        };
+        self.loader
+            .get_load_info(handle)
+            .map(|info| self.dependencies.get(&info.asset_id))
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|dependency| self.loader.get_load(dependency))
+            .collect()
     }
 
     /// Load an asset from data and return a handle.
@@ -231,6 +415,8 @@ impl Loader for LoaderWithStorage {
     }
 
     fn init_world(&mut self, resources: &mut Resources) {
+        resources.insert(self.priorities.clone());
+        resources.insert(self.variants.clone());
         for (_, storage) in self.storage_map.storages_by_asset_uuid.iter() {
             (storage.create_storage)(resources, &self.indirection_table);
         }
@@ -242,11 +428,21 @@ impl Loader for LoaderWithStorage {
     }
 
     fn process(&mut self, resources: &Resources) -> Result<(), Box<dyn Error + Send>> {
+        let mut released = Vec::new();
         loop {
             match self.ref_receiver.try_recv() {
                 Err(TryRecvError::Empty) => break,
                 Err(TryRecvError::Disconnected) => panic!("RefOp receiver disconnected"),
-                Ok(RefOp::Decrease(handle)) => self.loader.remove_ref(handle),
+                Ok(RefOp::Decrease(handle)) if self.variants.contains(handle) => {
+                    released.extend(self.variants.remove_ref(&self.loader, handle));
+                }
+                Ok(RefOp::Increase(handle)) if self.variants.contains(handle) => {
+                    self.variants.add_ref(handle);
+                }
+                Ok(RefOp::Decrease(handle)) => {
+                    self.loader.remove_ref(handle);
+                    released.push(handle);
+                }
                 Ok(RefOp::Increase(handle)) => {
                     self.loader
                         .get_load_info(handle)
@@ -257,7 +453,19 @@ impl Loader for LoaderWithStorage {
                 }
             }
         }
-        let storages = WorldStorages::new(resources, &self.storage_map, &self.ref_sender);
+        self.variants.update(&self.loader);
+        self.priorities.update(
+            &self.loader,
+            &self.indirection_table,
+            &self.variants,
+            &released,
+        );
+        let storages = WorldStorages::new(
+            resources,
+            &self.storage_map,
+            &self.ref_sender,
+            &self.dependencies,
+        );
         self.loader.process(&storages, &DefaultIndirectionResolver)
     }
 }
@@ -317,6 +525,12 @@ where
             Err(err) => {
                 debug!("Error in AssetTypeStorage deserialize");
                 let e = AmethystError::from_string(format!("{}", err));
+                self.1.push_event(
+                    handle,
+                    AssetEventKind::Failed {
+                        error: e.to_string(),
+                    },
+                );
                 load_op.error(err);
                 Err(e.into_error())
             }
@@ -378,6 +592,7 @@ struct WorldStorages<'a> {
     storage_map: &'a AssetStorageMap,
     ref_sender: &'a Sender<RefOp>,
     resources: &'a Resources,
+    dependencies: &'a LoadDependencies,
 }
 
 impl<'a> WorldStorages<'a> {
@@ -385,11 +600,13 @@ impl<'a> WorldStorages<'a> {
         resources: &'a Resources,
         storage_map: &'a AssetStorageMap,
         ref_sender: &'a Sender<RefOp>,
+        dependencies: &'a LoadDependencies,
     ) -> WorldStorages<'a> {
         WorldStorages {
             storage_map,
             ref_sender,
             resources,
+            dependencies,
         }
     }
 }
@@ -410,10 +627,12 @@ impl<'a> atelier_loader::storage::AssetStorage for WorldStorages<'a> {
         let moved_op = RefCell::new(Some(load_op));
         let moved_data = RefCell::new(Some(data));
         let mut result = None;
+        // The handles deserialized with the asset are its load dependencies
+        let recording_info = RecordingInfoProvider::new(loader_info);
         if let Some(asset_type) = self.storage_map.storages_by_data_uuid.get(asset_type) {
             (asset_type.with_storage)(self.resources, &mut |storage: &mut dyn AssetTypeStorage| {
                 result = futures_executor::block_on(SerdeContext::with(
-                    loader_info,
+                    &recording_info,
                     self.ref_sender.clone(),
                     async {
                         Some(storage.update_asset(
@@ -432,6 +651,7 @@ impl<'a> atelier_loader::storage::AssetStorage for WorldStorages<'a> {
             )
             .into_error()))
         }
+        recording_info.record(load_handle, self.dependencies);
         result.unwrap()
     }
     fn commit_asset_version(
@@ -476,6 +696,10 @@ pub struct AssetType {
     pub register_system: fn(&mut DispatcherBuilder),
     /// Function that runs another function, passing in the `AssetTypeStorage`.
     pub with_storage: fn(&Resources, &mut dyn FnMut(&mut dyn AssetTypeStorage)),
+    /// Function returning the memory usage of the `AssetStorage`, if it exists.
+    pub usage: fn(&Resources) -> Option<StorageUsage>,
+    /// Function writing the events queued by the `AssetStorage` to the `EventChannel`.
+    pub flush_events: fn(&Resources),
 }
 
 impl std::fmt::Debug for AssetType {
@@ -506,9 +730,23 @@ where
         asset_uuid: AssetTypeId(Asset::UUID),
         create_storage: |res, indirection_table| {
             debug!("Creating storage for {:x?}", Asset::UUID);
-            res.get_or_insert_with(|| AssetStorage::<Asset>::new(indirection_table.clone()));
+            let variants = res
+                .get::<AssetVariants>()
+                .map(|variants| variants.clone())
+                .unwrap_or_default();
+            res.get_or_insert_with(|| {
+                AssetStorage::<Asset>::with_variants(indirection_table.clone(), variants)
+            });
+            res.get_mut::<AssetStorage<Asset>>()
+                .expect("Could not get_mut AssetStorage")
+                .enable_events();
             debug!("Creating queue for intermediate {:x?}", Intermediate::UUID);
-            res.get_or_insert_with(ProcessingQueue::<Intermediate>::default);
+            let priorities = res
+                .get::<LoadPriorities>()
+                .map(|priorities| priorities.clone())
+                .unwrap_or_default();
+            res.get_or_insert_with(|| ProcessingQueue::<Intermediate>::with_priorities(priorities));
+            res.get_or_insert_with(EventChannel::<AssetEvent<Asset>>::new);
         },
         register_system: |builder| {
             builder.add_system(Box::new(ProcessorSystem::default()));
@@ -523,6 +761,21 @@ where
                     .deref_mut(),
             ))
         },
+        flush_events: |res| {
+            if let Some(storage) = res.get::<AssetStorage<Asset>>() {
+                // The events are dropped if the channel was removed.
+                let mut channel = res.get_mut::<EventChannel<AssetEvent<Asset>>>();
+                storage.drain_events(|event| {
+                    if let Some(channel) = channel.as_mut() {
+                        channel.single_write(event);
+                    }
+                });
+            }
+        },
+        usage: |res| {
+            res.get::<AssetStorage<Asset>>()
+                .map(|storage| storage.usage())
+        },
     }
 }
 
@@ -558,3 +811,33 @@ macro_rules! register_asset_type {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        prefab::{Prefab, RawPrefab},
+        storage::tests::NoIO,
+    };
+
+    #[derive(TypeUuid)]
+    #[uuid = "5d1e8b3a-7c42-4f09-b6a1-e28c94f0d517"]
+    struct Unregistered;
+
+    #[test]
+    fn paths_resolve_to_the_asset_of_the_handle_type() {
+        let loader = LoaderWithStorage::new(Box::new(NoIO));
+
+        match loader.path_identifier::<Prefab>("mesh/puffy.gltf") {
+            IndirectIdentifier::PathWithType(path, asset_type) => {
+                assert_eq!("mesh/puffy.gltf", path);
+                assert_eq!(AssetTypeId(RawPrefab::UUID), asset_type);
+            }
+            _ => panic!("Prefab paths are not resolved with the prefab type"),
+        }
+        assert!(matches!(
+            loader.path_identifier::<Unregistered>("mesh/puffy.gltf"),
+            IndirectIdentifier::Path(_)
+        ));
+    }
+}
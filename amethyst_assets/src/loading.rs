@@ -0,0 +1,427 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use amethyst_error::{format_err, Error};
+use atelier_assets::{
+    core::{ArtifactMetadata, AssetRef, AssetUuid},
+    loader::{
+        handle::{AssetHandle, GenericHandle, Handle},
+        storage::{LoadStatus, LoaderInfoProvider},
+        LoadHandle,
+    },
+};
+
+use crate::{
+    progress::{AssetErrorMeta, Completion},
+    Loader,
+};
+
+/// Load dependencies of the assets, as far as the loader has seen them.
+///
+/// These are recorded from the metadata served by the loader IOs of this crate, and from the
+/// handles deserialized with the data of an asset, as the metadata served by the asset daemon is
+/// not accessible.
+#[derive(Clone, Default)]
+pub(crate) struct LoadDependencies {
+    inner: Arc<RwLock<HashMap<AssetUuid, Vec<AssetUuid>>>>,
+}
+
+impl LoadDependencies {
+    /// Adds the given dependencies of the asset to the known ones.
+    pub(crate) fn insert(&self, asset: AssetUuid, dependencies: &[AssetUuid]) {
+        let mut inner = self
+            .inner
+            .write()
+            .expect("LoadDependencies lock was poisoned");
+        let known = inner.entry(asset).or_insert_with(Vec::new);
+        for dependency in dependencies {
+            if !known.contains(dependency) {
+                known.push(*dependency);
+            }
+        }
+    }
+
+    /// Adds the load dependencies given by UUID in the metadata of an artifact.
+    pub(crate) fn insert_metadata(&self, artifact: &ArtifactMetadata) {
+        let dependencies = artifact
+            .load_deps
+            .iter()
+            .filter_map(|dep| match dep {
+                AssetRef::Uuid(dep) => Some(*dep),
+                AssetRef::Path(_) => None,
+            })
+            .collect::<Vec<_>>();
+        self.insert(artifact.asset_id, &dependencies);
+    }
+
+    /// Returns the known dependencies of the asset.
+    pub(crate) fn get(&self, asset: &AssetUuid) -> Vec<AssetUuid> {
+        let inner = self
+            .inner
+            .read()
+            .expect("LoadDependencies lock was poisoned");
+        inner.get(asset).cloned().unwrap_or_default()
+    }
+}
+
+/// `LoaderInfoProvider` recording the load handles of the handles deserialized with an asset.
+pub(crate) struct RecordingInfoProvider<'a> {
+    inner: &'a dyn LoaderInfoProvider,
+    handles: Mutex<Vec<LoadHandle>>,
+}
+
+impl<'a> RecordingInfoProvider<'a> {
+    pub(crate) fn new(inner: &'a dyn LoaderInfoProvider) -> Self {
+        RecordingInfoProvider {
+            inner,
+            handles: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Records the assets of the handles deserialized so far as dependencies of the given load.
+    pub(crate) fn record(&self, load_handle: LoadHandle, dependencies: &LoadDependencies) {
+        let asset = match self.inner.get_asset_id(load_handle) {
+            Some(asset) => asset,
+            None => return,
+        };
+        let handles = self
+            .handles
+            .lock()
+            .expect("RecordingInfoProvider lock was poisoned");
+        let assets = handles
+            .iter()
+            .filter_map(|handle| self.inner.get_asset_id(*handle))
+            .filter(|dep| *dep != asset)
+            .collect::<Vec<_>>();
+        dependencies.insert(asset, &assets);
+    }
+}
+
+impl<'a> LoaderInfoProvider for RecordingInfoProvider<'a> {
+    fn get_load_handle(&self, id: &AssetRef) -> Option<LoadHandle> {
+        let handle = self.inner.get_load_handle(id)?;
+        self.handles
+            .lock()
+            .expect("RecordingInfoProvider lock was poisoned")
+            .push(handle);
+        Some(handle)
+    }
+
+    fn get_asset_id(&self, load: LoadHandle) -> Option<AssetUuid> {
+        self.inner.get_asset_id(load)
+    }
+}
+
+struct TrackedAsset {
+    /// Handle keeping the asset loaded, `None` for dependencies, which their dependents keep.
+    _handle: Option<GenericHandle>,
+    load_handle: LoadHandle,
+    asset_type_name: &'static str,
+    asset_name: String,
+    state: Completion,
+}
+
+/// Tracks the loading of a set of asset handles, such as for a loading screen.
+///
+/// Unlike `ProgressCounter`, which is passed to `Loader::load_from_data`, this works with the
+/// handles returned by `Loader::load` and `Loader::load_asset`. The tracker keeps the handles
+/// alive, and polls their load status in `update`.
+///
+/// An asset is only reported as loaded by the loader once its load dependencies are loaded. The
+/// dependencies the loader knows of, see `Loader::get_load_dependencies`, are tracked as well
+/// once they are known, so that their errors are collected and the tracker does not wait for an
+/// asset whose dependency failed. The number of tracked assets can therefore grow in `update`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use amethyst_assets::{DefaultLoader, Handle, Loader, LoadingTracker};
+/// # use type_uuid::TypeUuid;
+/// # #[derive(TypeUuid)]
+/// # #[uuid = "6ec3a2c2-2a4f-4c0b-9ed4-02b0b0d0f1a1"]
+/// # struct Texture;
+///
+/// # fn example(loader: &DefaultLoader) {
+/// let mut tracker = LoadingTracker::new();
+/// let texture: Handle<Texture> = loader.load("texture/logo.png");
+/// tracker.track_named(&texture, "texture/logo.png");
+///
+/// // Every frame:
+/// tracker.update(loader);
+/// println!("{}/{} loaded", tracker.num_finished(), tracker.num_assets());
+/// for error in tracker.errors() {
+///     eprintln!("Failed to load {}: {}", error.asset_name, error.error);
+/// }
+/// # }
+/// ```
+#[derive(Default)]
+pub struct LoadingTracker {
+    assets: Vec<TrackedAsset>,
+    errors: Vec<AssetErrorMeta>,
+}
+
+impl std::fmt::Debug for LoadingTracker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoadingTracker")
+            .field("num_assets", &self.num_assets())
+            .field("num_finished", &self.num_finished())
+            .field("num_failed", &self.num_failed())
+            .finish()
+    }
+}
+
+impl LoadingTracker {
+    /// Creates a tracker without any handles.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tracks the given handle.
+    pub fn track<A: 'static>(&mut self, handle: &Handle<A>) {
+        let asset_name = format!("{:?}", handle.load_handle());
+        self.track_named(handle, asset_name);
+    }
+
+    /// Tracks the given handle, using `asset_name` in errors, such as the path it was loaded from.
+    pub fn track_named<A: 'static, N: Into<String>>(&mut self, handle: &Handle<A>, asset_name: N) {
+        self.assets.push(TrackedAsset {
+            _handle: Some(GenericHandle::from(handle.clone())),
+            load_handle: handle.load_handle(),
+            asset_type_name: std::any::type_name::<A>(),
+            asset_name: asset_name.into(),
+            state: Completion::Loading,
+        });
+    }
+
+    /// Tracks the given generic handle, using `asset_name` in errors.
+    pub fn track_generic<N: Into<String>>(&mut self, handle: GenericHandle, asset_name: N) {
+        self.assets.push(TrackedAsset {
+            load_handle: handle.load_handle(),
+            _handle: Some(handle),
+            asset_type_name: "unknown",
+            asset_name: asset_name.into(),
+            state: Completion::Loading,
+        });
+    }
+
+    /// Tracks the newly known dependencies, and updates the state of the handles that are still
+    /// loading.
+    pub fn update<L: Loader>(&mut self, loader: &L) {
+        self.track_dependencies(|handle| loader.get_load_dependencies(handle));
+        self.update_with(|handle| loader.get_load_status_handle(handle));
+    }
+
+    fn track_dependencies<F>(&mut self, mut load_dependencies: F)
+    where
+        F: FnMut(LoadHandle) -> Vec<LoadHandle>,
+    {
+        // Dependencies are pushed to `assets`, so that their own dependencies are tracked too.
+        let mut index = 0;
+        while index < self.assets.len() {
+            if self.assets[index].state != Completion::Failed {
+                for dependency in load_dependencies(self.assets[index].load_handle) {
+                    if self
+                        .assets
+                        .iter()
+                        .any(|asset| asset.load_handle == dependency)
+                    {
+                        continue;
+                    }
+                    let asset_name = format!(
+                        "{:?} (dependency of {})",
+                        dependency, self.assets[index].asset_name
+                    );
+                    self.assets.push(TrackedAsset {
+                        _handle: None,
+                        load_handle: dependency,
+                        asset_type_name: "unknown",
+                        asset_name,
+                        state: Completion::Loading,
+                    });
+                }
+            }
+            index += 1;
+        }
+    }
+
+    fn update_with<F>(&mut self, mut load_status: F)
+    where
+        F: FnMut(LoadHandle) -> LoadStatus,
+    {
+        let errors = &mut self.errors;
+        for asset in self
+            .assets
+            .iter_mut()
+            .filter(|asset| asset.state == Completion::Loading)
+        {
+            let load_handle = asset.load_handle;
+            let error = match load_status(load_handle) {
+                LoadStatus::Loaded => {
+                    asset.state = Completion::Complete;
+                    continue;
+                }
+                LoadStatus::DoesNotExist => {
+                    format_err!("The asset {} does not exist", asset.asset_name)
+                }
+                LoadStatus::Error(error) => Error::from_string(error.to_string()),
+                _ => continue,
+            };
+            asset.state = Completion::Failed;
+            errors.push(AssetErrorMeta {
+                error,
+                handle_id: load_handle.0,
+                asset_type_name: asset.asset_type_name,
+                asset_name: asset.asset_name.clone(),
+            });
+        }
+    }
+
+    /// Returns the number of tracked handles.
+    pub fn num_assets(&self) -> usize {
+        self.assets.len()
+    }
+
+    /// Returns the number of handles that have loaded.
+    pub fn num_finished(&self) -> usize {
+        self.count(Completion::Complete)
+    }
+
+    /// Returns the number of handles that failed to load.
+    pub fn num_failed(&self) -> usize {
+        self.count(Completion::Failed)
+    }
+
+    /// Returns the number of handles that are still loading.
+    pub fn num_loading(&self) -> usize {
+        self.count(Completion::Loading)
+    }
+
+    fn count(&self, state: Completion) -> usize {
+        self.assets
+            .iter()
+            .filter(|asset| asset.state == state)
+            .count()
+    }
+
+    /// Returns the fraction of handles that have loaded, between `0.0` and `1.0`.
+    ///
+    /// This is `1.0` when no handles are tracked.
+    pub fn progress(&self) -> f32 {
+        if self.assets.is_empty() {
+            1.0
+        } else {
+            self.num_finished() as f32 / self.num_assets() as f32
+        }
+    }
+
+    /// Returns `Completion::Failed` if any handle failed to load, `Completion::Loading` if any
+    /// handle is still loading, and `Completion::Complete` otherwise.
+    pub fn complete(&self) -> Completion {
+        if self.num_failed() > 0 {
+            Completion::Failed
+        } else if self.num_loading() > 0 {
+            Completion::Loading
+        } else {
+            Completion::Complete
+        }
+    }
+
+    /// Returns `true` if all handles have loaded without error.
+    pub fn is_complete(&self) -> bool {
+        self.complete() == Completion::Complete
+    }
+
+    /// Returns the errors of the handles that failed to load.
+    pub fn errors(&self) -> &[AssetErrorMeta] {
+        &self.errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use atelier_assets::loader::crossbeam_channel::unbounded;
+
+    use super::*;
+
+    #[test]
+    fn tracker_counts_loaded_and_failed_handles() {
+        let (tx, _rx) = unbounded();
+        let mut tracker = LoadingTracker::new();
+        tracker.track_generic(GenericHandle::new(tx.clone(), LoadHandle(1)), "loaded");
+        tracker.track_generic(GenericHandle::new(tx.clone(), LoadHandle(2)), "missing");
+        tracker.track_generic(GenericHandle::new(tx, LoadHandle(3)), "loading");
+
+        tracker.update_with(|handle| match handle.0 {
+            1 => LoadStatus::Loaded,
+            2 => LoadStatus::DoesNotExist,
+            _ => LoadStatus::Loading,
+        });
+
+        assert_eq!(3, tracker.num_assets());
+        assert_eq!(1, tracker.num_finished());
+        assert_eq!(1, tracker.num_failed());
+        assert_eq!(1, tracker.num_loading());
+        assert_eq!(Completion::Failed, tracker.complete());
+        assert_eq!(1, tracker.errors().len());
+        assert_eq!("missing", tracker.errors()[0].asset_name);
+        assert_eq!(2, tracker.errors()[0].handle_id);
+    }
+
+    #[test]
+    fn tracker_collects_errors_of_dependencies() {
+        let (tx, _rx) = unbounded();
+        let mut tracker = LoadingTracker::new();
+        tracker.track_generic(GenericHandle::new(tx, LoadHandle(1)), "sprite_sheet");
+
+        let dependencies = |handle: LoadHandle| match handle.0 {
+            1 => vec![LoadHandle(2)],
+            2 => vec![LoadHandle(3), LoadHandle(1)],
+            _ => Vec::new(),
+        };
+        tracker.track_dependencies(dependencies);
+        // Dependencies already tracked are not tracked again
+        tracker.track_dependencies(dependencies);
+        tracker.update_with(|handle| match handle.0 {
+            3 => LoadStatus::DoesNotExist,
+            _ => LoadStatus::Loading,
+        });
+
+        assert_eq!(3, tracker.num_assets());
+        assert_eq!(1, tracker.num_failed());
+        assert_eq!(Completion::Failed, tracker.complete());
+        assert_eq!(3, tracker.errors()[0].handle_id);
+        assert!(tracker.errors()[0]
+            .asset_name
+            .ends_with("(dependency of sprite_sheet))"));
+    }
+
+    #[test]
+    fn dependencies_are_merged() {
+        let dependencies = LoadDependencies::default();
+        let asset = AssetUuid([1; 16]);
+        dependencies.insert(asset, &[AssetUuid([2; 16])]);
+        dependencies.insert(asset, &[AssetUuid([2; 16]), AssetUuid([3; 16])]);
+
+        assert_eq!(
+            vec![AssetUuid([2; 16]), AssetUuid([3; 16])],
+            dependencies.get(&asset)
+        );
+        assert!(dependencies.get(&AssetUuid([2; 16])).is_empty());
+    }
+
+    #[test]
+    fn tracker_is_complete_once_all_handles_loaded() {
+        let (tx, _rx) = unbounded();
+        let mut tracker = LoadingTracker::new();
+        tracker.track_generic(GenericHandle::new(tx, LoadHandle(1)), "asset");
+        assert_eq!(Completion::Loading, tracker.complete());
+        assert!(tracker.progress() < f32::EPSILON);
+
+        tracker.update_with(|_| LoadStatus::Loaded);
+
+        assert!(tracker.is_complete());
+        assert!((1.0 - tracker.progress()).abs() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,442 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use amethyst_error::{format_err, Error, ResultExt};
+use atelier_assets::{
+    core::{AssetMetadata, AssetRef, AssetUuid},
+    loader::{
+        io::{DataRequest, LoaderIO, MetadataRequest, ResolveRequest},
+        loader::LoaderState,
+    },
+};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    daemon::AssetDaemonConfig,
+    import::{source_files, ImporterSet},
+    loading::LoadDependencies,
+};
+
+const PACK_MAGIC: &[u8; 8] = b"AMPACK01";
+
+/// Index stored at the start of a pack file, with sorted maps so that packing the same assets
+/// twice writes the same file.
+#[derive(Default, Serialize, Deserialize)]
+struct PackIndex {
+    assets: BTreeMap<AssetUuid, PackedAsset>,
+    paths: BTreeMap<String, Vec<AssetUuid>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PackedAsset {
+    metadata: AssetMetadata,
+    /// Offset of the artifact data, relative to the end of the index.
+    offset: u64,
+    length: u64,
+}
+
+/// Normalizes a path so that the paths of the pack and the paths passed to `Loader::load` match
+/// on every platform.
+///
+/// `.` components are removed and `..` components remove the previous component, so that the
+/// paths of dependencies relative to their source file match the paths of the source files.
+pub(crate) fn normalize_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => match components.last() {
+                Some(last) if *last != ".." => {
+                    components.pop();
+                }
+                _ => components.push(component),
+            },
+            _ => components.push(component),
+        }
+    }
+    components.join("/")
+}
+
+/// Returns the asset imported from the path of a dependency of the asset imported from `source`.
+///
+/// Dependency paths are relative to the source file, or else to the asset directory.
+pub(crate) fn resolve_path_dependency(
+    paths: &HashMap<String, Vec<AssetUuid>>,
+    source: &str,
+    dependency: &Path,
+) -> Option<AssetUuid> {
+    let source_dir = Path::new(source).parent().unwrap_or_else(|| Path::new(""));
+    let relative = normalize_path(&source_dir.join(dependency).to_string_lossy());
+    let key = normalize_path(&dependency.to_string_lossy());
+    paths
+        .get(&relative)
+        .or_else(|| paths.get(&key))
+        .and_then(|ids| ids.first())
+        .copied()
+}
+
+/// Imports the source files of the asset directories and writes all resulting artifacts into a
+/// single pack file, which can be loaded with [`PackfileIO`](struct.PackfileIO.html) without
+/// running the asset daemon.
+///
+/// The source files are imported with the same importers as the asset daemon, and the importer
+/// options and state are read from the `.meta` files the daemon writes, so assets keep the UUIDs
+/// they had during development. The artifacts are imported again rather than read from the
+/// daemon's database, so the daemon does not need to run; their ids are stable hashes of their
+/// data, and packing the same sources always writes the same pack file.
+///
+/// As the packer does not read the daemon's database, the pack differs from what the daemon
+/// serves in some cases:
+///
+/// - Assets are packed as they are imported: build pipelines are not run on them.
+/// - The importer state only comes from the `.meta` files, so source files without one are
+///   imported with the default state and their assets get new UUIDs on every pack. Run the daemon
+///   on the asset directories before packing to write the `.meta` files.
+///
+/// # Examples
+///
+/// ```no_run
+/// use amethyst_assets::{AssetDaemonConfig, AssetPacker};
+///
+/// let packed = AssetPacker::new(AssetDaemonConfig::default().with_asset_dir("assets"))
+///     .pack("assets.pack")
+///     .expect("Failed to pack assets");
+/// println!("Packed {} assets", packed);
+/// ```
+pub struct AssetPacker {
+    asset_dirs: Vec<PathBuf>,
+    importers: ImporterSet,
+}
+
+impl fmt::Debug for AssetPacker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AssetPacker")
+            .field("asset_dirs", &self.asset_dirs)
+            .field("importers", &self.importers)
+            .finish()
+    }
+}
+
+impl AssetPacker {
+    /// Creates a packer for the asset directories and importers of the daemon configuration.
+    pub fn new(config: AssetDaemonConfig) -> Self {
+        let (asset_dirs, importers) = config.into_asset_dirs_and_importers();
+        AssetPacker {
+            asset_dirs,
+            importers: ImporterSet::new(importers),
+        }
+    }
+
+    /// Imports all source files and writes the pack file, returning the number of packed assets.
+    pub fn pack<P: AsRef<Path>>(&self, output: P) -> Result<usize, Error> {
+        let mut paths = HashMap::new();
+        let mut artifacts = Vec::new();
+        let mut sources = Vec::new();
+
+        for asset_dir in &self.asset_dirs {
+            for file in source_files(asset_dir)? {
+                let relative = file.strip_prefix(asset_dir).unwrap_or(&file);
+                let path = normalize_path(&relative.to_string_lossy());
+                let imported = match self.importers.import_file(&file)? {
+                    Some(imported) => imported,
+                    None => {
+                        debug!("No importer for {:?}, skipping", file);
+                        continue;
+                    }
+                };
+                for artifact in imported {
+                    paths
+                        .entry(path.clone())
+                        .or_insert_with(Vec::new)
+                        .push(artifact.metadata.id);
+                    artifacts.push(artifact);
+                    sources.push(path.clone());
+                }
+            }
+        }
+
+        // Artifacts refer to their dependencies by UUID once imported.
+        for (artifact, source) in artifacts.iter_mut().zip(&sources) {
+            if let Some(meta) = artifact.metadata.artifact.as_mut() {
+                for dep in meta.load_deps.iter_mut().chain(meta.build_deps.iter_mut()) {
+                    if let AssetRef::Path(dep_path) = dep {
+                        match resolve_path_dependency(&paths, source, dep_path) {
+                            Some(id) => *dep = AssetRef::Uuid(id),
+                            None => {
+                                warn!(
+                                    "Unresolved dependency {:?} of {:?}",
+                                    dep_path, meta.asset_id
+                                )
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut index = PackIndex {
+            assets: BTreeMap::new(),
+            paths: paths.into_iter().collect(),
+        };
+        let mut offset = 0;
+        for artifact in &artifacts {
+            let length = artifact.data.len() as u64;
+            index.assets.insert(
+                artifact.metadata.id,
+                PackedAsset {
+                    metadata: artifact.metadata.clone(),
+                    offset,
+                    length,
+                },
+            );
+            offset += length;
+        }
+
+        let output = output.as_ref();
+        let index_bytes = bincode::serialize(&index)?;
+        let mut writer = BufWriter::new(
+            File::create(output)
+                .with_context(|_| format_err!("Failed to create pack file {:?}", output))?,
+        );
+        writer.write_all(PACK_MAGIC)?;
+        writer.write_all(&(index_bytes.len() as u64).to_le_bytes())?;
+        writer.write_all(&index_bytes)?;
+        for artifact in &artifacts {
+            writer.write_all(&artifact.data)?;
+        }
+        writer.flush()?;
+
+        info!("Packed {} assets into {:?}", artifacts.len(), output);
+        Ok(artifacts.len())
+    }
+}
+
+/// Loader IO reading assets from a pack file written by
+/// [`AssetPacker`](struct.AssetPacker.html), for shipped games that do not run the asset daemon.
+///
+/// Paths passed to `Loader::load` are relative to the asset directory the asset was packed from.
+///
+/// # Examples
+///
+/// ```no_run
+/// use amethyst_assets::{DefaultLoader, PackfileIO};
+///
+/// let io = PackfileIO::open("assets.pack").expect("Failed to open pack file");
+/// let loader = DefaultLoader::new(Box::new(io));
+/// ```
+pub struct PackfileIO {
+    index: PackIndex,
+    file: File,
+    data_start: u64,
+    dependencies: LoadDependencies,
+}
+
+impl fmt::Debug for PackfileIO {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PackfileIO")
+            .field("assets", &self.index.assets.len())
+            .finish()
+    }
+}
+
+impl PackfileIO {
+    /// Opens a pack file and reads its index. Artifact data is read when it is requested.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let mut file = File::open(path)
+            .with_context(|_| format_err!("Failed to open pack file {:?}", path))?;
+
+        let mut magic = [0; 8];
+        file.read_exact(&mut magic)?;
+        if &magic != PACK_MAGIC {
+            return Err(format_err!("{:?} is not an asset pack file", path));
+        }
+        let mut length = [0; 8];
+        file.read_exact(&mut length)?;
+        let length = u64::from_le_bytes(length);
+        // The index must not allocate more than the file
+        if length > file.metadata()?.len().saturating_sub(16) {
+            return Err(format_err!(
+                "The index of pack file {:?} is larger than the file",
+                path
+            ));
+        }
+        let mut index_bytes = vec![0; length as usize];
+        file.read_exact(&mut index_bytes)?;
+        let index = bincode::deserialize(&index_bytes)
+            .with_context(|_| format_err!("Failed to read the index of pack file {:?}", path))?;
+
+        Ok(PackfileIO {
+            index,
+            file,
+            data_start: 16 + length,
+            dependencies: LoadDependencies::default(),
+        })
+    }
+
+    /// Records the dependencies in the metadata served by the pack in `dependencies`.
+    pub(crate) fn set_dependencies(&mut self, dependencies: LoadDependencies) {
+        self.dependencies = dependencies;
+    }
+
+    /// Returns the number of assets in the pack.
+    pub fn len(&self) -> usize {
+        self.index.assets.len()
+    }
+
+    /// Returns `true` if the pack contains no assets.
+    pub fn is_empty(&self) -> bool {
+        self.index.assets.is_empty()
+    }
+
+    /// Returns the UUIDs of the assets imported from the given path.
+    pub fn assets_at_path(&self, path: &str) -> &[AssetUuid] {
+        self.index
+            .paths
+            .get(&normalize_path(path))
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    fn read_artifact(&mut self, id: &AssetUuid) -> io::Result<Vec<u8>> {
+        let asset = self.index.assets.get(id).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Asset {:?} is not in the pack", id),
+            )
+        })?;
+        let mut data = vec![0; asset.length as usize];
+        self.file
+            .seek(SeekFrom::Start(self.data_start + asset.offset))?;
+        self.file.read_exact(&mut data)?;
+        Ok(data)
+    }
+}
+
+impl LoaderIO for PackfileIO {
+    fn get_asset_metadata_with_dependencies(&mut self, request: MetadataRequest) {
+        let mut pending = request.requested_assets().cloned().collect::<Vec<_>>();
+        let mut visited = Vec::new();
+        let mut metadata = Vec::new();
+        while let Some(id) = pending.pop() {
+            if visited.contains(&id) {
+                continue;
+            }
+            visited.push(id);
+            if let Some(artifact) = self
+                .index
+                .assets
+                .get(&id)
+                .and_then(|asset| asset.metadata.artifact.as_ref())
+            {
+                pending.extend(artifact.load_deps.iter().filter_map(|dep| match dep {
+                    AssetRef::Uuid(dep) => Some(*dep),
+                    AssetRef::Path(_) => None,
+                }));
+                self.dependencies.insert_metadata(artifact);
+                metadata.push(artifact.clone());
+            }
+        }
+        request.complete(metadata);
+    }
+
+    fn get_asset_candidates(&mut self, requests: Vec<ResolveRequest>) {
+        for request in requests {
+            let path = normalize_path(request.identifier().path());
+            let candidates = self
+                .assets_at_path(&path)
+                .iter()
+                .filter_map(|id| self.index.assets.get(id))
+                .map(|asset| asset.metadata.clone())
+                .collect::<Vec<_>>();
+            request.complete(vec![(PathBuf::from(path), candidates)]);
+        }
+    }
+
+    fn get_artifacts(&mut self, requests: Vec<DataRequest>) {
+        for request in requests {
+            match self.read_artifact(&request.asset_id()) {
+                Ok(data) => request.complete(data),
+                Err(err) => request.error(err),
+            }
+        }
+    }
+
+    fn tick(&mut self, _loader: &mut LoaderState) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    #[test]
+    fn empty_directory_packs_to_empty_pack() {
+        let dir = env::temp_dir().join("amethyst_assets_pack_empty");
+        fs::create_dir_all(&dir).unwrap();
+        let output = env::temp_dir().join("amethyst_assets_pack_empty.pack");
+
+        let packed = AssetPacker::new(AssetDaemonConfig::default().with_asset_dir(&dir))
+            .pack(&output)
+            .unwrap();
+        assert_eq!(0, packed);
+
+        let io = PackfileIO::open(&output).unwrap();
+        assert!(io.is_empty());
+        assert!(io.assets_at_path("missing.png").is_empty());
+    }
+
+    #[test]
+    fn index_is_written_in_a_stable_order() {
+        let mut first = PackIndex::default();
+        let mut second = PackIndex::default();
+        for id in 0..16 {
+            first
+                .paths
+                .insert(format!("{}.png", id), vec![AssetUuid([id; 16])]);
+        }
+        for id in (0..16).rev() {
+            second
+                .paths
+                .insert(format!("{}.png", id), vec![AssetUuid([id; 16])]);
+        }
+        assert_eq!(
+            bincode::serialize(&first).unwrap(),
+            bincode::serialize(&second).unwrap()
+        );
+    }
+
+    #[test]
+    fn open_rejects_other_files() {
+        let path = env::temp_dir().join("amethyst_assets_pack_invalid.pack");
+        fs::write(&path, b"not a pack file").unwrap();
+        assert!(PackfileIO::open(&path).is_err());
+    }
+
+    #[test]
+    fn open_rejects_index_larger_than_the_file() {
+        let path = env::temp_dir().join("amethyst_assets_pack_truncated.pack");
+        let mut bytes = PACK_MAGIC.to_vec();
+        bytes.extend_from_slice(&u64::max_value().to_le_bytes());
+        fs::write(&path, bytes).unwrap();
+        assert!(PackfileIO::open(&path).is_err());
+    }
+
+    #[test]
+    fn paths_are_normalized() {
+        assert_eq!("sprites/logo.png", normalize_path("./sprites\\logo.png"));
+        assert_eq!(
+            "texture/bat.png",
+            normalize_path("sprites/./../texture/bat.png")
+        );
+        assert_eq!("../bat.png", normalize_path("sprites/../../bat.png"));
+    }
+}
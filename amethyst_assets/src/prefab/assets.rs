@@ -1,8 +1,10 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use amethyst_core::ecs::*;
 use atelier_assets::importer as atelier_importer;
+use atelier_assets::loader::LoadHandle;
 use atelier_importer::{typetag, SerdeImportable};
+use prefab_format::PrefabUuid;
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
@@ -38,11 +40,94 @@ impl Asset for Prefab {
 
 register_asset_type!(crate; RawPrefab => Prefab; PrefabAssetProcessor);
 
+/// Returns the IDs of the prefab and the prefabs it references, recursively, with every prefab
+/// after the prefabs it references, or the ID of a referenced prefab missing from `raw_prefabs`.
+///
+/// References forming a cycle are ignored.
+fn cook_order(
+    prefab: &legion_prefab::Prefab,
+    raw_prefabs: &HashMap<PrefabUuid, Arc<legion_prefab::Prefab>>,
+) -> Result<Vec<PrefabUuid>, PrefabUuid> {
+    fn visit(
+        prefab: &legion_prefab::Prefab,
+        raw_prefabs: &HashMap<PrefabUuid, Arc<legion_prefab::Prefab>>,
+        order: &mut Vec<PrefabUuid>,
+        visiting: &mut Vec<PrefabUuid>,
+    ) -> Result<(), PrefabUuid> {
+        let id = prefab.prefab_id();
+        if order.contains(&id) {
+            return Ok(());
+        }
+        if visiting.contains(&id) {
+            log::warn!("Prefab {:?} references itself through nested prefabs", id);
+            return Ok(());
+        }
+        visiting.push(id);
+        for nested_id in prefab.prefab_meta.prefab_refs.keys() {
+            let nested = raw_prefabs.get(nested_id).ok_or(*nested_id)?;
+            visit(nested, raw_prefabs, order, visiting)?;
+        }
+        visiting.pop();
+        order.push(id);
+        Ok(())
+    }
+
+    let mut order = Vec::new();
+    visit(prefab, raw_prefabs, &mut order, &mut Vec::new())?;
+    Ok(order)
+}
+
+/// Returns the IDs of the prefabs in `raw_prefabs` that reference the prefab `id`, directly or
+/// through nested prefabs.
+fn dependents(
+    id: PrefabUuid,
+    raw_prefabs: &HashMap<PrefabUuid, Arc<legion_prefab::Prefab>>,
+) -> Vec<PrefabUuid> {
+    raw_prefabs
+        .iter()
+        .filter(|(other, prefab)| {
+            **other != id
+                && cook_order(prefab, raw_prefabs).map_or(false, |order| order.contains(&id))
+        })
+        .map(|(other, _)| *other)
+        .collect()
+}
+
+/// Cooks the last prefab of `order`, as returned by `cook_order`.
+fn cook(
+    component_registry: &ComponentRegistry,
+    order: &[PrefabUuid],
+    raw_prefabs: &HashMap<PrefabUuid, Arc<legion_prefab::Prefab>>,
+) -> Prefab {
+    let prefab_lookup = order
+        .iter()
+        .map(|id| (*id, &*raw_prefabs[id]))
+        .collect::<HashMap<_, _>>();
+
+    let prefab = legion_prefab::cook_prefab(
+        component_registry.components(),
+        component_registry.components_by_uuid(),
+        order,
+        &prefab_lookup,
+    );
+    Prefab { prefab }
+}
+
+/// Cooks prefabs, including the prefabs they reference with their component overrides.
+///
+/// The raw prefabs are kept while their prefab asset is loaded, so that the prefabs referencing
+/// them can be cooked. A prefab waits in the queue until the prefabs it references are processed.
+/// When a referenced prefab is reloaded, the loaded prefabs referencing it are cooked again, and
+/// are spawned again like reloaded prefabs.
 #[derive(Default)]
 struct PrefabAssetProcessor;
 
 impl System<'static> for PrefabAssetProcessor {
     fn build(&'static mut self) -> Box<dyn ParallelRunnable> {
+        let mut raw_prefabs: HashMap<PrefabUuid, Arc<legion_prefab::Prefab>> = HashMap::new();
+        // Load handle of each raw prefab, and whether its prefab asset was in the storage.
+        let mut loaded: HashMap<PrefabUuid, (LoadHandle, bool)> = HashMap::new();
+
         Box::new(
             SystemBuilder::new("PrefabAssetProcessorSystem")
                 .read_resource::<ComponentRegistry>()
@@ -53,19 +138,63 @@ impl System<'static> for PrefabAssetProcessor {
                         #[cfg(feature = "profiler")]
                         profile_scope!("prefab_asset_processor");
 
-                        processing_queue.process(prefab_storage, |RawPrefab { raw_prefab }| {
-                            let prefab_cook_order = vec![raw_prefab.prefab_id()];
-                            let mut prefab_lookup = HashMap::new();
-                            prefab_lookup.insert(raw_prefab.prefab_id(), &raw_prefab);
+                        let mut recook = Vec::new();
+                        processing_queue.process_with_handles(
+                            prefab_storage,
+                            |handle, RawPrefab { raw_prefab }| {
+                                let prefab_cook_order = match cook_order(&raw_prefab, &raw_prefabs)
+                                {
+                                    Ok(order) => order,
+                                    Err(missing) => {
+                                        log::debug!(
+                                            "Prefab {:?} waits for nested prefab {:?}",
+                                            raw_prefab.prefab_id(),
+                                            missing
+                                        );
+                                        return Ok(ProcessingState::Loading(RawPrefab {
+                                            raw_prefab,
+                                        }));
+                                    }
+                                };
+                                let id = raw_prefab.prefab_id();
+                                if raw_prefabs.insert(id, Arc::new(raw_prefab)).is_some() {
+                                    recook.extend(dependents(id, &raw_prefabs));
+                                }
+                                let committed =
+                                    matches!(loaded.get(&id), Some((h, true)) if *h == handle);
+                                loaded.insert(id, (handle, committed));
 
-                            let prefab = legion_prefab::cook_prefab(
-                                component_registry.components(),
-                                component_registry.components_by_uuid(),
-                                prefab_cook_order.as_slice(),
-                                &prefab_lookup,
-                            );
+                                Ok(ProcessingState::Loaded(cook(
+                                    component_registry,
+                                    &prefab_cook_order,
+                                    &raw_prefabs,
+                                )))
+                            },
+                        );
 
-                            Ok(ProcessingState::Loaded(Prefab { prefab }))
+                        recook.sort_unstable();
+                        recook.dedup();
+                        for id in recook {
+                            let handle = match loaded.get(&id) {
+                                Some((handle, _)) => *handle,
+                                None => continue,
+                            };
+                            if let Ok(order) = cook_order(&raw_prefabs[&id], &raw_prefabs) {
+                                log::debug!("Cook prefab {:?} again", id);
+                                let prefab = cook(component_registry, &order, &raw_prefabs);
+                                let byte_size = prefab.byte_size();
+                                prefab_storage.replace_asset(handle, prefab, byte_size);
+                            }
+                        }
+
+                        loaded.retain(|id, (handle, committed)| {
+                            if prefab_storage.contains(*handle) {
+                                *committed = true;
+                            } else if *committed {
+                                raw_prefabs.remove(id);
+                                return false;
+                            }
+                            true
                         });
                         prefab_storage.process_custom_drop(|_| {});
                     },
@@ -73,3 +202,128 @@ impl System<'static> for PrefabAssetProcessor {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::transform::Transform;
+    use legion_prefab::{ComponentOverride, PrefabMeta, PrefabRef};
+    use prefab_format::EntityUuid;
+
+    use super::*;
+    use crate::prefab::ComponentRegistryBuilder;
+
+    fn raw_prefab(
+        id: PrefabUuid,
+        entity: Option<EntityUuid>,
+        prefab_refs: Vec<(PrefabUuid, PrefabRef)>,
+    ) -> Arc<legion_prefab::Prefab> {
+        let mut world = World::default();
+        let entities = entity
+            .into_iter()
+            .map(|entity_id| (entity_id, world.push((Transform::default(),))))
+            .collect();
+        Arc::new(legion_prefab::Prefab {
+            world,
+            prefab_meta: PrefabMeta {
+                id,
+                entities,
+                prefab_refs: prefab_refs.into_iter().collect(),
+            },
+        })
+    }
+
+    fn prefab_ref() -> PrefabRef {
+        PrefabRef {
+            overrides: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn nested_prefabs_are_cooked_first() {
+        let mut raw_prefabs = HashMap::new();
+        let nested = raw_prefab([1; 16], Some([10; 16]), vec![]);
+        let prefab = raw_prefab([2; 16], Some([20; 16]), vec![([1; 16], prefab_ref())]);
+
+        assert_eq!(Err([1; 16]), cook_order(&prefab, &raw_prefabs));
+
+        raw_prefabs.insert([1; 16], nested);
+        raw_prefabs.insert([2; 16], prefab.clone());
+        let order = cook_order(&prefab, &raw_prefabs).unwrap();
+        assert_eq!(vec![[1; 16], [2; 16]], order);
+
+        let registry = ComponentRegistryBuilder::default()
+            .auto_register_components()
+            .build();
+        let cooked = cook(&registry, &order, &raw_prefabs);
+        assert!(cooked.prefab.entities.contains_key(&[10; 16]));
+        assert!(cooked.prefab.entities.contains_key(&[20; 16]));
+        assert_eq!(2, <&Transform>::query().iter(&cooked.prefab.world).count());
+    }
+
+    #[test]
+    fn overrides_are_applied_to_nested_prefabs() {
+        let mut moved = Transform::default();
+        moved.set_translation_x(5.0);
+        let diff = serde_diff::Diff::serializable(&Transform::default(), &moved);
+        let mut overrides = HashMap::new();
+        overrides.insert(
+            [10; 16],
+            vec![ComponentOverride {
+                component_type: <Transform as TypeUuid>::UUID,
+                data: bincode::serialize(&diff).unwrap(),
+            }],
+        );
+
+        let mut raw_prefabs = HashMap::new();
+        raw_prefabs.insert([1; 16], raw_prefab([1; 16], Some([10; 16]), vec![]));
+        let prefab = raw_prefab([2; 16], None, vec![([1; 16], PrefabRef { overrides })]);
+        raw_prefabs.insert([2; 16], prefab.clone());
+
+        let registry = ComponentRegistryBuilder::default()
+            .auto_register_components()
+            .build();
+        let order = cook_order(&prefab, &raw_prefabs).unwrap();
+        let cooked = cook(&registry, &order, &raw_prefabs);
+        let entity = cooked.prefab.entities[&[10; 16]];
+        let transform = cooked
+            .prefab
+            .world
+            .entry_ref(entity)
+            .unwrap()
+            .into_component::<Transform>()
+            .unwrap()
+            .clone();
+        assert_eq!(5.0, transform.translation().x);
+        // The referenced prefab is left unchanged.
+        let nested = &raw_prefabs[&[1; 16]];
+        let nested_entity = nested.prefab_meta.entities[&[10; 16]];
+        let nested_transform = nested
+            .world
+            .entry_ref(nested_entity)
+            .unwrap()
+            .into_component::<Transform>()
+            .unwrap()
+            .clone();
+        assert_eq!(0.0, nested_transform.translation().x);
+    }
+
+    #[test]
+    fn dependents_include_indirect_references() {
+        let mut raw_prefabs = HashMap::new();
+        raw_prefabs.insert([1; 16], raw_prefab([1; 16], None, vec![]));
+        raw_prefabs.insert(
+            [2; 16],
+            raw_prefab([2; 16], None, vec![([1; 16], prefab_ref())]),
+        );
+        raw_prefabs.insert(
+            [3; 16],
+            raw_prefab([3; 16], None, vec![([2; 16], prefab_ref())]),
+        );
+        raw_prefabs.insert([4; 16], raw_prefab([4; 16], None, vec![]));
+
+        let mut ids = dependents([1; 16], &raw_prefabs);
+        ids.sort_unstable();
+        assert_eq!(vec![[2; 16], [3; 16]], ids);
+        assert!(dependents([3; 16], &raw_prefabs).is_empty());
+    }
+}
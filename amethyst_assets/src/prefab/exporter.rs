@@ -0,0 +1,405 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hasher,
+};
+
+use amethyst_core::{
+    ecs::{Entity, EntityStore, IntoQuery, World},
+    transform::{Children, Parent},
+};
+use amethyst_error::{format_err, Error};
+use fnv::FnvHasher;
+use legion_prefab::{PrefabFormatSerializer, PrefabMeta, PrefabSerdeContext};
+use prefab_format::{EntityUuid, PrefabUuid};
+
+use crate::prefab::{ComponentRegistry, PrefabInstance, PrefabParent};
+
+/// Exports entities of a `World` into the `.prefab` format read by `PrefabImporter`.
+///
+/// The given entities are exported with the entities of their `Children` subtrees. Only the
+/// components registered in the `ComponentRegistry` are exported. The hierarchy is exported as
+/// `PrefabParent` components, which become `Parent` components again when the prefab is spawned.
+///
+/// The entities keep the same UUID between exports, so that the assets referencing them, such as
+/// prefabs overriding their components, stay valid. The UUID of an entity is the one given to
+/// `with_entity_id`, and is otherwise derived from the UUID of its parent, or of the prefab for
+/// the given entities, and from:
+///
+/// * the UUID of the prefab entity it was spawned from, if it belongs to a `PrefabInstance`, so
+///   that the entities of several instances of a prefab get distinct UUIDs,
+/// * otherwise its position among the given entities or the `Children` of its parent.
+///
+/// # Examples
+///
+/// ```no_run
+/// use amethyst_assets::prefab::{ComponentRegistry, PrefabExporter};
+/// use amethyst_core::ecs::*;
+///
+/// fn save_level(world: &World, resources: &Resources, roots: &[Entity]) {
+///     let registry = resources.get::<ComponentRegistry>().unwrap();
+///     let prefab_id = *uuid::Uuid::new_v4().as_bytes();
+///     let ron = PrefabExporter::new(prefab_id)
+///         .export_ron(world, &registry, roots)
+///         .expect("Failed to export the level");
+///     std::fs::write("assets/level.prefab", ron).unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PrefabExporter {
+    prefab_id: PrefabUuid,
+    entity_ids: HashMap<Entity, EntityUuid>,
+}
+
+impl PrefabExporter {
+    /// Creates an exporter for the prefab with the given UUID.
+    ///
+    /// Use the UUID of the exported prefab to replace it when it is imported again.
+    pub fn new(prefab_id: PrefabUuid) -> Self {
+        PrefabExporter {
+            prefab_id,
+            entity_ids: HashMap::new(),
+        }
+    }
+
+    /// Sets the UUID of an exported entity.
+    pub fn with_entity_id(mut self, entity: Entity, id: EntityUuid) -> Self {
+        self.entity_ids.insert(entity, id);
+        self
+    }
+
+    /// Exports the given entities and their `Children` subtrees into a prefab.
+    pub fn export(
+        &self,
+        world: &World,
+        registry: &ComponentRegistry,
+        roots: &[Entity],
+    ) -> Result<legion_prefab::Prefab, Error> {
+        let entities = subtrees(world, roots)?;
+
+        let mut instance_ids = HashMap::new();
+        <&PrefabInstance>::query().for_each(world, |instance| {
+            // The entity with the handle keeps the UUID of its position, like the other instances.
+            let children = instance.children().collect::<HashSet<_>>();
+            for (id, entity) in instance.entity_ids() {
+                if children.contains(&entity) {
+                    instance_ids.insert(entity, id);
+                }
+            }
+        });
+
+        let mut ids = HashMap::new();
+        let mut used = HashSet::new();
+        for exported in &entities {
+            let id = match self.entity_ids.get(&exported.entity) {
+                Some(id) => *id,
+                None => {
+                    let parent_id = match exported.parent {
+                        Some(parent) => ids[&parent],
+                        None => self.prefab_id,
+                    };
+                    let position = match exported.parent {
+                        Some(_) => EntityKey::Child(exported.index),
+                        None => EntityKey::Root(exported.index),
+                    };
+                    let key = match instance_ids.get(&exported.entity) {
+                        Some(instance_id) if exported.parent.is_some() => {
+                            EntityKey::Instance(*instance_id)
+                        }
+                        _ => position,
+                    };
+                    let id = derived_entity_id(&parent_id, key);
+                    if used.contains(&id) {
+                        derived_entity_id(&parent_id, position)
+                    } else {
+                        id
+                    }
+                }
+            };
+            if !used.insert(id) {
+                return Err(format_err!(
+                    "The UUID {:?} of the entity {:?} is already used",
+                    id,
+                    exported.entity
+                ));
+            }
+            ids.insert(exported.entity, id);
+        }
+
+        let mut prefab_world = World::default();
+        let mut prefab_entities = HashMap::new();
+        let mut clone_impl = registry.copy_clone_impl();
+        for exported in &entities {
+            let prefab_entity =
+                prefab_world.clone_from_single(world, exported.entity, &mut clone_impl);
+            let parent = exported.parent.or_else(|| {
+                world
+                    .entry_ref(exported.entity)
+                    .ok()
+                    .and_then(|entry| entry.get_component::<Parent>().ok().map(|p| p.0))
+            });
+            if let Some(parent_id) = parent.and_then(|parent| ids.get(&parent)) {
+                if let Some(mut entry) = prefab_world.entry(prefab_entity) {
+                    entry.add_component(PrefabParent(*parent_id));
+                }
+            }
+            prefab_entities.insert(ids[&exported.entity], prefab_entity);
+        }
+
+        Ok(legion_prefab::Prefab {
+            world: prefab_world,
+            prefab_meta: PrefabMeta {
+                id: self.prefab_id,
+                entities: prefab_entities,
+                prefab_refs: HashMap::new(),
+            },
+        })
+    }
+
+    /// Exports the given entities and their `Children` subtrees into the RON text of a `.prefab`
+    /// file.
+    pub fn export_ron(
+        &self,
+        world: &World,
+        registry: &ComponentRegistry,
+        roots: &[Entity],
+    ) -> Result<String, Error> {
+        let prefab = self.export(world, registry, roots)?;
+        let registered_components = registry
+            .components_by_uuid()
+            .iter()
+            .map(|(uuid, registration)| (*uuid, registration.clone()))
+            .collect();
+        let prefab_serde_context = PrefabSerdeContext {
+            registered_components: &registered_components,
+        };
+
+        let mut ron_ser = ron::ser::Serializer::new(Some(ron::ser::PrettyConfig::default()), true);
+        let prefab_ser = PrefabFormatSerializer::new(prefab_serde_context, &prefab);
+        prefab_format::serialize(&mut ron_ser, &prefab_ser, prefab.prefab_id())
+            .map_err(|e| format_err!("Failed to serialize the prefab: {}", e))?;
+        Ok(ron_ser.into_output_string())
+    }
+}
+
+/// Entity reached by `subtrees`.
+struct Exported {
+    entity: Entity,
+    /// Entity whose `Children` contain the entity, or `None` for the given entities.
+    parent: Option<Entity>,
+    /// Position of the entity among the given entities or the `Children` of its parent.
+    index: usize,
+}
+
+/// Returns the given entities and their descendants, depth first, each entity once.
+fn subtrees(world: &World, roots: &[Entity]) -> Result<Vec<Exported>, Error> {
+    let mut entities: Vec<Exported> = Vec::new();
+    let mut stack: Vec<Exported> = roots
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(index, entity)| Exported {
+            entity: *entity,
+            parent: None,
+            index,
+        })
+        .collect();
+    while let Some(exported) = stack.pop() {
+        if entities.iter().any(|e| e.entity == exported.entity) {
+            continue;
+        }
+        let entry = world
+            .entry_ref(exported.entity)
+            .map_err(|_| format_err!("The entity {:?} does not exist", exported.entity))?;
+        if let Ok(children) = entry.get_component::<Children>() {
+            stack.extend(
+                children
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(index, child)| Exported {
+                        entity: *child,
+                        parent: Some(exported.entity),
+                        index,
+                    }),
+            );
+        }
+        entities.push(exported);
+    }
+    Ok(entities)
+}
+
+/// Data from which the UUID of an exported entity is derived, with the UUID of its parent.
+#[derive(Debug, Clone, Copy)]
+enum EntityKey {
+    /// Position among the given entities.
+    Root(usize),
+    /// Position among the `Children` of the parent.
+    Child(usize),
+    /// UUID of the prefab entity the entity was spawned from.
+    Instance(EntityUuid),
+}
+
+/// Returns the UUID of an exported entity, which is the same for every export on every platform.
+fn derived_entity_id(parent_id: &EntityUuid, key: EntityKey) -> EntityUuid {
+    let (tag, data) = match key {
+        EntityKey::Root(index) => (0, (index as u64).to_le_bytes().to_vec()),
+        EntityKey::Child(index) => (1, (index as u64).to_le_bytes().to_vec()),
+        EntityKey::Instance(id) => (2, id.to_vec()),
+    };
+    let mut id = [0; 16];
+    for (half, bytes) in id.chunks_mut(8).enumerate() {
+        let mut hasher = FnvHasher::default();
+        hasher.write(&[half as u8, tag]);
+        hasher.write(parent_id);
+        hasher.write(&data);
+        bytes.copy_from_slice(&hasher.finish().to_le_bytes());
+    }
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::transform::Transform;
+    use atelier_assets::loader::LoadHandle;
+
+    use super::*;
+    use crate::prefab::{importers::registered_components, ComponentRegistryBuilder};
+
+    fn registry() -> ComponentRegistry {
+        ComponentRegistryBuilder::default()
+            .auto_register_components()
+            .build()
+    }
+
+    fn import_ron(ron: &str) -> legion_prefab::Prefab {
+        let registered_components = registered_components();
+        let prefab_deser = legion_prefab::PrefabFormatDeserializer::new(PrefabSerdeContext {
+            registered_components: &registered_components,
+        });
+        let mut de = ron::de::Deserializer::from_bytes(ron.as_bytes()).unwrap();
+        prefab_format::deserialize(&mut de, &prefab_deser).unwrap();
+        prefab_deser.prefab()
+    }
+
+    /// Returns the UUID of the prefab parent of each prefab entity that has one.
+    fn prefab_parents(prefab: &legion_prefab::Prefab) -> HashMap<EntityUuid, EntityUuid> {
+        prefab
+            .prefab_meta
+            .entities
+            .iter()
+            .filter_map(|(id, entity)| {
+                let entry = prefab.world.entry_ref(*entity).unwrap();
+                let parent = entry.get_component::<PrefabParent>().ok()?;
+                Some((*id, parent.0))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exported_entities_are_imported_again() {
+        let mut world = World::default();
+        let child = world.push((Transform::default(),));
+        let root = world.push((Transform::default(), Children::with(&[child])));
+        world.push((Transform::default(),));
+
+        let ron = PrefabExporter::new([1; 16])
+            .with_entity_id(root, [2; 16])
+            .export_ron(&world, &registry(), &[root])
+            .unwrap();
+        let prefab = import_ron(&ron);
+
+        let child_id = derived_entity_id(&[2; 16], EntityKey::Child(0));
+        assert_eq!([1; 16], prefab.prefab_id());
+        assert_eq!(2, prefab.prefab_meta.entities.len());
+        assert!(prefab.prefab_meta.entities.contains_key(&[2; 16]));
+        assert!(prefab.prefab_meta.entities.contains_key(&child_id));
+        assert_eq!(2, <&Transform>::query().iter(&prefab.world).count());
+        assert_eq!(Some(&[2; 16]), prefab_parents(&prefab).get(&child_id));
+    }
+
+    #[test]
+    fn instances_of_a_prefab_are_exported_with_distinct_ids() {
+        let mut world = World::default();
+        let level = world.push((Transform::default(),));
+        let mut instance_roots = Vec::new();
+        for _ in 0..2 {
+            let grandchild = world.push((Transform::default(),));
+            let child = world.push((Transform::default(), Children::with(&[grandchild])));
+            let root = world.push((
+                Transform::default(),
+                Parent(level),
+                Children::with(&[child]),
+            ));
+            world.entry(child).unwrap().add_component(Parent(root));
+            world
+                .entry(grandchild)
+                .unwrap()
+                .add_component(Parent(child));
+            let entities = vec![([5; 16], root), ([6; 16], child), ([7; 16], grandchild)];
+            let instance = PrefabInstance::new(
+                LoadHandle(1),
+                1,
+                0,
+                Some([5; 16]),
+                entities.into_iter().collect(),
+            );
+            world.entry(root).unwrap().add_component(instance);
+            instance_roots.push(root);
+        }
+        world
+            .entry(level)
+            .unwrap()
+            .add_component(Children::with(&instance_roots));
+
+        let exporter = PrefabExporter::new([1; 16]).with_entity_id(level, [2; 16]);
+        let ron = exporter.export_ron(&world, &registry(), &[level]).unwrap();
+        let prefab = import_ron(&ron);
+
+        assert_eq!(7, prefab.prefab_meta.entities.len());
+        assert_eq!(7, <&Transform>::query().iter(&prefab.world).count());
+        let parents = prefab_parents(&prefab);
+        assert_eq!(6, parents.len());
+        let roots = parents
+            .iter()
+            .filter(|(_, parent)| **parent == [2; 16])
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        assert_eq!(2, roots.len());
+        for root in roots {
+            let children = parents.iter().filter(|(_, parent)| **parent == root);
+            let (child, _) = children.clone().next().unwrap();
+            assert_eq!(1, children.count());
+            assert_eq!(
+                1,
+                parents.values().filter(|parent| *parent == child).count()
+            );
+        }
+
+        let again = exporter.export(&world, &registry(), &[level]).unwrap();
+        let mut ids = prefab.prefab_meta.entities.keys().collect::<Vec<_>>();
+        let mut ids_again = again.prefab_meta.entities.keys().collect::<Vec<_>>();
+        ids.sort();
+        ids_again.sort();
+        assert_eq!(ids, ids_again);
+    }
+
+    #[test]
+    fn derived_entity_ids_are_stable_and_distinct() {
+        assert_eq!(
+            derived_entity_id(&[1; 16], EntityKey::Root(0)),
+            derived_entity_id(&[1; 16], EntityKey::Root(0))
+        );
+        assert_ne!(
+            derived_entity_id(&[1; 16], EntityKey::Root(0)),
+            derived_entity_id(&[1; 16], EntityKey::Root(1))
+        );
+        assert_ne!(
+            derived_entity_id(&[1; 16], EntityKey::Root(0)),
+            derived_entity_id(&[1; 16], EntityKey::Child(0))
+        );
+        assert_ne!(
+            derived_entity_id(&[1; 16], EntityKey::Root(0)),
+            derived_entity_id(&[2; 16], EntityKey::Root(0))
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use amethyst_core::{
+    ecs::{Entity, EntityStore, World},
+    transform::Parent,
+};
+use prefab_format::EntityUuid;
+use serde::{Deserialize, Serialize};
+use serde_diff::SerdeDiff;
+use type_uuid::TypeUuid;
+
+/// Parent of a prefab entity, by the UUID of the parent entity in the prefab.
+///
+/// `PrefabExporter` exports the `Parent` components of the exported entities as `PrefabParent`,
+/// which is replaced by the `Parent` of the spawned parent entity when the prefab is spawned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[serde_diff(opaque)]
+#[uuid = "9c2f4a61-7e3b-4d58-a0c6-1b8e5f2d7a94"]
+pub struct PrefabParent(pub EntityUuid);
+
+/// Replaces the `PrefabParent` components of the spawned entities by a `Parent` of the entity
+/// spawned for the parent prefab entity.
+pub(crate) fn resolve_parents(world: &mut World, entities: &HashMap<EntityUuid, Entity>) {
+    for entity in entities.values() {
+        let parent = match world.entry_ref(*entity) {
+            Ok(entry) => match entry.get_component::<PrefabParent>() {
+                Ok(PrefabParent(id)) => *id,
+                Err(_) => continue,
+            },
+            Err(_) => continue,
+        };
+        if let Some(mut entry) = world.entry(*entity) {
+            entry.remove_component::<PrefabParent>();
+            match entities.get(&parent) {
+                Some(parent) => entry.add_component(Parent(*parent)),
+                None => log::warn!("Prefab parent {:?} of {:?} was not spawned", parent, entity),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefab_parents_are_replaced_by_parents() {
+        let mut world = World::default();
+        let root = world.push((0u8,));
+        let child = world.push((PrefabParent([1; 16]),));
+        let orphan = world.push((PrefabParent([3; 16]),));
+        let entities = vec![([1; 16], root), ([2; 16], child), ([4; 16], orphan)]
+            .into_iter()
+            .collect();
+
+        resolve_parents(&mut world, &entities);
+
+        let child = world.entry_ref(child).unwrap();
+        assert_eq!(&Parent(root), child.get_component::<Parent>().unwrap());
+        assert!(child.get_component::<PrefabParent>().is_err());
+        let orphan = world.entry_ref(orphan).unwrap();
+        assert!(orphan.get_component::<Parent>().is_err());
+        assert!(orphan.get_component::<PrefabParent>().is_err());
+    }
+}
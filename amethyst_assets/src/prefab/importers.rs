@@ -1,7 +1,7 @@
 use std::{collections::HashMap, io::Read};
 
 use atelier_assets::{
-    core::AssetUuid,
+    core::{AssetRef, AssetUuid},
     importer::{self as atelier_importer, ImportedAsset, Importer, ImporterValue},
 };
 use atelier_importer::ImportOp;
@@ -24,6 +24,14 @@ pub struct PrefabImporterState {
     pub id: Option<AssetUuid>,
 }
 
+/// Returns the registrations of the component types registered with `register_component_type!`,
+/// keyed by their UUID.
+pub(crate) fn registered_components() -> HashMap<ComponentTypeUuid, ComponentRegistration> {
+    legion_prefab::iter_component_registrations()
+        .map(|reg| (*reg.uuid(), reg.clone()))
+        .collect()
+}
+
 /// The importer for '.prefab' files.
 #[derive(Default, TypeUuid)]
 #[uuid = "5bdf4d06-a1cb-437b-b182-d6d8cb23512c"]
@@ -65,16 +73,8 @@ impl Importer for PrefabImporter {
         let mut de = ron::de::Deserializer::from_bytes(bytes.as_slice()).unwrap();
 
         // Create the component registry
-        let registered_components = {
-            let comp_registrations = legion_prefab::iter_component_registrations();
-            log::info!("Getting registered components");
-            let component_types: HashMap<ComponentTypeUuid, ComponentRegistration> =
-                comp_registrations
-                    .map(|reg| (*reg.uuid(), reg.clone()))
-                    .collect();
-
-            component_types
-        };
+        log::info!("Getting registered components");
+        let registered_components = registered_components();
 
         let prefab_serde_context = legion_prefab::PrefabSerdeContext {
             registered_components: &registered_components,
@@ -120,6 +120,15 @@ impl Importer for PrefabImporter {
         let prefab_id = prefab_asset.raw_prefab.prefab_id();
         state.id = Some(AssetUuid(prefab_id));
 
+        // Nested prefabs are cooked into this one, so they must be loaded first
+        let load_deps = prefab_asset
+            .raw_prefab
+            .prefab_meta
+            .prefab_refs
+            .keys()
+            .map(|id| AssetRef::Uuid(AssetUuid(*id)))
+            .collect();
+
         //{
         //    //let mut ron_serializer = ron::ser::Serializer::new(Some(ron::ser::PrettyConfig::default()), true);
         //    let ron_string = ron::ser::to_string_pretty(&prefab_asset, Default::default()).unwrap();
@@ -150,7 +159,7 @@ impl Importer for PrefabImporter {
                 id: state.id.expect("AssetUuid not generated"),
                 search_tags: Vec::new(),
                 build_deps: Vec::new(),
-                load_deps: Vec::new(),
+                load_deps,
                 asset_data: Box::new(prefab_asset),
                 build_pipeline: None,
             }],
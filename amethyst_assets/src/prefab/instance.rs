@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use amethyst_core::ecs::{storage::Component, Entity, World};
+use atelier_assets::loader::LoadHandle;
+use prefab_format::EntityUuid;
+
+/// Component overridden on an entity of a prefab instance.
+struct Override {
+    target: EntityUuid,
+    apply: Box<dyn Fn(&mut World, Entity) + Send + Sync>,
+    type_name: &'static str,
+}
+
+/// Records the entities spawned for a `Handle<Prefab>`, added to the entity with the handle once
+/// the prefab has been spawned.
+///
+/// The prefab is spawned once per entity. When the prefab asset is reloaded, or cooked again
+/// because a prefab it references was reloaded, the previously
+/// spawned entities are deleted and the prefab is spawned again, after which the overrides
+/// added with `add_override` are applied again. Other changes made to the spawned entities at
+/// runtime are lost, as the reloaded prefab can not be compared with the previous one.
+///
+/// The entity with the handle is not spawned again: it keeps the components it has when the
+/// prefab is reloaded, so changes to the root entity of the prefab only apply to instances
+/// spawned after the reload.
+///
+/// # Examples
+///
+/// ```
+/// use amethyst_assets::prefab::PrefabInstance;
+/// use amethyst_core::{ecs::*, transform::Transform};
+///
+/// fn move_instance(world: &mut World, entity: Entity, transform: Transform) {
+///     let mut entry = world.entry(entity).unwrap();
+///     entry.add_component(transform.clone());
+///     if let Ok(instance) = entry.get_component_mut::<PrefabInstance>() {
+///         // Keep the transform when the prefab is reloaded
+///         instance.add_override(entity, transform);
+///     }
+/// }
+/// ```
+#[derive(Default)]
+pub struct PrefabInstance {
+    prefab: Option<LoadHandle>,
+    version: u32,
+    revision: u32,
+    root: Option<EntityUuid>,
+    entities: HashMap<EntityUuid, Entity>,
+    overrides: Vec<Override>,
+}
+
+impl std::fmt::Debug for PrefabInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrefabInstance")
+            .field("prefab", &self.prefab)
+            .field("version", &self.version)
+            .field("entities", &self.entities)
+            .field(
+                "overrides",
+                &self
+                    .overrides
+                    .iter()
+                    .map(|o| o.type_name)
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl PrefabInstance {
+    pub(crate) fn new(
+        prefab: LoadHandle,
+        version: u32,
+        revision: u32,
+        root: Option<EntityUuid>,
+        entities: HashMap<EntityUuid, Entity>,
+    ) -> Self {
+        PrefabInstance {
+            prefab: Some(prefab),
+            version,
+            revision,
+            root,
+            entities,
+            overrides: Vec::new(),
+        }
+    }
+
+    /// Returns `true` if the instance was spawned from a prefab.
+    pub(crate) fn is_spawned(&self) -> bool {
+        self.prefab.is_some()
+    }
+
+    /// Returns `true` if the instance was spawned from the given version and revision of the
+    /// prefab.
+    pub(crate) fn is_spawned_from(&self, prefab: LoadHandle, version: u32, revision: u32) -> bool {
+        self.prefab == Some(prefab) && self.version == version && self.revision == revision
+    }
+
+    /// Returns the version of the prefab asset the instance was spawned from.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Returns all entities spawned for the prefab, including the entity with the handle.
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.entities.values().copied()
+    }
+
+    /// Returns the entities spawned for the prefab, other than the entity with the handle.
+    pub fn children(&self) -> impl Iterator<Item = Entity> + '_ {
+        let root = self.root;
+        self.entities
+            .iter()
+            .filter(move |(id, _)| Some(**id) != root)
+            .map(|(_, entity)| *entity)
+    }
+
+    /// Returns the UUIDs of the prefab entities with the entities spawned for them.
+    pub fn entity_ids(&self) -> impl Iterator<Item = (EntityUuid, Entity)> + '_ {
+        self.entities.iter().map(|(id, entity)| (*id, *entity))
+    }
+
+    /// Returns the entity spawned for the prefab entity with the given UUID.
+    pub fn entity(&self, id: &EntityUuid) -> Option<Entity> {
+        self.entities.get(id).copied()
+    }
+
+    /// Records that `component` overrides the prefab's component of type `T` on `entity`, so
+    /// that it is applied again when the prefab is respawned.
+    ///
+    /// This replaces any previous override of `T` on `entity`, and does not change the world.
+    /// Returns `false` if `entity` was not spawned for this instance.
+    pub fn add_override<T: Component + Clone>(&mut self, entity: Entity, component: T) -> bool {
+        let target = match self.entities.iter().find(|(_, e)| **e == entity) {
+            Some((id, _)) => *id,
+            None => return false,
+        };
+        let type_name = std::any::type_name::<T>();
+        self.overrides
+            .retain(|o| o.target != target || o.type_name != type_name);
+        self.overrides.push(Override {
+            target,
+            apply: Box::new(move |world, entity| {
+                if let Some(mut entry) = world.entry(entity) {
+                    entry.add_component(component.clone());
+                }
+            }),
+            type_name,
+        });
+        true
+    }
+
+    /// Removes the override of `T` on `entity`, which takes effect when the prefab is respawned.
+    pub fn remove_override<T: Component>(&mut self, entity: Entity) {
+        let type_name = std::any::type_name::<T>();
+        let entities = &self.entities;
+        self.overrides
+            .retain(|o| o.type_name != type_name || entities.get(&o.target) != Some(&entity));
+    }
+
+    /// Returns the number of overridden components.
+    pub fn num_overrides(&self) -> usize {
+        self.overrides.len()
+    }
+
+    /// Takes the overrides of `previous`, and applies them to the entities of this instance.
+    pub(crate) fn reapply_overrides(&mut self, previous: &mut PrefabInstance, world: &mut World) {
+        self.overrides = std::mem::take(&mut previous.overrides);
+        for o in &self.overrides {
+            match self.entities.get(&o.target) {
+                Some(entity) => (o.apply)(world, *entity),
+                None => {
+                    log::warn!(
+                        "Prefab override of {} targets an entity that is no longer in the prefab",
+                        o.type_name
+                    )
+                }
+            }
+        }
+    }
+}
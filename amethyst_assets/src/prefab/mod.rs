@@ -4,6 +4,15 @@ pub use importers::PrefabImporter;
 mod assets;
 pub use assets::{Prefab, RawPrefab};
 
+mod exporter;
+pub use exporter::PrefabExporter;
+
+mod hierarchy;
+pub use hierarchy::PrefabParent;
+
+mod instance;
+pub use instance::PrefabInstance;
+
 mod system;
 pub use system::prefab_spawning_tick;
 
@@ -14,3 +23,4 @@ pub use legion_prefab::{register_component_type, ComponentRegistration};
 // register core components
 register_component_type!(amethyst_core::transform::Transform);
 register_component_type!(amethyst_core::transform::TransformValues);
+register_component_type!(PrefabParent);
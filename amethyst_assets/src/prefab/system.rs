@@ -3,12 +3,14 @@ use std::collections::HashMap;
 use amethyst_core::ecs::{query, Entity, IntoQuery, Resources, World};
 
 use crate::{
-    prefab::{ComponentRegistry, Prefab},
-    AssetStorage, Handle,
+    prefab::{hierarchy::resolve_parents, ComponentRegistry, Prefab, PrefabInstance},
+    AssetHandle, AssetStorage, Handle,
 };
 
 /// Attaches prefabs to entities that have Handle<Prefab>
-/// FIXME: Add a check so that the prefab is only applied once.
+///
+/// The prefab is spawned once per entity, which then gets a `PrefabInstance`. It is spawned again
+/// when the prefab asset is reloaded or the handle changes, see `PrefabInstance` for what is kept.
 pub fn prefab_spawning_tick(world: &mut World, resources: &mut Resources) {
     let component_registry = resources
         .get::<ComponentRegistry>()
@@ -18,25 +20,127 @@ pub fn prefab_spawning_tick(world: &mut World, resources: &mut Resources) {
         .expect("AssetStorage<Prefab> can not be retrieved from ECS Resources");
 
     let mut clone_impl_result = HashMap::default();
-    let mut prefab_handle_query = <(Entity, &Handle<Prefab>)>::query();
+    let mut prefab_handle_query = <(Entity, &Handle<Prefab>, Option<&mut PrefabInstance>)>::query();
 
-    let mut prefabs: Vec<(Entity, &legion_prefab::CookedPrefab)> = Vec::new();
+    let mut prefabs = Vec::new();
 
-    prefab_handle_query.for_each(world, |(entity, handle)| {
-        if let Some(Prefab { prefab }) = prefab_storage.get(handle) {
-            prefabs.push((*entity, prefab));
+    prefab_handle_query.for_each_mut(world, |(entity, handle, instance)| {
+        if let Some((Prefab { prefab }, version)) = prefab_storage.get_asset_with_version(handle) {
+            let revision = prefab_storage
+                .get_revision(handle.load_handle())
+                .unwrap_or(0);
+            let previous = match instance {
+                Some(instance)
+                    if instance.is_spawned_from(handle.load_handle(), version, revision) =>
+                {
+                    return;
+                }
+                Some(instance) => std::mem::take(instance),
+                None => PrefabInstance::default(),
+            };
+            prefabs.push((*entity, handle.clone(), prefab, version, revision, previous));
         }
     });
 
     let mut entity_query = <(Entity,)>::query();
-    for (entity, prefab) in prefabs.iter() {
-        // Spawn the prefab in a new world.
+    for (entity, handle, prefab, version, revision, mut previous) in prefabs {
+        for child in previous.children() {
+            world.remove(child);
+        }
+        // The root of the prefab is spawned onto the entity with the handle the first time only,
+        // so that the entity keeps all its components when the prefab is spawned again.
+        let respawn = previous.is_spawned();
+        let prefab_root = entity_query.iter(&prefab.world).next().map(|(root,)| *root);
         clone_impl_result.clear();
-        if let Some((root_entity,)) = entity_query.iter(&prefab.world).next() {
-            clone_impl_result.insert(*root_entity, *entity);
-        };
+        if let (Some(prefab_root), false) = (prefab_root, respawn) {
+            clone_impl_result.insert(prefab_root, entity);
+        }
         let mut spawn_impl = component_registry.spawn_clone_impl(&resources, &clone_impl_result);
-        world.clone_from(&prefab.world, &query::any(), &mut spawn_impl);
+        let spawned = world.clone_from(&prefab.world, &query::any(), &mut spawn_impl);
+        drop(spawn_impl);
+
+        let root = prefab_root
+            .and_then(|root| prefab.entities.iter().find(|(_, e)| **e == root))
+            .map(|(id, _)| *id);
+        let mut entities = prefab
+            .entities
+            .iter()
+            .filter_map(|(id, e)| spawned.get(e).map(|spawned| (*id, *spawned)))
+            .collect::<HashMap<_, _>>();
+        if respawn {
+            if let Some(spawned_root) = root.and_then(|root| entities.insert(root, entity)) {
+                world.remove(spawned_root);
+            }
+        }
+        resolve_parents(world, &entities);
+        let mut instance =
+            PrefabInstance::new(handle.load_handle(), version, revision, root, entities);
+        instance.reapply_overrides(&mut previous, world);
+
+        if let Some(mut entry) = world.entry(entity) {
+            entry.add_component(handle);
+            entry.add_component(instance);
+        }
         log::debug!("Spawn {:?}", entity);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::transform::Transform;
+    use atelier_assets::loader::LoadHandle;
+
+    use super::*;
+    use crate::{prefab::ComponentRegistryBuilder, storage::tests::storage};
+
+    /// Component that is not registered with `register_component_type!`.
+    struct Unregistered(u32);
+
+    fn prefab() -> Prefab {
+        let mut world = World::default();
+        let root = world.push((Transform::default(),));
+        let child = world.push((Transform::default(),));
+        let entities = vec![([1; 16], root), ([2; 16], child)]
+            .into_iter()
+            .collect();
+        Prefab {
+            prefab: legion_prefab::CookedPrefab { world, entities },
+        }
+    }
+
+    #[test]
+    fn reloads_keep_the_components_of_the_entity_with_the_handle() {
+        let load_handle = LoadHandle(1);
+        let mut storage = storage::<Prefab>();
+        storage.update_asset(load_handle, prefab(), 1, 1);
+        storage.commit_asset(load_handle, 1);
+        let mut resources = Resources::default();
+        resources.insert(
+            ComponentRegistryBuilder::default()
+                .auto_register_components()
+                .build(),
+        );
+        resources.insert(storage);
+        let mut world = World::default();
+        let handle = Handle::<Prefab>::new(crossbeam_channel::unbounded().0, load_handle);
+        let entity = world.push((handle,));
+
+        prefab_spawning_tick(&mut world, &mut resources);
+        world.entry(entity).unwrap().add_component(Unregistered(7));
+        {
+            let mut storage = resources.get_mut::<AssetStorage<Prefab>>().unwrap();
+            storage.update_asset(load_handle, prefab(), 2, 1);
+            storage.commit_asset(load_handle, 2);
+        }
+        prefab_spawning_tick(&mut world, &mut resources);
+
+        let entry = world.entry_ref(entity).unwrap();
+        assert_eq!(7, entry.get_component::<Unregistered>().unwrap().0);
+        assert!(entry.get_component::<Transform>().is_ok());
+        assert!(entry
+            .get_component::<PrefabInstance>()
+            .unwrap()
+            .is_spawned_from(load_handle, 2, 0));
+        assert_eq!(2, world.len());
+    }
+}
@@ -0,0 +1,460 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    sync::{Arc, RwLock},
+};
+
+use atelier_assets::{
+    core::AssetUuid,
+    loader::{
+        io::{DataRequest, LoaderIO, MetadataRequest, ResolveRequest},
+        loader::LoaderState,
+        storage::{IndirectionTable, LoadStatus},
+        Loader as AtelierLoader,
+    },
+};
+
+use log::debug;
+
+use crate::{loader::LoadHandle, variant::AssetVariants};
+
+/// Urgency of loading an asset, see `Loader::load_with_priority`.
+///
+/// Pending assets with a higher priority are requested and processed first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LoadPriority {
+    /// For assets that are not needed soon, such as the props of the next level.
+    Low,
+    /// The priority of loads without a priority.
+    Normal,
+    /// For assets that are needed soon.
+    High,
+    /// For assets the player is waiting on, such as the font of the UI.
+    Critical,
+}
+
+impl Default for LoadPriority {
+    fn default() -> Self {
+        LoadPriority::Normal
+    }
+}
+
+/// Priorities and cancellations of the pending loads, shared by the loader, its IO and the
+/// processing queues.
+#[derive(Clone, Default)]
+pub(crate) struct LoadPriorities {
+    inner: Arc<RwLock<PrioritiesInner>>,
+}
+
+#[derive(Default)]
+struct PrioritiesInner {
+    /// Priorities as set on the handles of the user, which are indirect for loads by path.
+    requested: HashMap<LoadHandle, LoadPriority>,
+    /// Priorities by direct load handle.
+    handles: HashMap<LoadHandle, LoadPriority>,
+    /// Priorities by asset UUID.
+    assets: HashMap<AssetUuid, LoadPriority>,
+    /// Priorities of the paths loaded with `Loader::load_with_priority`, until they are resolved.
+    paths: HashMap<String, LoadPriority>,
+    /// Direct load handles of the assets whose last handle was dropped while loading.
+    cancelled: HashSet<LoadHandle>,
+    /// UUIDs of the assets of the cancelled loads.
+    cancelled_assets: HashSet<AssetUuid>,
+}
+
+impl LoadPriorities {
+    /// Sets the priority of the load of the given handle.
+    pub(crate) fn set(&self, handle: LoadHandle, priority: LoadPriority) {
+        let mut inner = self
+            .inner
+            .write()
+            .expect("LoadPriorities lock was poisoned");
+        inner.requested.insert(handle, priority);
+        if !handle.is_indirect() {
+            inner.handles.insert(handle, priority);
+        }
+    }
+
+    /// Sets the priority of resolving the given path to its asset.
+    pub(crate) fn set_path(&self, path: &str, priority: LoadPriority) {
+        let mut inner = self
+            .inner
+            .write()
+            .expect("LoadPriorities lock was poisoned");
+        inner.paths.insert(path.to_string(), priority);
+    }
+
+    /// Returns the priority of resolving the given path, and forgets it.
+    fn take_path(&self, path: &str) -> LoadPriority {
+        let mut inner = self
+            .inner
+            .write()
+            .expect("LoadPriorities lock was poisoned");
+        inner.paths.remove(path).unwrap_or_default()
+    }
+
+    /// Returns the priority of resolving the given path.
+    fn get_path(&self, path: &str) -> LoadPriority {
+        let inner = self.inner.read().expect("LoadPriorities lock was poisoned");
+        inner.paths.get(path).copied().unwrap_or_default()
+    }
+
+    /// Returns the priority of the load of the given direct load handle.
+    pub(crate) fn get(&self, handle: LoadHandle) -> LoadPriority {
+        let inner = self.inner.read().expect("LoadPriorities lock was poisoned");
+        inner.handles.get(&handle).copied().unwrap_or_default()
+    }
+
+    /// Returns the priority of the load of the given asset.
+    pub(crate) fn get_asset(&self, id: &AssetUuid) -> LoadPriority {
+        let inner = self.inner.read().expect("LoadPriorities lock was poisoned");
+        inner.assets.get(id).copied().unwrap_or_default()
+    }
+
+    /// Forgets the priority of the given direct load handle, once its asset is processed.
+    pub(crate) fn forget(&self, handle: LoadHandle) {
+        let mut inner = self
+            .inner
+            .write()
+            .expect("LoadPriorities lock was poisoned");
+        inner.requested.remove(&handle);
+        inner.handles.remove(&handle);
+    }
+
+    /// Returns `true` if the last handle to the asset of the direct load handle was dropped
+    /// before it was loaded.
+    pub(crate) fn is_cancelled(&self, handle: LoadHandle) -> bool {
+        let inner = self.inner.read().expect("LoadPriorities lock was poisoned");
+        inner.cancelled.contains(&handle)
+    }
+
+    /// Returns `true` if the last handle to the given asset was dropped before it was loaded.
+    fn is_asset_cancelled(&self, id: &AssetUuid) -> bool {
+        let inner = self.inner.read().expect("LoadPriorities lock was poisoned");
+        inner.cancelled_assets.contains(id)
+    }
+
+    /// Resolves the handles with a priority to their asset, forgets the priorities of finished
+    /// loads, and records the loads cancelled by dropping the handles in `released`.
+    pub(crate) fn update(
+        &self,
+        loader: &AtelierLoader,
+        indirection_table: &IndirectionTable,
+        variants: &AssetVariants,
+        released: &[LoadHandle],
+    ) {
+        let mut inner = self
+            .inner
+            .write()
+            .expect("LoadPriorities lock was poisoned");
+        let PrioritiesInner {
+            requested,
+            handles,
+            assets,
+            cancelled,
+            cancelled_assets,
+            ..
+        } = &mut *inner;
+
+        let direct = |handle: LoadHandle| {
+            if handle.is_indirect() {
+                indirection_table.resolve(handle)
+            } else {
+                Some(handle)
+            }
+        };
+
+        for handle in released.iter().filter_map(|handle| direct(*handle)) {
+            let unreferenced = loader
+                .get_load_info(handle)
+                .map_or(true, |info| info.refs == 0);
+            if unreferenced && matches!(loader.get_load_status(handle), LoadStatus::Loading) {
+                cancelled.insert(handle);
+            }
+        }
+        cancelled.retain(|handle| {
+            let referenced = loader
+                .get_load_info(*handle)
+                .map_or(false, |info| info.refs > 0);
+            let gone = matches!(
+                loader.get_load_status(*handle),
+                LoadStatus::NotRequested | LoadStatus::DoesNotExist
+            );
+            !referenced && !gone
+        });
+        cancelled_assets.clear();
+        cancelled_assets.extend(
+            cancelled
+                .iter()
+                .filter_map(|handle| loader.get_load_info(*handle))
+                .map(|info| info.asset_id),
+        );
+
+        requested.retain(|handle, _| {
+            match variants
+                .get_load_status(loader, *handle)
+                .unwrap_or_else(|| loader.get_load_status(*handle))
+            {
+                LoadStatus::Loaded | LoadStatus::DoesNotExist | LoadStatus::Error(_) => false,
+                _ => true,
+            }
+        });
+        handles.clear();
+        assets.clear();
+        for (handle, priority) in requested.iter() {
+            // Localized loads pass their priority on to the variant they load.
+            let handle = variants.loading(*handle).unwrap_or(*handle);
+            if let Some(handle) = direct(handle) {
+                if cancelled.contains(&handle) {
+                    continue;
+                }
+                let priority = handles
+                    .get(&handle)
+                    .map_or(*priority, |p| (*p).max(*priority));
+                handles.insert(handle, priority);
+                if let Some(info) = loader.get_load_info(handle) {
+                    assets.insert(info.asset_id, priority);
+                }
+            }
+        }
+    }
+}
+
+/// Maximum number of requests of each kind forwarded to the IO per tick of the loader, so that
+/// the requests of a higher priority made in later ticks are forwarded before the pending ones.
+const MAX_REQUESTS_PER_TICK: usize = 64;
+
+/// Requests waiting to be forwarded to the IO, see `PrioritizedIO`.
+struct PendingRequests<R> {
+    pending: Vec<R>,
+    forwarded: usize,
+    max_per_tick: usize,
+}
+
+impl<R> PendingRequests<R> {
+    fn new(max_per_tick: usize) -> Self {
+        PendingRequests {
+            pending: Vec::new(),
+            forwarded: 0,
+            max_per_tick,
+        }
+    }
+
+    /// Returns the requests to forward, highest priority first and in the order they were made
+    /// for the same priority, and the cancelled requests, which are removed from the queue.
+    fn take<P, C>(&mut self, priority: P, cancelled: C) -> (Vec<R>, Vec<R>)
+    where
+        P: Fn(&R) -> LoadPriority,
+        C: Fn(&R) -> bool,
+    {
+        let (cancelled, mut pending): (Vec<_>, Vec<_>) = self
+            .pending
+            .drain(..)
+            .partition(|request| cancelled(request));
+        pending.sort_by_key(|request| std::cmp::Reverse(priority(request)));
+        let count = self
+            .max_per_tick
+            .saturating_sub(self.forwarded)
+            .min(pending.len());
+        self.forwarded += count;
+        self.pending = pending.split_off(count);
+        (pending, cancelled)
+    }
+
+    /// Starts a new tick, in which `max_per_tick` requests can be forwarded again.
+    fn next_tick(&mut self) {
+        self.forwarded = 0;
+    }
+}
+
+/// Loader IO making the requests of the assets with a higher `LoadPriority` first.
+///
+/// At most `MAX_REQUESTS_PER_TICK` requests of each kind are forwarded per tick of the loader,
+/// highest priority first, so that urgent loads overtake the loads requested before them. The
+/// data of the cancelled loads is not requested. Metadata requests are not split, and have the
+/// highest priority of the assets they request.
+pub(crate) struct PrioritizedIO {
+    inner: Box<dyn LoaderIO>,
+    priorities: LoadPriorities,
+    metadata: PendingRequests<MetadataRequest>,
+    resolves: PendingRequests<ResolveRequest>,
+    artifacts: PendingRequests<DataRequest>,
+}
+
+impl PrioritizedIO {
+    pub(crate) fn new(inner: Box<dyn LoaderIO>, priorities: LoadPriorities) -> Self {
+        PrioritizedIO {
+            inner,
+            priorities,
+            metadata: PendingRequests::new(MAX_REQUESTS_PER_TICK),
+            resolves: PendingRequests::new(MAX_REQUESTS_PER_TICK),
+            artifacts: PendingRequests::new(MAX_REQUESTS_PER_TICK),
+        }
+    }
+
+    fn forward_metadata(&mut self) {
+        let priorities = &self.priorities;
+        let (requests, _) = self.metadata.take(
+            |request| {
+                request
+                    .requested_assets()
+                    .map(|id| priorities.get_asset(id))
+                    .max()
+                    .unwrap_or_default()
+            },
+            |_| false,
+        );
+        for request in requests {
+            self.inner.get_asset_metadata_with_dependencies(request);
+        }
+    }
+
+    fn forward_resolves(&mut self) {
+        let priorities = &self.priorities;
+        let (requests, _) = self.resolves.take(
+            |request| priorities.get_path(request.identifier().path()),
+            |_| false,
+        );
+        for request in &requests {
+            priorities.take_path(request.identifier().path());
+        }
+        if !requests.is_empty() {
+            self.inner.get_asset_candidates(requests);
+        }
+    }
+
+    fn forward_artifacts(&mut self) {
+        let priorities = &self.priorities;
+        let (requests, cancelled) = self.artifacts.take(
+            |request| priorities.get_asset(&request.asset_id()),
+            |request| priorities.is_asset_cancelled(&request.asset_id()),
+        );
+        for request in cancelled {
+            debug!(
+                "Data request of asset {:?} was cancelled",
+                request.asset_id()
+            );
+            request.error(io::Error::new(io::ErrorKind::Other, "Load cancelled"));
+        }
+        if !requests.is_empty() {
+            self.inner.get_artifacts(requests);
+        }
+    }
+}
+
+impl LoaderIO for PrioritizedIO {
+    fn get_asset_metadata_with_dependencies(&mut self, request: MetadataRequest) {
+        self.metadata.pending.push(request);
+        self.forward_metadata();
+    }
+
+    fn get_asset_candidates(&mut self, requests: Vec<ResolveRequest>) {
+        self.resolves.pending.extend(requests);
+        self.forward_resolves();
+    }
+
+    fn get_artifacts(&mut self, requests: Vec<DataRequest>) {
+        self.artifacts.pending.extend(requests);
+        self.forward_artifacts();
+    }
+
+    fn tick(&mut self, loader: &mut LoaderState) {
+        self.metadata.next_tick();
+        self.resolves.next_tick();
+        self.artifacts.next_tick();
+        self.forward_metadata();
+        self.forward_resolves();
+        self.forward_artifacts();
+        self.inner.tick(loader);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn priorities_are_ordered_by_urgency() {
+        let mut priorities = vec![
+            LoadPriority::Normal,
+            LoadPriority::Critical,
+            LoadPriority::Low,
+            LoadPriority::High,
+        ];
+        priorities.sort();
+        assert_eq!(
+            vec![
+                LoadPriority::Low,
+                LoadPriority::Normal,
+                LoadPriority::High,
+                LoadPriority::Critical
+            ],
+            priorities
+        );
+        assert_eq!(LoadPriority::Normal, LoadPriority::default());
+    }
+
+    #[test]
+    fn direct_handles_have_their_priority_immediately() {
+        let priorities = LoadPriorities::default();
+        let handle = LoadHandle(7);
+        assert_eq!(LoadPriority::Normal, priorities.get(handle));
+        priorities.set(handle, LoadPriority::High);
+        assert_eq!(LoadPriority::High, priorities.get(handle));
+        assert!(!priorities.is_cancelled(handle));
+        priorities.forget(handle);
+        assert_eq!(LoadPriority::Normal, priorities.get(handle));
+    }
+
+    #[test]
+    fn urgent_requests_overtake_pending_requests_of_earlier_batches() {
+        let priority = |request: &(u32, LoadPriority)| request.1;
+        let mut requests = PendingRequests::new(2);
+        requests.pending.extend(vec![
+            (1, LoadPriority::Low),
+            (2, LoadPriority::Normal),
+            (3, LoadPriority::Low),
+        ]);
+        let (forwarded, _) = requests.take(priority, |_| false);
+        assert_eq!(
+            vec![(2, LoadPriority::Normal), (1, LoadPriority::Low)],
+            forwarded
+        );
+
+        requests.pending.push((4, LoadPriority::High));
+        let (forwarded, _) = requests.take(priority, |_| false);
+        assert!(forwarded.is_empty());
+
+        requests.next_tick();
+        let (forwarded, _) = requests.take(priority, |_| false);
+        assert_eq!(
+            vec![(4, LoadPriority::High), (3, LoadPriority::Low)],
+            forwarded
+        );
+        assert!(requests.pending.is_empty());
+    }
+
+    #[test]
+    fn cancelled_requests_are_not_forwarded() {
+        let mut requests = PendingRequests::new(2);
+        requests.pending.extend(vec![1, 2, 3]);
+        let (forwarded, cancelled) = requests.take(|_| LoadPriority::Normal, |id| *id == 2);
+        assert_eq!(vec![1, 3], forwarded);
+        assert_eq!(vec![2], cancelled);
+    }
+
+    #[test]
+    fn paths_have_their_priority_until_resolved() {
+        let priorities = LoadPriorities::default();
+        priorities.set_path("music/title.ogg", LoadPriority::Critical);
+        assert_eq!(
+            LoadPriority::Critical,
+            priorities.get_path("music/title.ogg")
+        );
+        assert_eq!(
+            LoadPriority::Critical,
+            priorities.take_path("music/title.ogg")
+        );
+        assert_eq!(LoadPriority::Normal, priorities.get_path("music/title.ogg"));
+    }
+}
@@ -1,22 +1,29 @@
 use std::{
     borrow::Cow,
+    cmp::Reverse,
+    collections::HashSet,
     marker::PhantomData,
+    panic::{self, AssertUnwindSafe},
     sync::{Arc, Mutex},
 };
 
 use amethyst_core::{
     dispatcher::System,
     ecs::{systems::ParallelRunnable, SystemBuilder},
+    ArcThreadPool,
 };
-use amethyst_error::Error;
+use amethyst_error::{format_err, Error};
 use atelier_assets::loader::storage::AssetLoadOp;
 use crossbeam_queue::SegQueue;
 use derivative::Derivative;
 use log::debug;
+use rayon::ThreadPool;
 
 use crate::{
     asset::{Asset, ProcessableAsset},
+    event::AssetEventKind,
     loader::LoadHandle,
+    priority::LoadPriorities,
     progress::Tracker,
     storage::AssetStorage,
 };
@@ -27,6 +34,15 @@ use crate::{
 ///
 /// This system can only be used if the asset data implements
 /// `Into<Result<A, BoxedErr>>`.
+///
+/// `ProcessableAsset::process` runs on the `ArcThreadPool`, so that processing large assets does
+/// not stall the frame. The processed assets are added to the storage by later runs of the
+/// system, within the `ProcessingBudget`.
+///
+/// Assets processed by their own system with `ProcessingQueue::process`, which runs within the
+/// frame, are not affected: meshes and textures are uploaded with the GPU factory of the
+/// renderer, UI glyph textures with the glyph cache, and prefabs are cooked with the other
+/// prefabs they reference.
 #[derive(Derivative)]
 #[derivative(Default(bound = ""))]
 pub struct AssetProcessorSystem<A> {
@@ -38,21 +54,67 @@ where
     A: Asset + ProcessableAsset,
 {
     fn build(&mut self) -> Box<dyn ParallelRunnable> {
+        let mut in_progress = InProgress::default();
         Box::new(
             SystemBuilder::new(format!("Asset Processor: {}", A::name()))
+                .read_resource::<ArcThreadPool>()
+                .read_resource::<ProcessingBudget>()
                 .write_resource::<ProcessingQueue<A::Data>>()
                 .write_resource::<AssetStorage<A>>()
-                .build(|_, _, (queue, storage), _| {
-                    queue.process(storage, ProcessableAsset::process);
+                .build(move |_, _, (pool, budget, queue, storage), _| {
+                    queue.process_on_pool(storage, pool, &mut in_progress, budget);
                     storage.process_custom_drop(|_| {});
                 }),
         )
     }
 }
 
+/// Limits the work of the asset processing systems in each frame.
+///
+/// The `LoaderBundle` inserts the default budget, which has no limit, unless the resource
+/// already exists.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessingBudget {
+    /// Maximum number of processed assets of each type added to their storage per frame, or
+    /// `None` for no limit.
+    ///
+    /// The remaining processed assets are added in the next frames, which spreads the cost of
+    /// replacing many assets at once, such as after loading a level.
+    pub max_commits_per_frame: Option<usize>,
+}
+
+/// Asset data of type `A::Data` being processed on the thread pool, see
+/// `ProcessingQueue::process_on_pool`.
+pub(crate) struct InProgress<A: Asset> {
+    finished: Arc<SegQueue<Finished<A>>>,
+    ready: Vec<Finished<A>>,
+    handles: HashSet<LoadHandle>,
+}
+
+impl<A: Asset> Default for InProgress<A> {
+    fn default() -> Self {
+        InProgress {
+            finished: Arc::new(SegQueue::new()),
+            ready: Vec::new(),
+            handles: HashSet::new(),
+        }
+    }
+}
+
+/// Asset data processed on the thread pool.
+struct Finished<A: Asset> {
+    result: Result<ProcessingState<A::Data, A>, Error>,
+    load: PendingLoad,
+}
+
 /// Represents asset data processed by `atelier-assets` that needs to be loaded by Amethyst.
 pub(crate) struct Processed<T> {
     data: Result<T, Error>,
+    load: PendingLoad,
+}
+
+/// Load of a processed asset, completed once the asset is in the storage.
+struct PendingLoad {
     handle: LoadHandle,
     tracker: Option<Box<dyn Tracker>>,
     load_op: Option<AssetLoadOp>,
@@ -76,18 +138,25 @@ pub enum ProcessingState<D, A> {
 pub struct ProcessingQueue<T> {
     pub(crate) processed: Arc<SegQueue<Processed<T>>>,
     requeue: Mutex<Vec<Processed<T>>>,
+    priorities: LoadPriorities,
 }
 
 impl<T> Default for ProcessingQueue<T> {
     fn default() -> Self {
+        Self::with_priorities(LoadPriorities::default())
+    }
+}
+
+impl<T> ProcessingQueue<T> {
+    /// Creates a queue processing the data of assets in the order of their `LoadPriority`.
+    pub(crate) fn with_priorities(priorities: LoadPriorities) -> Self {
         Self {
             processed: Arc::new(SegQueue::new()),
             requeue: Mutex::new(Vec::new()),
+            priorities,
         }
     }
-}
 
-impl<T> ProcessingQueue<T> {
     /// Enqueue asset data for processing
     pub(crate) fn enqueue(&self, handle: LoadHandle, data: T, load_op: AssetLoadOp, version: u32) {
         self.enqueue_processed(Ok(data), handle, None, Some(load_op), version, false);
@@ -104,11 +173,13 @@ impl<T> ProcessingQueue<T> {
     ) {
         self.processed.push(Processed {
             data,
-            handle,
-            tracker,
-            load_op,
-            version,
-            commit,
+            load: PendingLoad {
+                handle,
+                tracker,
+                load_op,
+                version,
+                commit,
+            },
         })
     }
 
@@ -122,73 +193,206 @@ impl<T> ProcessingQueue<T> {
         self.enqueue_processed(Ok(data), handle, Some(tracker), None, version, true);
     }
 
+    /// Returns the queued asset data, highest `LoadPriority` first, and cancels the loads of the
+    /// assets whose handles were all dropped.
+    fn take_pending(&self) -> Vec<Processed<T>> {
+        let mut pending = Vec::new();
+        while let Ok(processed) = self.processed.pop() {
+            if self.priorities.is_cancelled(processed.load.handle) {
+                cancel(processed.load);
+            } else {
+                pending.push(processed);
+            }
+        }
+        let priorities = &self.priorities;
+        pending.sort_by_key(|p| Reverse(priorities.get(p.load.handle)));
+        pending
+    }
+
     /// Process asset data into assets
+    ///
+    /// The data of assets with a higher `LoadPriority` is processed first, by the calling thread
+    /// and regardless of the `ProcessingBudget`. Use `AssetProcessorSystem` to process assets on
+    /// the thread pool instead.
     pub fn process<F, A>(&mut self, storage: &mut AssetStorage<A>, mut f: F)
     where
+        A: Asset,
         F: FnMut(T) -> Result<ProcessingState<T, A>, Error>,
     {
-        {
-            let requeue = self
-                .requeue
-                .get_mut()
-                .expect("The mutex of `requeue` in `AssetStorage` was poisoned");
-            while let Ok(processed) = self.processed.pop() {
-                let f = &mut f;
-                let Processed {
-                    data,
+        self.process_with_handles(storage, |_, data| f(data));
+    }
+
+    /// Process asset data into assets like `process`, passing the load handle of each asset to
+    /// `f` with its data.
+    pub(crate) fn process_with_handles<F, A>(&mut self, storage: &mut AssetStorage<A>, mut f: F)
+    where
+        A: Asset,
+        F: FnMut(LoadHandle, T) -> Result<ProcessingState<T, A>, Error>,
+    {
+        let pending = self.take_pending();
+        let requeue = self
+            .requeue
+            .get_mut()
+            .expect("The mutex of `requeue` in `AssetStorage` was poisoned");
+        for Processed { data, load } in pending {
+            let handle = load.handle;
+            let result = data.and_then(|d| f(handle, d));
+            finish(storage, requeue, &self.priorities, load, result);
+        }
+
+        for p in requeue.drain(..) {
+            self.processed.push(p);
+        }
+    }
+
+    /// Process asset data into assets with `ProcessableAsset::process` on the thread pool.
+    ///
+    /// The assets processed since the previous call are added to the storage, highest
+    /// `LoadPriority` first and up to `max_commits_per_frame` of the budget, before the queued
+    /// data is sent to the pool. Data of an asset that is still being processed waits for the
+    /// previous version to finish, so that versions are added in order.
+    pub(crate) fn process_on_pool<A>(
+        &mut self,
+        storage: &mut AssetStorage<A>,
+        pool: &ThreadPool,
+        in_progress: &mut InProgress<A>,
+        budget: &ProcessingBudget,
+    ) where
+        A: Asset<Data = T> + ProcessableAsset,
+    {
+        let pending = self.take_pending();
+        let requeue = self
+            .requeue
+            .get_mut()
+            .expect("The mutex of `requeue` in `AssetStorage` was poisoned");
+        let priorities = &self.priorities;
+
+        while let Ok(finished) = in_progress.finished.pop() {
+            in_progress.ready.push(finished);
+        }
+        in_progress
+            .ready
+            .sort_by_key(|f| Reverse(priorities.get(f.load.handle)));
+        let mut commits = budget.max_commits_per_frame.unwrap_or(usize::MAX);
+        let mut ready = std::mem::take(&mut in_progress.ready).into_iter();
+        while commits > 0 {
+            let Finished { result, load } = match ready.next() {
+                Some(finished) => finished,
+                None => break,
+            };
+            in_progress.handles.remove(&load.handle);
+            if priorities.is_cancelled(load.handle) {
+                cancel(load);
+                continue;
+            }
+            finish(storage, requeue, priorities, load, result);
+            commits -= 1;
+        }
+        in_progress.ready.extend(ready);
+
+        for Processed { data, load } in pending {
+            if !in_progress.handles.insert(load.handle) {
+                requeue.push(Processed { data, load });
+                continue;
+            }
+            let finished = in_progress.finished.clone();
+            pool.spawn(move || {
+                // A panic fails the load, instead of aborting the process in the pool.
+                let result = panic::catch_unwind(AssertUnwindSafe(|| data.and_then(A::process)))
+                    .unwrap_or_else(|_| Err(format_err!("Processing the {} panicked", A::name())));
+                finished.push(Finished { result, load });
+            });
+        }
+
+        for p in requeue.drain(..) {
+            self.processed.push(p);
+        }
+    }
+}
+
+/// Adds a processed asset to the storage and completes its load, or requeues the asset data if
+/// it is still loading.
+fn finish<T, A: Asset>(
+    storage: &mut AssetStorage<A>,
+    requeue: &mut Vec<Processed<T>>,
+    priorities: &LoadPriorities,
+    load: PendingLoad,
+    result: Result<ProcessingState<T, A>, Error>,
+) {
+    let PendingLoad {
+        handle,
+        tracker,
+        load_op,
+        version,
+        commit,
+    } = load;
+
+    let asset = match result {
+        Ok(ProcessingState::Loaded(x)) => {
+            debug!(
+                "Asset (handle id: {:?}) has been loaded successfully",
+                handle,
+            );
+
+            if let Some(tracker) = tracker {
+                tracker.success();
+            }
+            if let Some(op) = load_op {
+                op.complete();
+            }
+            x
+        }
+        Ok(ProcessingState::Loading(x)) => {
+            requeue.push(Processed {
+                data: Ok(x),
+                load: PendingLoad {
                     handle,
                     tracker,
                     load_op,
                     version,
                     commit,
-                } = processed;
-
-                let asset = match data.and_then(|d| f(d)) {
-                    Ok(ProcessingState::Loaded(x)) => {
-                        debug!(
-                            "Asset (handle id: {:?}) has been loaded successfully",
-                            handle,
-                        );
-
-                        if let Some(tracker) = tracker {
-                            tracker.success();
-                        }
-                        if let Some(op) = load_op {
-                            op.complete();
-                        }
-                        x
-                    }
-                    Ok(ProcessingState::Loading(x)) => {
-                        requeue.push(Processed {
-                            data: Ok(x),
-                            handle,
-                            tracker,
-                            load_op,
-                            version,
-                            commit,
-                        });
-                        continue;
-                    }
-                    Err(e) => {
-                        if let Some(tracker) = tracker {
-                            tracker.fail(handle.0, &"", "".to_string(), e);
-                        }
-                        if let Some(op) = load_op {
-                            op.error(ProcessingError("ProcessingError".into()));
-                        }
-                        continue;
-                    }
-                };
-                storage.update_asset(handle, asset, version);
-                if commit {
-                    storage.commit_asset(handle, version);
-                }
+                },
+            });
+            return;
+        }
+        Err(e) => {
+            priorities.forget(handle);
+            storage.push_event(
+                handle,
+                AssetEventKind::Failed {
+                    error: e.to_string(),
+                },
+            );
+            if let Some(tracker) = tracker {
+                tracker.fail(handle.0, &"", "".to_string(), e);
             }
-
-            for p in requeue.drain(..) {
-                self.processed.push(p);
+            if let Some(op) = load_op {
+                op.error(ProcessingError("ProcessingError".into()));
             }
+            return;
         }
+    };
+    priorities.forget(handle);
+    let byte_size = asset.byte_size();
+    storage.update_asset(handle, asset, version, byte_size);
+    if commit {
+        storage.commit_asset(handle, version);
+    }
+}
+
+/// Fails the load of an asset whose handles were all dropped before it was loaded.
+fn cancel(load: PendingLoad) {
+    debug!("Load of asset (handle id: {:?}) was cancelled", load.handle);
+    if let Some(tracker) = load.tracker {
+        tracker.fail(
+            load.handle.0,
+            &"",
+            "".to_string(),
+            Error::from_string("Load cancelled"),
+        );
+    }
+    if let Some(op) = load.load_op {
+        op.error(ProcessingError("Load cancelled".into()));
     }
 }
 
@@ -203,3 +407,127 @@ impl std::fmt::Display for ProcessingError {
 }
 
 impl std::error::Error for ProcessingError {}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use rayon::ThreadPoolBuilder;
+
+    use super::*;
+    use crate::storage::tests::storage;
+
+    #[derive(Debug, PartialEq)]
+    struct Number(u32);
+
+    impl Asset for Number {
+        fn name() -> &'static str {
+            "Number"
+        }
+        type Data = u32;
+    }
+
+    impl ProcessableAsset for Number {
+        fn process(data: u32) -> Result<ProcessingState<u32, Number>, Error> {
+            if data == 0 {
+                panic!("Number 0 can not be processed");
+            }
+            Ok(ProcessingState::Loaded(Number(data)))
+        }
+    }
+
+    fn pool() -> ThreadPool {
+        ThreadPoolBuilder::new().num_threads(1).build().unwrap()
+    }
+
+    /// Waits until `count` assets were processed on the pool.
+    fn wait_for(in_progress: &InProgress<Number>, count: usize) {
+        for _ in 0..1000 {
+            if in_progress.finished.len() >= count {
+                return;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        panic!("The assets were not processed");
+    }
+
+    #[test]
+    fn processed_assets_are_added_by_the_next_run() {
+        let (pool, budget) = (pool(), ProcessingBudget::default());
+        let mut storage = storage::<Number>();
+        let mut in_progress = InProgress::default();
+        let mut queue = ProcessingQueue::default();
+        queue.enqueue_from_data(LoadHandle(1), 1, Box::new(()), 1);
+
+        queue.process_on_pool(&mut storage, &pool, &mut in_progress, &budget);
+        assert!(!storage.contains(LoadHandle(1)));
+
+        wait_for(&in_progress, 1);
+        queue.process_on_pool(&mut storage, &pool, &mut in_progress, &budget);
+        assert_eq!(Some(&Number(1)), storage.get_for_load_handle(LoadHandle(1)));
+    }
+
+    #[test]
+    fn commits_are_limited_by_the_budget() {
+        let pool = pool();
+        let budget = ProcessingBudget {
+            max_commits_per_frame: Some(2),
+        };
+        let mut storage = storage::<Number>();
+        let mut in_progress = InProgress::default();
+        let mut queue = ProcessingQueue::default();
+        for id in 1..=3 {
+            queue.enqueue_from_data(LoadHandle(id), id as u32, Box::new(()), 1);
+        }
+        queue.process_on_pool(&mut storage, &pool, &mut in_progress, &budget);
+        wait_for(&in_progress, 3);
+
+        queue.process_on_pool(&mut storage, &pool, &mut in_progress, &budget);
+        assert_eq!(2, storage.usage().num_assets);
+        queue.process_on_pool(&mut storage, &pool, &mut in_progress, &budget);
+        assert_eq!(3, storage.usage().num_assets);
+    }
+
+    #[test]
+    fn versions_are_added_in_order() {
+        let (pool, budget) = (pool(), ProcessingBudget::default());
+        let mut storage = storage::<Number>();
+        let mut in_progress = InProgress::default();
+        let mut queue = ProcessingQueue::default();
+        let handle = LoadHandle(1);
+        queue.enqueue_from_data(handle, 1, Box::new(()), 1);
+        queue.enqueue_from_data(handle, 2, Box::new(()), 2);
+
+        // The second version waits for the first one to be processed.
+        queue.process_on_pool(&mut storage, &pool, &mut in_progress, &budget);
+        wait_for(&in_progress, 1);
+        queue.process_on_pool(&mut storage, &pool, &mut in_progress, &budget);
+        assert_eq!(Some(&Number(1)), storage.get_for_load_handle(handle));
+
+        wait_for(&in_progress, 1);
+        queue.process_on_pool(&mut storage, &pool, &mut in_progress, &budget);
+        assert_eq!(Some(&Number(2)), storage.get_for_load_handle(handle));
+    }
+
+    #[test]
+    fn panics_fail_the_load() {
+        let (pool, budget) = (pool(), ProcessingBudget::default());
+        let mut storage = storage::<Number>();
+        let mut in_progress = InProgress::default();
+        let mut queue = ProcessingQueue::default();
+        let handle = LoadHandle(1);
+        queue.enqueue_from_data(handle, 0, Box::new(()), 1);
+
+        queue.process_on_pool(&mut storage, &pool, &mut in_progress, &budget);
+        wait_for(&in_progress, 1);
+        queue.process_on_pool(&mut storage, &pool, &mut in_progress, &budget);
+        assert!(!storage.contains(handle));
+        assert!(in_progress.handles.is_empty());
+
+        queue.enqueue_from_data(handle, 1, Box::new(()), 2);
+        queue.process_on_pool(&mut storage, &pool, &mut in_progress, &budget);
+        wait_for(&in_progress, 1);
+        queue.process_on_pool(&mut storage, &pool, &mut in_progress, &budget);
+        assert_eq!(Some(&Number(1)), storage.get_for_load_handle(handle));
+    }
+}
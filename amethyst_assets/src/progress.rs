@@ -157,11 +157,16 @@ impl Tracker for ProgressCounterTracker {
     }
 }
 
+/// An asset that failed to load, with the reason it failed.
 #[derive(Debug)]
 pub struct AssetErrorMeta {
+    /// The error the asset failed to load with.
     pub error: Error,
+    /// Id of the load handle of the asset.
     pub handle_id: u64,
+    /// Name of the Rust type of the asset.
     pub asset_type_name: &'static str,
+    /// Name of the asset, such as the path it was loaded from.
     pub asset_name: String,
 }
 
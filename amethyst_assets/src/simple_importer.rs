@@ -1,7 +1,14 @@
-use std::io::Read;
+use std::{io::Read, path::PathBuf};
 
-use atelier_assets::importer::{
-    self as importer, BoxedImporter, ImportOp, ImportedAsset, Importer, ImporterValue, SerdeObj,
+use atelier_assets::{
+    core::AssetRef,
+    importer::{
+        self as importer, BoxedImporter, ImportOp, ImportedAsset, Importer, ImporterValue, SerdeObj,
+    },
+    loader::{
+        handle::{Handle, SerdeContext},
+        storage::LoaderInfoProvider,
+    },
 };
 use serde::{Deserialize, Serialize};
 use type_uuid::TypeUuid;
@@ -16,7 +23,57 @@ pub struct SimpleImporterState {
     id: Option<AssetUuid>,
 }
 
+/// Import options of a source file imported by a `SimpleImporter`.
+///
+/// These are stored as the `importer_options` in the `.meta` file next to the source file, where
+/// they can be edited to import the file with other settings, which re-imports it. `None` imports
+/// the file with the format of the importer, and `Some` with the given format, such as
+/// `importer_options: Some((sampler_info: ..., generate_mips: true, ...))` for an image.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SimpleImporterOptions<T>(pub Option<T>);
+
+impl<T> Default for SimpleImporterOptions<T> {
+    fn default() -> Self {
+        SimpleImporterOptions(None)
+    }
+}
+
+/// Combined with the UUID of the format into the UUID of its `SimpleImporterOptions`.
+const OPTIONS_UUID_MASK: type_uuid::Bytes = [
+    0x3f, 0x9d, 0x2c, 0x71, 0x8a, 0x4e, 0x4b, 0x06, 0xb5, 0xe1, 0xc2, 0x7a, 0x90, 0xd4, 0xe6, 0x8f,
+];
+
+impl<T: TypeUuid> TypeUuid for SimpleImporterOptions<T> {
+    // Distinct from the UUID of the format, which is also the UUID of the importer.
+    const UUID: type_uuid::Bytes = {
+        let u = T::UUID;
+        let m = OPTIONS_UUID_MASK;
+        [
+            u[0] ^ m[0],
+            u[1] ^ m[1],
+            u[2] ^ m[2],
+            u[3] ^ m[3],
+            u[4] ^ m[4],
+            u[5] ^ m[5],
+            u[6] ^ m[6],
+            u[7] ^ m[7],
+            u[8] ^ m[8],
+            u[9] ^ m[9],
+            u[10] ^ m[10],
+            u[11] ^ m[11],
+            u[12] ^ m[12],
+            u[13] ^ m[13],
+            u[14] ^ m[14],
+            u[15] ^ m[15],
+        ]
+    };
+}
+
 /// Wrapper struct to be able to impl Importer for any SimpleFormat
+///
+/// The source files are imported with the wrapped format, unless their `.meta` file sets other
+/// options, see [`SimpleImporterOptions`](struct.SimpleImporterOptions.html).
 pub struct SimpleImporter<A: 'static, T: Format<A> + TypeUuid>(
     pub T,
     ::std::marker::PhantomData<A>,
@@ -39,13 +96,13 @@ where
     A: SerdeObj,
 {
     type State = SimpleImporterState;
-    type Options = T;
+    type Options = SimpleImporterOptions<T>;
 
     fn version_static() -> u32
     where
         Self: Sized,
     {
-        1
+        3
     }
     fn version(&self) -> u32 {
         Self::version_static()
@@ -63,7 +120,8 @@ where
         }
         let mut bytes = Vec::new();
         source.read_to_end(&mut bytes)?;
-        let import_result = options
+        let format = options.0.as_ref().unwrap_or(&self.0);
+        let import_result = format
             .import_simple(bytes)
             .map_err(|e| importer::Error::Boxed(e.into_error()))?;
         Ok(ImporterValue {
@@ -78,6 +136,41 @@ where
         })
     }
 }
+/// Creates a handle to the asset with the given UUID, to store in the data of an asset produced
+/// by an importer.
+///
+/// The asset should also be one of the `load_deps` of the imported asset, so that it is loaded
+/// with it.
+///
+/// # Panics
+///
+/// Panics when called outside of `Importer::import`.
+pub fn make_handle<A>(id: AssetUuid) -> Handle<A> {
+    make_handle_from_ref(AssetRef::Uuid(id))
+}
+
+/// Creates a handle to the asset imported from the source file at `path`, to store in the data
+/// of an asset produced by an importer.
+///
+/// The path is relative to the imported source file. The asset should also be one of the
+/// `load_deps` of the imported asset, as `AssetRef::Path`, so that it is loaded with it.
+///
+/// # Panics
+///
+/// Panics when called outside of `Importer::import`.
+pub fn make_handle_from_path<A, P: Into<PathBuf>>(path: P) -> Handle<A> {
+    make_handle_from_ref(AssetRef::Path(path.into()))
+}
+
+fn make_handle_from_ref<A>(asset_ref: AssetRef) -> Handle<A> {
+    SerdeContext::with_active(|loader_info, ref_op_sender| {
+        let load_handle = loader_info
+            .get_load_handle(&asset_ref)
+            .unwrap_or_else(|| panic!("Failed to get a load handle for {:?}", asset_ref));
+        Handle::new(ref_op_sender.clone(), load_handle)
+    })
+}
+
 /// Use [inventory::submit!] to register an importer to use for a file extension.
 #[derive(Debug)]
 pub struct SourceFileImporter {
@@ -98,31 +191,63 @@ pub fn get_source_importers(
 
 /// Associates the given file extension with a `Format` implementation
 ///
-/// The `AssetDaemon` will automatically re-import the asset when a file of that format is created
-/// or modified.
+/// The `AssetDaemon` will automatically re-import the asset when a file of that format, or its
+/// `.meta` file, is created or modified. Files are imported with the default value of the format,
+/// or with the given value, unless their `.meta` file sets other options, see
+/// [`SimpleImporterOptions`](struct.SimpleImporterOptions.html).
 ///
 /// # Parameters
 ///
 /// * `ext`: File extension including the leading `.`, such as `".ron"`.
 /// * `format`: Type that implements the `Format` trait.
+/// * `options`: Value of the format to import files with, `Default::default()` if omitted.
 ///
 /// # Examples
 ///
 /// ```rust,ignore
 /// amethyst_assets::register_importer!(".ron", RonFormat<SpriteSheet>);
+/// amethyst_assets::register_importer!(".hdr", ImageFormat, ImageFormat(hdr_texture_config()));
 /// ```
 #[macro_export]
 macro_rules! register_importer {
     ($ext:literal, $format:ty) => {
         $crate::register_importer!(amethyst_assets; $ext, $format);
     };
+    ($ext:literal, $format:ty, $options:expr) => {
+        $crate::register_importer!(amethyst_assets; $ext, $format, $options);
+    };
     ($krate:ident; $ext:literal, $format:ty) => {
+        $crate::register_importer!($krate; $ext, $format, <$format as Default>::default());
+    };
+    ($krate:ident; $ext:literal, $format:ty, $options:expr) => {
         $crate::inventory::submit!{
             #![crate = $krate]
             $crate::SourceFileImporter {
                 extension: $ext,
-                instantiator: || Box::new($crate::SimpleImporter::from(<$format as Default>::default())),
+                instantiator: || Box::new($crate::SimpleImporter::<_, $format>::from($options)),
             }
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(TypeUuid)]
+    #[uuid = "0b6d3e52-1f8a-4c79-9e24-7a5c1d8b3f60"]
+    struct FormatA;
+
+    #[derive(TypeUuid)]
+    #[uuid = "e4a17c09-52d3-4b8e-86f1-3c9b0a2d5e71"]
+    struct FormatB;
+
+    #[test]
+    fn options_have_their_own_uuid() {
+        assert_ne!(FormatA::UUID, SimpleImporterOptions::<FormatA>::UUID);
+        assert_ne!(
+            SimpleImporterOptions::<FormatA>::UUID,
+            SimpleImporterOptions::<FormatB>::UUID
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use amethyst_error::{format_err, Error, ResultExt};
+use parking_lot::Mutex;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+use zip::ZipArchive;
+
+use crate::{error, source::Source};
+
+/// Source reading files from a zip archive.
+///
+/// Paths are relative to the root of the archive and use `/` as separator. The modification time
+/// of a file is the modification time of the archive, and the archive is reopened when it
+/// changes, so replacing the archive reloads its assets.
+///
+/// # Examples
+///
+/// ```no_run
+/// use amethyst_assets::ZipSource;
+///
+/// let source = ZipSource::open("dlc.zip").expect("Failed to open dlc.zip");
+/// ```
+pub struct ZipSource {
+    path: PathBuf,
+    archive: Mutex<(ZipArchive<BufReader<File>>, u64)>,
+}
+
+impl std::fmt::Debug for ZipSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ZipSource")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl ZipSource {
+    /// Opens the zip archive at the given path.
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<Self, Error> {
+        let path = path.into();
+        let archive = Self::open_archive(&path)?;
+        Ok(ZipSource {
+            path,
+            archive: Mutex::new(archive),
+        })
+    }
+
+    fn open_archive(path: &Path) -> Result<(ZipArchive<BufReader<File>>, u64), Error> {
+        let modified = Self::archive_modified(path)?;
+        let file = File::open(path)
+            .with_context(|_| format_err!("Failed to open zip archive {:?}", path))?;
+        let archive = ZipArchive::new(BufReader::new(file))
+            .with_context(|_| format_err!("Failed to read zip archive {:?}", path))?;
+        Ok((archive, modified))
+    }
+
+    fn archive_modified(path: &Path) -> Result<u64, Error> {
+        fs::metadata(path)
+            .with_context(|_| format_err!("Failed to fetch metadata for {:?}", path))?
+            .modified()
+            .with_context(|_| format_err!("Could not get modification time"))?
+            .duration_since(UNIX_EPOCH)
+            .with_context(|_| {
+                format_err!("Anomalies with the system clock caused `duration_since` to fail")
+            })
+            .map(|d| d.as_secs())
+    }
+
+    /// Calls `f` with the archive, reopening it first if it was modified since it was opened.
+    fn with_archive<T>(
+        &self,
+        f: impl FnOnce(&mut ZipArchive<BufReader<File>>, u64) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let mut archive = self.archive.lock();
+        if Self::archive_modified(&self.path)? != archive.1 {
+            *archive = Self::open_archive(&self.path)?;
+        }
+        let (archive, modified) = &mut *archive;
+        f(archive, *modified)
+    }
+}
+
+impl Source for ZipSource {
+    fn modified(&self, path: &str) -> Result<u64, Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("zip_modified_asset");
+
+        self.with_archive(|archive, modified| {
+            archive
+                .by_name(path)
+                .with_context(|_| format_err!("Failed to find {:?} in {:?}", path, self.path))
+                .with_context(|_| error::Error::Source)?;
+            Ok(modified)
+        })
+    }
+
+    fn load(&self, path: &str) -> Result<Vec<u8>, Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("zip_load_asset");
+
+        self.with_archive(|archive, _| {
+            let mut file = archive
+                .by_name(path)
+                .with_context(|_| format_err!("Failed to find {:?} in {:?}", path, self.path))
+                .with_context(|_| error::Error::Source)?;
+            let mut v = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut v)
+                .with_context(|_| format_err!("Failed to read {:?} from {:?}", path, self.path))
+                .with_context(|_| error::Error::Source)?;
+            Ok(v)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, fs::File, io::Write};
+
+    use zip::{write::FileOptions, ZipWriter};
+
+    use super::ZipSource;
+    use crate::source::Source;
+
+    #[test]
+    fn loads_file_from_archive() {
+        let path = env::temp_dir().join("amethyst_assets_zip_source.zip");
+        {
+            let mut writer = ZipWriter::new(File::create(&path).unwrap());
+            writer
+                .start_file("subdir/asset", FileOptions::default())
+                .unwrap();
+            writer.write_all(b"data").unwrap();
+            writer.finish().unwrap();
+        }
+
+        let source = ZipSource::open(&path).unwrap();
+        assert_eq!(b"data".to_vec(), source.load("subdir/asset").unwrap());
+        assert!(source.modified("subdir/asset").is_ok());
+        assert!(source.load("missing").is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use amethyst_error::{format_err, Error, ResultExt};
+
+use crate::{error, source::Source};
+
+/// In-memory source, mostly useful for tests.
+///
+/// Every insert counts as a modification, so that replacing a file can be used to test hot
+/// reloading.
+///
+/// # Examples
+///
+/// ```
+/// use amethyst_assets::{InMemorySource, Source};
+///
+/// let source = InMemorySource::new().with_file("config.ron", b"(val: 123)".to_vec());
+/// assert_eq!(b"(val: 123)".to_vec(), source.load("config.ron").unwrap());
+/// ```
+#[derive(Debug, Default)]
+pub struct InMemorySource {
+    files: HashMap<String, (Vec<u8>, u64)>,
+    revision: u64,
+}
+
+impl InMemorySource {
+    /// Creates an empty source.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file, replacing any file with the same path.
+    pub fn with_file<P: Into<String>>(mut self, path: P, bytes: Vec<u8>) -> Self {
+        self.insert(path, bytes);
+        self
+    }
+
+    /// Adds a file, returning the content of the file it replaced, if any.
+    pub fn insert<P: Into<String>>(&mut self, path: P, bytes: Vec<u8>) -> Option<Vec<u8>> {
+        self.revision += 1;
+        self.files
+            .insert(path.into(), (bytes, self.revision))
+            .map(|(bytes, _)| bytes)
+    }
+
+    /// Removes a file, returning its content if it existed.
+    pub fn remove(&mut self, path: &str) -> Option<Vec<u8>> {
+        self.files.remove(path).map(|(bytes, _)| bytes)
+    }
+
+    /// Returns `true` if the source has a file with the given path.
+    pub fn contains(&self, path: &str) -> bool {
+        self.files.contains_key(path)
+    }
+
+    fn get(&self, path: &str) -> Result<&(Vec<u8>, u64), Error> {
+        self.files
+            .get(path)
+            .ok_or_else(|| format_err!("The file {:?} is not in the `InMemorySource`", path))
+            .with_context(|_| error::Error::Source)
+    }
+}
+
+impl Source for InMemorySource {
+    /// Returns the number of inserts into the source up to the last insert of the file, rather
+    /// than a time.
+    fn modified(&self, path: &str) -> Result<u64, Error> {
+        self.get(path).map(|(_, revision)| *revision)
+    }
+
+    fn load(&self, path: &str) -> Result<Vec<u8>, Error> {
+        self.get(path).map(|(bytes, _)| bytes.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::InMemorySource;
+    use crate::source::Source;
+
+    #[test]
+    fn insert_updates_content_and_modification() {
+        let mut source = InMemorySource::new().with_file("file", b"first".to_vec());
+        let modified = source.modified("file").unwrap();
+
+        assert_eq!(
+            Some(b"first".to_vec()),
+            source.insert("file", b"second".to_vec())
+        );
+
+        assert_eq!(b"second".to_vec(), source.load("file").unwrap());
+        assert!(source.modified("file").unwrap() > modified);
+    }
+
+    #[test]
+    fn missing_file_is_an_error() {
+        let source = InMemorySource::new();
+        assert!(source.load("missing").is_err());
+        assert!(source.modified("missing").is_err());
+    }
+}
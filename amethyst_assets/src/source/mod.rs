@@ -2,9 +2,22 @@ use amethyst_error::Error;
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
-pub use self::dir::Directory;
+#[cfg(feature = "zip")]
+pub use self::archive::ZipSource;
+pub(crate) use self::sources::SourceIO;
+pub use self::{
+    dir::Directory,
+    memory::InMemorySource,
+    overlay::OverlaySource,
+    sources::{AssetSources, SOURCE_SEPARATOR},
+};
 
+#[cfg(feature = "zip")]
+mod archive;
 mod dir;
+mod memory;
+mod overlay;
+mod sources;
 
 /// A trait for asset sources, which provides
 /// methods for loading bytes.
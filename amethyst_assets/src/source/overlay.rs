@@ -0,0 +1,152 @@
+use amethyst_error::{format_err, Error, ResultExt};
+
+use crate::{error, source::Source};
+
+/// Source stacking several sources by priority, so that files of higher priority sources
+/// override the files of lower priority ones with the same path.
+///
+/// This is useful for DLC and mod folders overriding base game files. `modified` is forwarded to
+/// the source a file is loaded from, so hot reloading keeps working.
+///
+/// # Examples
+///
+/// ```
+/// use amethyst_assets::{InMemorySource, OverlaySource, Source};
+///
+/// let base = InMemorySource::new()
+///     .with_file("player.ron", b"base".to_vec())
+///     .with_file("enemy.ron", b"base".to_vec());
+/// let dlc = InMemorySource::new().with_file("player.ron", b"dlc".to_vec());
+///
+/// let source = OverlaySource::new()
+///     .with_layer(0, base)
+///     .with_layer(10, dlc);
+///
+/// assert_eq!(b"dlc".to_vec(), source.load("player.ron").unwrap());
+/// assert_eq!(b"base".to_vec(), source.load("enemy.ron").unwrap());
+/// ```
+#[derive(Default)]
+pub struct OverlaySource {
+    /// Sorted from highest to lowest priority.
+    layers: Vec<(i32, Box<dyn Source>)>,
+}
+
+impl std::fmt::Debug for OverlaySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OverlaySource")
+            .field(
+                "priorities",
+                &self
+                    .layers
+                    .iter()
+                    .map(|(priority, _)| priority)
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl OverlaySource {
+    /// Creates an overlay without any layers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a layer with the given priority, see `add_layer`.
+    pub fn with_layer<S: Source>(mut self, priority: i32, source: S) -> Self {
+        self.add_layer(priority, source);
+        self
+    }
+
+    /// Adds a layer with the given priority.
+    ///
+    /// Files are looked up in the layers from the highest to the lowest priority. Of layers with
+    /// the same priority, the one added last is looked up first.
+    pub fn add_layer<S: Source>(&mut self, priority: i32, source: S) {
+        let index = self
+            .layers
+            .iter()
+            .position(|(p, _)| *p <= priority)
+            .unwrap_or_else(|| self.layers.len());
+        self.layers.insert(index, (priority, Box::new(source)));
+    }
+
+    /// Returns the layer with the highest priority that has the given file, and the file's
+    /// modification time in that layer.
+    fn layer_for(&self, path: &str) -> Result<(&dyn Source, u64), Error> {
+        self.layers
+            .iter()
+            .find_map(|(_, source)| {
+                source
+                    .modified(path)
+                    .ok()
+                    .map(|modified| (&**source, modified))
+            })
+            .ok_or_else(|| format_err!("No layer of the `OverlaySource` has the file {:?}", path))
+            .with_context(|_| error::Error::Source)
+    }
+}
+
+impl Source for OverlaySource {
+    fn modified(&self, path: &str) -> Result<u64, Error> {
+        self.layer_for(path).map(|(_, modified)| modified)
+    }
+
+    fn load(&self, path: &str) -> Result<Vec<u8>, Error> {
+        self.layer_for(path)?.0.load(path)
+    }
+
+    fn load_with_metadata(&self, path: &str) -> Result<(Vec<u8>, u64), Error> {
+        let (source, modified) = self.layer_for(path)?;
+        Ok((source.load(path)?, modified))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::OverlaySource;
+    use crate::source::{InMemorySource, Source};
+
+    #[test]
+    fn higher_priority_layers_override_files() {
+        let source = OverlaySource::new()
+            .with_layer(10, InMemorySource::new().with_file("file", b"mod".to_vec()))
+            .with_layer(
+                0,
+                InMemorySource::new()
+                    .with_file("file", b"base".to_vec())
+                    .with_file("other", b"base".to_vec()),
+            );
+
+        assert_eq!(b"mod".to_vec(), source.load("file").unwrap());
+        assert_eq!(b"base".to_vec(), source.load("other").unwrap());
+        assert!(source.load("missing").is_err());
+    }
+
+    #[test]
+    fn last_added_layer_wins_on_equal_priority() {
+        let source = OverlaySource::new()
+            .with_layer(
+                0,
+                InMemorySource::new().with_file("file", b"first".to_vec()),
+            )
+            .with_layer(
+                0,
+                InMemorySource::new().with_file("file", b"second".to_vec()),
+            );
+
+        assert_eq!(b"second".to_vec(), source.load("file").unwrap());
+    }
+
+    #[test]
+    fn modified_comes_from_the_overriding_layer() {
+        let base = InMemorySource::new().with_file("file", b"base".to_vec());
+        let mut dlc = InMemorySource::new();
+        dlc.insert("unrelated", Vec::new());
+        dlc.insert("file", b"dlc".to_vec());
+
+        let source = OverlaySource::new().with_layer(0, base).with_layer(1, dlc);
+
+        assert_eq!(2, source.modified("file").unwrap());
+    }
+}
@@ -0,0 +1,513 @@
+use std::{
+    collections::HashMap,
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use atelier_assets::{
+    core::{AssetRef, AssetUuid},
+    loader::{
+        io::{DataRequest, LoaderIO, MetadataRequest, ResolveRequest},
+        loader::LoaderState,
+    },
+};
+use log::{debug, error, warn};
+
+use crate::{
+    import::{ImportedArtifact, ImporterSet, ReadFile},
+    loading::LoadDependencies,
+    pack::normalize_path,
+    source::Source,
+};
+
+/// Separates the source name from the path in paths passed to `Loader::load`.
+pub const SOURCE_SEPARATOR: &str = "://";
+
+/// Named asset sources the loader reads source files from, in addition to the asset daemon.
+///
+/// Files of a named source are loaded by prefixing their path with the source name and
+/// `://`, such as `loader.load("mods://sprites/logo.png")`. Paths without a prefix are looked up
+/// in the default source first, if one is set, and are served by the asset daemon otherwise.
+///
+/// The files are imported with the importers registered with `register_importer!` when they are
+/// first requested, and imported again when they are requested after they changed. They are not
+/// reloaded when they change. Dependencies of the assets given by path are looked up in the same
+/// source, relative to the file or else to the root of the source, and dependencies given by UUID
+/// that are not imported from a source are loaded from the asset daemon.
+///
+/// Sourced assets and daemon assets whose metadata the loader requests in the same batch can not
+/// be told apart by the asset daemon, so such batches are served by the daemon and the sourced
+/// assets in them are reported as missing.
+///
+/// This resource is picked up by the `LoaderBundle`, use
+/// [`ApplicationBuilder::with_source`] to register sources.
+///
+/// [`ApplicationBuilder::with_source`]: ../amethyst/struct.ApplicationBuilder.html#method.with_source
+#[derive(Default)]
+pub struct AssetSources {
+    sources: HashMap<String, Arc<dyn Source>>,
+    default_source: Option<Arc<dyn Source>>,
+}
+
+impl fmt::Debug for AssetSources {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AssetSources")
+            .field("sources", &self.sources.keys().collect::<Vec<_>>())
+            .field("default_source", &self.default_source.is_some())
+            .finish()
+    }
+}
+
+impl AssetSources {
+    /// Registers a source under the given name, replacing any source of the same name.
+    pub fn add_source<N, S>(&mut self, name: N, source: S)
+    where
+        N: Into<String>,
+        S: Source,
+    {
+        self.sources.insert(name.into(), Arc::new(source));
+    }
+
+    /// Sets the source to look up paths without a source prefix in.
+    pub fn set_default_source<S: Source>(&mut self, source: S) {
+        self.default_source = Some(Arc::new(source));
+    }
+
+    /// Returns the source a path refers to, with the path inside that source.
+    fn resolve<'a>(&self, path: &'a str) -> Option<(&Arc<dyn Source>, &'a str)> {
+        match path.find(SOURCE_SEPARATOR) {
+            Some(index) => {
+                let source = self.sources.get(&path[..index])?;
+                Some((source, &path[index + SOURCE_SEPARATOR.len()..]))
+            }
+            None => {
+                let source = self.default_source.as_ref()?;
+                // The default source only serves the files it has.
+                source.modified(path).ok()?;
+                Some((source, path))
+            }
+        }
+    }
+}
+
+/// The assets imported from a source file.
+struct ImportedFile {
+    /// Modification time of the file when it was imported.
+    modified: u64,
+    ids: Vec<AssetUuid>,
+}
+
+/// Reads the files next to the file at `path` in the source.
+fn sibling_files(source: Arc<dyn Source>, path: &str) -> ReadFile {
+    let dir = Path::new(path)
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .to_path_buf();
+    Box::new(move |file| {
+        source
+            .load(&normalize_path(&dir.join(file).to_string_lossy()))
+            .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e.to_string()))
+    })
+}
+
+/// Loader IO serving the files of `AssetSources`, and forwarding everything else to another IO.
+pub(crate) struct SourceIO {
+    inner: Box<dyn LoaderIO>,
+    sources: AssetSources,
+    importers: ImporterSet,
+    /// Imported files by the path passed to `Loader::load`.
+    files: HashMap<String, ImportedFile>,
+    artifacts: HashMap<AssetUuid, ImportedArtifact>,
+    dependencies: LoadDependencies,
+}
+
+impl SourceIO {
+    pub(crate) fn new(
+        inner: Box<dyn LoaderIO>,
+        sources: AssetSources,
+        dependencies: LoadDependencies,
+    ) -> Self {
+        SourceIO {
+            inner,
+            sources,
+            importers: ImporterSet::new(Vec::new()),
+            files: HashMap::new(),
+            artifacts: HashMap::new(),
+            dependencies,
+        }
+    }
+
+    /// Imports the file at the given path, unless it did not change since it was last imported.
+    ///
+    /// Returns `None` if the path is not served by a source.
+    fn import(&mut self, path: &str) -> Option<Result<Vec<AssetUuid>, io::Error>> {
+        let (source, source_path) = self.sources.resolve(path)?;
+        let modified = match source.modified(source_path) {
+            Ok(modified) => modified,
+            Err(e) => return Some(Err(io::Error::new(io::ErrorKind::NotFound, e.to_string()))),
+        };
+        if let Some(file) = self
+            .files
+            .get(path)
+            .filter(|file| file.modified == modified)
+        {
+            return Some(Ok(file.ids.clone()));
+        }
+        let result = source.load(source_path).and_then(|bytes| {
+            let meta = source.load(&format!("{}.meta", source_path)).ok();
+            let files = sibling_files(source.clone(), source_path);
+            self.importers
+                .import(source_path, &mut bytes.as_slice(), files, meta.as_deref())
+        });
+        let artifacts = match result {
+            Ok(Some(artifacts)) => artifacts,
+            Ok(None) => {
+                return Some(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("No importer for {:?}", path),
+                )))
+            }
+            Err(e) => {
+                error!("Failed to import {:?}: {}", path, e);
+                return Some(Err(io::Error::new(io::ErrorKind::Other, e.to_string())));
+            }
+        };
+        debug!("Imported {} assets from {:?}", artifacts.len(), path);
+        let ids = artifacts
+            .iter()
+            .map(|artifact| artifact.metadata.id)
+            .collect::<Vec<_>>();
+        if let Some(previous) = self.files.remove(path) {
+            for id in previous.ids.iter().filter(|id| !ids.contains(id)) {
+                self.artifacts.remove(id);
+            }
+        }
+        for artifact in artifacts {
+            self.artifacts.insert(artifact.metadata.id, artifact);
+        }
+        // The file is recorded first, so that files depending on each other are imported once.
+        self.files.insert(
+            path.to_string(),
+            ImportedFile {
+                modified,
+                ids: ids.clone(),
+            },
+        );
+        self.resolve_dependencies(path, &ids);
+        Some(Ok(ids))
+    }
+
+    /// Replaces the dependencies given by path of the assets imported from the file at `path`
+    /// with the UUIDs of the assets imported from the same source.
+    fn resolve_dependencies(&mut self, path: &str, ids: &[AssetUuid]) {
+        let prefix_len = path
+            .find(SOURCE_SEPARATOR)
+            .map_or(0, |index| index + SOURCE_SEPARATOR.len());
+        let (prefix, file) = path.split_at(prefix_len);
+        let file_dir = Path::new(file).parent().unwrap_or_else(|| Path::new(""));
+
+        for id in ids {
+            let dep_paths = match self
+                .artifacts
+                .get(id)
+                .and_then(|artifact| artifact.metadata.artifact.as_ref())
+            {
+                Some(meta) => meta
+                    .load_deps
+                    .iter()
+                    .chain(meta.build_deps.iter())
+                    .filter_map(|dep| match dep {
+                        AssetRef::Path(dep_path) => Some(dep_path.clone()),
+                        AssetRef::Uuid(_) => None,
+                    })
+                    .collect::<Vec<_>>(),
+                None => continue,
+            };
+
+            for dep_path in dep_paths {
+                let candidates = [
+                    normalize_path(&file_dir.join(&dep_path).to_string_lossy()),
+                    normalize_path(&dep_path.to_string_lossy()),
+                ];
+                let resolved = candidates.iter().find_map(|candidate| {
+                    match self.import(&format!("{}{}", prefix, candidate)) {
+                        Some(Ok(dep_ids)) => dep_ids.first().copied(),
+                        _ => None,
+                    }
+                });
+                let meta = self
+                    .artifacts
+                    .get_mut(id)
+                    .and_then(|artifact| artifact.metadata.artifact.as_mut())
+                    .expect("Imported artifact has metadata");
+                for dep in meta.load_deps.iter_mut().chain(meta.build_deps.iter_mut()) {
+                    if *dep == AssetRef::Path(dep_path.clone()) {
+                        match resolved {
+                            Some(dep_id) => *dep = AssetRef::Uuid(dep_id),
+                            None => warn!(
+                                "Dependency {:?} of {:?} is not in the source of {:?}",
+                                dep_path, id, path
+                            ),
+                        }
+                    }
+                }
+                if resolved.is_none() {
+                    // The loader only loads dependencies given by UUID.
+                    meta.load_deps
+                        .retain(|dep| *dep != AssetRef::Path(dep_path.clone()));
+                }
+            }
+        }
+    }
+}
+
+impl LoaderIO for SourceIO {
+    fn get_asset_metadata_with_dependencies(&mut self, request: MetadataRequest) {
+        let sourced = request
+            .requested_assets()
+            .filter(|id| self.artifacts.contains_key(id))
+            .cloned()
+            .collect::<Vec<_>>();
+        if sourced.len() < request.requested_assets().count() {
+            // A request can not be split, so the daemon serves the whole batch.
+            if !sourced.is_empty() {
+                warn!(
+                    "Sourced assets {:?} were requested together with daemon assets",
+                    sourced
+                );
+            }
+            self.inner.get_asset_metadata_with_dependencies(request);
+            return;
+        }
+
+        let mut pending = request.requested_assets().cloned().collect::<Vec<_>>();
+        let mut visited = Vec::new();
+        let mut metadata = Vec::new();
+        while let Some(id) = pending.pop() {
+            if visited.contains(&id) {
+                continue;
+            }
+            visited.push(id);
+            // Dependencies that are not sourced are requested from the daemon by the loader.
+            if let Some(artifact) = self
+                .artifacts
+                .get(&id)
+                .and_then(|artifact| artifact.metadata.artifact.as_ref())
+            {
+                pending.extend(artifact.load_deps.iter().filter_map(|dep| match dep {
+                    AssetRef::Uuid(dep) => Some(*dep),
+                    AssetRef::Path(_) => None,
+                }));
+                self.dependencies.insert_metadata(artifact);
+                metadata.push(artifact.clone());
+            }
+        }
+        request.complete(metadata);
+    }
+
+    fn get_asset_candidates(&mut self, requests: Vec<ResolveRequest>) {
+        let mut forwarded = Vec::new();
+        for request in requests {
+            let path = request.identifier().path().to_string();
+            match self.import(&path) {
+                Some(Ok(ids)) => {
+                    let candidates = ids
+                        .iter()
+                        .filter_map(|id| self.artifacts.get(id))
+                        .map(|artifact| artifact.metadata.clone())
+                        .collect();
+                    request.complete(vec![(PathBuf::from(path), candidates)]);
+                }
+                Some(Err(e)) => request.error(e),
+                None => forwarded.push(request),
+            }
+        }
+        if !forwarded.is_empty() {
+            self.inner.get_asset_candidates(forwarded);
+        }
+    }
+
+    fn get_artifacts(&mut self, requests: Vec<DataRequest>) {
+        let mut forwarded = Vec::new();
+        for request in requests {
+            match self.artifacts.get(&request.asset_id()) {
+                Some(artifact) => {
+                    let data = artifact.data.clone();
+                    request.complete(data);
+                }
+                None => forwarded.push(request),
+            }
+        }
+        if !forwarded.is_empty() {
+            self.inner.get_artifacts(forwarded);
+        }
+    }
+
+    fn tick(&mut self, loader: &mut LoaderState) {
+        self.inner.tick(loader);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use amethyst_error::{format_err, Error};
+    use atelier_assets::importer::BoxedImporter;
+    use serde::{Deserialize, Serialize};
+    use type_uuid::TypeUuid;
+
+    use super::*;
+    use crate::{read_import_file, Directory, Format, InMemorySource, SimpleImporter};
+
+    #[derive(Serialize, Deserialize, TypeUuid)]
+    #[uuid = "1c5b5b0a-6f0e-4b1c-9a57-2a8f6c1c7e11"]
+    struct Text(String);
+
+    #[derive(Clone, Default, Serialize, Deserialize, TypeUuid)]
+    #[uuid = "8d0b8a6e-3a4f-4e55-8f5d-4a5e0b7f1d22"]
+    struct TextFormat;
+
+    impl Format<Text> for TextFormat {
+        fn name(&self) -> &'static str {
+            "Text"
+        }
+
+        fn import_simple(&self, bytes: Vec<u8>) -> Result<Text, Error> {
+            Ok(Text(String::from_utf8_lossy(&bytes).into_owned()))
+        }
+    }
+
+    /// Imports the text of the file named in the imported file.
+    #[derive(Clone, Default, Serialize, Deserialize, TypeUuid)]
+    #[uuid = "3e9a0c71-5b2d-4f86-a1c4-96d07e5b8f33"]
+    struct IncludeFormat;
+
+    impl Format<Text> for IncludeFormat {
+        fn name(&self) -> &'static str {
+            "Include"
+        }
+
+        fn import_simple(&self, bytes: Vec<u8>) -> Result<Text, Error> {
+            let path = String::from_utf8_lossy(&bytes).into_owned();
+            let included = read_import_file(&path)
+                .ok_or_else(|| format_err!("Imported without source files"))??;
+            Ok(Text(String::from_utf8_lossy(&included).into_owned()))
+        }
+    }
+
+    struct NoIO;
+
+    impl LoaderIO for NoIO {
+        fn get_asset_metadata_with_dependencies(&mut self, _request: MetadataRequest) {}
+        fn get_asset_candidates(&mut self, _requests: Vec<ResolveRequest>) {}
+        fn get_artifacts(&mut self, _requests: Vec<DataRequest>) {}
+        fn tick(&mut self, _loader: &mut LoaderState) {}
+    }
+
+    fn source_io(source: InMemorySource) -> SourceIO {
+        let mut sources = AssetSources::default();
+        sources.add_source("mem", source);
+        let mut io = SourceIO::new(Box::new(NoIO), sources, LoadDependencies::default());
+        let importer: Box<dyn BoxedImporter> =
+            Box::new(SimpleImporter::<Text, _>::from(TextFormat));
+        io.importers = ImporterSet::new(vec![("txt", importer)]);
+        io
+    }
+
+    fn load_deps(io: &SourceIO, id: &AssetUuid) -> Vec<AssetRef> {
+        io.artifacts[id]
+            .metadata
+            .artifact
+            .as_ref()
+            .unwrap()
+            .load_deps
+            .clone()
+    }
+
+    #[test]
+    fn unchanged_files_are_imported_once() {
+        let mut io = source_io(InMemorySource::new().with_file("a.txt", b"a".to_vec()));
+
+        let first = io.import("mem://a.txt").unwrap().unwrap();
+        // Without a `.meta` file every import generates a new UUID.
+        let second = io.import("mem://a.txt").unwrap().unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(1, io.artifacts.len());
+        assert!(io.import("other://a.txt").is_none());
+    }
+
+    #[test]
+    fn files_next_to_the_source_file_are_readable_while_importing() {
+        let mut io = source_io(
+            InMemorySource::new()
+                .with_file("text/a.inc", b"../data/b.txt".to_vec())
+                .with_file("data/b.txt", b"included".to_vec()),
+        );
+        let importer: Box<dyn BoxedImporter> =
+            Box::new(SimpleImporter::<Text, _>::from(IncludeFormat));
+        io.importers = ImporterSet::new(vec![("inc", importer)]);
+
+        let id = io.import("mem://text/a.inc").unwrap().unwrap()[0];
+
+        assert!(io.artifacts[&id].data.ends_with(b"included"));
+    }
+
+    #[test]
+    fn path_dependencies_are_resolved_in_the_same_source() {
+        let mut io = source_io(
+            InMemorySource::new()
+                .with_file("sprites/sprite.txt", b"sprite".to_vec())
+                .with_file("sprites/sibling.txt", b"sibling".to_vec())
+                .with_file("textures/root.txt", b"root".to_vec()),
+        );
+        let id = io.import("mem://sprites/sprite.txt").unwrap().unwrap()[0];
+        {
+            let meta = io
+                .artifacts
+                .get_mut(&id)
+                .and_then(|artifact| artifact.metadata.artifact.as_mut())
+                .unwrap();
+            meta.load_deps = vec![
+                AssetRef::Path(PathBuf::from("sibling.txt")),
+                AssetRef::Path(PathBuf::from("textures/root.txt")),
+                AssetRef::Path(PathBuf::from("missing.txt")),
+                AssetRef::Uuid(AssetUuid([9; 16])),
+            ];
+        }
+
+        io.resolve_dependencies("mem://sprites/sprite.txt", &[id]);
+
+        let sibling = io.files["mem://sprites/sibling.txt"].ids[0];
+        let root = io.files["mem://textures/root.txt"].ids[0];
+        assert_eq!(
+            vec![
+                AssetRef::Uuid(sibling),
+                AssetRef::Uuid(root),
+                AssetRef::Uuid(AssetUuid([9; 16])),
+            ],
+            load_deps(&io, &id)
+        );
+    }
+
+    #[test]
+    fn paths_resolve_to_named_and_default_sources() {
+        let dir = env::temp_dir().join("amethyst_assets_sources");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("present.txt"), b"present").unwrap();
+
+        let mut sources = AssetSources::default();
+        sources.add_source("mods", Directory::new(&dir));
+        sources.set_default_source(Directory::new(&dir));
+
+        let (source, path) = sources.resolve("mods://present.txt").unwrap();
+        assert_eq!("present.txt", path);
+        assert_eq!(b"present".to_vec(), source.load(path).unwrap());
+
+        assert_eq!("present.txt", sources.resolve("present.txt").unwrap().1);
+        assert!(sources.resolve("missing.txt").is_none());
+        assert!(sources.resolve("unknown://present.txt").is_none());
+    }
+}
@@ -4,9 +4,17 @@ use atelier_assets::loader::{handle::AssetHandle, storage::IndirectionTable, Loa
 use crossbeam_queue::SegQueue;
 use log::debug;
 
+use crate::{
+    event::{AssetEvent, AssetEventKind},
+    usage::StorageUsage,
+    variant::AssetVariants,
+};
+
 struct AssetState<A> {
     version: u32,
+    revision: u32,
     asset: A,
+    byte_size: usize,
 }
 
 /// An asset storage, storing the actual assets
@@ -18,17 +26,33 @@ pub struct AssetStorage<A> {
     assets: HashMap<LoadHandle, AssetState<A>>,
     uncommitted: HashMap<LoadHandle, AssetState<A>>,
     to_drop: SegQueue<A>,
+    events: SegQueue<AssetEvent<A>>,
+    events_enabled: bool,
     indirection_table: IndirectionTable,
+    variants: AssetVariants,
+    bytes: usize,
 }
 
 impl<A> AssetStorage<A> {
     /// Creates a new asset storage.
     pub fn new(indirection_table: IndirectionTable) -> Self {
+        Self::with_variants(indirection_table, AssetVariants::default())
+    }
+
+    /// Creates a new asset storage resolving the handles of localized assets to their variants.
+    pub(crate) fn with_variants(
+        indirection_table: IndirectionTable,
+        variants: AssetVariants,
+    ) -> Self {
         Self {
             assets: Default::default(),
             uncommitted: Default::default(),
             to_drop: SegQueue::new(),
+            events: SegQueue::new(),
+            events_enabled: false,
             indirection_table,
+            variants,
+            bytes: 0,
         }
     }
 
@@ -36,9 +60,16 @@ impl<A> AssetStorage<A> {
     pub fn unload_all(&mut self) {
         // FIXME do the unload correctly
         self.assets.clear();
+        self.bytes = 0;
     }
 
-    pub(crate) fn update_asset(&mut self, handle: LoadHandle, asset: A, version: u32) {
+    pub(crate) fn update_asset(
+        &mut self,
+        handle: LoadHandle,
+        asset: A,
+        version: u32,
+        byte_size: usize,
+    ) {
         debug!(
             "AssetStorage<A>::update_asset load_handle: {:?}, version: {}",
             handle, version
@@ -55,8 +86,15 @@ impl<A> AssetStorage<A> {
             "insert asset with load_handle: {:?}, version: {}",
             handle, version
         );
-        self.uncommitted
-            .insert(handle, AssetState { version, asset });
+        self.uncommitted.insert(
+            handle,
+            AssetState {
+                version,
+                revision: 0,
+                asset,
+                byte_size,
+            },
+        );
     }
 
     pub(crate) fn remove_asset(&mut self, handle: LoadHandle, version: u32) {
@@ -68,8 +106,10 @@ impl<A> AssetStorage<A> {
         }
         if let Some(data) = self.assets.get(&handle) {
             if data.version == version {
-                self.to_drop
-                    .push(self.uncommitted.remove(&handle).unwrap().asset);
+                let data = self.assets.remove(&handle).unwrap();
+                self.bytes -= data.byte_size;
+                self.to_drop.push(data.asset);
+                self.push_event(handle, AssetEventKind::Unloaded);
             }
         }
     }
@@ -79,43 +119,96 @@ impl<A> AssetStorage<A> {
             if data.version != version {
                 panic!("attempted to commit asset version which mismatches with existing uncommitted version")
             }
-            if let Some(existing) = self.assets.remove(&handle) {
+            let kind = if let Some(existing) = self.assets.remove(&handle) {
                 // data already exists for the handle, drop it
+                self.bytes -= existing.byte_size;
                 self.to_drop.push(existing.asset);
-            }
-            self.assets.insert(
-                handle,
-                AssetState {
-                    version,
-                    asset: data.asset,
-                },
-            );
+                AssetEventKind::Reloaded { version }
+            } else {
+                AssetEventKind::Loaded { version }
+            };
+            self.push_event(handle, kind);
+            self.bytes += data.byte_size;
+            self.assets.insert(handle, data);
         } else {
             panic!("attempted to commit asset which doesn't exist");
         }
     }
 
+    /// Replaces the asset of a handle without a new load, such as a prefab cooked again after a
+    /// prefab it references was reloaded. Returns `false` if the handle has no asset.
+    ///
+    /// A committed asset keeps its version and gets the next revision, see `get_revision`.
+    pub(crate) fn replace_asset(&mut self, handle: LoadHandle, asset: A, byte_size: usize) -> bool {
+        if let Some(data) = self.uncommitted.get_mut(&handle) {
+            self.to_drop.push(std::mem::replace(&mut data.asset, asset));
+            data.byte_size = byte_size;
+            return true;
+        }
+        if let Some(data) = self.assets.get_mut(&handle) {
+            self.to_drop.push(std::mem::replace(&mut data.asset, asset));
+            self.bytes = self.bytes - data.byte_size + byte_size;
+            data.byte_size = byte_size;
+            data.revision += 1;
+            let version = data.version;
+            self.push_event(handle, AssetEventKind::Reloaded { version });
+            return true;
+        }
+        false
+    }
+
+    /// Returns the number of times the loaded asset of a handle was replaced since it was
+    /// committed, or `None` if has not completed loading.
+    pub(crate) fn get_revision(&self, load_handle: LoadHandle) -> Option<u32> {
+        self.get_asset_state(load_handle).map(|a| a.revision)
+    }
+
+    /// Queues the events of the storage from now on, to be drained by `drain_events` every frame.
+    ///
+    /// The events are not queued otherwise, so that they don't pile up in storages that are not
+    /// drained.
+    pub(crate) fn enable_events(&mut self) {
+        self.events_enabled = true;
+    }
+
+    /// Queues an event, written to the `EventChannel<AssetEvent<A>>` by `drain_events`.
+    pub(crate) fn push_event(&self, handle: LoadHandle, kind: AssetEventKind) {
+        if self.events_enabled {
+            self.events.push(AssetEvent::new(handle, kind));
+        }
+    }
+
+    /// Calls `event_fn` with the queued events, oldest first.
+    pub(crate) fn drain_events<F>(&self, mut event_fn: F)
+    where
+        F: FnMut(AssetEvent<A>),
+    {
+        while let Ok(event) = self.events.pop() {
+            event_fn(event);
+        }
+    }
+
+    /// Returns the direct load handle of a load handle, resolving handles of assets loaded by
+    /// path and of localized assets.
+    pub(crate) fn resolve(&self, load_handle: LoadHandle) -> Option<LoadHandle> {
+        let load_handle = self.variants.resolve(load_handle).unwrap_or(load_handle);
+        if load_handle.is_indirect() {
+            self.indirection_table.resolve(load_handle)
+        } else {
+            Some(load_handle)
+        }
+    }
+
     /// returns true when asset is loaded for this handle
     pub fn contains(&self, load_handle: LoadHandle) -> bool {
-        let load_handle = if load_handle.is_indirect() {
-            if let Some(handle) = self.indirection_table.resolve(load_handle) {
-                handle
-            } else {
-                return false;
-            }
-        } else {
-            load_handle
-        };
-        self.assets.contains_key(&load_handle)
+        match self.resolve(load_handle) {
+            Some(load_handle) => self.assets.contains_key(&load_handle),
+            None => false,
+        }
     }
 
     fn get_asset_state(&self, load_handle: LoadHandle) -> Option<&AssetState<A>> {
-        let load_handle = if load_handle.is_indirect() {
-            self.indirection_table.resolve(load_handle)?
-        } else {
-            load_handle
-        };
-        self.assets.get(&load_handle)
+        self.assets.get(&self.resolve(load_handle)?)
     }
 
     /// Returns the asset for the given load handle, or `None` if has not completed loading.
@@ -168,6 +261,30 @@ impl<A> AssetStorage<A> {
             .map(|a| (&a.asset, a.version))
     }
 
+    /// Returns the size in bytes of a loaded asset, as reported by `Asset::byte_size`, or `None`
+    /// if has not completed loading.
+    ///
+    /// # Parameters
+    ///
+    /// * `handle`: Handle of the asset.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `T`: Asset handle type.
+    pub fn get_byte_size<T: AssetHandle>(&self, handle: &T) -> Option<usize> {
+        self.get_asset_state(handle.load_handle())
+            .map(|a| a.byte_size)
+    }
+
+    /// Returns the number of loaded assets and their total size in bytes.
+    pub fn usage(&self) -> StorageUsage {
+        StorageUsage {
+            type_name: std::any::type_name::<A>(),
+            num_assets: self.assets.len(),
+            bytes: self.bytes,
+        }
+    }
+
     /// Process finished asset data and maintain the storage.
     ///
     /// This calls the `drop_fn` function for assets that were removed from the storage.
@@ -200,3 +317,136 @@ impl<A> atelier_assets::loader::handle::TypedAssetStorage<A> for AssetStorage<A>
         self.get_asset_with_version(handle)
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use atelier_assets::loader::{
+        io::{DataRequest, LoaderIO, MetadataRequest, ResolveRequest},
+        loader::LoaderState,
+        Loader as AtelierLoader,
+    };
+
+    use super::*;
+    use crate::{processor::ProcessingQueue, Asset};
+
+    pub(crate) struct NoIO;
+
+    impl LoaderIO for NoIO {
+        fn get_asset_metadata_with_dependencies(&mut self, _request: MetadataRequest) {}
+        fn get_asset_candidates(&mut self, _requests: Vec<ResolveRequest>) {}
+        fn get_artifacts(&mut self, _requests: Vec<DataRequest>) {}
+        fn tick(&mut self, _loader: &mut LoaderState) {}
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Text(&'static str);
+
+    impl Asset for Text {
+        fn name() -> &'static str {
+            "Text"
+        }
+        type Data = &'static str;
+    }
+
+    pub(crate) fn storage<A>() -> AssetStorage<A> {
+        AssetStorage::new(AtelierLoader::new(Box::new(NoIO)).indirection_table())
+    }
+
+    #[test]
+    fn replaced_assets_get_a_new_revision() {
+        let mut storage = storage();
+        let handle = LoadHandle(1);
+        assert!(!storage.replace_asset(handle, "a", 1));
+
+        storage.update_asset(handle, "a", 1, 1);
+        assert!(storage.replace_asset(handle, "b", 2));
+        storage.commit_asset(handle, 1);
+        assert_eq!(Some(&"b"), storage.get_for_load_handle(handle));
+        assert_eq!(Some(0), storage.get_revision(handle));
+
+        assert!(storage.replace_asset(handle, "c", 3));
+        assert_eq!(Some(&"c"), storage.get_for_load_handle(handle));
+        assert_eq!(Some(1), storage.get_revision(handle));
+        assert_eq!(3, storage.usage().bytes);
+
+        let mut dropped = Vec::new();
+        storage.process_custom_drop(|asset| dropped.push(asset));
+        assert_eq!(vec!["a", "b"], dropped);
+    }
+
+    #[test]
+    fn unloaded_assets_are_removed() {
+        let mut storage = storage();
+        let handle = LoadHandle(1);
+        storage.update_asset(handle, "a", 1, 1);
+        storage.commit_asset(handle, 1);
+        storage.remove_asset(handle, 1);
+        assert!(!storage.contains(handle));
+        assert_eq!(0, storage.usage().bytes);
+    }
+
+    fn events<A>(storage: &AssetStorage<A>) -> Vec<(LoadHandle, AssetEventKind)> {
+        let mut events = Vec::new();
+        storage.drain_events(|event| events.push((event.handle, event.kind)));
+        events
+    }
+
+    #[test]
+    fn events_are_queued_for_loads_reloads_and_unloads() {
+        let mut storage = storage();
+        storage.enable_events();
+        let handle = LoadHandle(1);
+
+        storage.update_asset(handle, "a", 1, 1);
+        storage.commit_asset(handle, 1);
+        storage.update_asset(handle, "b", 2, 1);
+        storage.commit_asset(handle, 2);
+        // The previous version is freed after the new one is committed.
+        storage.remove_asset(handle, 1);
+        storage.remove_asset(handle, 2);
+
+        assert_eq!(
+            vec![
+                (handle, AssetEventKind::Loaded { version: 1 }),
+                (handle, AssetEventKind::Reloaded { version: 2 }),
+                (handle, AssetEventKind::Unloaded),
+            ],
+            events(&storage)
+        );
+        assert!(events(&storage).is_empty());
+    }
+
+    #[test]
+    fn events_are_queued_for_failed_loads() {
+        let mut storage = storage::<Text>();
+        storage.enable_events();
+        let handle = LoadHandle(1);
+        let mut queue = ProcessingQueue::default();
+        queue.enqueue_from_data(handle, "a", Box::new(()), 1);
+
+        queue.process(&mut storage, |_| {
+            Err(amethyst_error::format_err!("invalid"))
+        });
+
+        assert!(!storage.contains(handle));
+        assert_eq!(
+            vec![(
+                handle,
+                AssetEventKind::Failed {
+                    error: "invalid".to_string()
+                }
+            )],
+            events(&storage)
+        );
+    }
+
+    #[test]
+    fn events_are_not_queued_unless_enabled() {
+        let mut storage = storage();
+        let handle = LoadHandle(1);
+        storage.update_asset(handle, "a", 1, 1);
+        storage.commit_asset(handle, 1);
+        storage.remove_asset(handle, 1);
+        assert!(events(&storage).is_empty());
+    }
+}
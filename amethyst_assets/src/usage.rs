@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use atelier_assets::loader::AssetTypeId;
+use type_uuid::TypeUuid;
+
+/// Number of loaded assets of one type and their total size.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StorageUsage {
+    /// Name of the asset type.
+    pub type_name: &'static str,
+    /// Number of loaded assets.
+    pub num_assets: usize,
+    /// Total size of the loaded assets in bytes, as reported by `Asset::byte_size`.
+    pub bytes: usize,
+}
+
+/// Resource with the memory usage of the asset storages of all registered asset types.
+///
+/// This is updated by the `LoaderBundle` every frame after the assets are loaded.
+///
+/// # Examples
+///
+/// ```
+/// use amethyst_assets::AssetUsage;
+///
+/// fn log_usage(usage: &AssetUsage) {
+///     for storage in usage.storages().filter(|s| s.num_assets > 0) {
+///         println!("{}: {} bytes", storage.type_name, storage.bytes);
+///     }
+///     println!("Total: {} bytes", usage.total_bytes());
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct AssetUsage {
+    storages: HashMap<AssetTypeId, StorageUsage>,
+}
+
+impl AssetUsage {
+    /// Returns the usage of the storage of `A`, if the asset type is registered.
+    pub fn get<A: TypeUuid>(&self) -> Option<&StorageUsage> {
+        self.storages.get(&AssetTypeId(A::UUID))
+    }
+
+    /// Returns the usage of the storages of all registered asset types.
+    pub fn storages(&self) -> impl Iterator<Item = &StorageUsage> {
+        self.storages.values()
+    }
+
+    /// Returns the number of loaded assets of all types.
+    pub fn total_assets(&self) -> usize {
+        self.storages().map(|s| s.num_assets).sum()
+    }
+
+    /// Returns the total size of the loaded assets of all types in bytes.
+    pub fn total_bytes(&self) -> usize {
+        self.storages().map(|s| s.bytes).sum()
+    }
+
+    pub(crate) fn set(&mut self, asset_type: AssetTypeId, usage: StorageUsage) {
+        self.storages.insert(asset_type, usage);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(TypeUuid)]
+    #[uuid = "0d5f5ad4-8c1c-4b0e-9d07-5a3a36a6d8a1"]
+    struct Texture;
+
+    #[test]
+    fn totals_sum_all_storages() {
+        let mut usage = AssetUsage::default();
+        usage.set(
+            AssetTypeId(Texture::UUID),
+            StorageUsage {
+                type_name: "Texture",
+                num_assets: 2,
+                bytes: 300,
+            },
+        );
+        usage.set(
+            AssetTypeId([1; 16]),
+            StorageUsage {
+                type_name: "Mesh",
+                num_assets: 1,
+                bytes: 50,
+            },
+        );
+
+        assert_eq!(3, usage.total_assets());
+        assert_eq!(350, usage.total_bytes());
+        assert_eq!(300, usage.get::<Texture>().unwrap().bytes);
+    }
+}
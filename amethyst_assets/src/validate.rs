@@ -0,0 +1,410 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use amethyst_error::Error;
+use atelier_assets::core::{AssetRef, AssetUuid};
+
+use crate::{
+    daemon::AssetDaemonConfig,
+    import::{source_files, ImporterSet},
+    pack::{normalize_path, resolve_path_dependency},
+};
+
+/// Problem found in the asset directories by [`AssetValidator`](struct.AssetValidator.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// The source file could not be imported.
+    ImportFailed {
+        /// Path of the source file.
+        path: PathBuf,
+        /// Description of the import error.
+        error: String,
+    },
+    /// No importer is registered for the extension of the source file.
+    UnknownExtension {
+        /// Path of the source file.
+        path: PathBuf,
+    },
+    /// An imported asset depends on an asset that is not imported from the asset directories.
+    MissingDependency {
+        /// Path of the source file of the asset.
+        path: PathBuf,
+        /// UUID of the asset.
+        asset: AssetUuid,
+        /// UUID or path of the missing dependency.
+        dependency: String,
+    },
+    /// Several imported assets have the same UUID, such as after a `.meta` file was copied.
+    DuplicateUuid {
+        /// The UUID.
+        asset: AssetUuid,
+        /// Paths of the source files of the assets.
+        paths: Vec<PathBuf>,
+    },
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationIssue::ImportFailed { path, error } => {
+                write!(f, "{}: import failed: {}", path.display(), error)
+            }
+            ValidationIssue::UnknownExtension { path } => {
+                write!(f, "{}: no importer for this extension", path.display())
+            }
+            ValidationIssue::MissingDependency {
+                path,
+                asset,
+                dependency,
+            } => write!(
+                f,
+                "{}: asset {:?} depends on missing asset {}",
+                path.display(),
+                asset,
+                dependency
+            ),
+            ValidationIssue::DuplicateUuid { asset, paths } => {
+                write!(f, "asset UUID {:?} is used by", asset)?;
+                for path in paths {
+                    write!(f, " {}", path.display())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Result of validating the asset directories with
+/// [`AssetValidator`](struct.AssetValidator.html).
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    files: usize,
+    assets: usize,
+    issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Returns `true` if no issue was found.
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Returns the issues found, in the order of the source files.
+    pub fn issues(&self) -> &[ValidationIssue] {
+        &self.issues
+    }
+
+    /// Returns the number of source files, not counting `.meta` files.
+    pub fn num_files(&self) -> usize {
+        self.files
+    }
+
+    /// Returns the number of imported assets.
+    pub fn num_assets(&self) -> usize {
+        self.assets
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for issue in &self.issues {
+            writeln!(f, "{}", issue)?;
+        }
+        write!(
+            f,
+            "Imported {} assets from {} files, found {} issues",
+            self.assets,
+            self.files,
+            self.issues.len()
+        )
+    }
+}
+
+/// Imported asset with the source file it was imported from.
+struct ValidatedAsset {
+    path: PathBuf,
+    relative: String,
+    id: AssetUuid,
+    deps: Vec<AssetRef>,
+}
+
+/// Runs the importers of the asset daemon over the asset directories without starting the
+/// daemon or a game, to find broken assets before the game loads them.
+///
+/// Every source file is imported with the importer options and state of its `.meta` file, like
+/// the daemon and [`AssetPacker`](struct.AssetPacker.html) do, and the report lists the files
+/// that fail to import, the files without importer, the dependencies that are not imported and
+/// the UUIDs used by several assets. Hidden files, such as `.gitignore`, are skipped.
+///
+/// Only the importers linked into the running binary are used, so validate the assets from a
+/// binary that depends on the same crates as the game.
+///
+/// # Examples
+///
+/// ```no_run
+/// use amethyst_assets::{AssetDaemonConfig, AssetValidator};
+///
+/// let report = AssetValidator::new(AssetDaemonConfig::default().with_asset_dir("assets"))
+///     .with_ignored_extension("md")
+///     .validate()
+///     .expect("Failed to read the asset directories");
+/// println!("{}", report);
+/// if !report.is_ok() {
+///     std::process::exit(1);
+/// }
+/// ```
+pub struct AssetValidator {
+    asset_dirs: Vec<PathBuf>,
+    importers: ImporterSet,
+    ignored_extensions: Vec<String>,
+}
+
+impl fmt::Debug for AssetValidator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AssetValidator")
+            .field("asset_dirs", &self.asset_dirs)
+            .field("importers", &self.importers)
+            .field("ignored_extensions", &self.ignored_extensions)
+            .finish()
+    }
+}
+
+impl AssetValidator {
+    /// Creates a validator for the asset directories and importers of the daemon configuration.
+    pub fn new(config: AssetDaemonConfig) -> Self {
+        let (asset_dirs, importers) = config.into_asset_dirs_and_importers();
+        AssetValidator {
+            asset_dirs,
+            importers: ImporterSet::new(importers),
+            ignored_extensions: Vec::new(),
+        }
+    }
+
+    /// Skips the files with the given extension, such as documentation or tool files kept with
+    /// the assets, instead of reporting them as `UnknownExtension`.
+    pub fn with_ignored_extension<S: Into<String>>(mut self, ext: S) -> Self {
+        self.ignored_extensions
+            .push(ext.into().trim_start_matches('.').to_lowercase());
+        self
+    }
+
+    /// Imports all source files and returns the issues found.
+    ///
+    /// Returns an error only if an asset directory cannot be read.
+    pub fn validate(&self) -> Result<ValidationReport, Error> {
+        let mut report = ValidationReport::default();
+        let mut assets = Vec::new();
+
+        for asset_dir in &self.asset_dirs {
+            for file in source_files(asset_dir)? {
+                if self.is_skipped(&file) {
+                    continue;
+                }
+                report.files += 1;
+                let relative = file.strip_prefix(asset_dir).unwrap_or(&file);
+                let relative = normalize_path(&relative.to_string_lossy());
+                match self.importers.import_file(&file) {
+                    Ok(Some(imported)) => {
+                        assets.extend(imported.into_iter().map(|artifact| {
+                            let deps = artifact
+                                .metadata
+                                .artifact
+                                .map(|meta| {
+                                    meta.load_deps
+                                        .into_iter()
+                                        .chain(meta.build_deps.into_iter())
+                                        .collect()
+                                })
+                                .unwrap_or_default();
+                            ValidatedAsset {
+                                path: file.clone(),
+                                relative: relative.clone(),
+                                id: artifact.metadata.id,
+                                deps,
+                            }
+                        }));
+                    }
+                    Ok(None) => report
+                        .issues
+                        .push(ValidationIssue::UnknownExtension { path: file }),
+                    Err(err) => report.issues.push(ValidationIssue::ImportFailed {
+                        path: file,
+                        error: error_chain(&err),
+                    }),
+                }
+            }
+        }
+
+        report.assets = assets.len();
+        report.issues.extend(dependency_issues(&assets));
+        report.issues.extend(duplicate_issues(&assets));
+        Ok(report)
+    }
+
+    fn is_skipped(&self, file: &Path) -> bool {
+        let hidden = file
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map_or(false, |name| name.starts_with('.'));
+        let ext = file
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_lowercase);
+        hidden
+            || ext.as_deref() == Some("meta")
+            || ext.map_or(false, |ext| self.ignored_extensions.contains(&ext))
+    }
+}
+
+/// Returns the description of an error with the errors that caused it.
+fn error_chain(err: &Error) -> String {
+    err.causes()
+        .map(|cause| cause.to_string())
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+fn dependency_issues(assets: &[ValidatedAsset]) -> Vec<ValidationIssue> {
+    let ids: HashSet<AssetUuid> = assets.iter().map(|asset| asset.id).collect();
+    let mut paths = HashMap::new();
+    for asset in assets {
+        paths
+            .entry(asset.relative.clone())
+            .or_insert_with(Vec::new)
+            .push(asset.id);
+    }
+
+    let mut issues = Vec::new();
+    for asset in assets {
+        for dep in &asset.deps {
+            let found = match dep {
+                AssetRef::Uuid(id) => ids.contains(id),
+                AssetRef::Path(path) => {
+                    resolve_path_dependency(&paths, &asset.relative, path).is_some()
+                }
+            };
+            if !found {
+                issues.push(ValidationIssue::MissingDependency {
+                    path: asset.path.clone(),
+                    asset: asset.id,
+                    dependency: match dep {
+                        AssetRef::Uuid(id) => format!("{:?}", id),
+                        AssetRef::Path(path) => path.display().to_string(),
+                    },
+                });
+            }
+        }
+    }
+    issues
+}
+
+fn duplicate_issues(assets: &[ValidatedAsset]) -> Vec<ValidationIssue> {
+    let mut paths_by_id: HashMap<AssetUuid, Vec<PathBuf>> = HashMap::new();
+    let mut order = Vec::new();
+    for asset in assets {
+        let paths = paths_by_id.entry(asset.id).or_insert_with(|| {
+            order.push(asset.id);
+            Vec::new()
+        });
+        paths.push(asset.path.clone());
+    }
+    order
+        .into_iter()
+        .filter_map(|asset| {
+            let paths = paths_by_id.remove(&asset)?;
+            if paths.len() > 1 {
+                Some(ValidationIssue::DuplicateUuid { asset, paths })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    fn asset(path: &str, id: u8, deps: Vec<AssetRef>) -> ValidatedAsset {
+        ValidatedAsset {
+            path: PathBuf::from(path),
+            relative: path.to_string(),
+            id: AssetUuid([id; 16]),
+            deps,
+        }
+    }
+
+    #[test]
+    fn unknown_extensions_are_reported_unless_ignored() {
+        let dir = env::temp_dir().join("amethyst_assets_validate_unknown");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("notes.unknown"), b"").unwrap();
+        fs::write(dir.join("README.md"), b"").unwrap();
+        fs::write(dir.join(".gitignore"), b"").unwrap();
+
+        let report = AssetValidator::new(AssetDaemonConfig::default().with_asset_dir(&dir))
+            .with_ignored_extension(".md")
+            .validate()
+            .unwrap();
+
+        assert!(!report.is_ok());
+        assert_eq!(1, report.num_files());
+        assert_eq!(
+            &[ValidationIssue::UnknownExtension {
+                path: dir.join("notes.unknown")
+            }],
+            report.issues()
+        );
+    }
+
+    #[test]
+    fn missing_dependencies_are_reported() {
+        let assets = vec![
+            asset("a.ron", 1, vec![AssetRef::Uuid(AssetUuid([2; 16]))]),
+            asset("b.ron", 2, vec![AssetRef::Path(PathBuf::from("c.png"))]),
+            asset(
+                "sprites/d.ron",
+                3,
+                vec![
+                    AssetRef::Path(PathBuf::from("b.ron")),
+                    AssetRef::Path(PathBuf::from("../texture/bat.32x32.png")),
+                ],
+            ),
+            asset("texture/bat.32x32.png", 4, Vec::new()),
+        ];
+
+        let issues = dependency_issues(&assets);
+
+        assert_eq!(1, issues.len());
+        assert_eq!(
+            ValidationIssue::MissingDependency {
+                path: PathBuf::from("b.ron"),
+                asset: AssetUuid([2; 16]),
+                dependency: "c.png".to_string(),
+            },
+            issues[0]
+        );
+    }
+
+    #[test]
+    fn duplicate_uuids_are_reported_once() {
+        let assets = vec![
+            asset("a.ron", 1, Vec::new()),
+            asset("b.ron", 2, Vec::new()),
+            asset("copy_of_a.ron", 1, Vec::new()),
+        ];
+
+        assert_eq!(
+            vec![ValidationIssue::DuplicateUuid {
+                asset: AssetUuid([1; 16]),
+                paths: vec![PathBuf::from("a.ron"), PathBuf::from("copy_of_a.ron")],
+            }],
+            duplicate_issues(&assets)
+        );
+    }
+}
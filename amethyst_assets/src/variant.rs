@@ -0,0 +1,336 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use atelier_assets::loader::{
+    storage::{IndirectIdentifier, LoadStatus},
+    AssetTypeId, Loader as AtelierLoader,
+};
+use log::{debug, warn};
+
+use crate::loader::LoadHandle;
+
+/// Placeholder in the paths passed to `Loader::load` that is replaced by the locale set with
+/// `Loader::set_locale`, such as in `"ui/title.{lang}.png"`.
+pub const LOCALE_PLACEHOLDER: &str = "{lang}";
+
+/// Returns the paths to look up for a path with a locale placeholder, in order.
+///
+/// For the locale `fr-CA`, `ui/title.{lang}.png` is looked up as `ui/title.fr-CA.png`, then as
+/// `ui/title.fr.png` and finally as `ui/title.png`. A separator next to the placeholder is
+/// removed with it in the last path, so `voice/{lang}/intro.ogg` falls back to `voice/intro.ogg`.
+pub(crate) fn locale_candidates(path: &str, locale: Option<&str>) -> Vec<String> {
+    let mut candidates = Vec::new();
+    if let Some(locale) = locale {
+        candidates.push(path.replace(LOCALE_PLACEHOLDER, locale));
+        if let Some(index) = locale.find(|c| c == '-' || c == '_') {
+            candidates.push(path.replace(LOCALE_PLACEHOLDER, &locale[..index]));
+        }
+    }
+    candidates.push(without_placeholder(path));
+    candidates.dedup();
+    candidates
+}
+
+fn without_placeholder(path: &str) -> String {
+    let mut path = path.to_string();
+    while let Some(start) = path.find(LOCALE_PLACEHOLDER) {
+        let mut end = start + LOCALE_PLACEHOLDER.len();
+        let mut start = start;
+        if path[end..].starts_with('/') {
+            end += 1;
+        } else if path[..start].ends_with(|c| c == '.' || c == '_' || c == '-') {
+            start -= 1;
+        }
+        path.replace_range(start..end, "");
+    }
+    path
+}
+
+/// Load of a path with a locale placeholder, behind a handle that keeps referring to it when the
+/// locale changes.
+struct VariantLoad {
+    path: String,
+    /// Type of the data of the asset type of the handle, which selects the asset of the variants
+    /// that produce several assets.
+    asset_type: Option<AssetTypeId>,
+    refs: usize,
+    /// Locale the candidates are looked up for.
+    locale: Option<String>,
+    candidates: Vec<String>,
+    /// Index and handle of the candidate that is loading.
+    pending: Option<(usize, LoadHandle)>,
+    /// Handle of the loaded candidate.
+    current: Option<LoadHandle>,
+}
+
+#[derive(Default)]
+struct VariantsInner {
+    locale: Option<String>,
+    loads: HashMap<LoadHandle, VariantLoad>,
+    /// Handles of the loaded candidates by variant handle, read by the asset storages.
+    resolved: HashMap<LoadHandle, LoadHandle>,
+}
+
+/// Loads of localized asset variants, shared by the loader and the asset storages.
+#[derive(Clone, Default)]
+pub(crate) struct AssetVariants {
+    inner: Arc<RwLock<VariantsInner>>,
+}
+
+impl AssetVariants {
+    /// Registers the load of a path with a locale placeholder for the given handle.
+    ///
+    /// The variants are resolved like `Loader::load` resolves paths, see `path_identifier`.
+    pub(crate) fn insert(&self, handle: LoadHandle, path: &str, asset_type: Option<AssetTypeId>) {
+        let mut inner = self.inner.write().expect("AssetVariants lock was poisoned");
+        inner.loads.insert(
+            handle,
+            VariantLoad {
+                path: path.to_string(),
+                asset_type,
+                refs: 1,
+                locale: None,
+                candidates: Vec::new(),
+                pending: None,
+                current: None,
+            },
+        );
+    }
+
+    /// Returns `true` if the handle is the handle of a localized load.
+    pub(crate) fn contains(&self, handle: LoadHandle) -> bool {
+        let inner = self.inner.read().expect("AssetVariants lock was poisoned");
+        inner.loads.contains_key(&handle)
+    }
+
+    /// Returns the handle of the loaded variant of a localized load.
+    pub(crate) fn resolve(&self, handle: LoadHandle) -> Option<LoadHandle> {
+        let inner = self.inner.read().expect("AssetVariants lock was poisoned");
+        inner.resolved.get(&handle).copied()
+    }
+
+    /// Returns the handle of the variant a localized load is loading, or of the loaded variant.
+    pub(crate) fn loading(&self, handle: LoadHandle) -> Option<LoadHandle> {
+        let inner = self.inner.read().expect("AssetVariants lock was poisoned");
+        inner
+            .loads
+            .get(&handle)
+            .and_then(|load| load.pending.map(|(_, pending)| pending).or(load.current))
+    }
+
+    /// Returns the load status of a localized load, which stays `Loaded` while the variant for a
+    /// new locale is loading, or `None` if the handle is not the handle of a localized load.
+    pub(crate) fn get_load_status(
+        &self,
+        loader: &AtelierLoader,
+        handle: LoadHandle,
+    ) -> Option<LoadStatus> {
+        let inner = self.inner.read().expect("AssetVariants lock was poisoned");
+        inner.loads.get(&handle).map(|load| match load.current {
+            Some(current) => loader.get_load_status(current),
+            // The next candidate is requested by `update` when a variant does not exist.
+            None if load.pending.is_some() || load.candidates.is_empty() => LoadStatus::Loading,
+            None => LoadStatus::DoesNotExist,
+        })
+    }
+
+    /// Returns the locale the variants are loaded for.
+    pub(crate) fn locale(&self) -> Option<String> {
+        let inner = self.inner.read().expect("AssetVariants lock was poisoned");
+        inner.locale.clone()
+    }
+
+    /// Sets the locale the variants are loaded for, the loaded variants are replaced once the
+    /// variants for the new locale are loaded.
+    pub(crate) fn set_locale(&self, locale: Option<&str>) {
+        let mut inner = self.inner.write().expect("AssetVariants lock was poisoned");
+        inner.locale = locale.map(str::to_string);
+    }
+
+    /// Counts a new handle to a localized load.
+    pub(crate) fn add_ref(&self, handle: LoadHandle) {
+        let mut inner = self.inner.write().expect("AssetVariants lock was poisoned");
+        if let Some(load) = inner.loads.get_mut(&handle) {
+            load.refs += 1;
+        }
+    }
+
+    /// Counts a dropped handle to a localized load, and releases its variants with the last one.
+    ///
+    /// Returns the handles of the released variants.
+    pub(crate) fn remove_ref(&self, loader: &AtelierLoader, handle: LoadHandle) -> Vec<LoadHandle> {
+        let mut inner = self.inner.write().expect("AssetVariants lock was poisoned");
+        let released = match inner.loads.get_mut(&handle) {
+            Some(load) => {
+                load.refs -= 1;
+                load.refs == 0
+            }
+            None => false,
+        };
+        if !released {
+            return Vec::new();
+        }
+        let load = inner.loads.remove(&handle).unwrap();
+        inner.resolved.remove(&handle);
+        let handles = load
+            .pending
+            .map(|(_, h)| h)
+            .into_iter()
+            .chain(load.current)
+            .collect::<Vec<_>>();
+        for handle in &handles {
+            loader.remove_ref(*handle);
+        }
+        handles
+    }
+
+    /// Requests the variants for the current locale, moves to the next candidate when a variant
+    /// does not exist, and switches the localized loads to the variants that finished loading.
+    pub(crate) fn update(&self, loader: &AtelierLoader) {
+        let mut inner = self.inner.write().expect("AssetVariants lock was poisoned");
+        let VariantsInner {
+            locale,
+            loads,
+            resolved,
+        } = &mut *inner;
+
+        for (handle, load) in loads.iter_mut() {
+            if load.candidates.is_empty() || load.locale != *locale {
+                if let Some((_, pending)) = load.pending.take() {
+                    loader.remove_ref(pending);
+                }
+                load.locale = locale.clone();
+                load.candidates = locale_candidates(&load.path, locale.as_deref());
+                let first = path_identifier(&load.candidates[0], load.asset_type);
+                load.pending = Some((0, loader.add_ref_indirect(first)));
+            }
+
+            let (index, pending) = match load.pending {
+                Some(pending) => pending,
+                None => continue,
+            };
+            match loader.get_load_status(pending) {
+                LoadStatus::Loaded => {
+                    debug!("Loaded {:?} for {:?}", load.candidates[index], load.path);
+                    load.pending = None;
+                    resolved.insert(*handle, pending);
+                    if let Some(previous) = load.current.replace(pending) {
+                        if previous != pending {
+                            loader.remove_ref(previous);
+                        }
+                    }
+                }
+                LoadStatus::DoesNotExist | LoadStatus::Error(_) => {
+                    loader.remove_ref(pending);
+                    load.pending = match load.candidates.get(index + 1) {
+                        Some(next) => {
+                            let next = path_identifier(next, load.asset_type);
+                            Some((index + 1, loader.add_ref_indirect(next)))
+                        }
+                        None => {
+                            warn!(
+                                "No variant of {:?} exists for the locale {:?}",
+                                load.path, load.locale
+                            );
+                            None
+                        }
+                    };
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Returns the identifier of a path, which selects the asset with the data type `asset_type` if
+/// the source file produces several assets.
+pub(crate) fn path_identifier(path: &str, asset_type: Option<AssetTypeId>) -> IndirectIdentifier {
+    match asset_type {
+        Some(asset_type) => IndirectIdentifier::PathWithType(path.to_string(), asset_type),
+        None => IndirectIdentifier::Path(path.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use atelier_assets::loader::{
+        io::{DataRequest, LoaderIO, MetadataRequest, ResolveRequest},
+        loader::LoaderState,
+    };
+
+    use super::*;
+
+    #[test]
+    fn candidates_fall_back_to_language_and_path_without_locale() {
+        assert_eq!(
+            vec!["ui/title.fr-CA.png", "ui/title.fr.png", "ui/title.png"],
+            locale_candidates("ui/title.{lang}.png", Some("fr-CA"))
+        );
+        assert_eq!(
+            vec!["voice/de/intro.ogg", "voice/intro.ogg"],
+            locale_candidates("voice/{lang}/intro.ogg", Some("de"))
+        );
+        assert_eq!(
+            vec!["fonts/main.ttf"],
+            locale_candidates("fonts/main_{lang}.ttf", None)
+        );
+    }
+
+    #[test]
+    fn candidates_are_resolved_with_the_asset_type_of_the_load() {
+        let asset_type = AssetTypeId([7; 16]);
+        match path_identifier("ui/title.fr.png", Some(asset_type)) {
+            IndirectIdentifier::PathWithType(path, candidate_type) => {
+                assert_eq!("ui/title.fr.png", path);
+                assert_eq!(asset_type, candidate_type);
+            }
+            _ => panic!("Candidates are not resolved with the asset type"),
+        }
+        assert!(matches!(
+            path_identifier("ui/title.png", None),
+            IndirectIdentifier::Path(_)
+        ));
+    }
+
+    struct NoIO;
+
+    impl LoaderIO for NoIO {
+        fn get_asset_metadata_with_dependencies(&mut self, _request: MetadataRequest) {}
+        fn get_asset_candidates(&mut self, _requests: Vec<ResolveRequest>) {}
+        fn get_artifacts(&mut self, _requests: Vec<DataRequest>) {}
+        fn tick(&mut self, _loader: &mut LoaderState) {}
+    }
+
+    #[test]
+    fn localized_loads_keep_loading_when_the_locale_changes() {
+        let loader = AtelierLoader::new(Box::new(NoIO));
+        let variants = AssetVariants::default();
+        let handle = LoadHandle(42);
+        variants.insert(handle, "ui/title.{lang}.png", None);
+        assert!(matches!(
+            variants.get_load_status(&loader, handle),
+            Some(LoadStatus::Loading)
+        ));
+
+        variants.update(&loader);
+        let untranslated = variants.loading(handle).unwrap();
+        assert!(matches!(
+            variants.get_load_status(&loader, handle),
+            Some(LoadStatus::Loading)
+        ));
+
+        variants.set_locale(Some("fr"));
+        variants.update(&loader);
+        let translated = variants.loading(handle).unwrap();
+        assert_ne!(untranslated, translated);
+        assert!(matches!(
+            variants.get_load_status(&loader, handle),
+            Some(LoadStatus::Loading)
+        ));
+
+        assert_eq!(vec![translated], variants.remove_ref(&loader, handle));
+        assert!(variants.get_load_status(&loader, handle).is_none());
+    }
+}
@@ -1,6 +1,6 @@
-use std::{panic, path::PathBuf, sync::Once};
+use std::{env, panic, sync::Once};
 
-use amethyst_assets::start_asset_daemon;
+use amethyst_assets::AssetDaemonConfig;
 
 pub fn setup_logger() {
     fern::Dispatch::new()
@@ -27,7 +27,11 @@ where
 {
     INIT.call_once(|| {
         setup_logger();
-        start_asset_daemon(vec![PathBuf::from("tests/assets")]);
+        AssetDaemonConfig::default()
+            .with_asset_dir("tests/assets")
+            .with_db_dir(env::temp_dir().join("amethyst_assets_tests_db"))
+            .start()
+            .detach();
     });
 
     let result = panic::catch_unwind(|| test());
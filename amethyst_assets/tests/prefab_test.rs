@@ -1,7 +1,7 @@
 use std::time::{Duration, Instant};
 
 use amethyst_assets::{
-    prefab::{register_component_type, Prefab},
+    prefab::{register_component_type, Prefab, PrefabInstance},
     AssetHandle, AssetStorage, DefaultLoader, Handle, LoadStatus, Loader, LoaderBundle,
 };
 use amethyst_core::ecs::{
@@ -121,6 +121,66 @@ fn a_prefab_is_applied_to_an_entity() {
     })
 }
 
+#[test]
+#[serial]
+fn a_prefab_is_applied_only_once() {
+    common::run_test(|| {
+        let (mut dispatcher, mut world, mut resources) = setup();
+
+        let prefab_handle: Handle<Prefab> = {
+            let loader = resources
+                .get_mut::<DefaultLoader>()
+                .expect("Missing loader");
+            loader.load("test_provided_component.prefab")
+        };
+
+        execute_dispatcher_until_loaded(
+            &mut dispatcher,
+            &mut world,
+            &mut resources,
+            prefab_handle.clone(),
+        );
+
+        let entity = world.push((prefab_handle,));
+
+        execute_dispatcher_until_prefab_is_applied(
+            &mut dispatcher,
+            &mut world,
+            &mut resources,
+            entity,
+        );
+
+        world
+            .entry(entity)
+            .expect("Could not retrieve entity from world")
+            .add_component(Position2D { x: 1, y: 2 });
+
+        for _ in 0..3 {
+            dispatcher.execute(&mut world, &mut resources);
+        }
+
+        let entry = world
+            .entry(entity)
+            .expect("Could not retrieve entity from world");
+
+        assert_eq!(
+            Position2D { x: 1, y: 2 },
+            *entry.get_component::<Position2D>().unwrap(),
+            "The prefab was applied again",
+        );
+        assert_eq!(
+            1,
+            entry
+                .get_component::<PrefabInstance>()
+                .expect("Missing PrefabInstance")
+                .entities()
+                .count()
+        );
+
+        dispatcher.unload(&mut world, &mut resources).unwrap();
+    })
+}
+
 fn execute_dispatcher_until_prefab_is_applied(
     dispatcher: &mut Dispatcher,
     world: &mut World,
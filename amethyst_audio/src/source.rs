@@ -28,6 +28,10 @@ impl Asset for Source {
         "audio::Source"
     }
     type Data = AudioData;
+
+    fn byte_size(&self) -> usize {
+        self.bytes.len()
+    }
 }
 
 impl ProcessableAsset for Source {
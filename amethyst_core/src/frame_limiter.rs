@@ -176,6 +176,11 @@ impl FrameLimiter {
         self.frame_duration = Duration::from_secs(1) / fps;
     }
 
+    /// Returns the frame rate limiting strategy.
+    pub fn strategy(&self) -> &FrameRateLimitStrategy {
+        &self.strategy
+    }
+
     /// Creates a new frame limiter with the given config.
     pub fn from_config(config: FrameRateLimitConfig) -> Self {
         Self::new(config.strategy, config.fps)
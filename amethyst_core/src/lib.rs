@@ -55,6 +55,9 @@ pub mod frame_limiter;
 /// The geometry module.
 pub mod geometry;
 
+/// Background tasks on the shared thread pool.
+pub mod tasks;
+
 /// The timing module.
 pub mod timing;
 
@@ -0,0 +1,469 @@
+//! Background tasks running on the shared thread pool.
+//!
+//! Systems spawn work through the [`Tasks`] resource and either poll the returned
+//! [`TaskHandle`], or have the result delivered as a [`TaskResult`] event. Events are written on
+//! the main thread by [`apply_completed_tasks`], which the application calls at the start of
+//! every frame, and which the [`TasksBundle`] also calls at the point of the dispatcher where the
+//! bundle was added.
+//!
+//! [`Tasks`]: struct.Tasks.html
+//! [`TaskHandle`]: struct.TaskHandle.html
+//! [`TaskResult`]: struct.TaskResult.html
+//! [`apply_completed_tasks`]: fn.apply_completed_tasks.html
+//! [`TasksBundle`]: struct.TasksBundle.html
+
+use std::{
+    fmt,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use amethyst_error::Error;
+use log::error;
+
+use crate::{
+    ecs::{DispatcherBuilder, Resources, SystemBundle, World},
+    ArcThreadPool, EventChannel,
+};
+
+/// Identifies a task spawned through [`Tasks`](struct.Tasks.html).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TaskId(u64);
+
+/// The event written to `EventChannel<TaskResult<T>>` when a task spawned with
+/// [`Tasks::spawn_event`](struct.Tasks.html#method.spawn_event) completes.
+#[derive(Clone, Debug)]
+pub struct TaskResult<T> {
+    /// The task that produced this result.
+    pub id: TaskId,
+    /// The value returned by the task.
+    pub value: T,
+}
+
+/// Lets a running task check whether it was cancelled, so that long-running work can stop early.
+#[derive(Clone, Debug)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Returns `true` if the task was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Result of polling a [`TaskHandle`](struct.TaskHandle.html).
+#[derive(Debug, PartialEq, Eq)]
+pub enum TaskPoll<T> {
+    /// The task has not completed yet.
+    Pending,
+    /// The task completed with the given value.
+    Ready(T),
+    /// The value was already returned by a previous poll.
+    Taken,
+    /// The task was cancelled before it completed.
+    Cancelled,
+    /// The task panicked.
+    Panicked,
+}
+
+#[derive(Debug)]
+enum TaskState<T> {
+    Pending,
+    Done(T),
+    Taken,
+    Cancelled,
+    Panicked,
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    state: Mutex<TaskState<T>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// A handle to a task spawned with [`Tasks::spawn`](struct.Tasks.html#method.spawn).
+///
+/// The handles of `spawn_event` tasks hold no value: they are `Ready` once the result was queued
+/// for the event channel.
+///
+/// Dropping the handle does not cancel the task, use `cancel` for that.
+#[derive(Debug)]
+pub struct TaskHandle<T> {
+    id: TaskId,
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> TaskHandle<T> {
+    /// Returns the id of the task.
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    /// Returns the result of the task if it has completed, without blocking.
+    pub fn poll(&self) -> TaskPoll<T> {
+        let mut state = self.shared.state.lock().unwrap_or_else(|e| e.into_inner());
+        match std::mem::replace(&mut *state, TaskState::Taken) {
+            TaskState::Pending => {
+                *state = TaskState::Pending;
+                TaskPoll::Pending
+            }
+            TaskState::Done(value) => TaskPoll::Ready(value),
+            TaskState::Taken => TaskPoll::Taken,
+            TaskState::Cancelled => {
+                *state = TaskState::Cancelled;
+                TaskPoll::Cancelled
+            }
+            TaskState::Panicked => {
+                *state = TaskState::Panicked;
+                TaskPoll::Panicked
+            }
+        }
+    }
+
+    /// Returns `true` if the task is no longer pending.
+    pub fn is_finished(&self) -> bool {
+        !matches!(
+            *self.shared.state.lock().unwrap_or_else(|e| e.into_inner()),
+            TaskState::Pending
+        )
+    }
+
+    /// Cancels the task.
+    ///
+    /// A task that has not started yet will not run. A running task keeps running unless it
+    /// checks its `CancellationToken`, but its result is discarded, and for `spawn_event` tasks
+    /// no event is written. Tasks that already completed are not affected.
+    pub fn cancel(&self) {
+        self.shared.cancelled.store(true, Ordering::Relaxed);
+        let mut state = self.shared.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let TaskState::Pending = *state {
+            *state = TaskState::Cancelled;
+        }
+    }
+}
+
+type Completion = Box<dyn FnOnce(&mut Resources) + Send>;
+
+/// Resource for running background jobs, such as pathfinding, procedural generation or file IO,
+/// on the shared `ArcThreadPool`.
+///
+/// Inserted by the application builder, or by the [`TasksBundle`](struct.TasksBundle.html) if it is
+/// missing. The results of `spawn_event` tasks are delivered by
+/// [`apply_completed_tasks`](fn.apply_completed_tasks.html).
+///
+/// # Examples
+///
+/// ```
+/// use amethyst_core::tasks::{TaskPoll, Tasks};
+/// # use std::sync::Arc;
+///
+/// # let pool = Arc::new(rayon::ThreadPoolBuilder::new().build().unwrap());
+/// let tasks = Tasks::new(pool);
+/// let handle = tasks.spawn(|| (1..=10).sum::<u32>());
+///
+/// loop {
+///     match handle.poll() {
+///         TaskPoll::Ready(sum) => break assert_eq!(55, sum),
+///         TaskPoll::Pending => std::thread::yield_now(),
+///         _ => unreachable!(),
+///     }
+/// }
+/// ```
+pub struct Tasks {
+    pool: ArcThreadPool,
+    next_id: AtomicU64,
+    completed: Arc<Mutex<Vec<Completion>>>,
+}
+
+impl fmt::Debug for Tasks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tasks")
+            .field("next_id", &self.next_id)
+            .finish()
+    }
+}
+
+impl Tasks {
+    /// Creates a new `Tasks` resource spawning onto the given pool.
+    pub fn new(pool: ArcThreadPool) -> Self {
+        Tasks {
+            pool,
+            next_id: AtomicU64::new(0),
+            completed: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    fn next_id(&self) -> TaskId {
+        TaskId(self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Runs the closure on the thread pool, returning a handle to poll for its result.
+    pub fn spawn<F, T>(&self, f: F) -> TaskHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.spawn_cancellable(move |_| f())
+    }
+
+    /// Like `spawn`, but passes a `CancellationToken` the closure can check to stop early.
+    pub fn spawn_cancellable<F, T>(&self, f: F) -> TaskHandle<T>
+    where
+        F: FnOnce(&CancellationToken) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.spawn_with(f, |_, value| value)
+    }
+
+    /// Runs the future to completion on the thread pool, returning a handle to poll for its result.
+    ///
+    /// The future is driven by blocking one of the pool's threads, so it should not wait on
+    /// events that need that pool to make progress.
+    pub fn spawn_future<F>(&self, future: F) -> TaskHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.spawn(move || futures_executor::block_on(future))
+    }
+
+    /// Runs the closure on the thread pool, and writes its result as a `TaskResult<T>` to the
+    /// `EventChannel<TaskResult<T>>` resource once it completes.
+    ///
+    /// The event is written on the main thread by `apply_completed_tasks`, and the channel is
+    /// created if it does not exist yet. The returned handle can be used to check whether the
+    /// task completed, and to cancel it.
+    pub fn spawn_event<F, T>(&self, f: F) -> TaskHandle<()>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + Sync + 'static,
+    {
+        let completed = self.completed.clone();
+        self.spawn_with(
+            move |_| f(),
+            move |id, value| {
+                completed
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push(Box::new(move |resources: &mut Resources| {
+                        resources
+                            .get_or_insert_with(EventChannel::<TaskResult<T>>::new)
+                            .single_write(TaskResult { id, value });
+                    }));
+            },
+        )
+    }
+
+    /// Runs the closure on the thread pool, and stores the value `complete` makes of its result
+    /// in the handle, unless the task was cancelled.
+    fn spawn_with<F, T, C, U>(&self, f: F, complete: C) -> TaskHandle<U>
+    where
+        F: FnOnce(&CancellationToken) -> T + Send + 'static,
+        C: FnOnce(TaskId, T) -> U + Send + 'static,
+        T: Send + 'static,
+        U: Send + 'static,
+    {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let shared = Arc::new(Shared {
+            state: Mutex::new(TaskState::Pending),
+            cancelled: cancelled.clone(),
+        });
+        let id = self.next_id();
+        let task_shared = shared.clone();
+        self.pool.spawn(move || {
+            let token = CancellationToken { cancelled };
+            if token.is_cancelled() {
+                return;
+            }
+            let result = panic::catch_unwind(AssertUnwindSafe(|| f(&token)));
+            if result.is_err() {
+                error!("Task {:?} panicked", id);
+            }
+            let mut state = task_shared.state.lock().unwrap_or_else(|e| e.into_inner());
+            if let TaskState::Pending = *state {
+                *state = match result {
+                    Ok(value) => TaskState::Done(complete(id, value)),
+                    Err(_) => TaskState::Panicked,
+                };
+            }
+        });
+        TaskHandle { id, shared }
+    }
+
+    fn take_completed(&self) -> Vec<Completion> {
+        std::mem::take(&mut *self.completed.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// Writes the results of completed `spawn_event` tasks to their event channels.
+///
+/// This is called on the main thread by the application at the start of every frame, and by the
+/// `TasksBundle`. It does nothing if there is no `Tasks` resource.
+pub fn apply_completed_tasks(resources: &mut Resources) {
+    // The completions need mutable access to the resources, so `Tasks` can not stay borrowed.
+    let completed = match resources.get::<Tasks>() {
+        Some(tasks) => tasks.take_completed(),
+        None => return,
+    };
+    for complete in completed {
+        complete(resources);
+    }
+}
+
+/// Inserts the `Tasks` resource using the application's `ArcThreadPool`, and writes the results
+/// of completed `spawn_event` tasks to their event channels.
+///
+/// The application already writes the results at the start of every frame. This bundle also
+/// writes them at the point in the dispatcher where it is added, so that systems after it handle
+/// results that complete during the frame, and it is needed by applications driving the
+/// dispatcher themselves.
+#[derive(Debug, Default)]
+pub struct TasksBundle;
+
+impl SystemBundle for TasksBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        if !resources.contains::<Tasks>() {
+            let pool = resources
+                .get::<ArcThreadPool>()
+                .map(|pool| pool.clone())
+                .ok_or_else(|| Error::from_string("TasksBundle requires an ArcThreadPool"))?;
+            resources.insert(Tasks::new(pool));
+        }
+        builder.add_thread_local_fn(|_, resources| apply_completed_tasks(resources));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{mpsc, Arc},
+        time::Duration,
+    };
+
+    use super::*;
+
+    fn tasks() -> Tasks {
+        Tasks::new(Arc::new(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(1)
+                .build()
+                .unwrap(),
+        ))
+    }
+
+    fn wait_for<T>(handle: &TaskHandle<T>) {
+        while !handle.is_finished() {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn spawned_task_can_be_polled() {
+        let tasks = tasks();
+        let handle = tasks.spawn(|| 42);
+        wait_for(&handle);
+        assert_eq!(TaskPoll::Ready(42), handle.poll());
+        assert_eq!(TaskPoll::Taken, handle.poll());
+    }
+
+    #[test]
+    fn cancelled_task_does_not_run() {
+        let tasks = tasks();
+        let (tx, rx) = mpsc::channel::<()>();
+        let ran = Arc::new(AtomicBool::new(false));
+        // Keep the only pool thread busy until the second task is cancelled.
+        let blocker = tasks.spawn(move || rx.recv().unwrap());
+        let task_ran = ran.clone();
+        let handle = tasks.spawn(move || task_ran.store(true, Ordering::SeqCst));
+        handle.cancel();
+        let marker = tasks.spawn(|| ());
+        tx.send(()).unwrap();
+        wait_for(&blocker);
+        wait_for(&marker);
+        assert!(!ran.load(Ordering::SeqCst));
+        assert_eq!(TaskPoll::Cancelled, handle.poll());
+    }
+
+    #[test]
+    fn running_task_observes_cancellation() {
+        let tasks = tasks();
+        let (tx, rx) = mpsc::channel::<()>();
+        let handle = tasks.spawn_cancellable(move |token| {
+            tx.send(()).unwrap();
+            while !token.is_cancelled() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            42
+        });
+        rx.recv().unwrap();
+        handle.cancel();
+        let marker = tasks.spawn(|| ());
+        wait_for(&marker);
+        assert_eq!(TaskPoll::Cancelled, handle.poll());
+    }
+
+    #[test]
+    fn panicking_task_is_reported() {
+        let tasks = tasks();
+        let handle = tasks.spawn(|| -> u32 { panic!("task failure") });
+        wait_for(&handle);
+        assert_eq!(TaskPoll::Panicked, handle.poll());
+    }
+
+    #[test]
+    fn event_task_result_is_written_on_apply() {
+        let tasks = tasks();
+        let mut resources = Resources::default();
+        let mut reader = resources
+            .get_or_insert_with(EventChannel::<TaskResult<u32>>::new)
+            .register_reader();
+
+        let handle = tasks.spawn_event(|| 7u32);
+        wait_for(&handle);
+        assert_eq!(TaskPoll::Ready(()), handle.poll());
+        resources.insert(tasks);
+        apply_completed_tasks(&mut resources);
+
+        let channel = resources.get::<EventChannel<TaskResult<u32>>>().unwrap();
+        let results = channel.read(&mut reader).collect::<Vec<_>>();
+        assert_eq!(1, results.len());
+        assert_eq!(handle.id(), results[0].id);
+        assert_eq!(7, results[0].value);
+    }
+
+    #[test]
+    fn cancelled_event_task_writes_no_event() {
+        let tasks = tasks();
+        let mut resources = Resources::default();
+        let mut reader = resources
+            .get_or_insert_with(EventChannel::<TaskResult<u32>>::new)
+            .register_reader();
+
+        let (tx, rx) = mpsc::channel::<()>();
+        let blocker = tasks.spawn(move || rx.recv().unwrap());
+        let handle = tasks.spawn_event(|| 7u32);
+        handle.cancel();
+        let marker = tasks.spawn(|| ());
+        tx.send(()).unwrap();
+        wait_for(&blocker);
+        wait_for(&marker);
+        resources.insert(tasks);
+        apply_completed_tasks(&mut resources);
+
+        assert_eq!(TaskPoll::Cancelled, handle.poll());
+        let channel = resources.get::<EventChannel<TaskResult<u32>>>().unwrap();
+        assert_eq!(0, channel.read(&mut reader).count());
+    }
+}
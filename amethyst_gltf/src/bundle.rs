@@ -0,0 +1,24 @@
+use amethyst_core::ecs::{DispatcherBuilder, Resources, SystemBundle, World};
+use amethyst_error::Error;
+
+use crate::system::gltf_scene_tick;
+
+/// Sets up the glTF scenes spawned from the prefabs produced by the `GltfImporter`.
+///
+/// The bundle must be added after the `LoaderBundle`, so that the scenes are set up in the frame
+/// their prefab is spawned. Add the `AnimationBundle<usize, Transform>` and the
+/// `VertexSkinningBundle` to play the animations of the scenes.
+#[derive(Debug, Default)]
+pub struct GltfBundle;
+
+impl SystemBundle for GltfBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        _resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        builder.add_thread_local_fn(gltf_scene_tick);
+        Ok(())
+    }
+}
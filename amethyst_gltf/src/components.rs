@@ -0,0 +1,127 @@
+use amethyst_animation::Animation;
+use amethyst_assets::{
+    prefab::register_component_type, register_asset_type, Asset, AssetProcessorSystem, Handle,
+};
+use amethyst_core::{math::Matrix4, transform::Transform};
+use amethyst_rendy::{camera::Camera, light::Light, types::Mesh, Material};
+use prefab_format::EntityUuid;
+use serde::{Deserialize, Serialize};
+use serde_diff::SerdeDiff;
+use type_uuid::TypeUuid;
+
+/// Skin of a glTF file, which deforms the meshes using it with the transforms of its joints.
+///
+/// The joints are glTF node indices, which are mapped to the entities spawned for them when the
+/// scene is spawned.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "a3e4c8f1-5b27-4d90-8e6a-2f1b7c9d0e43"]
+pub struct GltfSkin {
+    /// Node indices of the joints.
+    pub joints: Vec<usize>,
+    /// Matrices bringing the meshes into the local space of each joint.
+    pub inverse_bind_matrices: Vec<Matrix4<f32>>,
+}
+
+impl Asset for GltfSkin {
+    fn name() -> &'static str {
+        "gltf::GltfSkin"
+    }
+    type Data = Self;
+}
+
+register_asset_type!(GltfSkin => GltfSkin; AssetProcessorSystem<GltfSkin>);
+
+/// Projection of a glTF camera.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum GltfCamera {
+    /// Orthographic projection.
+    Orthographic {
+        /// Left side of the view volume.
+        left: f32,
+        /// Right side of the view volume.
+        right: f32,
+        /// Bottom of the view volume.
+        bottom: f32,
+        /// Top of the view volume.
+        top: f32,
+        /// Distance of the near clipping plane.
+        znear: f32,
+        /// Distance of the far clipping plane.
+        zfar: f32,
+    },
+    /// Perspective projection.
+    Perspective {
+        /// Aspect ratio of the view.
+        aspect: f32,
+        /// Vertical field of view, in radians.
+        fovy: f32,
+        /// Distance of the near clipping plane.
+        znear: f32,
+    },
+}
+
+impl From<&GltfCamera> for Camera {
+    fn from(camera: &GltfCamera) -> Self {
+        match *camera {
+            GltfCamera::Orthographic {
+                left,
+                right,
+                bottom,
+                top,
+                znear,
+                zfar,
+            } => Camera::orthographic(left, right, bottom, top, znear, zfar),
+            GltfCamera::Perspective {
+                aspect,
+                fovy,
+                znear,
+            } => Camera::perspective(aspect, fovy, znear),
+        }
+    }
+}
+
+/// Node of a glTF scene prefab, replaced by the components it describes when the scene is
+/// spawned, see `GltfBundle`.
+///
+/// Nodes with several mesh primitives are spawned with a child entity per primitive, which has
+/// no node index.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[serde_diff(opaque)]
+#[uuid = "1d6f0b72-93c4-4e85-a7d1-5c8e2b4f9a06"]
+pub struct GltfNode {
+    /// Index of the node in the glTF file.
+    pub index: Option<usize>,
+    /// Prefab entity of the parent node, or of the scene root.
+    pub parent: Option<EntityUuid>,
+    /// Name of the node.
+    pub name: Option<String>,
+    /// Mesh of the node.
+    pub mesh: Option<Handle<Mesh>>,
+    /// Material of the mesh.
+    pub material: Option<Handle<Material>>,
+    /// Whether the material is blended with the meshes behind it.
+    pub transparent: bool,
+    /// Skin deforming the mesh.
+    pub skin: Option<Handle<GltfSkin>>,
+    /// Bounds of the mesh and of the meshes of the child nodes, as minimum and maximum corner.
+    pub extent: Option<([f32; 3], [f32; 3])>,
+    /// Camera of the node.
+    pub camera: Option<GltfCamera>,
+    /// Light of the node.
+    pub light: Option<Light>,
+}
+
+/// Root of a glTF scene prefab, replaced by an `AnimationSet` and an `AnimationHierarchy` of the
+/// scene when it is spawned, on the entity with the `Handle<Prefab>`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[serde_diff(opaque)]
+#[uuid = "6b9e2d45-0c71-4f3a-b8e2-93a5d1c7f086"]
+pub struct GltfScene {
+    /// Animations of the scene, by glTF animation index.
+    pub animations: Vec<(usize, Handle<Animation<Transform>>)>,
+    /// Bounds of the meshes of the scene, as minimum and maximum corner.
+    pub extent: Option<([f32; 3], [f32; 3])>,
+}
+
+register_component_type!(GltfNode);
+register_component_type!(GltfScene);
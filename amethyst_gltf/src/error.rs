@@ -30,6 +30,19 @@ pub enum Error {
     #[error(display = "Not implemented")]
     NotImplemented,
 
+    /// A glTF buffer is stored in a separate file, which the importer can not read when it runs
+    /// in the asset daemon.
+    #[error(
+        display = "Buffer '{}' is stored in a separate file, which can only be read when the file \
+                   is loaded from `AssetSources`, use a .glb file or embed the buffer",
+        _0
+    )]
+    ExternalBuffer(String),
+
+    /// A glTF image has a format that is not supported.
+    #[error(display = "Unsupported image format: {}", _0)]
+    UnsupportedImage(String),
+
     /// A loaded glTF buffer is not of the required length.
     #[error(display = "Loaded buffer does not match required length")]
     BufferLength(gltf::json::Path),
@@ -1,47 +1,61 @@
-use std::collections::HashMap;
+use std::collections::HashSet;
 
 use amethyst_animation::{
-    AnimationPrefab, AnimationSetPrefab, InterpolationFunction, InterpolationPrimitive, Sampler,
-    SamplerPrimitive, TransformChannel,
+    Animation, InterpolationFunction, InterpolationPrimitive, Sampler, SamplerPrimitive,
+    TransformChannel,
 };
+use amethyst_assets::{make_handle, AssetRef, AssetUuid};
 use amethyst_core::{
     math::{convert, Vector3, Vector4},
     Transform,
 };
 use amethyst_error::Error;
 
-use super::Buffers;
+use super::{Buffers, GltfAssets};
 use crate::error;
 
+/// Imports the animations targeting the imported nodes, with an asset per animation channel.
+///
+/// Returns the glTF animation index and the asset of each animation.
 pub fn load_animations(
     gltf: &gltf::Gltf,
     buffers: &Buffers,
-    node_map: &HashMap<usize, usize>,
-) -> Result<AnimationSetPrefab<usize, Transform>, Error> {
-    let mut prefab = AnimationSetPrefab::default();
+    nodes: &HashSet<usize>,
+    assets: &mut GltfAssets<'_>,
+) -> Result<Vec<(usize, AssetUuid)>, Error> {
+    let mut animations = Vec::new();
     for animation in gltf.animations() {
-        let anim = load_animation(&animation, buffers)?;
-        if anim
-            .samplers
-            .iter()
-            .any(|sampler| node_map.contains_key(&sampler.0))
+        if !animation
+            .channels()
+            .any(|channel| nodes.contains(&channel.target().node().index()))
         {
-            prefab.animations.push((animation.index(), anim));
+            continue;
         }
+        animations.push((
+            animation.index(),
+            load_animation(&animation, buffers, assets)?,
+        ));
     }
-    Ok(prefab)
+    Ok(animations)
 }
 
 fn load_animation(
     animation: &gltf::Animation<'_>,
     buffers: &Buffers,
-) -> Result<AnimationPrefab<Transform>, Error> {
-    let mut a = AnimationPrefab::default();
-    a.samplers = animation
-        .channels()
-        .map(|ref channel| load_channel(channel, buffers))
-        .collect::<Result<Vec<_>, Error>>()?;
-    Ok(a)
+    assets: &mut GltfAssets<'_>,
+) -> Result<AssetUuid, Error> {
+    let mut anim = Animation::<Transform>::new();
+    let mut deps = Vec::new();
+    for (index, channel) in animation.channels().enumerate() {
+        let (node_index, transform_channel, sampler) = load_channel(&channel, buffers)?;
+        let id = assets.id(format!("animation/{}/sampler/{}", animation.index(), index));
+        assets.push(id, Vec::new(), Box::new(sampler));
+        deps.push(AssetRef::Uuid(id));
+        anim.add(node_index, transform_channel, make_handle(id));
+    }
+    let id = assets.id(format!("animation/{}", animation.index()));
+    assets.push(id, deps, Box::new(anim));
+    Ok(id)
 }
 
 fn load_channel(
@@ -1,27 +1,35 @@
-use std::{path::Path, sync::Arc};
+use std::io;
 
-use amethyst_assets::Source as AssetSource;
-use amethyst_error::Error;
+use amethyst_error::{format_err, Error, ResultExt};
 use gltf::{self, json, Gltf};
 
 use crate::error;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ImageFormat {
     Png,
     Jpeg,
 }
 
 impl ImageFormat {
-    fn from_mime_type(mime: &str) -> Self {
+    fn from_mime_type(mime: &str) -> Option<Self> {
         match mime {
-            "image/jpeg" => ImageFormat::Jpeg,
-            "image/png" => ImageFormat::Png,
-            _ => unreachable!(),
+            "image/jpeg" => Some(ImageFormat::Jpeg),
+            "image/png" => Some(ImageFormat::Png),
+            _ => None,
         }
     }
 }
 
+/// Image data of a texture, either stored in the glTF file or in a separate file.
+#[derive(Debug)]
+pub enum ImageData {
+    /// Image stored in a buffer view or a data URI.
+    Embedded(Vec<u8>, ImageFormat),
+    /// Image file, with its path relative to the glTF file.
+    External(String),
+}
+
 /// Buffer data returned from `import`.
 #[derive(Clone, Debug)]
 pub struct Buffers(Vec<Vec<u8>>);
@@ -48,55 +56,46 @@ impl Buffers {
     }
 }
 
-/// Imports glTF 2.0
-pub fn import<P>(source: Arc<dyn AssetSource>, path: P) -> Result<(Gltf, Buffers), Error>
+/// Imports glTF 2.0 from the bytes of a `.gltf` or `.glb` file.
+///
+/// Buffers stored in separate files are read with `read_file`, given their path relative to the
+/// glTF file, which returns `None` if the files next to the glTF file can not be read.
+pub fn import<F>(data: &[u8], read_file: F) -> Result<(Gltf, Buffers), Error>
 where
-    P: AsRef<Path>,
+    F: Fn(&str) -> Option<io::Result<Vec<u8>>>,
 {
-    let path = path.as_ref();
-    let data = read_to_end(source.clone(), path)?;
     if data.starts_with(b"glTF") {
-        import_binary(&data, source, path)
+        import_binary(data, &read_file)
     } else {
-        import_standard(&data, source, path)
+        import_standard(data, &read_file)
     }
 }
 
-fn read_to_end<P: AsRef<Path>>(source: Arc<dyn AssetSource>, path: P) -> Result<Vec<u8>, Error> {
-    let path = path.as_ref();
-    source.load(
-        path.to_str()
-            .expect("Path contains invalid UTF-8 charcters"),
-    )
-}
-
 fn parse_data_uri(uri: &str) -> Result<Vec<u8>, Error> {
     let encoded = uri.split(',').nth(1).expect("URI does not contain ','");
     let decoded = base64::decode(&encoded)?;
     Ok(decoded)
 }
 
-fn load_external_buffers(
-    source: Arc<dyn AssetSource>,
-    base_path: &Path,
+fn load_buffers<F>(
     gltf: &Gltf,
     mut bin: Option<Vec<u8>>,
-) -> Result<Vec<Vec<u8>>, Error> {
+    read_file: &F,
+) -> Result<Vec<Vec<u8>>, Error>
+where
+    F: Fn(&str) -> Option<io::Result<Vec<u8>>>,
+{
     use gltf::buffer::Source;
     let mut buffers = vec![];
     for (index, buffer) in gltf.buffers().enumerate() {
         let data = match buffer.source() {
-            Source::Uri(uri) => {
-                if uri.starts_with("data:") {
-                    parse_data_uri(uri)?
-                } else {
-                    let path = base_path
-                        .parent()
-                        .unwrap_or_else(|| Path::new("./"))
-                        .join(uri);
-                    read_to_end(source.clone(), &path)?
+            Source::Uri(uri) if uri.starts_with("data:") => parse_data_uri(uri)?,
+            Source::Uri(uri) => match read_file(uri) {
+                Some(data) => {
+                    data.with_context(|_| format_err!("Failed to read buffer {:?}", uri))?
                 }
-            }
+                None => return Err(error::Error::ExternalBuffer(uri.to_string()).into()),
+            },
             Source::Bin => bin
                 .take()
                 .expect("`BIN` section of binary glTF file is empty or used by another buffer"),
@@ -111,50 +110,44 @@ fn load_external_buffers(
     Ok(buffers)
 }
 
-fn import_standard(
-    data: &[u8],
-    source: Arc<dyn AssetSource>,
-    base_path: &Path,
-) -> Result<(Gltf, Buffers), Error> {
+fn import_standard<F>(data: &[u8], read_file: &F) -> Result<(Gltf, Buffers), Error>
+where
+    F: Fn(&str) -> Option<io::Result<Vec<u8>>>,
+{
     let gltf = Gltf::from_slice(data)?;
-    let buffers = Buffers(load_external_buffers(source, base_path, &gltf, None)?);
+    let buffers = Buffers(load_buffers(&gltf, None, read_file)?);
     Ok((gltf, buffers))
 }
 
-fn import_binary(
-    data: &[u8],
-    source: Arc<dyn AssetSource>,
-    base_path: &Path,
-) -> Result<(Gltf, Buffers), Error> {
+fn import_binary<F>(data: &[u8], read_file: &F) -> Result<(Gltf, Buffers), Error>
+where
+    F: Fn(&str) -> Option<io::Result<Vec<u8>>>,
+{
     let gltf::binary::Glb { json, bin, .. } = gltf::binary::Glb::from_slice(data)?;
     let gltf = Gltf::from_slice(&json)?;
     let bin = bin.map(|x| x.to_vec());
-    let buffers = Buffers(load_external_buffers(source, base_path, &gltf, bin)?);
+    let buffers = Buffers(load_buffers(&gltf, bin, read_file)?);
     Ok((gltf, buffers))
 }
 
-pub fn get_image_data(
-    image: &gltf::Image<'_>,
-    buffers: &Buffers,
-    source: Arc<dyn AssetSource>,
-    base_path: &Path,
-) -> Result<(Vec<u8>, ImageFormat), Error> {
+pub fn get_image_data(image: &gltf::Image<'_>, buffers: &Buffers) -> Result<ImageData, Error> {
     use gltf::image::Source;
     match image.source() {
         Source::View { view, mime_type } => {
             let data = buffers
                 .view(&view)
                 .expect("`view` of image data points to a buffer which does not exist");
-            Ok((data.to_vec(), ImageFormat::from_mime_type(mime_type)))
+            let format = ImageFormat::from_mime_type(mime_type)
+                .ok_or_else(|| error::Error::UnsupportedImage(mime_type.to_string()))?;
+            Ok(ImageData::Embedded(data.to_vec(), format))
         }
 
         Source::Uri { uri, mime_type } => {
             if uri.starts_with("data:") {
                 let data = parse_data_uri(uri)?;
-                if let Some(ty) = mime_type {
-                    Ok((data, ImageFormat::from_mime_type(ty)))
-                } else {
-                    let mimetype = uri
+                let mime_type = match mime_type {
+                    Some(ty) => ty,
+                    None => uri
                         .split(',')
                         .next()
                         .expect("Unreachable: `split` will always return at least one element")
@@ -163,32 +156,13 @@ pub fn get_image_data(
                         .expect("URI does not contain ':'")
                         .split(';')
                         .next()
-                        .expect("Unreachable: `split` will always return at least one element");
-                    Ok((data, ImageFormat::from_mime_type(mimetype)))
-                }
+                        .expect("Unreachable: `split` will always return at least one element"),
+                };
+                let format = ImageFormat::from_mime_type(mime_type)
+                    .ok_or_else(|| error::Error::UnsupportedImage(mime_type.to_string()))?;
+                Ok(ImageData::Embedded(data, format))
             } else {
-                let path = base_path
-                    .parent()
-                    .unwrap_or_else(|| Path::new("./"))
-                    .join(uri);
-                let data = source.load(
-                    path.to_str()
-                        .expect("Path contains invalid UTF-8 characters"),
-                )?;
-                if let Some(ty) = mime_type {
-                    Ok((data, ImageFormat::from_mime_type(ty)))
-                } else {
-                    let ext = path
-                        .extension()
-                        .and_then(|s| s.to_str())
-                        .map_or("".to_string(), |s| s.to_ascii_lowercase());
-                    let format = match &ext[..] {
-                        "jpg" | "jpeg" => ImageFormat::Jpeg,
-                        "png" => ImageFormat::Png,
-                        _ => unreachable!(),
-                    };
-                    Ok((data, format))
-                }
+                Ok(ImageData::External(uri.to_string()))
             }
         }
     }
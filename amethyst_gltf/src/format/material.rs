@@ -1,165 +1,196 @@
-use std::sync::Arc;
-
-use amethyst_assets::Source;
+use amethyst_assets::{make_handle, make_handle_from_path, AssetRef, AssetUuid, Handle};
 use amethyst_error::Error;
 use amethyst_rendy::{
-    formats::{mtl::MaterialPrefab, texture::TexturePrefab},
+    mtl::TextureOffset,
     palette::{LinSrgba, Srgba},
     rendy::{
         hal,
         texture::{
             image::{load_from_image, ImageFormat as DataFormat, ImageTextureConfig, Repr},
             palette::{load_from_linear_rgba, load_from_srgba},
-            MipLevels, TextureBuilder,
         },
     },
+    types::{Texture, TextureData},
+    Material,
 };
 use gltf::{self, material::AlphaMode};
 
-use super::{get_image_data, Buffers, ImageFormat as ImportDataFormat};
+use super::{get_image_data, Buffers, GltfAssets, ImageData, ImageFormat as ImportDataFormat};
+
+/// Imported material, with whether it is blended with the meshes behind it.
+#[derive(Clone, Copy, Debug)]
+pub struct ImportedMaterial {
+    pub id: AssetUuid,
+    pub transparent: bool,
+}
 
-// Load a single material, and transform into a format usable by the engine
+/// Imports a material and its textures, and transforms them into a format usable by the engine.
+///
+/// Textures shared by several materials are imported once, and the textures used for the
+/// factors of materials without texture are imported per material.
 pub fn load_material(
     material: &gltf::Material<'_>,
     buffers: &Buffers,
-    source: Arc<dyn Source>,
-    name: &str,
-) -> Result<MaterialPrefab, Error> {
-    let mut prefab = MaterialPrefab::default();
+    assets: &mut GltfAssets<'_>,
+) -> Result<ImportedMaterial, Error> {
+    let key = match material.index() {
+        Some(index) => format!("material/{}", index),
+        None => "material/default".to_string(),
+    };
+    let mut deps = Vec::new();
 
     let pbr = material.pbr_metallic_roughness();
 
-    prefab.albedo = Some(
-        load_texture_with_factor(
-            pbr.base_color_texture(),
-            pbr.base_color_factor(),
-            buffers,
-            source.clone(),
-            name,
-            true,
-        )
-        .map(|(texture, _)| TexturePrefab::Data(texture.into()))?,
-    );
+    let albedo = load_texture_with_factor(
+        pbr.base_color_texture().map(|info| info.texture()),
+        pbr.base_color_factor(),
+        buffers,
+        assets,
+        &format!("{}/albedo", key),
+        true,
+        &mut deps,
+    )?;
 
     // metallic from B channel
     // roughness from G channel
     let metallic_roughness = load_texture_with_factor(
-        pbr.metallic_roughness_texture(),
+        pbr.metallic_roughness_texture().map(|info| info.texture()),
         [1.0, pbr.roughness_factor(), pbr.metallic_factor(), 1.0],
         buffers,
-        source.clone(),
-        name,
+        assets,
+        &format!("{}/metallic_roughness", key),
         false,
-    )?
-    .0;
-
-    prefab.metallic_roughness = Some(TexturePrefab::Data(metallic_roughness.into()));
+        &mut deps,
+    )?;
 
     let em_factor = material.emissive_factor();
-    prefab.emission = Some(TexturePrefab::Data(
-        load_texture_with_factor(
-            material.emissive_texture(),
-            [em_factor[0], em_factor[1], em_factor[2], 1.0],
-            buffers,
-            source.clone(),
-            name,
-            true,
-        )?
-        .0
-        .into(),
-    ));
-
-    // Can't use map/and_then because of Result returning from the load_texture function
-    prefab.normal = match material.normal_texture() {
-        Some(normal_texture) => Some(
-            load_texture(
-                &normal_texture.texture(),
-                buffers,
-                source.clone(),
-                name,
-                false,
-            )
-            .map(|data| TexturePrefab::Data(data.into()))?,
-        ),
-
-        None => None,
-    };
+    let emission = load_texture_with_factor(
+        material.emissive_texture().map(|info| info.texture()),
+        [em_factor[0], em_factor[1], em_factor[2], 1.0],
+        buffers,
+        assets,
+        &format!("{}/emission", key),
+        true,
+        &mut deps,
+    )?;
+
+    let normal = load_texture_with_factor(
+        material.normal_texture().map(|info| info.texture()),
+        [0.5, 0.5, 1.0, 1.0],
+        buffers,
+        assets,
+        &format!("{}/normal", key),
+        false,
+        &mut deps,
+    )?;
+
+    let ambient_occlusion = load_texture_with_factor(
+        material.occlusion_texture().map(|info| info.texture()),
+        [1.0, 1.0, 1.0, 1.0],
+        buffers,
+        assets,
+        &format!("{}/ambient_occlusion", key),
+        false,
+        &mut deps,
+    )?;
+
+    let cavity = load_texture_with_factor(
+        None,
+        [1.0, 1.0, 1.0, 1.0],
+        buffers,
+        assets,
+        &format!("{}/cavity", key),
+        false,
+        &mut deps,
+    )?;
 
-    // Can't use map/and_then because of Result returning from the load_texture function
-    prefab.ambient_occlusion = match material.occlusion_texture() {
-        Some(occlusion_texture) => Some(
-            load_texture(
-                &occlusion_texture.texture(),
-                buffers,
-                source.clone(),
-                name,
-                false,
-            )
-            .map(|data| TexturePrefab::Data(data.into()))?,
-        ),
-
-        None => None,
+    let (alpha_cutoff, transparent) = match material.alpha_mode() {
+        AlphaMode::Blend => (0.0, true),
+        AlphaMode::Mask => (material.alpha_cutoff(), false),
+        AlphaMode::Opaque => (0.0, false),
     };
 
-    match material.alpha_mode() {
-        AlphaMode::Blend => {
-            prefab.transparent = true;
-        }
-        AlphaMode::Mask => {
-            prefab.alpha_cutoff = material.alpha_cutoff();
-        }
-        AlphaMode::Opaque => {
-            prefab.alpha_cutoff = 0.0;
-        }
-    }
-    Ok(prefab)
+    let id = assets.id(key);
+    assets.push(
+        id,
+        deps,
+        Box::new(Material {
+            alpha_cutoff,
+            albedo,
+            emission,
+            normal,
+            metallic_roughness,
+            ambient_occlusion,
+            cavity,
+            uv_offset: TextureOffset::default(),
+        }),
+    );
+    Ok(ImportedMaterial { id, transparent })
 }
 
+/// Imports the texture, or a single pixel texture of the factor if there is none.
 fn load_texture_with_factor(
-    texture: Option<gltf::texture::Info<'_>>,
+    texture: Option<gltf::Texture<'_>>,
     factor: [f32; 4],
     buffers: &Buffers,
-    source: Arc<dyn Source>,
-    name: &str,
+    assets: &mut GltfAssets<'_>,
+    factor_key: &str,
     srgb: bool,
-) -> Result<(TextureBuilder<'static>, [f32; 4]), Error> {
-    match texture {
-        Some(info) => Ok((
-            load_texture(&info.texture(), buffers, source, name, srgb)?
-                .with_mip_levels(MipLevels::GenerateAuto),
-            factor,
-        )),
-        None => Ok((
-            if srgb {
-                load_from_srgba(Srgba::new(factor[0], factor[1], factor[2], factor[3]))
-            } else {
-                load_from_linear_rgba(LinSrgba::new(factor[0], factor[1], factor[2], factor[3]))
-            },
-            [1.0, 1.0, 1.0, 1.0],
-        )),
+    deps: &mut Vec<AssetRef>,
+) -> Result<Handle<Texture>, Error> {
+    if let Some(texture) = texture {
+        return load_texture(&texture, buffers, assets, srgb, deps);
     }
+
+    let builder = if srgb {
+        load_from_srgba(Srgba::new(factor[0], factor[1], factor[2], factor[3]))
+    } else {
+        load_from_linear_rgba(LinSrgba::new(factor[0], factor[1], factor[2], factor[3]))
+    };
+    let id = assets.id(factor_key);
+    assets.push(id, Vec::new(), Box::new(TextureData::from(builder)));
+    deps.push(AssetRef::Uuid(id));
+    Ok(make_handle(id))
 }
 
 fn load_texture(
     texture: &gltf::Texture<'_>,
     buffers: &Buffers,
-    source: Arc<dyn Source>,
-    name: &str,
+    assets: &mut GltfAssets<'_>,
     srgb: bool,
-) -> Result<TextureBuilder<'static>, Error> {
-    let (data, format) = get_image_data(&texture.source(), buffers, source, name.as_ref())?;
-
-    let metadata = ImageTextureConfig {
-        repr: if srgb { Repr::Srgb } else { Repr::Unorm },
-        format: match format {
-            ImportDataFormat::Png => Some(DataFormat::PNG),
-            ImportDataFormat::Jpeg => Some(DataFormat::JPEG),
-        },
-        sampler_info: load_sampler_info(&texture.sampler()),
-        ..Default::default()
+    deps: &mut Vec<AssetRef>,
+) -> Result<Handle<Texture>, Error> {
+    let (data, format) = match get_image_data(&texture.source(), buffers)? {
+        ImageData::Embedded(data, format) => (data, format),
+        ImageData::External(path) => {
+            // Image files are imported by the image importer, with the options of their `.meta`.
+            deps.push(AssetRef::Path(path.clone().into()));
+            return Ok(make_handle_from_path(path));
+        }
     };
 
-    load_from_image(std::io::Cursor::new(&data), metadata).map_err(|e| e.into())
+    let key = format!(
+        "texture/{}/{}",
+        texture.index(),
+        if srgb { "srgb" } else { "linear" }
+    );
+    let id = assets.id(key);
+    deps.push(AssetRef::Uuid(id));
+    if !assets.contains(id) {
+        let metadata = ImageTextureConfig {
+            repr: if srgb { Repr::Srgb } else { Repr::Unorm },
+            format: match format {
+                ImportDataFormat::Png => Some(DataFormat::PNG),
+                ImportDataFormat::Jpeg => Some(DataFormat::JPEG),
+            },
+            sampler_info: load_sampler_info(&texture.sampler()),
+            generate_mips: true,
+            ..Default::default()
+        };
+        let builder = load_from_image(std::io::Cursor::new(&data), metadata)?;
+        assets.push(id, Vec::new(), Box::new(TextureData::from(builder)));
+    }
+    Ok(make_handle(id))
 }
 
 fn load_sampler_info(sampler: &gltf::texture::Sampler<'_>) -> hal::image::SamplerDesc {
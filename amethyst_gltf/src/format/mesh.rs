@@ -1,13 +1,12 @@
 use std::{iter::repeat, ops::Range};
 
-use amethyst_core::math::{zero, Vector3};
 use amethyst_error::Error;
 use amethyst_rendy::{
+    formats::mesh::{calculate_normals, calculate_tangents},
     rendy::mesh::{Color, MeshBuilder, Normal, Position, Tangent, TexCoord},
     skinning::JointCombined,
 };
-use log::{trace, warn};
-use mikktspace::{generate_tangents, Geometry};
+use log::trace;
 
 use super::Buffers;
 use crate::{error, GltfSceneOptions};
@@ -35,19 +34,11 @@ enum Indices {
 }
 
 impl Indices {
-    fn len(&self) -> Option<usize> {
+    fn to_u32(&self) -> Option<Vec<u32>> {
         match self {
             Indices::None => None,
-            Indices::U16(vec) => Some(vec.len()),
-            Indices::U32(vec) => Some(vec.len()),
-        }
-    }
-
-    fn map(&self, face: usize, vert: usize) -> usize {
-        match self {
-            Indices::None => face * 3 + vert,
-            Indices::U16(vec) => vec[face * 3 + vert] as usize,
-            Indices::U32(vec) => vec[face * 3 + vert] as usize,
+            Indices::U16(vec) => Some(vec.iter().copied().map(u32::from).collect()),
+            Indices::U32(vec) => Some(vec.clone()),
         }
     }
 }
@@ -73,6 +64,7 @@ pub fn load_mesh(
             Some(ReadIndices::U32(iter)) => Indices::U32(iter.collect()),
             None => Indices::None,
         };
+        let generate_indices = indices.to_u32();
 
         trace!("Loading positions");
         let positions = reader
@@ -87,7 +79,7 @@ pub fn load_mesh(
                 normals.map(Normal).collect::<Vec<_>>()
             } else {
                 trace!("Calculating normals");
-                calculate_normals(&positions, &indices)
+                calculate_normals(&positions, generate_indices.as_deref())
             }
         });
 
@@ -121,7 +113,7 @@ pub fn load_mesh(
                         &positions,
                         normals.as_ref().unwrap(),
                         tex_coords.as_ref().unwrap(),
-                        &indices,
+                        generate_indices.as_deref(),
                     )
                 }
             }
@@ -177,147 +169,3 @@ pub fn load_mesh(
     trace!("Loaded mesh");
     Ok(primitives)
 }
-
-fn calculate_normals(positions: &[Position], indices: &Indices) -> Vec<Normal> {
-    let mut normals = vec![zero::<Vector3<f32>>(); positions.len()];
-    let num_faces = indices.len().unwrap_or_else(|| positions.len()) / 3;
-    for face in 0..num_faces {
-        let i0 = indices.map(face, 0);
-        let i1 = indices.map(face, 1);
-        let i2 = indices.map(face, 2);
-        let a = Vector3::from(positions[i0].0);
-        let b = Vector3::from(positions[i1].0);
-        let c = Vector3::from(positions[i2].0);
-        let n = (b - a).cross(&(c - a));
-        normals[i0] += n;
-        normals[i1] += n;
-        normals[i2] += n;
-    }
-    normals
-        .into_iter()
-        .map(|n| Normal(n.normalize().into()))
-        .collect::<Vec<_>>()
-}
-
-struct TangentsGeometry<'a> {
-    tangents: Vec<Tangent>,
-    num_faces: usize,
-    positions: &'a [Position],
-    normals: &'a [Normal],
-    tex_coords: &'a [TexCoord],
-    indices: &'a Indices,
-}
-
-impl<'a> Geometry for TangentsGeometry<'a> {
-    fn num_faces(&self) -> usize {
-        self.num_faces
-    }
-    fn num_vertices_of_face(&self, _face: usize) -> usize {
-        3
-    }
-    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
-        self.positions[self.indices.map(face, vert)].0
-    }
-    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
-        self.normals[self.indices.map(face, vert)].0
-    }
-    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
-        self.tex_coords[self.indices.map(face, vert)].0
-    }
-
-    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
-        let [x, y, z, w] = tangent;
-        self.tangents[self.indices.map(face, vert)] = Tangent([x, y, z, -w]);
-    }
-}
-
-fn calculate_tangents(
-    positions: &[Position],
-    normals: &[Normal],
-    tex_coords: &[TexCoord],
-    indices: &Indices,
-) -> Vec<Tangent> {
-    let mut geometry = TangentsGeometry {
-        tangents: vec![Tangent([0.0, 0.0, 0.0, 0.0]); positions.len()],
-        num_faces: indices.len().unwrap_or_else(|| positions.len()) / 3,
-        positions,
-        normals,
-        tex_coords,
-        indices,
-    };
-
-    if !generate_tangents(&mut geometry) {
-        warn!("Could not generate tangents!");
-    }
-
-    geometry.tangents
-}
-
-#[cfg(test)]
-mod tests {
-    use amethyst_rendy::rendy::mesh::{Normal, Position, Tangent, TexCoord};
-
-    use super::{calculate_tangents, Indices};
-
-    const POSITIONS: &[Position] = &[
-        Position([0.0, 0.0, 0.0]),
-        Position([0.0, 1.0, 0.0]),
-        Position([1.0, 1.0, 0.0]),
-        Position([0.0, 1.0, 0.0]),
-        Position([1.0, 1.0, 0.0]),
-        Position([1.0, 0.0, 0.0]),
-    ];
-    const NORMALS: &[Normal] = &[
-        Normal([0.0, 0.0, 1.0]),
-        Normal([0.0, 0.0, 1.0]),
-        Normal([0.0, 0.0, 1.0]),
-        Normal([0.0, 0.0, 1.0]),
-        Normal([0.0, 0.0, 1.0]),
-        Normal([1.0, 0.0, 0.0]),
-    ];
-    const TEX_COORDS: &[TexCoord] = &[
-        TexCoord([0.0, 0.0]),
-        TexCoord([0.0, 1.0]),
-        TexCoord([1.0, 1.0]),
-        TexCoord([0.0, 1.0]),
-        TexCoord([1.0, 1.0]),
-        TexCoord([1.0, 0.0]),
-    ];
-
-    #[test]
-    fn test_tangent_calc() {
-        let tangents = calculate_tangents(POSITIONS, NORMALS, TEX_COORDS, &Indices::None);
-        assert_eq!(
-            tangents,
-            vec![
-                Tangent([1.0, 0.0, 0.0, 1.0]),
-                Tangent([1.0, 0.0, 0.0, 1.0]),
-                Tangent([1.0, 0.0, 0.0, 1.0]),
-                Tangent([1.0, 0.0, 0.0, 1.0]),
-                Tangent([1.0, 0.0, 0.0, 1.0]),
-                Tangent([0.0, 0.0, 0.0, 1.0]),
-            ]
-        );
-    }
-
-    #[test]
-    fn test_indexed_tangent_calc() {
-        let tangents = calculate_tangents(
-            POSITIONS,
-            NORMALS,
-            TEX_COORDS,
-            &Indices::U32(vec![3, 4, 5, 0, 1, 2]),
-        );
-        assert_eq!(
-            tangents,
-            vec![
-                Tangent([1.0, 0.0, 0.0, 1.0]),
-                Tangent([1.0, 0.0, 0.0, 1.0]),
-                Tangent([1.0, 0.0, 0.0, 1.0]),
-                Tangent([1.0, 0.0, 0.0, 1.0]),
-                Tangent([1.0, 0.0, 0.0, 1.0]),
-                Tangent([0.0, 0.0, 0.0, 1.0]),
-            ]
-        );
-    }
-}
@@ -1,27 +1,44 @@
 //! GLTF format
 
-use std::{cmp::Ordering, collections::HashMap, sync::Arc};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    io::{self, Read},
+    ops::Range,
+};
 
-use amethyst_animation::AnimationHierarchyPrefab;
-use amethyst_assets::{Format, FormatValue, Prefab, Source};
+use amethyst_assets::{
+    atelier_importer::{self, ImportOp, ImportedAsset, Importer, ImporterValue, SerdeObj},
+    make_handle,
+    prefab::RawPrefab,
+    AssetRef, AssetUuid,
+};
 use amethyst_core::{
+    ecs::{Entity, World},
     math::{convert, Quaternion, Unit, Vector3, Vector4},
     transform::Transform,
 };
 use amethyst_error::{format_err, Error, ResultExt};
-use amethyst_rendy::{camera::CameraPrefab, light::LightPrefab};
+use amethyst_rendy::{
+    light::{DirectionalLight, Light, PointLight, SpotLight},
+    palette::Srgb,
+    types::MeshData,
+};
 use gltf::{self, Gltf};
+use legion_prefab::PrefabMeta;
 use log::debug;
+use prefab_format::EntityUuid;
 use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
 
 use self::{
     animation::load_animations,
-    importer::{get_image_data, import, Buffers, ImageFormat},
-    material::load_material,
+    importer::{get_image_data, import, Buffers, ImageData, ImageFormat},
+    material::{load_material, ImportedMaterial},
     mesh::load_mesh,
     skin::load_skin,
 };
-use crate::{error, GltfMaterialSet, GltfNodeExtent, GltfPrefab, GltfSceneOptions, Named};
+use crate::{error, GltfCamera, GltfNode, GltfNodeExtent, GltfScene, GltfSceneOptions};
 
 mod animation;
 mod importer;
@@ -29,67 +46,164 @@ mod material;
 mod mesh;
 mod skin;
 
-/// Gltf scene format, will load a single scene from a Gltf file.
+/// Keeps the UUIDs of the assets imported from a glTF file between imports, so that the assets
+/// referencing them stay valid.
+#[derive(Debug, Default, Serialize, Deserialize, TypeUuid)]
+#[uuid = "e2b5d8c4-71a9-4f36-9c0e-58d4a3f71b92"]
+pub struct GltfImporterState {
+    ids: HashMap<String, AssetUuid>,
+}
+
+/// Imports a single scene of a glTF 2.0 file, registered for `.gltf` and `.glb` files.
+///
+/// The importer produces a `Prefab` of the scene, and the meshes, materials, textures, skins and
+/// animations it uses, which are loaded with the prefab. Images can be separate files, which are
+/// imported with their own importer. Buffers in separate `.bin` files are read while importing,
+/// which the asset daemon does not support: load such files from `AssetSources`, or store the
+/// buffers in the binary chunk of a `.glb` file or embed them in the `.gltf` file.
 ///
-/// Using the `GltfSceneLoaderSystem` a `Handle<GltfSceneAsset>` from this format can be attached
-/// to an entity in ECS, and the system will then load the full scene using the given entity
-/// as the root node of the scene hierarchy.
+/// The scene is spawned on the entity with the `Handle<Prefab>`, which becomes the root of the
+/// scene hierarchy. The hierarchy, skins and animations are set up by the `GltfBundle` once the
+/// prefab is spawned.
 ///
-/// See `GltfSceneOptions` for more information about the load options.
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
-#[serde(transparent)]
-pub struct GltfSceneFormat(pub GltfSceneOptions);
-
-impl Format<Prefab<GltfPrefab>> for GltfSceneFormat {
-    fn name(&self) -> &'static str {
-        "GLTFScene"
+/// ```rust,ignore
+/// let scene: Handle<Prefab> = loader.load("mesh/puffy.gltf");
+/// world.push((scene,));
+/// ```
+///
+/// See `GltfSceneOptions` for the import options, which are set in the `.meta` file of the glTF
+/// file.
+#[derive(Debug, Default, TypeUuid)]
+#[uuid = "4a7c21e8-3f05-4b9d-a6e1-c0d97b52f834"]
+pub struct GltfImporter;
+
+impl Importer for GltfImporter {
+    type State = GltfImporterState;
+    type Options = GltfSceneOptions;
+
+    fn version_static() -> u32 {
+        2
+    }
+
+    fn version(&self) -> u32 {
+        Self::version_static()
     }
 
     fn import(
         &self,
-        name: String,
-        source: Arc<dyn Source>,
-        _create_reload: Option<Box<dyn Format<Prefab<GltfPrefab>>>>,
-    ) -> Result<FormatValue<Prefab<GltfPrefab>>, Error> {
-        Ok(FormatValue::data(
-            load_gltf(source, &name, &self.0)
-                .with_context(|_| format_err!("Failed to import gltf scene '{:?}'", name))?,
-        ))
+        _op: &mut ImportOp,
+        source: &mut dyn Read,
+        options: &Self::Options,
+        state: &mut Self::State,
+    ) -> atelier_importer::Result<ImporterValue> {
+        let mut bytes = Vec::new();
+        source.read_to_end(&mut bytes)?;
+        load_gltf(&bytes, amethyst_assets::read_import_file, options, state)
+            .with_context(|_| format_err!("Failed to import gltf scene"))
+            .map_err(|e| atelier_importer::Error::Boxed(e.into_error()))
     }
 }
 
-fn load_gltf(
-    source: Arc<dyn Source>,
-    name: &str,
-    options: &GltfSceneOptions,
-) -> Result<Prefab<GltfPrefab>, Error> {
-    debug!("Loading GLTF scene '{}'", name);
-    import(source.clone(), name)
-        .with_context(|_| error::Error::GltfImporterError)
-        .and_then(|(gltf, buffers)| {
-            load_data(&gltf, &buffers, options, source, name).map_err(Into::into)
-        })
+amethyst_assets::inventory::submit! {
+    #![crate = amethyst_assets]
+    amethyst_assets::SourceFileImporter {
+        extension: ".gltf",
+        instantiator: || Box::new(GltfImporter),
+    }
+}
+
+amethyst_assets::inventory::submit! {
+    #![crate = amethyst_assets]
+    amethyst_assets::SourceFileImporter {
+        extension: ".glb",
+        instantiator: || Box::new(GltfImporter),
+    }
+}
+
+/// Assets imported from a glTF file, with a UUID per key that is kept between imports.
+pub struct GltfAssets<'a> {
+    state: &'a mut GltfImporterState,
+    assets: Vec<ImportedAsset>,
+}
+
+impl<'a> GltfAssets<'a> {
+    fn new(state: &'a mut GltfImporterState) -> Self {
+        GltfAssets {
+            state,
+            assets: Vec::new(),
+        }
+    }
+
+    /// Returns the UUID of the asset imported for the key.
+    pub fn id(&mut self, key: impl Into<String>) -> AssetUuid {
+        *self
+            .state
+            .ids
+            .entry(key.into())
+            .or_insert_with(|| AssetUuid(*uuid::Uuid::new_v4().as_bytes()))
+    }
+
+    /// Returns `true` if the asset has already been imported.
+    pub fn contains(&self, id: AssetUuid) -> bool {
+        self.assets.iter().any(|asset| asset.id == id)
+    }
+
+    /// Adds an imported asset, with the assets it references as `load_deps`.
+    pub fn push(&mut self, id: AssetUuid, load_deps: Vec<AssetRef>, asset_data: Box<dyn SerdeObj>) {
+        self.assets.push(ImportedAsset {
+            id,
+            search_tags: Vec::new(),
+            build_deps: Vec::new(),
+            load_deps,
+            asset_data,
+            build_pipeline: None,
+        });
+    }
 }
 
-fn load_data(
-    gltf: &Gltf,
-    buffers: &Buffers,
+fn load_gltf<F>(
+    data: &[u8],
+    read_file: F,
     options: &GltfSceneOptions,
-    source: Arc<dyn Source>,
-    name: &str,
-) -> Result<Prefab<GltfPrefab>, Error> {
-    let scene_index = get_scene_index(gltf, options)?;
-    let mut prefab = Prefab::<GltfPrefab>::new();
-    load_scene(
-        gltf,
-        scene_index,
-        buffers,
-        options,
-        source,
-        name,
-        &mut prefab,
-    )?;
-    Ok(prefab)
+    state: &mut GltfImporterState,
+) -> Result<ImporterValue, Error>
+where
+    F: Fn(&str) -> Option<io::Result<Vec<u8>>>,
+{
+    let (gltf, buffers) =
+        import(data, read_file).with_context(|_| error::Error::GltfImporterError)?;
+    let scene_index = get_scene_index(&gltf, options)?;
+    debug!("Loading GLTF scene {}", scene_index);
+    let scene = gltf
+        .scenes()
+        .nth(scene_index)
+        .expect("Tried to load a scene which does not exist");
+
+    let mut builder = SceneBuilder::new(&gltf, &buffers, options, GltfAssets::new(state));
+    let mut bounding_box = GltfNodeExtent::default();
+    for node in scene.nodes() {
+        let extent = builder.load_node(&node, builder.root_id)?;
+        if extent.valid() {
+            bounding_box.extend(&extent);
+        }
+    }
+
+    let animations = if options.load_animations {
+        load_animations(&gltf, &buffers, &builder.nodes, &mut builder.assets)?
+    } else {
+        Vec::new()
+    };
+    builder
+        .load_deps
+        .extend(animations.iter().map(|(_, id)| AssetRef::Uuid(*id)));
+
+    builder.finish(GltfScene {
+        animations: animations
+            .into_iter()
+            .map(|(index, id)| (index, make_handle(id)))
+            .collect(),
+        extent: extent_corners(&bounding_box),
+    })
 }
 
 fn get_scene_index(gltf: &Gltf, options: &GltfSceneOptions) -> Result<usize, Error> {
@@ -105,241 +219,407 @@ fn get_scene_index(gltf: &Gltf, options: &GltfSceneOptions) -> Result<usize, Err
     }
 }
 
-fn load_scene(
-    gltf: &Gltf,
-    scene_index: usize,
-    buffers: &Buffers,
-    options: &GltfSceneOptions,
-    source: Arc<dyn Source>,
-    name: &str,
-    prefab: &mut Prefab<GltfPrefab>,
-) -> Result<(), Error> {
-    let scene = gltf
-        .scenes()
-        .nth(scene_index)
-        .expect("Tried to load a scene which does not exist");
-    let mut node_map = HashMap::new();
-    let mut skin_map = HashMap::new();
-    let mut bounding_box = GltfNodeExtent::default();
-    let mut material_set = GltfMaterialSet::default();
-    for node in scene.nodes() {
-        let index = prefab.add(Some(0), None);
-        load_node(
+/// Mesh asset of a primitive, with the index of its material.
+#[derive(Clone, Debug)]
+struct Primitive {
+    id: AssetUuid,
+    material: Option<usize>,
+    bounds: Range<[f32; 3]>,
+}
+
+/// Builds the world of the scene prefab, importing the assets of the nodes once.
+struct SceneBuilder<'a, 'b> {
+    gltf: &'a Gltf,
+    buffers: &'a Buffers,
+    options: &'a GltfSceneOptions,
+    assets: GltfAssets<'b>,
+    world: World,
+    root: Entity,
+    root_id: EntityUuid,
+    entities: HashMap<EntityUuid, Entity>,
+    nodes: HashSet<usize>,
+    meshes: HashMap<usize, Vec<Primitive>>,
+    materials: HashMap<usize, ImportedMaterial>,
+    load_deps: Vec<AssetRef>,
+}
+
+impl<'a, 'b> SceneBuilder<'a, 'b> {
+    fn new(
+        gltf: &'a Gltf,
+        buffers: &'a Buffers,
+        options: &'a GltfSceneOptions,
+        mut assets: GltfAssets<'b>,
+    ) -> Self {
+        // The first entity of the prefab is spawned on the entity with the prefab handle.
+        let mut world = World::default();
+        let root = world.push((GltfScene::default(),));
+        let root_id = assets.id("node/root").0;
+        let mut entities = HashMap::new();
+        entities.insert(root_id, root);
+        SceneBuilder {
             gltf,
-            &node,
-            index,
             buffers,
             options,
-            source.clone(),
-            name,
-            prefab,
-            &mut node_map,
-            &mut skin_map,
-            &mut bounding_box,
-            &mut material_set,
-        )?;
+            assets,
+            world,
+            root,
+            root_id,
+            entities,
+            nodes: HashSet::new(),
+            meshes: HashMap::new(),
+            materials: HashMap::new(),
+            load_deps: Vec::new(),
+        }
     }
-    if bounding_box.valid() {
-        prefab.data_or_default(0).extent = Some(bounding_box);
+
+    /// Adds the entities of the node and its children, and returns the bounds of their meshes.
+    fn load_node(
+        &mut self,
+        node: &gltf::Node<'_>,
+        parent: EntityUuid,
+    ) -> Result<GltfNodeExtent, Error> {
+        let id = self.assets.id(format!("node/{}", node.index())).0;
+        self.nodes.insert(node.index());
+
+        let mut data = GltfNode {
+            index: Some(node.index()),
+            parent: Some(parent),
+            name: node.name().map(str::to_string),
+            ..Default::default()
+        };
+
+        // Load transformation data, default will be identity
+        let (translation, rotation, scale) = node.transform().decomposed();
+        let mut local_transform = Transform::default();
+        *local_transform.translation_mut() = convert::<_, Vector3<f32>>(Vector3::from(translation));
+        *local_transform.rotation_mut() = Unit::new_normalize(convert::<_, Quaternion<f32>>(
+            Quaternion::from(Vector4::from(rotation)),
+        ));
+        *local_transform.scale_mut() = convert::<_, Vector3<f32>>(Vector3::from(scale));
+
+        if let Some(camera) = node.camera() {
+            data.camera = Some(load_camera(&camera)?);
+        }
+        data.light = node.light().map(|light| load_light(&light));
+
+        // check for skinning
+        if let Some(skin) = node.skin() {
+            let skin_id = load_skin(&skin, self.buffers, &mut self.assets)?;
+            self.load_deps.push(AssetRef::Uuid(skin_id));
+            data.skin = Some(make_handle(skin_id));
+        }
+
+        let mut bounding_box = GltfNodeExtent::default();
+
+        // load graphics
+        if let Some(mesh) = node.mesh() {
+            let primitives = self.primitives(&mesh)?;
+            match primitives.len().cmp(&1) {
+                Ordering::Equal => {
+                    // single primitive can be loaded directly onto the node
+                    let primitive = &primitives[0];
+                    bounding_box.extend_range(&primitive.bounds);
+                    data.mesh = Some(make_handle(primitive.id));
+                    self.set_material(&mut data, primitive.material)?;
+                }
+                Ordering::Greater => {
+                    // if we have multiple primitives,
+                    // we need to add each primitive as a child entity to the node
+                    for (index, primitive) in primitives.iter().enumerate() {
+                        let primitive_id = self
+                            .assets
+                            .id(format!("node/{}/primitive/{}", node.index(), index))
+                            .0;
+                        let mut primitive_data = GltfNode {
+                            parent: Some(id),
+                            mesh: Some(make_handle(primitive.id)),
+                            extent: extent_corners(&primitive.bounds.clone().into()),
+                            ..Default::default()
+                        };
+                        self.set_material(&mut primitive_data, primitive.material)?;
+                        bounding_box.extend_range(&primitive.bounds);
+                        self.push(primitive_id, Transform::default(), primitive_data);
+                    }
+                }
+                Ordering::Less => {}
+            }
+        }
+
+        // load children
+        for child in node.children() {
+            let extent = self.load_node(&child, id)?;
+            if extent.valid() {
+                bounding_box.extend(&extent);
+            }
+        }
+        data.extent = extent_corners(&bounding_box);
+
+        self.push(id, local_transform, data);
+        Ok(bounding_box)
     }
-    prefab.data_or_default(0).materials = Some(material_set);
-
-    // load skins
-    for (node_index, skin_info) in skin_map {
-        load_skin(
-            &gltf.skins().nth(skin_info.skin_index).expect(
-                "Unreachable: `skin_map` is initialized with indexes from the `Gltf` object",
-            ),
-            buffers,
-            *node_map
-                .get(&node_index)
-                .expect("Unreachable: `node_map` should contain all nodes present in `skin_map`"),
-            &node_map,
-            skin_info.mesh_indices,
-            prefab,
-        )?;
+
+    /// Imports the primitives of a mesh, once per mesh.
+    fn primitives(&mut self, mesh: &gltf::Mesh<'_>) -> Result<Vec<Primitive>, Error> {
+        if let Some(primitives) = self.meshes.get(&mesh.index()) {
+            return Ok(primitives.clone());
+        }
+
+        let mut primitives = Vec::new();
+        for (index, (builder, material, bounds)) in load_mesh(mesh, self.buffers, self.options)?
+            .into_iter()
+            .enumerate()
+        {
+            let id = self.assets.id(format!("mesh/{}/{}", mesh.index(), index));
+            self.assets
+                .push(id, Vec::new(), Box::new(MeshData::from(builder)));
+            self.load_deps.push(AssetRef::Uuid(id));
+            primitives.push(Primitive {
+                id,
+                material,
+                bounds,
+            });
+        }
+        self.meshes.insert(mesh.index(), primitives.clone());
+        Ok(primitives)
     }
 
-    // load animations, if applicable
-    if options.load_animations {
-        let mut hierarchy_prefab = AnimationHierarchyPrefab::default();
-        hierarchy_prefab.nodes = node_map
-            .iter()
-            .map(|(node, entity)| (*node, *entity))
-            .collect();
-        prefab
-            .data_or_default(0)
-            .animatable
-            .get_or_insert_with(Default::default)
-            .hierarchy = Some(hierarchy_prefab);
-
-        prefab
-            .data_or_default(0)
-            .animatable
-            .get_or_insert_with(Default::default)
-            .animation_set = Some(load_animations(gltf, buffers, &node_map)?);
+    /// Sets the material of a node, imported once per material.
+    fn set_material(&mut self, data: &mut GltfNode, index: Option<usize>) -> Result<(), Error> {
+        let (index, material) =
+            match index.and_then(|index| self.gltf.materials().nth(index).map(|m| (index, m))) {
+                Some(material) => material,
+                None => return Ok(()),
+            };
+        let imported = match self.materials.get(&index) {
+            Some(imported) => *imported,
+            None => {
+                let imported = load_material(&material, self.buffers, &mut self.assets)?;
+                self.load_deps.push(AssetRef::Uuid(imported.id));
+                self.materials.insert(index, imported);
+                imported
+            }
+        };
+        data.material = Some(make_handle(imported.id));
+        data.transparent = imported.transparent;
+        Ok(())
     }
 
-    Ok(())
-}
+    fn push(&mut self, id: EntityUuid, transform: Transform, data: GltfNode) {
+        let entity = self.world.push((transform, data));
+        self.entities.insert(id, entity);
+    }
 
-#[derive(Debug)]
-struct SkinInfo {
-    skin_index: usize,
-    mesh_indices: Vec<usize>,
-}
+    /// Returns the imported assets, with the scene prefab.
+    fn finish(mut self, scene: GltfScene) -> Result<ImporterValue, Error> {
+        self.world
+            .entry(self.root)
+            .expect("Unreachable: the root entity is never removed")
+            .add_component(scene);
 
-fn load_node(
-    gltf: &Gltf,
-    node: &gltf::Node<'_>,
-    entity_index: usize,
-    buffers: &Buffers,
-    options: &GltfSceneOptions,
-    source: Arc<dyn Source>,
-    name: &str,
-    prefab: &mut Prefab<GltfPrefab>,
-    node_map: &mut HashMap<usize, usize>,
-    skin_map: &mut HashMap<usize, SkinInfo>,
-    parent_bounding_box: &mut GltfNodeExtent,
-    material_set: &mut GltfMaterialSet,
-) -> Result<(), Error> {
-    node_map.insert(node.index(), entity_index);
-
-    // Load node name.
-    if let Some(name) = node.name() {
-        prefab.data_or_default(entity_index).name = Some(Named::new(name.to_string()));
+        let id = self.assets.id("scene");
+        let prefab = RawPrefab {
+            raw_prefab: legion_prefab::Prefab {
+                world: self.world,
+                prefab_meta: PrefabMeta {
+                    id: id.0,
+                    entities: self.entities,
+                    prefab_refs: HashMap::new(),
+                },
+            },
+        };
+        self.assets.push(id, self.load_deps, Box::new(prefab));
+        Ok(ImporterValue {
+            assets: self.assets.assets,
+        })
     }
+}
 
-    // Load transformation data, default will be identity
-    let (translation, rotation, scale) = node.transform().decomposed();
-    let mut local_transform = Transform::default();
-    *local_transform.translation_mut() = convert::<_, Vector3<f32>>(Vector3::from(translation));
-    *local_transform.rotation_mut() = Unit::new_normalize(convert::<_, Quaternion<f32>>(
-        Quaternion::from(Vector4::from(rotation)),
-    ));
-    *local_transform.scale_mut() = convert::<_, Vector3<f32>>(Vector3::from(scale));
-    prefab.data_or_default(entity_index).transform = Some(local_transform);
-
-    // Load camera
-    if let Some(camera) = node.camera() {
-        prefab.data_or_default(entity_index).camera = Some(match camera.projection() {
-            gltf::camera::Projection::Orthographic(proj) => CameraPrefab::Orthographic {
-                left: -proj.xmag(),
-                right: proj.xmag(),
-                bottom: -proj.ymag(),
-                top: proj.ymag(),
-                znear: proj.znear(),
-                zfar: proj.zfar(),
-            },
-            gltf::camera::Projection::Perspective(proj) => CameraPrefab::Perspective {
-                aspect: proj.aspect_ratio().ok_or_else(|| {
-                    format_err!(
-                        "Camera {} is a perspective projection, but has no aspect ratio",
-                        camera.index()
-                    )
-                })?,
-                fovy: proj.yfov(),
-                znear: proj.znear(),
-            },
-        });
+fn extent_corners(extent: &GltfNodeExtent) -> Option<([f32; 3], [f32; 3])> {
+    if extent.valid() {
+        Some((extent.start.coords.into(), extent.end.coords.into()))
+    } else {
+        None
     }
+}
+
+fn load_camera(camera: &gltf::Camera<'_>) -> Result<GltfCamera, Error> {
+    Ok(match camera.projection() {
+        gltf::camera::Projection::Orthographic(proj) => GltfCamera::Orthographic {
+            left: -proj.xmag(),
+            right: proj.xmag(),
+            bottom: -proj.ymag(),
+            top: proj.ymag(),
+            znear: proj.znear(),
+            zfar: proj.zfar(),
+        },
+        gltf::camera::Projection::Perspective(proj) => GltfCamera::Perspective {
+            aspect: proj.aspect_ratio().ok_or_else(|| {
+                format_err!(
+                    "Camera {} is a perspective projection, but has no aspect ratio",
+                    camera.index()
+                )
+            })?,
+            fovy: proj.yfov(),
+            znear: proj.znear(),
+        },
+    })
+}
+
+fn load_light(light: &gltf::khr_lights_punctual::Light<'_>) -> Light {
+    use gltf::khr_lights_punctual::Kind;
 
-    // Load lights
-    if let Some(light) = node.light() {
-        prefab.data_or_default(entity_index).light = Some(LightPrefab::from(light));
+    let [r, g, b] = light.color();
+    let color = Srgb::new(r, g, b);
+    let intensity = light.intensity();
+    match light.kind() {
+        Kind::Directional => DirectionalLight {
+            color,
+            intensity,
+            ..Default::default()
+        }
+        .into(),
+        Kind::Point => PointLight {
+            color,
+            intensity,
+            radius: light.range().unwrap_or(PointLight::default().radius),
+            ..Default::default()
+        }
+        .into(),
+        Kind::Spot {
+            outer_cone_angle, ..
+        } => SpotLight {
+            angle: outer_cone_angle,
+            color,
+            intensity,
+            range: light.range().unwrap_or(SpotLight::default().range),
+            ..Default::default()
+        }
+        .into(),
     }
+}
 
-    // check for skinning
-    let mut skin = node.skin().map(|skin| SkinInfo {
-        skin_index: skin.index(),
-        mesh_indices: Vec::default(),
-    });
+#[cfg(test)]
+mod tests {
+    use type_uuid::TypeUuidDynamic;
 
-    let mut bounding_box = GltfNodeExtent::default();
+    use super::*;
 
-    // load graphics
-    if let Some(mesh) = node.mesh() {
-        let mut graphics = load_mesh(&mesh, buffers, options)?;
-        match graphics.len().cmp(&1) {
-            Ordering::Equal => {
-                // single primitive can be loaded directly onto the node
-                let (mesh, material_index, bounds) = graphics.remove(0);
-                bounding_box.extend_range(&bounds);
-                let prefab_data = prefab.data_or_default(entity_index);
-                prefab_data.mesh = Some(mesh);
-                if let Some((material_id, material)) =
-                    material_index.and_then(|index| gltf.materials().nth(index).map(|m| (index, m)))
-                {
-                    material_set
-                        .materials
-                        .entry(material_id)
-                        .or_insert(load_material(&material, buffers, source.clone(), name)?);
-                    prefab_data.material_id = Some(material_id);
-                }
-                // if we have a skin we need to track the mesh entities
-                if let Some(ref mut skin) = skin {
-                    skin.mesh_indices.push(entity_index);
-                }
-            }
-            Ordering::Greater => {
-                // if we have multiple primitives,
-                // we need to add each primitive as a child entity to the node
-                for (mesh, material_index, bounds) in graphics {
-                    let mesh_entity = prefab.add(Some(entity_index), None);
-                    let prefab_data = prefab.data_or_default(mesh_entity);
-                    prefab_data.transform = Some(Transform::default());
-                    prefab_data.mesh = Some(mesh);
-                    if let Some((material_id, material)) = material_index
-                        .and_then(|index| gltf.materials().nth(index).map(|m| (index, m)))
-                    {
-                        material_set
-                            .materials
-                            .entry(material_id)
-                            .or_insert(load_material(&material, buffers, source.clone(), name)?);
-                        prefab_data.material_id = Some(material_id);
-                    }
+    fn triangle_buffer() -> Vec<u8> {
+        [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes().to_vec())
+            .collect()
+    }
 
-                    // if we have a skin we need to track the mesh entities
-                    if let Some(ref mut skin) = skin {
-                        skin.mesh_indices.push(mesh_entity);
-                    }
+    /// A glTF file with a triangle, with its buffer at `uri`.
+    fn triangle(uri: &str) -> Vec<u8> {
+        format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "scene": 0,
+                "scenes": [{{ "nodes": [0] }}],
+                "nodes": [{{ "mesh": 0, "name": "triangle" }}],
+                "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }} }}] }}],
+                "buffers": [{{ "uri": "{}", "byteLength": 36 }}],
+                "bufferViews": [{{ "buffer": 0, "byteLength": 36 }}],
+                "accessors": [{{
+                    "bufferView": 0,
+                    "componentType": 5126,
+                    "count": 3,
+                    "type": "VEC3",
+                    "min": [0.0, 0.0, 0.0],
+                    "max": [1.0, 1.0, 0.0]
+                }}]
+            }}"#,
+            uri
+        )
+        .into_bytes()
+    }
 
-                    // extent
-                    bounding_box.extend_range(&bounds);
-                    prefab_data.extent = Some(bounds.into());
-                }
-            }
-            Ordering::Less => {}
-        }
+    fn embedded_triangle() -> Vec<u8> {
+        triangle(&format!(
+            "data:application/octet-stream;base64,{}",
+            base64::encode(&triangle_buffer())
+        ))
     }
 
-    // load children
-    for child in node.children() {
-        let index = prefab.add(Some(entity_index), None);
-        load_node(
-            gltf,
-            &child,
-            index,
-            buffers,
-            options,
-            source.clone(),
-            name,
-            prefab,
-            node_map,
-            skin_map,
-            &mut bounding_box,
-            material_set,
-        )?;
+    fn assets_of_type(value: &ImporterValue, uuid: type_uuid::Bytes) -> Vec<&ImportedAsset> {
+        value
+            .assets
+            .iter()
+            .filter(|asset| asset.asset_data.uuid() == uuid)
+            .collect()
     }
-    if bounding_box.valid() {
-        parent_bounding_box.extend(&bounding_box);
-        prefab.data_or_default(entity_index).extent = Some(bounding_box);
+
+    #[test]
+    fn gltf_files_are_imported_into_a_scene_prefab() {
+        let mut state = GltfImporterState::default();
+        let value = GltfImporter
+            .import(
+                &mut ImportOp::default(),
+                &mut embedded_triangle().as_slice(),
+                &GltfSceneOptions::default(),
+                &mut state,
+            )
+            .unwrap();
+
+        let meshes = assets_of_type(&value, MeshData::UUID);
+        let scenes = assets_of_type(&value, RawPrefab::UUID);
+        assert_eq!(1, meshes.len());
+        assert_eq!(1, scenes.len());
+        assert_eq!(2, value.assets.len());
+        assert_eq!(vec![AssetRef::Uuid(meshes[0].id)], scenes[0].load_deps);
     }
 
-    // propagate skin information
-    if let Some(skin) = skin {
-        skin_map.insert(node.index(), skin);
+    #[test]
+    fn asset_ids_are_kept_between_imports() {
+        let mut state = GltfImporterState::default();
+        let options = GltfSceneOptions::default();
+        let ids = |value: ImporterValue| {
+            value
+                .assets
+                .iter()
+                .map(|asset| asset.id)
+                .collect::<Vec<_>>()
+        };
+
+        let first = load_gltf(&embedded_triangle(), |_| None, &options, &mut state).unwrap();
+        let second = load_gltf(&embedded_triangle(), |_| None, &options, &mut state).unwrap();
+
+        assert_eq!(ids(first), ids(second));
     }
 
-    Ok(())
+    #[test]
+    fn external_buffers_are_read_next_to_the_gltf_file() {
+        let options = GltfSceneOptions::default();
+        let read_file = |path: &str| match path {
+            "triangle.bin" => Some(Ok(triangle_buffer())),
+            _ => Some(Err(io::Error::from(io::ErrorKind::NotFound))),
+        };
+
+        let value = load_gltf(
+            &triangle("triangle.bin"),
+            read_file,
+            &options,
+            &mut GltfImporterState::default(),
+        )
+        .unwrap();
+        assert_eq!(1, assets_of_type(&value, MeshData::UUID).len());
+
+        assert!(load_gltf(
+            &triangle("missing.bin"),
+            read_file,
+            &options,
+            &mut GltfImporterState::default(),
+        )
+        .is_err());
+        // The asset daemon can not read the files next to the glTF file.
+        assert!(load_gltf(
+            &triangle("triangle.bin"),
+            |_| None,
+            &options,
+            &mut GltfImporterState::default(),
+        )
+        .is_err());
+    }
 }
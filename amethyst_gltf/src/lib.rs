@@ -1,4 +1,7 @@
 //! A crate for loading GLTF format scenes into Amethyst
+//!
+//! `.gltf` and `.glb` files are imported by the `GltfImporter` into a `Prefab` of the scene, which
+//! is set up by the `GltfBundle` when it is spawned.
 
 #![doc(
     html_logo_url = "https://amethyst.rs/brand/logo-standard.svg",
@@ -13,24 +16,25 @@
 #![warn(clippy::all)]
 #![allow(clippy::new_without_default)]
 
-use std::{collections::HashMap, ops::Range};
+use std::ops::Range;
 
-use amethyst_assets::{AssetStorage, Handle, Loader, ProgressCounter};
-use amethyst_core::{
-    ecs::prelude::{Entity, Read, ReadExpect, Write, WriteStorage},
-    math::{convert, Point3, Vector3},
-    transform::Transform,
-    Named,
-};
-use amethyst_error::Error;
-use amethyst_rendy::{rendy::mesh::MeshBuilder, types::Mesh, visibility::BoundingSphere};
+use amethyst_core::math::{convert, Point3, Vector3};
+use amethyst_rendy::visibility::BoundingSphere;
 use derivative::Derivative;
 use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
 
-pub use crate::format::GltfSceneFormat;
+pub use crate::{
+    bundle::GltfBundle,
+    components::{GltfCamera, GltfNode, GltfScene, GltfSkin},
+    format::{GltfImporter, GltfImporterState},
+};
 
+mod bundle;
+mod components;
 mod error;
 mod format;
+mod system;
 
 /// A GLTF node extent
 #[derive(Clone, Debug)]
@@ -114,17 +118,11 @@ impl From<Range<[f32; 3]>> for GltfNodeExtent {
     }
 }
 
-/// Used during gltf loading to contain the materials used from scenes in the file
-#[derive(Debug, Derivative)]
-#[derivative(Default(bound = ""))]
-pub struct GltfMaterialSet {
-    pub(crate) materials: HashMap<usize, MaterialPrefab>,
-}
-
-/// Options used when loading a GLTF file
-#[derive(Debug, Clone, Derivative, Serialize, Deserialize)]
+/// Options used when importing a GLTF file, set in the `.meta` file of the GLTF file
+#[derive(Debug, Clone, Derivative, Serialize, Deserialize, TypeUuid)]
 #[derivative(Default)]
 #[serde(default)]
+#[uuid = "9f3e6a1d-2c84-4b57-8d0f-71e5a2c9b364"]
 pub struct GltfSceneOptions {
     /// Generate texture coordinates if none exist in the Gltf file
     pub generate_tex_coords: (f32, f32),
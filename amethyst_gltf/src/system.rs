@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+
+use amethyst_animation::{AnimationHierarchy, AnimationSet, Joint, Skin};
+use amethyst_assets::{prefab::PrefabInstance, AssetStorage, Handle};
+use amethyst_core::{
+    ecs::{Entity, IntoQuery, Resources, World},
+    math::{Matrix4, Point3},
+    transform::{Parent, Transform},
+    Named,
+};
+use amethyst_rendy::{
+    camera::Camera, skinning::JointTransforms, visibility::BoundingSphere, Transparent,
+};
+use fnv::FnvHashMap;
+use prefab_format::EntityUuid;
+
+use crate::{GltfNode, GltfNodeExtent, GltfScene, GltfSkin};
+
+/// Replaces the `GltfNode` and `GltfScene` components of the spawned glTF scene prefabs with the
+/// components they describe, setting up the hierarchy, skins and animations of the scenes.
+///
+/// A scene is set up once the skins it uses are loaded.
+pub fn gltf_scene_tick(world: &mut World, resources: &mut Resources) {
+    let skin_storage = resources
+        .get::<AssetStorage<GltfSkin>>()
+        .expect("AssetStorage<GltfSkin> can not be retrieved from ECS Resources");
+
+    let mut scenes = Vec::new();
+    <(Entity, &GltfScene, &PrefabInstance)>::query().for_each(
+        world,
+        |(entity, scene, instance)| {
+            scenes.push((
+                *entity,
+                scene.clone(),
+                instance.entity_ids().collect::<HashMap<_, _>>(),
+            ));
+        },
+    );
+
+    for (root, scene, entities) in scenes {
+        setup_scene(world, root, scene, &entities, |skin| skin_storage.get(skin));
+    }
+}
+
+/// Sets up the scene spawned on `root`, with the entities of the prefab by their id, once the
+/// skins returned by `skin` are loaded.
+fn setup_scene<'s, F>(
+    world: &mut World,
+    root: Entity,
+    scene: GltfScene,
+    entities: &HashMap<EntityUuid, Entity>,
+    skin: F,
+) where
+    F: Fn(&Handle<GltfSkin>) -> Option<&'s GltfSkin>,
+{
+    let nodes = entities
+        .iter()
+        .filter_map(|(id, entity)| {
+            let node = world
+                .entry_ref(*entity)
+                .ok()?
+                .get_component::<GltfNode>()
+                .ok()?
+                .clone();
+            Some((*id, *entity, node))
+        })
+        .collect::<Vec<_>>();
+
+    let skins_loaded = nodes
+        .iter()
+        .filter_map(|(_, _, node)| node.skin.as_ref())
+        .all(|handle| skin(handle).is_some());
+    if !skins_loaded {
+        return;
+    }
+
+    let node_entities = nodes
+        .iter()
+        .filter_map(|(_, entity, node)| node.index.map(|index| (index, *entity)))
+        .collect::<FnvHashMap<_, _>>();
+
+    for (_, entity, node) in &nodes {
+        let mut entry = match world.entry(*entity) {
+            Some(entry) => entry,
+            None => continue,
+        };
+        entry.remove_component::<GltfNode>();
+        if let Some(parent) = node.parent.and_then(|id| entities.get(&id)) {
+            entry.add_component(Parent(*parent));
+        }
+        if let Some(ref name) = node.name {
+            entry.add_component(Named::new(name.clone()));
+        }
+        if let Some(ref mesh) = node.mesh {
+            entry.add_component(mesh.clone());
+        }
+        if let Some(ref material) = node.material {
+            entry.add_component(material.clone());
+        }
+        if node.transparent {
+            entry.add_component(Transparent);
+        }
+        if let Some(ref camera) = node.camera {
+            entry.add_component(Camera::from(camera));
+        }
+        if let Some(ref light) = node.light {
+            entry.add_component(light.clone());
+        }
+        if let Some(extent) = node.extent {
+            entry.add_component(bounding_sphere(extent));
+        }
+    }
+
+    for (id, entity, node) in &nodes {
+        if let Some(skin) = node.skin.as_ref().and_then(&skin) {
+            add_skin(
+                world,
+                *entity,
+                skin,
+                mesh_entities(&nodes, id),
+                &node_entities,
+            );
+        }
+    }
+
+    if let Some(mut entry) = world.entry(root) {
+        entry.remove_component::<GltfScene>();
+        if let Some(extent) = scene.extent {
+            entry.add_component(bounding_sphere(extent));
+        }
+        if !scene.animations.is_empty() {
+            let mut animation_set = AnimationSet::<usize, Transform>::new();
+            for (index, animation) in scene.animations {
+                animation_set.insert(index, animation);
+            }
+            entry.add_component(animation_set);
+            entry.add_component(AnimationHierarchy::<Transform>::new_many(node_entities));
+        }
+    }
+}
+
+/// Returns the entities with the meshes of a node, which are the node itself or the entities of
+/// its primitives.
+fn mesh_entities(nodes: &[(EntityUuid, Entity, GltfNode)], node_id: &EntityUuid) -> Vec<Entity> {
+    nodes
+        .iter()
+        .filter(|(id, _, node)| {
+            node.mesh.is_some()
+                && (id == node_id || (node.index.is_none() && node.parent == Some(*node_id)))
+        })
+        .map(|(_, entity, _)| *entity)
+        .collect()
+}
+
+fn add_skin(
+    world: &mut World,
+    skin_entity: Entity,
+    skin: &GltfSkin,
+    meshes: Vec<Entity>,
+    node_entities: &FnvHashMap<usize, Entity>,
+) {
+    let joints = skin
+        .joints
+        .iter()
+        .filter_map(|index| node_entities.get(index).copied())
+        .collect::<Vec<_>>();
+
+    for joint in &joints {
+        if let Some(mut entry) = world.entry(*joint) {
+            match entry.get_component_mut::<Joint>() {
+                Ok(joint) => joint.skins.push(skin_entity),
+                Err(_) => entry.add_component(Joint {
+                    skins: vec![skin_entity],
+                }),
+            }
+        }
+    }
+    for mesh in &meshes {
+        if let Some(mut entry) = world.entry(*mesh) {
+            entry.add_component(JointTransforms {
+                skin: skin_entity,
+                matrices: vec![Matrix4::identity(); joints.len()],
+            });
+        }
+    }
+
+    if let Some(mut entry) = world.entry(skin_entity) {
+        let joint_matrices = Vec::with_capacity(joints.len());
+        entry.add_component(Skin {
+            joints,
+            meshes,
+            bind_shape_matrix: Matrix4::identity(),
+            inverse_bind_matrices: skin.inverse_bind_matrices.clone(),
+            joint_matrices,
+        });
+    }
+}
+
+fn bounding_sphere((start, end): ([f32; 3], [f32; 3])) -> BoundingSphere {
+    GltfNodeExtent {
+        start: Point3::from(start),
+        end: Point3::from(end),
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scene_nodes_are_replaced_by_their_components() {
+        let mut world = World::default();
+        let scene = GltfScene {
+            animations: Vec::new(),
+            extent: Some(([0.0; 3], [1.0; 3])),
+        };
+        let root = world.push((scene.clone(),));
+        let node = world.push((
+            Transform::default(),
+            GltfNode {
+                index: Some(0),
+                parent: Some([1; 16]),
+                name: Some("triangle".to_string()),
+                transparent: true,
+                ..Default::default()
+            },
+        ));
+        let entities = vec![([1; 16], root), ([2; 16], node)]
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+
+        setup_scene(&mut world, root, scene, &entities, |_| None);
+
+        let root_entry = world.entry_ref(root).unwrap();
+        assert!(root_entry.get_component::<GltfScene>().is_err());
+        assert!(root_entry.get_component::<BoundingSphere>().is_ok());
+        let node = world.entry_ref(node).unwrap();
+        assert!(node.get_component::<GltfNode>().is_err());
+        assert_eq!(root, node.get_component::<Parent>().unwrap().0);
+        assert_eq!("triangle", node.get_component::<Named>().unwrap().0);
+        assert!(node.get_component::<Transparent>().is_ok());
+    }
+}
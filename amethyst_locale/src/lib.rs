@@ -1,6 +1,10 @@
 //! # amethyst_locale
 //!
 //! Localisation binding a `Fluent` file to an Asset<Locale> via the use of amethyst_assets.
+//!
+//! The `ActiveLocale` resource selects the variants of the assets loaded with the `{lang}`
+//! placeholder in their path, such as `"ui/title.{lang}.png"`, when the `LocaleBundle` is added
+//! after the `LoaderBundle`.
 
 #![doc(
     html_logo_url = "https://amethyst.rs/brand/logo-standard.svg",
@@ -15,14 +19,19 @@
 #![warn(clippy::all)]
 
 use amethyst_assets::{
-    register_asset_type, register_importer, Asset, AssetProcessorSystem, Format, ProcessableAsset,
-    ProcessingState,
+    register_asset_type, register_importer, Asset, AssetProcessorSystem, DefaultLoader, Format,
+    Loader, ProcessableAsset, ProcessingState,
+};
+use amethyst_core::ecs::{
+    systems::ParallelRunnable, DispatcherBuilder, Resources, System, SystemBuilder, SystemBundle,
+    World,
 };
 use amethyst_error::Error;
 pub use fluent::{concurrent::FluentBundle, FluentResource};
 use serde::{Deserialize, Serialize};
 use type_uuid::*;
 use unic_langid::langid;
+pub use unic_langid::LanguageIdentifier;
 
 /// Internal representation of a Locale
 #[derive(Clone, Debug, Serialize, Deserialize, TypeUuid)]
@@ -79,3 +88,72 @@ impl Format<LocaleData> for FTLFormat {
         Ok(LocaleData(bytes))
     }
 }
+
+/// The locale of the game, which selects the variants of the localized assets.
+///
+/// Changing it reloads the assets loaded with the `{lang}` placeholder in their path for the new
+/// locale, see `Loader::set_locale`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActiveLocale(pub LanguageIdentifier);
+
+impl Default for ActiveLocale {
+    fn default() -> Self {
+        ActiveLocale(langid!("en"))
+    }
+}
+
+/// Applies the `ActiveLocale` to the `DefaultLoader` when it changes.
+#[derive(Debug, Default)]
+pub struct ActiveLocaleSystem;
+
+impl System<'static> for ActiveLocaleSystem {
+    fn build(&mut self) -> Box<dyn ParallelRunnable> {
+        let mut applied: Option<LanguageIdentifier> = None;
+        Box::new(
+            SystemBuilder::new("ActiveLocaleSystem")
+                .read_resource::<ActiveLocale>()
+                .read_resource::<DefaultLoader>()
+                .build(move |_, _, (active, loader), _| {
+                    if applied.as_ref() != Some(&active.0) {
+                        loader.set_locale(Some(&active.0.to_string()));
+                        applied = Some(active.0.clone());
+                    }
+                }),
+        )
+    }
+}
+
+/// Adds the `ActiveLocale` resource, if it does not exist, and the `ActiveLocaleSystem`.
+///
+/// Add this bundle after the `LoaderBundle`.
+#[derive(Debug, Default)]
+pub struct LocaleBundle {
+    locale: Option<LanguageIdentifier>,
+}
+
+impl LocaleBundle {
+    /// Creates a bundle starting with the given locale instead of `en`.
+    pub fn new(locale: LanguageIdentifier) -> Self {
+        LocaleBundle {
+            locale: Some(locale),
+        }
+    }
+}
+
+impl SystemBundle for LocaleBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        match self.locale.take() {
+            Some(locale) => resources.insert(ActiveLocale(locale)),
+            None => {
+                resources.get_or_insert_with(ActiveLocale::default);
+            }
+        }
+        builder.add_system(Box::new(ActiveLocaleSystem));
+        Ok(())
+    }
+}
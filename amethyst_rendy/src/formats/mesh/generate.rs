@@ -0,0 +1,243 @@
+//! Generation of the vertex attributes missing from imported meshes.
+use amethyst_core::math::{zero, Vector3};
+use log::warn;
+use mikktspace::Geometry;
+use rendy::mesh::{Color, MeshBuilder, Normal, Position, Tangent, TexCoord};
+
+use crate::types::MeshData;
+
+/// Vertex attributes read from a mesh file.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct MeshAttributes {
+    pub(crate) positions: Vec<Position>,
+    pub(crate) normals: Option<Vec<Normal>>,
+    pub(crate) tex_coords: Option<Vec<TexCoord>>,
+    pub(crate) colors: Option<Vec<Color>>,
+    /// Triangle list indices, or `None` if every three vertices form a triangle.
+    pub(crate) indices: Option<Vec<u32>>,
+}
+
+impl MeshAttributes {
+    /// Builds the mesh, with the normals, tangents and texture coordinates used by the 3D passes
+    /// generated when the file does not have them.
+    ///
+    /// Meshes without texture coordinates have no tangent space, so their tangents are
+    /// perpendicular to the normals in an arbitrary direction, see `fallback_tangent`.
+    pub(crate) fn into_mesh_data(self) -> MeshData {
+        let indices = self.indices.as_deref();
+        let normals = self
+            .normals
+            .unwrap_or_else(|| calculate_normals(&self.positions, indices));
+        let (tex_coords, tangents) = match self.tex_coords {
+            Some(tex_coords) => {
+                let tangents = calculate_tangents(&self.positions, &normals, &tex_coords, indices);
+                (tex_coords, tangents)
+            }
+            None => (
+                vec![TexCoord([0.0, 0.0]); self.positions.len()],
+                normals.iter().map(fallback_tangent).collect(),
+            ),
+        };
+
+        let mut builder = MeshBuilder::new();
+        if let Some(indices) = self.indices {
+            builder.set_indices(indices);
+        }
+        builder.add_vertices(self.positions);
+        builder.add_vertices(normals);
+        builder.add_vertices(tangents);
+        builder.add_vertices(tex_coords);
+        if let Some(colors) = self.colors {
+            builder.add_vertices(colors);
+        }
+        builder.into()
+    }
+}
+
+fn vertex_index(indices: Option<&[u32]>, face: usize, vert: usize) -> usize {
+    match indices {
+        Some(indices) => indices[face * 3 + vert] as usize,
+        None => face * 3 + vert,
+    }
+}
+
+fn num_faces(positions: &[Position], indices: Option<&[u32]>) -> usize {
+    indices.map_or(positions.len(), <[u32]>::len) / 3
+}
+
+/// Calculates smooth vertex normals of a triangle list, weighted by the area of the triangles.
+///
+/// `indices` are the indices of the triangle list, or `None` if every three vertices form a
+/// triangle.
+pub fn calculate_normals(positions: &[Position], indices: Option<&[u32]>) -> Vec<Normal> {
+    let mut normals = vec![zero::<Vector3<f32>>(); positions.len()];
+    for face in 0..num_faces(positions, indices) {
+        let i0 = vertex_index(indices, face, 0);
+        let i1 = vertex_index(indices, face, 1);
+        let i2 = vertex_index(indices, face, 2);
+        let a = Vector3::from(positions[i0].0);
+        let b = Vector3::from(positions[i1].0);
+        let c = Vector3::from(positions[i2].0);
+        let n = (b - a).cross(&(c - a));
+        normals[i0] += n;
+        normals[i1] += n;
+        normals[i2] += n;
+    }
+    normals
+        .into_iter()
+        .map(|n| Normal(n.normalize().into()))
+        .collect::<Vec<_>>()
+}
+
+struct TangentsGeometry<'a> {
+    tangents: Vec<Tangent>,
+    num_faces: usize,
+    positions: &'a [Position],
+    normals: &'a [Normal],
+    tex_coords: &'a [TexCoord],
+    indices: Option<&'a [u32]>,
+}
+
+impl<'a> Geometry for TangentsGeometry<'a> {
+    fn num_faces(&self) -> usize {
+        self.num_faces
+    }
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.positions[vertex_index(self.indices, face, vert)].0
+    }
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.normals[vertex_index(self.indices, face, vert)].0
+    }
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.tex_coords[vertex_index(self.indices, face, vert)].0
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        let [x, y, z, w] = tangent;
+        self.tangents[vertex_index(self.indices, face, vert)] = Tangent([x, y, z, -w]);
+    }
+}
+
+/// Returns a unit tangent perpendicular to the normal, for the vertices without texture
+/// coordinates.
+fn fallback_tangent(normal: &Normal) -> Tangent {
+    let normal = Vector3::from(normal.0);
+    let axis = if normal.x.abs() < 0.9 {
+        Vector3::x()
+    } else {
+        Vector3::y()
+    };
+    let tangent = axis - normal * normal.dot(&axis);
+    let [x, y, z]: [f32; 3] = tangent.try_normalize(0.0).unwrap_or(axis).into();
+    Tangent([x, y, z, 1.0])
+}
+
+/// Calculates the tangents of a triangle list with the MikkTSpace algorithm, which normal maps
+/// are usually baked with.
+///
+/// `indices` are the indices of the triangle list, or `None` if every three vertices form a
+/// triangle.
+pub fn calculate_tangents(
+    positions: &[Position],
+    normals: &[Normal],
+    tex_coords: &[TexCoord],
+    indices: Option<&[u32]>,
+) -> Vec<Tangent> {
+    let mut geometry = TangentsGeometry {
+        tangents: vec![Tangent([0.0, 0.0, 0.0, 0.0]); positions.len()],
+        num_faces: num_faces(positions, indices),
+        positions,
+        normals,
+        tex_coords,
+        indices,
+    };
+
+    if !mikktspace::generate_tangents(&mut geometry) {
+        warn!("Could not generate tangents!");
+    }
+
+    geometry.tangents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POSITIONS: &[Position] = &[
+        Position([0.0, 0.0, 0.0]),
+        Position([0.0, 1.0, 0.0]),
+        Position([1.0, 1.0, 0.0]),
+        Position([0.0, 1.0, 0.0]),
+        Position([1.0, 1.0, 0.0]),
+        Position([1.0, 0.0, 0.0]),
+    ];
+    const NORMALS: &[Normal] = &[
+        Normal([0.0, 0.0, 1.0]),
+        Normal([0.0, 0.0, 1.0]),
+        Normal([0.0, 0.0, 1.0]),
+        Normal([0.0, 0.0, 1.0]),
+        Normal([0.0, 0.0, 1.0]),
+        Normal([1.0, 0.0, 0.0]),
+    ];
+    const TEX_COORDS: &[TexCoord] = &[
+        TexCoord([0.0, 0.0]),
+        TexCoord([0.0, 1.0]),
+        TexCoord([1.0, 1.0]),
+        TexCoord([0.0, 1.0]),
+        TexCoord([1.0, 1.0]),
+        TexCoord([1.0, 0.0]),
+    ];
+
+    #[test]
+    fn fallback_tangents_are_perpendicular_to_the_normals() {
+        for normal in NORMALS.iter().chain(&[Normal([0.6, 0.8, 0.0])]) {
+            let Tangent([x, y, z, w]) = fallback_tangent(normal);
+            let tangent = Vector3::new(x, y, z);
+            assert!((tangent.norm() - 1.0).abs() < 1e-6);
+            assert!(tangent.dot(&Vector3::from(normal.0)).abs() < 1e-6);
+            assert_eq!(1.0, w);
+        }
+    }
+
+    #[test]
+    fn test_tangent_calc() {
+        let tangents = calculate_tangents(POSITIONS, NORMALS, TEX_COORDS, None);
+        assert_eq!(
+            tangents,
+            vec![
+                Tangent([1.0, 0.0, 0.0, 1.0]),
+                Tangent([1.0, 0.0, 0.0, 1.0]),
+                Tangent([1.0, 0.0, 0.0, 1.0]),
+                Tangent([1.0, 0.0, 0.0, 1.0]),
+                Tangent([1.0, 0.0, 0.0, 1.0]),
+                Tangent([0.0, 0.0, 0.0, 1.0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_indexed_tangent_calc() {
+        let tangents =
+            calculate_tangents(POSITIONS, NORMALS, TEX_COORDS, Some(&[3, 4, 5, 0, 1, 2]));
+        assert_eq!(
+            tangents,
+            vec![
+                Tangent([1.0, 0.0, 0.0, 1.0]),
+                Tangent([1.0, 0.0, 0.0, 1.0]),
+                Tangent([1.0, 0.0, 0.0, 1.0]),
+                Tangent([1.0, 0.0, 0.0, 1.0]),
+                Tangent([1.0, 0.0, 0.0, 1.0]),
+                Tangent([0.0, 0.0, 0.0, 1.0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_normal_calc() {
+        let normals = calculate_normals(&POSITIONS[..3], None);
+        assert_eq!(vec![Normal([0.0, 0.0, -1.0]); 3], normals);
+    }
+}
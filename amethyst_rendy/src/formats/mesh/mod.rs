@@ -1,4 +1,10 @@
 //! Module for mesh support.
+pub use self::{
+    generate::{calculate_normals, calculate_tangents},
+    ply::PlyFormat,
+    stl::StlFormat,
+};
+
 use amethyst_assets::Format;
 use amethyst_error::Error;
 use serde::{Deserialize, Serialize};
@@ -6,6 +12,10 @@ use type_uuid::TypeUuid;
 
 use crate::types::MeshData;
 
+mod generate;
+mod ply;
+mod stl;
+
 /// 'Obj' mesh format `Format` implementation.
 #[derive(
     Debug,
@@ -0,0 +1,594 @@
+use std::{
+    convert::{TryFrom, TryInto},
+    str,
+};
+
+use amethyst_assets::Format;
+use amethyst_error::{format_err, Error};
+use rendy::mesh::{Color, Normal, Position, TexCoord};
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+use super::generate::MeshAttributes;
+use crate::types::MeshData;
+
+/// 'Ply' (Stanford polygon file) mesh format `Format` implementation.
+///
+/// Reads the ASCII and binary encodings. Vertex positions, normals, texture coordinates and
+/// colors are read from the `vertex` element, and the polygons of the `face` element are
+/// triangulated. Normals and tangents are generated when the file does not have them.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    TypeUuid,
+)]
+#[uuid = "0fe6e5ad-d1e4-4e9a-8c36-4bd5b8a7a8b4"]
+pub struct PlyFormat;
+
+amethyst_assets::register_importer!(".ply", PlyFormat);
+impl Format<MeshData> for PlyFormat {
+    fn name(&self) -> &'static str {
+        "PLY"
+    }
+
+    fn import_simple(&self, bytes: Vec<u8>) -> Result<MeshData, Error> {
+        parse_ply(&bytes).map(MeshAttributes::into_mesh_data)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Result<Self, Error> {
+        Ok(match name {
+            "char" | "int8" => ScalarType::I8,
+            "uchar" | "uint8" => ScalarType::U8,
+            "short" | "int16" => ScalarType::I16,
+            "ushort" | "uint16" => ScalarType::U16,
+            "int" | "int32" => ScalarType::I32,
+            "uint" | "uint32" => ScalarType::U32,
+            "float" | "float32" => ScalarType::F32,
+            "double" | "float64" => ScalarType::F64,
+            _ => return Err(format_err!("Unknown PLY property type `{}`", name)),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ScalarType::I8 | ScalarType::U8 => 1,
+            ScalarType::I16 | ScalarType::U16 => 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+            ScalarType::F64 => 8,
+        }
+    }
+
+    /// The value integer colors of this type are divided by to get the `0.0..=1.0` range.
+    fn color_scale(self) -> f32 {
+        match self {
+            ScalarType::U8 => 255.0,
+            ScalarType::U16 => 65535.0,
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PropertyType {
+    Scalar(ScalarType),
+    List { count: ScalarType, item: ScalarType },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Property {
+    name: String,
+    ty: PropertyType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn property(&self, names: &[&str]) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|property| names.contains(&property.name.as_str()))
+    }
+}
+
+#[derive(Debug)]
+struct Header {
+    encoding: Encoding,
+    elements: Vec<Element>,
+    /// Offset of the body in the file.
+    body: usize,
+}
+
+fn parse_header(bytes: &[u8]) -> Result<Header, Error> {
+    const END: &[u8] = b"end_header";
+    let end = bytes
+        .windows(END.len())
+        .position(|window| window == END)
+        .ok_or_else(|| format_err!("PLY file has no `end_header`"))?;
+    let mut body = end + END.len();
+    if bytes.get(body) == Some(&b'\r') {
+        body += 1;
+    }
+    if bytes.get(body) == Some(&b'\n') {
+        body += 1;
+    }
+
+    let header =
+        str::from_utf8(&bytes[..end]).map_err(|_| format_err!("PLY header is not valid UTF-8"))?;
+    let mut lines = header.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        return Err(format_err!("File is not a PLY file"));
+    }
+
+    let mut encoding = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            [] | ["comment", ..] | ["obj_info", ..] => {}
+            ["format", format, _version] => {
+                encoding = Some(match *format {
+                    "ascii" => Encoding::Ascii,
+                    "binary_little_endian" => Encoding::LittleEndian,
+                    "binary_big_endian" => Encoding::BigEndian,
+                    _ => return Err(format_err!("Unknown PLY format `{}`", format)),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: (*name).to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| format_err!("Invalid PLY element count `{}`", count))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => push_property(
+                &mut elements,
+                name,
+                PropertyType::List {
+                    count: ScalarType::parse(count)?,
+                    item: ScalarType::parse(item)?,
+                },
+            )?,
+            ["property", ty, name] => push_property(
+                &mut elements,
+                name,
+                PropertyType::Scalar(ScalarType::parse(ty)?),
+            )?,
+            _ => return Err(format_err!("Invalid PLY header line `{}`", line)),
+        }
+    }
+
+    Ok(Header {
+        encoding: encoding.ok_or_else(|| format_err!("PLY header has no `format`"))?,
+        elements,
+        body,
+    })
+}
+
+fn push_property(elements: &mut [Element], name: &str, ty: PropertyType) -> Result<(), Error> {
+    elements
+        .last_mut()
+        .ok_or_else(|| format_err!("PLY property `{}` is not part of an element", name))?
+        .properties
+        .push(Property {
+            name: name.to_string(),
+            ty,
+        });
+    Ok(())
+}
+
+/// Reads the values of the body of a PLY file.
+enum Values<'a> {
+    Ascii(str::SplitAsciiWhitespace<'a>),
+    Binary {
+        bytes: &'a [u8],
+        little_endian: bool,
+    },
+}
+
+impl<'a> Values<'a> {
+    fn new(encoding: Encoding, body: &'a [u8]) -> Result<Self, Error> {
+        Ok(match encoding {
+            Encoding::Ascii => Values::Ascii(
+                str::from_utf8(body)
+                    .map_err(|_| format_err!("ASCII PLY body is not valid UTF-8"))?
+                    .split_ascii_whitespace(),
+            ),
+            Encoding::LittleEndian => Values::Binary {
+                bytes: body,
+                little_endian: true,
+            },
+            Encoding::BigEndian => Values::Binary {
+                bytes: body,
+                little_endian: false,
+            },
+        })
+    }
+
+    fn read(&mut self, ty: ScalarType) -> Result<f64, Error> {
+        match self {
+            Values::Ascii(words) => {
+                let word = words
+                    .next()
+                    .ok_or_else(|| format_err!("Unexpected end of PLY file"))?;
+                word.parse()
+                    .map_err(|_| format_err!("Invalid PLY value `{}`", word))
+            }
+            Values::Binary {
+                bytes,
+                little_endian,
+            } => {
+                if bytes.len() < ty.size() {
+                    return Err(format_err!("Unexpected end of PLY file"));
+                }
+                let (value, rest) = bytes.split_at(ty.size());
+                *bytes = rest;
+                Ok(decode(ty, value, *little_endian))
+            }
+        }
+    }
+
+    fn skip(&mut self, ty: &PropertyType) -> Result<(), Error> {
+        match *ty {
+            PropertyType::Scalar(ty) => {
+                self.read(ty)?;
+            }
+            PropertyType::List { count, item } => {
+                for _ in 0..self.read(count)? as usize {
+                    self.read(item)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn decode(ty: ScalarType, bytes: &[u8], little_endian: bool) -> f64 {
+    macro_rules! decode {
+        ($ty:ty) => {{
+            let bytes = bytes.try_into().expect("Value has the size of its type");
+            if little_endian {
+                <$ty>::from_le_bytes(bytes) as f64
+            } else {
+                <$ty>::from_be_bytes(bytes) as f64
+            }
+        }};
+    }
+    match ty {
+        ScalarType::I8 => decode!(i8),
+        ScalarType::U8 => decode!(u8),
+        ScalarType::I16 => decode!(i16),
+        ScalarType::U16 => decode!(u16),
+        ScalarType::I32 => decode!(i32),
+        ScalarType::U32 => decode!(u32),
+        ScalarType::F32 => decode!(f32),
+        ScalarType::F64 => decode!(f64),
+    }
+}
+
+/// Indices of the properties of the `vertex` element read into the mesh.
+struct VertexLayout {
+    position: [usize; 3],
+    normal: Option<[usize; 3]>,
+    tex_coord: Option<[usize; 2]>,
+    color: Option<[usize; 3]>,
+    alpha: Option<usize>,
+}
+
+impl VertexLayout {
+    fn new(element: &Element) -> Result<Self, Error> {
+        let property = |names: &[&str]| element.property(names);
+        let position = match (property(&["x"]), property(&["y"]), property(&["z"])) {
+            (Some(x), Some(y), Some(z)) => [x, y, z],
+            _ => {
+                return Err(format_err!(
+                    "PLY vertices have no `x`, `y` and `z` properties"
+                ))
+            }
+        };
+        let normal = match (property(&["nx"]), property(&["ny"]), property(&["nz"])) {
+            (Some(x), Some(y), Some(z)) => Some([x, y, z]),
+            _ => None,
+        };
+        let tex_coord = match (
+            property(&["u", "s", "texture_u", "texture_s"]),
+            property(&["v", "t", "texture_v", "texture_t"]),
+        ) {
+            (Some(u), Some(v)) => Some([u, v]),
+            _ => None,
+        };
+        let color = match (
+            property(&["red"]),
+            property(&["green"]),
+            property(&["blue"]),
+        ) {
+            (Some(r), Some(g), Some(b)) => Some([r, g, b]),
+            _ => None,
+        };
+        let alpha = color.and(property(&["alpha"]));
+
+        let layout = VertexLayout {
+            position,
+            normal,
+            tex_coord,
+            color,
+            alpha,
+        };
+        let is_list = |index: &usize| match element.properties[*index].ty {
+            PropertyType::List { .. } => true,
+            PropertyType::Scalar(_) => false,
+        };
+        if layout
+            .position
+            .iter()
+            .chain(layout.normal.iter().flatten())
+            .chain(layout.tex_coord.iter().flatten())
+            .chain(layout.color.iter().flatten())
+            .chain(layout.alpha.iter())
+            .any(is_list)
+        {
+            return Err(format_err!("PLY vertex attributes must not be lists"));
+        }
+        Ok(layout)
+    }
+}
+
+fn parse_ply(bytes: &[u8]) -> Result<MeshAttributes, Error> {
+    let header = parse_header(bytes)?;
+    let mut values = Values::new(header.encoding, &bytes[header.body..])?;
+
+    let mut mesh = MeshAttributes::default();
+    let mut normals = Vec::new();
+    let mut tex_coords = Vec::new();
+    let mut colors = Vec::new();
+    let mut indices = Vec::new();
+    let mut has_faces = false;
+
+    for element in &header.elements {
+        match element.name.as_str() {
+            "vertex" => {
+                let layout = VertexLayout::new(element)?;
+                let mut row = vec![0.0; element.properties.len()];
+                for _ in 0..element.count {
+                    for (value, property) in row.iter_mut().zip(&element.properties) {
+                        match property.ty {
+                            PropertyType::Scalar(ty) => *value = values.read(ty)?,
+                            ref list => values.skip(list)?,
+                        }
+                    }
+                    let get = |index: usize| row[index] as f32;
+                    let [x, y, z] = layout.position;
+                    mesh.positions.push(Position([get(x), get(y), get(z)]));
+                    if let Some([x, y, z]) = layout.normal {
+                        normals.push(Normal([get(x), get(y), get(z)]));
+                    }
+                    if let Some([u, v]) = layout.tex_coord {
+                        tex_coords.push(TexCoord([get(u), get(v)]));
+                    }
+                    if let Some([r, g, b]) = layout.color {
+                        let channel = |index: usize| match element.properties[index].ty {
+                            PropertyType::Scalar(ty) => get(index) / ty.color_scale(),
+                            PropertyType::List { .. } => unreachable!(),
+                        };
+                        let a = layout.alpha.map_or(1.0, channel);
+                        colors.push(Color([channel(r), channel(g), channel(b), a]));
+                    }
+                }
+                if layout.normal.is_some() {
+                    mesh.normals = Some(std::mem::take(&mut normals));
+                }
+                if layout.tex_coord.is_some() {
+                    mesh.tex_coords = Some(std::mem::take(&mut tex_coords));
+                }
+                if layout.color.is_some() {
+                    mesh.colors = Some(std::mem::take(&mut colors));
+                }
+            }
+            "face" => {
+                has_faces = true;
+                let list = element
+                    .property(&["vertex_indices", "vertex_index"])
+                    .ok_or_else(|| format_err!("PLY faces have no `vertex_indices` property"))?;
+                let mut polygon = Vec::new();
+                for _ in 0..element.count {
+                    for (index, property) in element.properties.iter().enumerate() {
+                        match property.ty {
+                            PropertyType::List { count, item } if index == list => {
+                                polygon.clear();
+                                for _ in 0..values.read(count)? as usize {
+                                    let index = values.read(item)?;
+                                    polygon.push(u32::try_from(index as i64).map_err(|_| {
+                                        format_err!("PLY face uses vertex {}", index)
+                                    })?);
+                                }
+                            }
+                            ref ty => values.skip(ty)?,
+                        }
+                    }
+                    for i in 2..polygon.len() {
+                        indices.extend_from_slice(&[polygon[0], polygon[i - 1], polygon[i]]);
+                    }
+                }
+            }
+            _ => {
+                for _ in 0..element.count {
+                    for property in &element.properties {
+                        values.skip(&property.ty)?;
+                    }
+                }
+            }
+        }
+    }
+
+    if !has_faces {
+        return Err(format_err!("PLY file has no faces"));
+    }
+    let vertex_count = mesh.positions.len() as u32;
+    if let Some(index) = indices.iter().find(|&&index| index >= vertex_count) {
+        return Err(format_err!(
+            "PLY face uses vertex {} of {} vertices",
+            index,
+            vertex_count
+        ));
+    }
+    mesh.indices = Some(indices);
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASCII: &str = "ply
+format ascii 1.0
+comment a quad
+element vertex 4
+property float x
+property float y
+property float z
+property float s
+property float t
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 0 0 255 0 0
+1 0 0 1 0 0 255 0
+1 1 0 1 1 0 0 255
+0 1 0 0 1 255 255 255
+4 0 1 2 3
+";
+
+    #[test]
+    fn ascii_quad_is_triangulated() {
+        let mesh = parse_ply(ASCII.as_bytes()).unwrap();
+        assert_eq!(
+            mesh.positions,
+            vec![
+                Position([0.0, 0.0, 0.0]),
+                Position([1.0, 0.0, 0.0]),
+                Position([1.0, 1.0, 0.0]),
+                Position([0.0, 1.0, 0.0]),
+            ]
+        );
+        assert_eq!(mesh.normals, None);
+        assert_eq!(mesh.tex_coords.unwrap()[2], TexCoord([1.0, 1.0]),);
+        assert_eq!(
+            mesh.colors.unwrap(),
+            vec![
+                Color([1.0, 0.0, 0.0, 1.0]),
+                Color([0.0, 1.0, 0.0, 1.0]),
+                Color([0.0, 0.0, 1.0, 1.0]),
+                Color([1.0, 1.0, 1.0, 1.0]),
+            ]
+        );
+        assert_eq!(mesh.indices, Some(vec![0, 1, 2, 0, 2, 3]));
+    }
+
+    fn binary(little_endian: bool) -> Vec<u8> {
+        let format = if little_endian {
+            "binary_little_endian"
+        } else {
+            "binary_big_endian"
+        };
+        let mut bytes = format!(
+            "ply\nformat {} 1.0\nelement vertex 3\nproperty float x\nproperty float y\n\
+             property float z\nproperty float nx\nproperty float ny\nproperty float nz\n\
+             element face 1\nproperty uchar flags\nproperty list uchar uint vertex_index\n\
+             end_header\n",
+            format
+        )
+        .into_bytes();
+        let floats: [f32; 18] = [
+            0.0, 0.0, 0.0, 0.0, 0.0, 1.0, //
+            1.0, 0.0, 0.0, 0.0, 0.0, 1.0, //
+            0.0, 1.0, 0.0, 0.0, 0.0, 1.0,
+        ];
+        for value in &floats {
+            if little_endian {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            } else {
+                bytes.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        bytes.extend_from_slice(&[7, 3]);
+        for index in 0u32..3 {
+            if little_endian {
+                bytes.extend_from_slice(&index.to_le_bytes());
+            } else {
+                bytes.extend_from_slice(&index.to_be_bytes());
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn binary_triangle() {
+        for &little_endian in &[true, false] {
+            let mesh = parse_ply(&binary(little_endian)).unwrap();
+            assert_eq!(
+                mesh.positions,
+                vec![
+                    Position([0.0, 0.0, 0.0]),
+                    Position([1.0, 0.0, 0.0]),
+                    Position([0.0, 1.0, 0.0]),
+                ]
+            );
+            assert_eq!(mesh.normals, Some(vec![Normal([0.0, 0.0, 1.0]); 3]));
+            assert_eq!(mesh.tex_coords, None);
+            assert_eq!(mesh.colors, None);
+            assert_eq!(mesh.indices, Some(vec![0, 1, 2]));
+        }
+    }
+
+    #[test]
+    fn truncated_binary_is_an_error() {
+        let mut bytes = binary(true);
+        bytes.pop();
+        assert!(parse_ply(&bytes).is_err());
+    }
+
+    #[test]
+    fn out_of_range_index_is_an_error() {
+        let ply = ASCII.replace("4 0 1 2 3", "3 0 1 4");
+        assert!(parse_ply(ply.as_bytes()).is_err());
+        let ply = ASCII.replace("4 0 1 2 3", "3 0 1 -1");
+        assert!(parse_ply(ply.as_bytes()).is_err());
+    }
+}
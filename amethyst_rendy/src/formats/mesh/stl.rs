@@ -0,0 +1,267 @@
+use std::{convert::TryInto, str};
+
+use amethyst_assets::Format;
+use amethyst_core::math::Vector3;
+use amethyst_error::{format_err, Error};
+use rendy::mesh::{Color, Normal, Position};
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+use super::generate::MeshAttributes;
+use crate::types::MeshData;
+
+/// 'Stl' (stereolithography) mesh format `Format` implementation.
+///
+/// Reads the ASCII and binary encodings. Every vertex has the normal of its facet, and the facet
+/// colors of binary files using the VisCAM/SolidView convention are read as vertex colors.
+/// As STL files have no texture coordinates, the tangents are perpendicular to the normals in an
+/// arbitrary direction.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    TypeUuid,
+)]
+#[uuid = "5c1a8b1e-3ad4-4b4e-9d8e-1f0c2a6f2e57"]
+pub struct StlFormat;
+
+amethyst_assets::register_importer!(".stl", StlFormat);
+impl Format<MeshData> for StlFormat {
+    fn name(&self) -> &'static str {
+        "STL"
+    }
+
+    fn import_simple(&self, bytes: Vec<u8>) -> Result<MeshData, Error> {
+        parse_stl(&bytes).map(MeshAttributes::into_mesh_data)
+    }
+}
+
+const HEADER_SIZE: usize = 80;
+const TRIANGLE_SIZE: usize = 50;
+
+fn parse_stl(bytes: &[u8]) -> Result<MeshAttributes, Error> {
+    // ASCII files start with `solid` too, but so do the headers of some binary files, so the
+    // size of the file is checked instead.
+    let is_binary = bytes.len() >= HEADER_SIZE + 4 && {
+        let count = read_u32(&bytes[HEADER_SIZE..]) as usize;
+        bytes.len() == HEADER_SIZE + 4 + count * TRIANGLE_SIZE
+    };
+    let mesh = if is_binary {
+        parse_binary(bytes)
+    } else {
+        parse_ascii(bytes)?
+    };
+    if mesh.positions.is_empty() {
+        return Err(format_err!("STL file has no facets"));
+    }
+    Ok(mesh)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[..4].try_into().expect("Slice has 4 bytes"))
+}
+
+fn read_vector(bytes: &[u8]) -> [f32; 3] {
+    let mut vector = [0.0; 3];
+    for (i, value) in vector.iter_mut().enumerate() {
+        *value = f32::from_bits(read_u32(&bytes[i * 4..]));
+    }
+    vector
+}
+
+/// Decodes the VisCAM/SolidView facet color, where the highest bit is set for valid colors
+/// and the color is stored as 5 bit red, green and blue channels.
+fn decode_color(attribute: u16) -> Option<Color> {
+    if attribute & 0x8000 == 0 {
+        return None;
+    }
+    let channel = |shift: u16| f32::from((attribute >> shift) & 0x1f) / 31.0;
+    Some(Color([channel(10), channel(5), channel(0), 1.0]))
+}
+
+fn parse_binary(bytes: &[u8]) -> MeshAttributes {
+    let mut mesh = MeshAttributes::default();
+    let mut normals = Vec::new();
+    let mut colors = Vec::new();
+    let mut has_colors = false;
+    for triangle in bytes[HEADER_SIZE + 4..].chunks_exact(TRIANGLE_SIZE) {
+        let positions = [
+            read_vector(&triangle[12..]),
+            read_vector(&triangle[24..]),
+            read_vector(&triangle[36..]),
+        ];
+        let normal = facet_normal(read_vector(triangle), &positions);
+        let attribute = u16::from_le_bytes([triangle[48], triangle[49]]);
+        let color = decode_color(attribute);
+        has_colors |= color.is_some();
+        for position in &positions {
+            mesh.positions.push(Position(*position));
+            normals.push(normal);
+            colors.push(color.unwrap_or(Color([1.0, 1.0, 1.0, 1.0])));
+        }
+    }
+    mesh.normals = Some(normals);
+    if has_colors {
+        mesh.colors = Some(colors);
+    }
+    mesh
+}
+
+fn parse_ascii(bytes: &[u8]) -> Result<MeshAttributes, Error> {
+    let text = str::from_utf8(bytes).map_err(|_| format_err!("ASCII STL file is not valid UTF-8"))?;
+    let mut words = text.split_ascii_whitespace();
+    if words.next() != Some("solid") {
+        return Err(format_err!("File is not a STL file"));
+    }
+
+    let mut mesh = MeshAttributes::default();
+    let mut normals = Vec::new();
+    let mut normal = [0.0; 3];
+    let mut polygon = Vec::new();
+    let parse_vector = |words: &mut str::SplitAsciiWhitespace<'_>| -> Result<[f32; 3], Error> {
+        let mut vector = [0.0; 3];
+        for value in &mut vector {
+            let word = words
+                .next()
+                .ok_or_else(|| format_err!("Unexpected end of STL file"))?;
+            *value = word
+                .parse()
+                .map_err(|_| format_err!("Invalid STL number `{}`", word))?;
+        }
+        Ok(vector)
+    };
+    while let Some(word) = words.next() {
+        match word {
+            "facet" => {
+                if words.next() != Some("normal") {
+                    return Err(format_err!("STL facet has no normal"));
+                }
+                normal = parse_vector(&mut words)?;
+            }
+            "outer" => {
+                words.next();
+                polygon.clear();
+            }
+            "vertex" => polygon.push(parse_vector(&mut words)?),
+            "endloop" => {
+                if polygon.len() < 3 {
+                    return Err(format_err!("STL facet has less than 3 vertices"));
+                }
+                for i in 2..polygon.len() {
+                    let triangle = [polygon[0], polygon[i - 1], polygon[i]];
+                    let normal = facet_normal(normal, &triangle);
+                    for position in &triangle {
+                        mesh.positions.push(Position(*position));
+                        normals.push(normal);
+                    }
+                }
+            }
+            // The name of the solid can follow `solid` and `endsolid`.
+            _ => {}
+        }
+    }
+    mesh.normals = Some(normals);
+    Ok(mesh)
+}
+
+/// Returns the normal of the facet, which is calculated from its vertices if the file has a
+/// zero normal. Degenerate facets without a normal face along the Z axis.
+fn facet_normal(normal: [f32; 3], positions: &[[f32; 3]; 3]) -> Normal {
+    let [a, b, c] = positions;
+    let (a, b, c) = (Vector3::from(*a), Vector3::from(*b), Vector3::from(*c));
+    let normal = Vector3::from(normal)
+        .try_normalize(0.0)
+        .or_else(|| (b - a).cross(&(c - a)).try_normalize(0.0))
+        .unwrap_or_else(Vector3::z);
+    Normal(normal.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASCII: &str = "solid triangle
+  facet normal 0 0 0
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid triangle
+";
+
+    #[test]
+    fn ascii_triangle() {
+        let mesh = parse_stl(ASCII.as_bytes()).unwrap();
+        assert_eq!(
+            mesh.positions,
+            vec![
+                Position([0.0, 0.0, 0.0]),
+                Position([1.0, 0.0, 0.0]),
+                Position([0.0, 1.0, 0.0]),
+            ]
+        );
+        assert_eq!(mesh.normals, Some(vec![Normal([0.0, 0.0, 1.0]); 3]));
+        assert_eq!(mesh.colors, None);
+        assert_eq!(mesh.indices, None);
+    }
+
+    fn binary(attributes: &[u16]) -> Vec<u8> {
+        // Binary headers may start with `solid` as well
+        let mut bytes = b"solid binary".to_vec();
+        bytes.resize(HEADER_SIZE, 0);
+        bytes.extend_from_slice(&(attributes.len() as u32).to_le_bytes());
+        for attribute in attributes {
+            let floats: [f32; 12] = [
+                0.0, 0.0, 2.0, //
+                0.0, 0.0, 0.0, //
+                1.0, 0.0, 0.0, //
+                0.0, 1.0, 0.0,
+            ];
+            for value in &floats {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            bytes.extend_from_slice(&attribute.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn degenerate_facets_have_a_normal() {
+        let ascii = ASCII.replace("vertex 0 1 0", "vertex 2 0 0");
+        let mesh = parse_stl(ascii.as_bytes()).unwrap();
+        assert_eq!(mesh.normals, Some(vec![Normal([0.0, 0.0, 1.0]); 3]));
+    }
+
+    #[test]
+    fn binary_triangles() {
+        let mesh = parse_stl(&binary(&[0, 0])).unwrap();
+        assert_eq!(mesh.positions.len(), 6);
+        assert_eq!(mesh.positions[4], Position([1.0, 0.0, 0.0]));
+        assert_eq!(mesh.normals, Some(vec![Normal([0.0, 0.0, 1.0]); 6]));
+        assert_eq!(mesh.colors, None);
+    }
+
+    #[test]
+    fn binary_facet_colors() {
+        let red = 0x8000 | (31 << 10);
+        let mesh = parse_stl(&binary(&[red, 0])).unwrap();
+        let colors = mesh.colors.unwrap();
+        assert_eq!(colors[0], Color([1.0, 0.0, 0.0, 1.0]));
+        assert_eq!(colors[3], Color([1.0, 1.0, 1.0, 1.0]));
+    }
+
+    #[test]
+    fn empty_file_is_an_error() {
+        assert!(parse_stl(b"solid empty\nendsolid empty\n").is_err());
+    }
+}
@@ -1,3 +1,4 @@
 //! Pre-defined graphical formats and data provided by amethyst_rendy
 pub mod mesh;
+pub mod sprite_sheet;
 pub mod texture;
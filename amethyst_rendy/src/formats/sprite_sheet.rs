@@ -0,0 +1,398 @@
+//! Sprite sheet importers.
+//!
+//! These importers produce a `SpriteSheet` asset together with the `Sprites` asset it refers to,
+//! and load the texture of the sheet as a dependency, so that loading the sprite sheet by path is
+//! enough to render its sprites.
+use std::io::Read;
+
+use amethyst_assets::{
+    atelier_importer::{self, ImportOp, ImportedAsset, Importer, ImporterValue},
+    make_handle, make_handle_from_path, AssetRef, AssetUuid,
+};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+use crate::sprite::{SpriteList, SpritePosition, SpriteSheet, Sprites};
+
+/// Options of the sprite sheet importers, which currently have none.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, TypeUuid)]
+#[uuid = "d2f8ad6b-3c8e-4b1e-9b0c-2f4a61c5e7a3"]
+pub struct SpriteSheetImporterOptions {}
+
+/// Keeps the UUIDs of the imported `SpriteSheet` and `Sprites` assets between imports.
+#[derive(Debug, Default, Serialize, Deserialize, TypeUuid)]
+#[uuid = "5a1f9c3e-7d42-4b6a-8e21-93c0d4b8f615"]
+pub struct SpriteSheetImporterState {
+    sprite_sheet: Option<AssetUuid>,
+    sprites: Option<AssetUuid>,
+}
+
+impl SpriteSheetImporterState {
+    /// Returns the imported sprite sheet, with the sprites and the texture as load dependencies.
+    fn assets(&mut self, texture: &str, sprites: Sprites) -> ImporterValue {
+        let sprite_sheet_id = *self.sprite_sheet.get_or_insert_with(new_asset_uuid);
+        let sprites_id = *self.sprites.get_or_insert_with(new_asset_uuid);
+        let sprite_sheet = SpriteSheet {
+            texture: make_handle_from_path(texture),
+            sprites: make_handle(sprites_id),
+        };
+
+        ImporterValue {
+            assets: vec![
+                ImportedAsset {
+                    id: sprites_id,
+                    search_tags: Vec::new(),
+                    build_deps: Vec::new(),
+                    load_deps: Vec::new(),
+                    asset_data: Box::new(sprites),
+                    build_pipeline: None,
+                },
+                ImportedAsset {
+                    id: sprite_sheet_id,
+                    search_tags: Vec::new(),
+                    build_deps: Vec::new(),
+                    load_deps: vec![AssetRef::Path(texture.into()), AssetRef::Uuid(sprites_id)],
+                    asset_data: Box::new(sprite_sheet),
+                    build_pipeline: None,
+                },
+            ],
+        }
+    }
+}
+
+fn new_asset_uuid() -> AssetUuid {
+    AssetUuid(*uuid::Uuid::new_v4().as_bytes())
+}
+
+fn read_source(source: &mut dyn Read) -> atelier_importer::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    source.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Description of a sprite sheet in a `.spritesheet` file.
+///
+/// ```text,ignore
+/// #![enable(implicit_some)]
+/// (
+///     // Path of the texture, relative to the sprite sheet file
+///     texture: "bat.png",
+///     sprites: Grid((
+///         texture_width: 192,
+///         texture_height: 64,
+///         columns: 6,
+///         sprite_count: 10,
+///     )),
+/// )
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpriteSheetDescription {
+    /// Path of the texture, relative to the sprite sheet file.
+    pub texture: String,
+    /// Sprites on the texture, as a `List` or a `Grid`.
+    pub sprites: Sprites,
+}
+
+/// Imports `SpriteSheetDescription`s in RON format, registered for `.spritesheet` files.
+///
+/// The importer produces a `SpriteSheet` and its `Sprites`, and the texture is loaded with the
+/// sprite sheet:
+///
+/// ```rust,ignore
+/// let sprite_sheet: Handle<SpriteSheet> = loader.load("sprites/bat.spritesheet");
+/// ```
+#[derive(Debug, Default, TypeUuid)]
+#[uuid = "8c4d7e21-6b3a-4f0d-a5e9-1d72c3b0f948"]
+pub struct SpriteSheetImporter;
+
+impl Importer for SpriteSheetImporter {
+    type State = SpriteSheetImporterState;
+    type Options = SpriteSheetImporterOptions;
+
+    fn version_static() -> u32 {
+        1
+    }
+
+    fn version(&self) -> u32 {
+        Self::version_static()
+    }
+
+    fn import(
+        &self,
+        _op: &mut ImportOp,
+        source: &mut dyn Read,
+        _options: &Self::Options,
+        state: &mut Self::State,
+    ) -> atelier_importer::Result<ImporterValue> {
+        let description: SpriteSheetDescription = ron::de::from_bytes(&read_source(source)?)
+            .map_err(|e| atelier_importer::Error::Boxed(Box::new(e)))?;
+        Ok(state.assets(&description.texture, description.sprites))
+    }
+}
+
+amethyst_assets::inventory::submit! {
+    #![crate = amethyst_assets]
+    amethyst_assets::SourceFileImporter {
+        extension: ".spritesheet",
+        instantiator: || Box::new(SpriteSheetImporter),
+    }
+}
+
+/// JSON texture atlas, in the format of TexturePacker and of the sprite sheets exported by
+/// Aseprite.
+#[derive(Debug, Deserialize)]
+struct JsonAtlas {
+    frames: JsonFrames,
+    meta: JsonMeta,
+}
+
+/// Frames of a JSON atlas, keyed by name in the "hash" layout, or listed in the "array" layout.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum JsonFrames {
+    Array(Vec<JsonFrame>),
+    Hash(IndexMap<String, JsonFrame>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonFrame {
+    frame: JsonRect,
+    #[serde(default)]
+    rotated: bool,
+    #[serde(default)]
+    trimmed: bool,
+    sprite_source_size: Option<JsonRect>,
+    source_size: Option<JsonSize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonMeta {
+    image: String,
+    size: JsonSize,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonRect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonSize {
+    w: u32,
+    h: u32,
+}
+
+impl JsonAtlas {
+    /// Returns the sprites of the atlas, in the order of its frames.
+    fn sprite_list(&self) -> Result<SpriteList, String> {
+        let frames: Vec<&JsonFrame> = match &self.frames {
+            JsonFrames::Array(frames) => frames.iter().collect(),
+            JsonFrames::Hash(frames) => frames.values().collect(),
+        };
+        let sprites = frames
+            .into_iter()
+            .enumerate()
+            .map(|(index, frame)| {
+                if frame.rotated {
+                    return Err(format!(
+                        "Frame {} is rotated, which is not supported; \
+                         export the atlas without rotation",
+                        index
+                    ));
+                }
+                Ok(SpritePosition {
+                    x: frame.frame.x,
+                    y: frame.frame.y,
+                    width: frame.frame.w,
+                    height: frame.frame.h,
+                    offsets: frame.offsets(),
+                    flip_horizontal: false,
+                    flip_vertical: false,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(SpriteList {
+            texture_width: self.meta.size.w,
+            texture_height: self.meta.size.h,
+            sprites,
+        })
+    }
+}
+
+impl JsonFrame {
+    /// Returns the offsets that keep a trimmed frame where it was in the untrimmed image, which
+    /// is centered on the entity.
+    fn offsets(&self) -> Option<[f32; 2]> {
+        match (self.trimmed, &self.sprite_source_size, &self.source_size) {
+            (true, Some(trimmed), Some(source)) => {
+                let dx = (trimmed.x as f32 + trimmed.w as f32 / 2.0) - source.w as f32 / 2.0;
+                let dy = (trimmed.y as f32 + trimmed.h as f32 / 2.0) - source.h as f32 / 2.0;
+                Some([-dx, dy])
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Imports the JSON texture atlases of TexturePacker and Aseprite into a `SpriteSheet` and its
+/// `Sprites`, and loads the image of the atlas with the sprite sheet.
+///
+/// Both the "hash" and the "array" layouts are supported, and the sprites are numbered in the
+/// order of the frames in the file. Trimmed frames are offset so that they are rendered where
+/// they were in the untrimmed image. Rotated frames are not supported.
+///
+/// The importer is registered for `.atlas` files, so the atlases exported as `.json` files are
+/// given the `.atlas` extension to be imported:
+///
+/// ```rust,ignore
+/// let sprite_sheet: Handle<SpriteSheet> = loader.load("sprites/bat.atlas");
+/// ```
+#[derive(Debug, Default, TypeUuid)]
+#[uuid = "3e9b6a50-1f7c-4d28-b4a3-6c8e0d2f5a17"]
+pub struct JsonAtlasImporter;
+
+impl Importer for JsonAtlasImporter {
+    type State = SpriteSheetImporterState;
+    type Options = SpriteSheetImporterOptions;
+
+    fn version_static() -> u32 {
+        1
+    }
+
+    fn version(&self) -> u32 {
+        Self::version_static()
+    }
+
+    fn import(
+        &self,
+        _op: &mut ImportOp,
+        source: &mut dyn Read,
+        _options: &Self::Options,
+        state: &mut Self::State,
+    ) -> atelier_importer::Result<ImporterValue> {
+        let atlas: JsonAtlas = serde_json::from_slice(&read_source(source)?)
+            .map_err(|e| atelier_importer::Error::Boxed(Box::new(e)))?;
+        let sprites = atlas.sprite_list().map_err(|e| {
+            atelier_importer::Error::Boxed(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                e,
+            )))
+        })?;
+        Ok(state.assets(&atlas.meta.image, Sprites::List(sprites)))
+    }
+}
+
+amethyst_assets::inventory::submit! {
+    #![crate = amethyst_assets]
+    amethyst_assets::SourceFileImporter {
+        extension: ".atlas",
+        instantiator: || Box::new(JsonAtlasImporter),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_atlas_keeps_frame_order() {
+        let atlas: JsonAtlas = serde_json::from_str(
+            r#"{
+                "frames": {
+                    "bat 10.aseprite": { "frame": { "x": 32, "y": 0, "w": 32, "h": 16 }, "duration": 100 },
+                    "bat 2.aseprite": { "frame": { "x": 0, "y": 0, "w": 32, "h": 16 }, "duration": 100 }
+                },
+                "meta": { "app": "http://www.aseprite.org/", "image": "bat.png", "size": { "w": 64, "h": 16 } }
+            }"#,
+        )
+        .unwrap();
+
+        let list = atlas.sprite_list().unwrap();
+        assert_eq!((64, 16), (list.texture_width, list.texture_height));
+        assert_eq!(
+            vec![(32, 0), (0, 0)],
+            list.sprites.iter().map(|s| (s.x, s.y)).collect::<Vec<_>>()
+        );
+        assert_eq!("bat.png", atlas.meta.image);
+    }
+
+    #[test]
+    fn trimmed_frames_are_offset() {
+        let atlas: JsonAtlas = serde_json::from_str(
+            r#"{
+                "frames": [
+                    {
+                        "filename": "hero.png",
+                        "frame": { "x": 0, "y": 0, "w": 10, "h": 20 },
+                        "rotated": false,
+                        "trimmed": true,
+                        "spriteSourceSize": { "x": 20, "y": 2, "w": 10, "h": 20 },
+                        "sourceSize": { "w": 40, "h": 40 }
+                    }
+                ],
+                "meta": { "image": "atlas.png", "size": { "w": 16, "h": 32 } }
+            }"#,
+        )
+        .unwrap();
+
+        let list = atlas.sprite_list().unwrap();
+        assert_eq!(Some([-5.0, -8.0]), list.sprites[0].offsets);
+    }
+
+    #[test]
+    fn rotated_frames_are_rejected() {
+        let atlas: JsonAtlas = serde_json::from_str(
+            r#"{
+                "frames": [{ "frame": { "x": 0, "y": 0, "w": 8, "h": 8 }, "rotated": true }],
+                "meta": { "image": "atlas.png", "size": { "w": 8, "h": 8 } }
+            }"#,
+        )
+        .unwrap();
+
+        assert!(atlas.sprite_list().is_err());
+    }
+
+    #[test]
+    fn importers_are_registered() {
+        let extensions = amethyst_assets::inventory::iter::<amethyst_assets::SourceFileImporter>
+            .into_iter()
+            .map(|importer| importer.extension)
+            .collect::<Vec<_>>();
+        assert!(extensions.contains(&".spritesheet"));
+        assert!(extensions.contains(&".atlas"));
+        assert!(!extensions.contains(&".json"));
+    }
+
+    #[test]
+    fn sprite_sheets_are_validated_with_their_texture() {
+        use amethyst_assets::{AssetDaemonConfig, AssetValidator};
+
+        let dir = std::env::temp_dir().join("amethyst_rendy_validate_sprite_sheet");
+        std::fs::create_dir_all(dir.join("sprites")).unwrap();
+        std::fs::create_dir_all(dir.join("texture")).unwrap();
+        image::RgbaImage::new(32, 32)
+            .save(dir.join("texture/bat.32x32.png"))
+            .unwrap();
+        std::fs::write(
+            dir.join("sprites/bat.spritesheet"),
+            r#"(
+                texture: "../texture/bat.32x32.png",
+                sprites: Grid((texture_width: 32, texture_height: 32, columns: 1)),
+            )"#,
+        )
+        .unwrap();
+
+        let report = AssetValidator::new(AssetDaemonConfig::default().with_asset_dir(&dir))
+            .validate()
+            .unwrap();
+
+        assert!(report.is_ok(), "{}", report);
+        assert_eq!(2, report.num_files());
+        assert_eq!(3, report.num_assets());
+    }
+}
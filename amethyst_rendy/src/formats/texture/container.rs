@@ -0,0 +1,262 @@
+//! Textures read from GPU texture containers, with their mip levels, array layers and faces.
+use std::num::NonZeroU8;
+
+use amethyst_error::{format_err, Error};
+use rendy::{
+    hal::{
+        format::Format,
+        image::{Filter, Kind, Lod, PackedColor, SamplerDesc, ViewKind, WrapMode},
+    },
+    texture::{MipLevels, TextureBuilder},
+};
+
+use crate::types::{TextureData, TextureMipLevel};
+
+/// Sampler of the textures read from containers, filtering between texels and mip levels.
+pub(crate) fn default_sampler_info() -> SamplerDesc {
+    SamplerDesc {
+        min_filter: Filter::Linear,
+        mag_filter: Filter::Linear,
+        mip_filter: Filter::Linear,
+        wrap_mode: (WrapMode::Tile, WrapMode::Tile, WrapMode::Tile),
+        lod_bias: Lod(0.0),
+        lod_range: std::ops::Range {
+            start: Lod(0.0),
+            end: Lod(1000.0),
+        },
+        comparison: None,
+        border: PackedColor(0),
+        normalized: true,
+        anisotropy_clamp: None,
+    }
+}
+
+/// Size of an image of a mip level in a texture format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LevelSize {
+    /// Size of the level in texels.
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    /// Size of the data of the level in texels, rounded up to whole blocks.
+    pub(crate) data_width: u32,
+    pub(crate) data_height: u32,
+    /// Size of the data of one array layer or face of the level.
+    pub(crate) bytes: usize,
+}
+
+impl LevelSize {
+    pub(crate) fn new(format: Format, width: u32, height: u32, level: u32) -> Result<Self, Error> {
+        let desc = format.surface_desc();
+        let (block_width, block_height) = (u32::from(desc.dim.0), u32::from(desc.dim.1));
+        let width = width.checked_shr(level).unwrap_or(0).max(1);
+        let height = height.checked_shr(level).unwrap_or(0).max(1);
+        let blocks = |texels: u32, block: u32| texels.checked_add(block - 1).map(|t| t / block);
+        let size = (|| {
+            let blocks_x = blocks(width, block_width)?;
+            let blocks_y = blocks(height, block_height)?;
+            Some(LevelSize {
+                width,
+                height,
+                data_width: blocks_x.checked_mul(block_width)?,
+                data_height: blocks_y.checked_mul(block_height)?,
+                bytes: (blocks_x as usize)
+                    .checked_mul(blocks_y as usize)?
+                    .checked_mul(usize::from(desc.bits / 8))?,
+            })
+        })();
+        size.ok_or_else(|| format_err!("Texture of {}x{} texels is too large", width, height))
+    }
+}
+
+/// Largest width and height of the textures read from containers, which is the largest image
+/// size of the Direct3D 11 and 12 backends and of most Vulkan and Metal devices.
+pub(crate) const MAX_SIZE: u32 = 16384;
+
+/// Checks the size of a texture before the size of its data is computed.
+pub(crate) fn check_size(width: u32, height: u32, layers: u32) -> Result<(), Error> {
+    if width > MAX_SIZE || height > MAX_SIZE {
+        return Err(format_err!(
+            "Texture of {}x{} texels is larger than {}x{}",
+            width,
+            height,
+            MAX_SIZE,
+            MAX_SIZE
+        ));
+    }
+    if layers > u32::from(u16::max_value()) {
+        return Err(format_err!("Texture has {} layers", layers));
+    }
+    Ok(())
+}
+
+/// Two-dimensional texture read from a container, which can be an array or a cubemap.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ContainerTexture {
+    pub(crate) format: Format,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    /// Number of array layers, which is 6 times the number of cubemaps for cubemaps.
+    pub(crate) layers: u16,
+    pub(crate) cubemap: bool,
+    /// Whether the texture is an array texture, even if it has a single layer.
+    pub(crate) array: bool,
+    /// Data of the mip levels, each holding the images of all layers of the level in order.
+    pub(crate) levels: Vec<Vec<u8>>,
+    /// Whether the mip levels are generated when the texture is loaded.
+    pub(crate) generate_mips: bool,
+}
+
+impl ContainerTexture {
+    /// Checks that the texture has data for all of its layers and levels.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        check_size(self.width, self.height, u32::from(self.layers))?;
+        if self.width == 0 || self.height == 0 || self.layers == 0 {
+            return Err(format_err!("Texture has no texels"));
+        }
+        if self.cubemap && self.layers % 6 != 0 {
+            return Err(format_err!("Cubemap texture does not have 6 faces"));
+        }
+        if self.levels.is_empty() || self.levels.len() > usize::from(u8::max_value()) {
+            return Err(format_err!("Texture has {} mip levels", self.levels.len()));
+        }
+        if self.generate_mips && self.format.surface_desc().dim != (1, 1) {
+            return Err(format_err!(
+                "Mip levels can not be generated for the block-compressed format {:?}",
+                self.format
+            ));
+        }
+        for (level, data) in self.levels.iter().enumerate() {
+            let size = LevelSize::new(self.format, self.width, self.height, level as u32)?;
+            let bytes = size
+                .bytes
+                .checked_mul(usize::from(self.layers))
+                .ok_or_else(|| format_err!("Mip level {} is too large", level))?;
+            if data.len() != bytes {
+                return Err(format_err!(
+                    "Mip level {} has {} bytes instead of {}",
+                    level,
+                    data.len(),
+                    bytes
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn view_kind(&self) -> ViewKind {
+        let faces = if self.cubemap { 6 } else { 1 };
+        match (self.cubemap, self.array || self.layers > faces) {
+            (true, false) => ViewKind::Cube,
+            (true, true) => ViewKind::CubeArray,
+            (false, false) => ViewKind::D2,
+            (false, true) => ViewKind::D2Array,
+        }
+    }
+
+    /// Creates the texture data, which passes the texels to the `TextureBuilder` in the format
+    /// of the container.
+    pub(crate) fn into_texture_data(self, sampler_info: SamplerDesc) -> TextureData {
+        let view_kind = self.view_kind();
+        let ContainerTexture {
+            format,
+            width,
+            height,
+            layers,
+            levels,
+            generate_mips,
+            ..
+        } = self;
+
+        let mip_levels = if generate_mips {
+            MipLevels::GenerateAuto
+        } else {
+            MipLevels::Levels(NonZeroU8::new(levels.len() as u8).expect("Texture has mip levels"))
+        };
+        let first = LevelSize::new(format, width, height, 0).expect("Texture is validated");
+        let mut levels = levels.into_iter();
+        let builder = TextureBuilder::new()
+            .with_kind(Kind::D2(width, height, layers, 1))
+            .with_view_kind(view_kind)
+            .with_data_width(first.data_width)
+            .with_data_height(first.data_height)
+            .with_sampler_info(sampler_info)
+            .with_mip_levels(mip_levels)
+            .with_raw_data(levels.next().expect("Texture has mip levels"), format);
+        let mip_levels = levels
+            .enumerate()
+            .map(|(index, data)| {
+                let level = index as u32 + 1;
+                let size =
+                    LevelSize::new(format, width, height, level).expect("Texture is validated");
+                TextureMipLevel {
+                    level: level as u8,
+                    width: size.width,
+                    height: size.height,
+                    data_width: size.data_width,
+                    data_height: size.data_height,
+                    data,
+                }
+            })
+            .collect();
+        TextureData(builder, mip_levels)
+    }
+}
+
+/// Reads little endian integers from a texture container.
+pub(crate) fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, Error> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| format_err!("Unexpected end of texture file"))
+}
+
+pub(crate) fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, Error> {
+    Ok(u64::from(read_u32(bytes, offset)?) | u64::from(read_u32(bytes, offset + 4)?) << 32)
+}
+
+/// Returns the data of a byte range of a texture container.
+pub(crate) fn slice(bytes: &[u8], offset: u64, len: u64) -> Result<&[u8], Error> {
+    let start = offset as usize;
+    start
+        .checked_add(len as usize)
+        .and_then(|end| bytes.get(start..end))
+        .ok_or_else(|| format_err!("Texture data is out of the bounds of the file"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compressed_level_sizes_round_up_to_blocks() {
+        let size = LevelSize::new(Format::Bc1RgbaUnorm, 16, 6, 2).unwrap();
+        assert_eq!(
+            size,
+            LevelSize {
+                width: 4,
+                height: 1,
+                data_width: 4,
+                data_height: 4,
+                bytes: 8,
+            }
+        );
+        assert_eq!(
+            LevelSize::new(Format::Bc3Unorm, 16, 16, 0).unwrap().bytes,
+            256
+        );
+        assert_eq!(
+            LevelSize::new(Format::Rgba8Unorm, 5, 3, 1).unwrap().bytes,
+            2 * 4
+        );
+    }
+
+    #[test]
+    fn oversized_textures_are_errors() {
+        let max = u32::max_value();
+        assert!(LevelSize::new(Format::Bc1RgbaUnorm, max, 4, 0).is_err());
+        assert!(LevelSize::new(Format::Bc1RgbaUnorm, 4, 4, 40).is_ok());
+        assert!(check_size(MAX_SIZE, MAX_SIZE, 1).is_ok());
+        assert!(check_size(MAX_SIZE + 1, 1, 1).is_err());
+        assert!(check_size(1, 1, max).is_err());
+    }
+}
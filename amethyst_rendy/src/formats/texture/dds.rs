@@ -0,0 +1,350 @@
+use amethyst_assets::Format;
+use amethyst_error::{format_err, Error};
+use rendy::hal::{self, format::Format as HalFormat, image::SamplerDesc};
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+use super::container::{check_size, default_sampler_info, read_u32, ContainerTexture, LevelSize};
+use crate::types::TextureData;
+
+/// 'Dds' (DirectDraw surface) texture format `Format` implementation.
+///
+/// Loads two-dimensional textures, texture arrays and cubemaps with their mip levels, passing
+/// uncompressed and BC texels to the `TextureBuilder` as they are. Volume textures are not
+/// supported.
+#[derive(Debug, Clone, Serialize, Deserialize, TypeUuid)]
+#[uuid = "a9f2c1b4-6e0d-4f43-8d0b-2e5c7b9a1f36"]
+pub struct DdsFormat {
+    /// Sampler of the texture.
+    pub sampler_info: SamplerDesc,
+    /// Loads the texels of the formats that do not give their color space as sRGB. These are the
+    /// `DXT1`, `DXT3` and `DXT5` formats and the uncompressed RGBA formats of the legacy header.
+    pub srgb: bool,
+}
+
+impl Default for DdsFormat {
+    fn default() -> Self {
+        DdsFormat {
+            sampler_info: default_sampler_info(),
+            srgb: true,
+        }
+    }
+}
+
+amethyst_assets::register_importer!(".dds", DdsFormat);
+impl Format<TextureData> for DdsFormat {
+    fn name(&self) -> &'static str {
+        "DDS"
+    }
+
+    fn import_simple(&self, bytes: Vec<u8>) -> Result<TextureData, Error> {
+        parse_dds(&bytes, self.srgb)
+            .map(|texture| texture.into_texture_data(self.sampler_info.clone()))
+    }
+}
+
+const MAGIC: &[u8] = b"DDS ";
+const HEADER_SIZE: usize = 128;
+const DX10_HEADER_SIZE: usize = 20;
+
+const DDSD_MIPMAPCOUNT: u32 = 0x2_0000;
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_CUBEMAP_ALLFACES: u32 = 0xFC00;
+const DDSCAPS2_VOLUME: u32 = 0x20_0000;
+const DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+const D3D10_RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+
+fn four_cc(code: &[u8; 4]) -> u32 {
+    u32::from_le_bytes(*code)
+}
+
+fn parse_dds(bytes: &[u8], srgb: bool) -> Result<ContainerTexture, Error> {
+    if !bytes.starts_with(MAGIC) {
+        return Err(format_err!("File is not a DDS file"));
+    }
+    let flags = read_u32(bytes, 8)?;
+    let height = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 16)?;
+    let mip_count = if flags & DDSD_MIPMAPCOUNT != 0 {
+        read_u32(bytes, 28)?.max(1)
+    } else {
+        1
+    };
+    let pixel_flags = read_u32(bytes, 80)?;
+    let fourcc = read_u32(bytes, 84)?;
+    let caps2 = read_u32(bytes, 112)?;
+
+    if caps2 & DDSCAPS2_VOLUME != 0 {
+        return Err(format_err!("DDS volume textures are not supported"));
+    }
+
+    let (format, layers, cubemap, array, data_offset) =
+        if pixel_flags & DDPF_FOURCC != 0 && fourcc == four_cc(b"DX10") {
+            let format = dxgi_format_to_hal(read_u32(bytes, HEADER_SIZE)?)?;
+            if read_u32(bytes, HEADER_SIZE + 4)? != D3D10_RESOURCE_DIMENSION_TEXTURE2D {
+                return Err(format_err!(
+                    "Only two-dimensional DDS textures are supported"
+                ));
+            }
+            let cubemap = read_u32(bytes, HEADER_SIZE + 8)? & DDS_RESOURCE_MISC_TEXTURECUBE != 0;
+            let array_size = read_u32(bytes, HEADER_SIZE + 12)?.max(1);
+            let layers = if cubemap {
+                array_size
+                    .checked_mul(6)
+                    .ok_or_else(|| format_err!("DDS texture has {} cubemaps", array_size))?
+            } else {
+                array_size
+            };
+            (
+                format,
+                layers,
+                cubemap,
+                array_size > 1,
+                HEADER_SIZE + DX10_HEADER_SIZE,
+            )
+        } else {
+            let format = legacy_format_to_hal(bytes, pixel_flags, fourcc, srgb)?;
+            let cubemap = caps2 & DDSCAPS2_CUBEMAP != 0;
+            if cubemap && caps2 & DDSCAPS2_CUBEMAP_ALLFACES != DDSCAPS2_CUBEMAP_ALLFACES {
+                return Err(format_err!(
+                    "DDS cubemaps without all 6 faces are not supported"
+                ));
+            }
+            let layers = if cubemap { 6 } else { 1 };
+            (format, layers, cubemap, false, HEADER_SIZE)
+        };
+    check_size(width, height, layers)?;
+    if mip_count > u32::from(u8::max_value()) {
+        return Err(format_err!("DDS texture has {} mip levels", mip_count));
+    }
+
+    // The file stores all mip levels of a layer after the other, but the texture is uploaded one
+    // mip level at a time.
+    let sizes = (0..mip_count)
+        .map(|level| LevelSize::new(format, width, height, level).map(|size| size.bytes))
+        .collect::<Result<Vec<_>, Error>>()?;
+    let layer_size = sizes.iter().sum::<usize>();
+    let data = bytes
+        .get(data_offset..)
+        .filter(|data| {
+            layer_size
+                .checked_mul(layers as usize)
+                .map_or(false, |size| data.len() >= size)
+        })
+        .ok_or_else(|| format_err!("DDS file is too short for its texture"))?;
+    let mut levels = sizes
+        .iter()
+        .map(|size| Vec::with_capacity(size * layers as usize))
+        .collect::<Vec<_>>();
+    for layer in data.chunks_exact(layer_size).take(layers as usize) {
+        let mut offset = 0;
+        for (level, size) in levels.iter_mut().zip(&sizes) {
+            level.extend_from_slice(&layer[offset..offset + size]);
+            offset += size;
+        }
+    }
+
+    let texture = ContainerTexture {
+        format,
+        width,
+        height,
+        layers: layers as u16,
+        cubemap,
+        array,
+        levels,
+        generate_mips: false,
+    };
+    texture.validate()?;
+    Ok(texture)
+}
+
+fn legacy_format_to_hal(
+    bytes: &[u8],
+    pixel_flags: u32,
+    fourcc: u32,
+    srgb: bool,
+) -> Result<HalFormat, Error> {
+    use hal::format::Format::*;
+    let pick = |unorm, srgb_format| if srgb { srgb_format } else { unorm };
+
+    if pixel_flags & DDPF_FOURCC != 0 {
+        return Ok(match &fourcc.to_le_bytes() {
+            b"DXT1" => pick(Bc1RgbaUnorm, Bc1RgbaSrgb),
+            b"DXT3" => pick(Bc2Unorm, Bc2Srgb),
+            b"DXT5" => pick(Bc3Unorm, Bc3Srgb),
+            b"ATI1" | b"BC4U" => Bc4Unorm,
+            b"BC4S" => Bc4Snorm,
+            b"ATI2" | b"BC5U" => Bc5Unorm,
+            b"BC5S" => Bc5Snorm,
+            code => {
+                return Err(format_err!(
+                    "Unsupported DDS format `{}`",
+                    String::from_utf8_lossy(code)
+                ))
+            }
+        });
+    }
+
+    let bit_count = read_u32(bytes, 88)?;
+    let masks = (
+        read_u32(bytes, 92)?,
+        read_u32(bytes, 96)?,
+        read_u32(bytes, 100)?,
+        if pixel_flags & DDPF_ALPHAPIXELS != 0 {
+            read_u32(bytes, 104)?
+        } else {
+            0
+        },
+    );
+    match (pixel_flags & DDPF_RGB != 0, bit_count, masks) {
+        (true, 32, (0xFF, 0xFF00, 0xFF_0000, _)) => Ok(pick(Rgba8Unorm, Rgba8Srgb)),
+        (true, 32, (0xFF_0000, 0xFF00, 0xFF, _)) => Ok(pick(Bgra8Unorm, Bgra8Srgb)),
+        _ => Err(format_err!(
+            "Unsupported uncompressed DDS format with {} bits and masks {:x?}",
+            bit_count,
+            masks
+        )),
+    }
+}
+
+fn dxgi_format_to_hal(dxgi_format: u32) -> Result<HalFormat, Error> {
+    use hal::format::Format::*;
+    Ok(match dxgi_format {
+        2 => Rgba32Sfloat,
+        10 => Rgba16Sfloat,
+        28 => Rgba8Unorm,
+        29 => Rgba8Srgb,
+        34 => Rg16Sfloat,
+        41 => R32Sfloat,
+        49 => Rg8Unorm,
+        54 => R16Sfloat,
+        61 => R8Unorm,
+        71 => Bc1RgbaUnorm,
+        72 => Bc1RgbaSrgb,
+        74 => Bc2Unorm,
+        75 => Bc2Srgb,
+        77 => Bc3Unorm,
+        78 => Bc3Srgb,
+        80 => Bc4Unorm,
+        81 => Bc4Snorm,
+        83 => Bc5Unorm,
+        84 => Bc5Snorm,
+        87 => Bgra8Unorm,
+        91 => Bgra8Srgb,
+        95 => Bc6hUfloat,
+        96 => Bc6hSfloat,
+        98 => Bc7Unorm,
+        99 => Bc7Srgb,
+        _ => return Err(format_err!("Unsupported DXGI format {}", dxgi_format)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(width: u32, height: u32, mip_count: u32, fourcc: &[u8; 4], caps2: u32) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.resize(HEADER_SIZE, 0);
+        let mut write = |offset: usize, value: u32| {
+            bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        };
+        write(4, 124);
+        write(8, 0x1007 | DDSD_MIPMAPCOUNT);
+        write(12, height);
+        write(16, width);
+        write(28, mip_count);
+        write(76, 32);
+        write(80, DDPF_FOURCC);
+        write(84, four_cc(fourcc));
+        write(112, caps2);
+        bytes
+    }
+
+    #[test]
+    fn dxt1_mip_chain() {
+        let mut bytes = header(8, 4, 3, b"DXT1", 0);
+        // 8x4, 4x2 and 2x1 levels of 2, 1 and 1 blocks
+        bytes.extend_from_slice(&[1; 16]);
+        bytes.extend_from_slice(&[2; 8]);
+        bytes.extend_from_slice(&[3; 8]);
+        let texture = parse_dds(&bytes, true).unwrap();
+        assert_eq!(
+            texture,
+            ContainerTexture {
+                format: HalFormat::Bc1RgbaSrgb,
+                width: 8,
+                height: 4,
+                layers: 1,
+                cubemap: false,
+                array: false,
+                levels: vec![vec![1; 16], vec![2; 8], vec![3; 8]],
+                generate_mips: false,
+            }
+        );
+        assert_eq!(
+            parse_dds(&bytes, false).unwrap().format,
+            HalFormat::Bc1RgbaUnorm
+        );
+    }
+
+    #[test]
+    fn cubemap_faces_are_grouped_by_level() {
+        let mut bytes = header(
+            4,
+            4,
+            2,
+            b"DXT5",
+            DDSCAPS2_CUBEMAP | DDSCAPS2_CUBEMAP_ALLFACES,
+        );
+        for face in 0..6 {
+            bytes.extend_from_slice(&[face; 16]);
+            bytes.extend_from_slice(&[face + 10; 16]);
+        }
+        let texture = parse_dds(&bytes, false).unwrap();
+        assert_eq!(texture.format, HalFormat::Bc3Unorm);
+        assert_eq!(texture.layers, 6);
+        assert!(texture.cubemap);
+        assert_eq!(texture.levels[0][16..32], [1; 16]);
+        assert_eq!(texture.levels[1][16..32], [11; 16]);
+    }
+
+    #[test]
+    fn dx10_texture_array() {
+        let mut bytes = header(2, 2, 1, b"DX10", 0);
+        for value in &[28, D3D10_RESOURCE_DIMENSION_TEXTURE2D, 0, 3, 0] {
+            bytes.extend_from_slice(&u32::to_le_bytes(*value));
+        }
+        bytes.extend_from_slice(&[0; 3 * 2 * 2 * 4]);
+        let texture = parse_dds(&bytes, true).unwrap();
+        assert_eq!(texture.format, HalFormat::Rgba8Unorm);
+        assert_eq!(texture.layers, 3);
+        assert!(texture.array);
+        assert_eq!(texture.levels.len(), 1);
+    }
+
+    #[test]
+    fn oversized_header_is_an_error() {
+        let mut bytes = header(u32::max_value(), u32::max_value(), 1, b"DXT1", 0);
+        bytes.extend_from_slice(&[0; 8]);
+        assert!(parse_dds(&bytes, true).is_err());
+
+        let mut bytes = header(4, 4, 1, b"DX10", 0);
+        let array_size = u32::max_value();
+        for value in &[71, D3D10_RESOURCE_DIMENSION_TEXTURE2D, 4, array_size, 0] {
+            bytes.extend_from_slice(&u32::to_le_bytes(*value));
+        }
+        bytes.extend_from_slice(&[0; 8]);
+        assert!(parse_dds(&bytes, true).is_err());
+    }
+
+    #[test]
+    fn truncated_file_is_an_error() {
+        let mut bytes = header(4, 4, 1, b"DXT1", 0);
+        bytes.extend_from_slice(&[0; 7]);
+        assert!(parse_dds(&bytes, true).is_err());
+    }
+}
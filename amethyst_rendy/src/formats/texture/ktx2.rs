@@ -0,0 +1,272 @@
+use amethyst_assets::Format;
+use amethyst_error::{format_err, Error};
+use rendy::hal::{self, image::SamplerDesc};
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+use super::container::{
+    check_size, default_sampler_info, read_u32, read_u64, slice, ContainerTexture,
+};
+use crate::types::TextureData;
+
+/// 'Ktx2' (Khronos texture 2.0) texture format `Format` implementation.
+///
+/// Loads two-dimensional textures, texture arrays and cubemaps with their mip levels, passing
+/// uncompressed, BC and ASTC texels to the `TextureBuilder` as they are. The mip levels of
+/// uncompressed files without levels are generated on load. Supercompressed files are not
+/// supported.
+#[derive(Debug, Clone, Serialize, Deserialize, TypeUuid)]
+#[uuid = "3ac32e37-0b2b-4a6f-9a38-4c1b3f1f2d71"]
+pub struct Ktx2Format {
+    /// Sampler of the texture.
+    pub sampler_info: SamplerDesc,
+}
+
+impl Default for Ktx2Format {
+    fn default() -> Self {
+        Ktx2Format {
+            sampler_info: default_sampler_info(),
+        }
+    }
+}
+
+amethyst_assets::register_importer!(".ktx2", Ktx2Format);
+impl Format<TextureData> for Ktx2Format {
+    fn name(&self) -> &'static str {
+        "KTX2"
+    }
+
+    fn import_simple(&self, bytes: Vec<u8>) -> Result<TextureData, Error> {
+        parse_ktx2(&bytes).map(|texture| texture.into_texture_data(self.sampler_info.clone()))
+    }
+}
+
+const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const LEVEL_INDEX: usize = 80;
+
+fn parse_ktx2(bytes: &[u8]) -> Result<ContainerTexture, Error> {
+    if !bytes.starts_with(&IDENTIFIER) {
+        return Err(format_err!("File is not a KTX2 file"));
+    }
+    let header = |index: usize| read_u32(bytes, 12 + index * 4);
+    let vk_format = header(0)?;
+    let width = header(2)?;
+    let height = header(3)?;
+    let depth = header(4)?;
+    let layer_count = header(5)?;
+    let face_count = header(6)?;
+    let level_count = header(7)?;
+    let supercompression = header(8)?;
+
+    if supercompression != 0 {
+        return Err(format_err!("Supercompressed KTX2 files are not supported"));
+    }
+    if depth > 1 {
+        return Err(format_err!("3D KTX2 textures are not supported"));
+    }
+    if height == 0 {
+        return Err(format_err!("1D KTX2 textures are not supported"));
+    }
+    if face_count != 1 && face_count != 6 {
+        return Err(format_err!("KTX2 texture has {} faces", face_count));
+    }
+    let format = vk_format_to_hal(vk_format)?;
+    let layers = layer_count
+        .max(1)
+        .checked_mul(face_count)
+        .ok_or_else(|| format_err!("KTX2 texture has {} layers", layer_count))?;
+    check_size(width, height, layers)?;
+
+    // The level index lists the levels from the largest one
+    let levels = (0..level_count.max(1) as usize)
+        .map(|level| {
+            let entry = LEVEL_INDEX + level * 24;
+            slice(bytes, read_u64(bytes, entry)?, read_u64(bytes, entry + 8)?).map(<[u8]>::to_vec)
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let texture = ContainerTexture {
+        format,
+        width,
+        height,
+        layers: layers as u16,
+        cubemap: face_count == 6,
+        array: layer_count > 0,
+        levels,
+        generate_mips: level_count == 0,
+    };
+    texture.validate()?;
+    Ok(texture)
+}
+
+fn vk_format_to_hal(vk_format: u32) -> Result<hal::format::Format, Error> {
+    use hal::format::Format::*;
+    Ok(match vk_format {
+        9 => R8Unorm,
+        16 => Rg8Unorm,
+        37 => Rgba8Unorm,
+        43 => Rgba8Srgb,
+        44 => Bgra8Unorm,
+        50 => Bgra8Srgb,
+        76 => R16Sfloat,
+        83 => Rg16Sfloat,
+        97 => Rgba16Sfloat,
+        100 => R32Sfloat,
+        103 => Rg32Sfloat,
+        109 => Rgba32Sfloat,
+        131 => Bc1RgbUnorm,
+        132 => Bc1RgbSrgb,
+        133 => Bc1RgbaUnorm,
+        134 => Bc1RgbaSrgb,
+        135 => Bc2Unorm,
+        136 => Bc2Srgb,
+        137 => Bc3Unorm,
+        138 => Bc3Srgb,
+        139 => Bc4Unorm,
+        140 => Bc4Snorm,
+        141 => Bc5Unorm,
+        142 => Bc5Snorm,
+        143 => Bc6hUfloat,
+        144 => Bc6hSfloat,
+        145 => Bc7Unorm,
+        146 => Bc7Srgb,
+        157 => Astc4x4Unorm,
+        158 => Astc4x4Srgb,
+        159 => Astc5x4Unorm,
+        160 => Astc5x4Srgb,
+        161 => Astc5x5Unorm,
+        162 => Astc5x5Srgb,
+        163 => Astc6x5Unorm,
+        164 => Astc6x5Srgb,
+        165 => Astc6x6Unorm,
+        166 => Astc6x6Srgb,
+        167 => Astc8x5Unorm,
+        168 => Astc8x5Srgb,
+        169 => Astc8x6Unorm,
+        170 => Astc8x6Srgb,
+        171 => Astc8x8Unorm,
+        172 => Astc8x8Srgb,
+        173 => Astc10x5Unorm,
+        174 => Astc10x5Srgb,
+        175 => Astc10x6Unorm,
+        176 => Astc10x6Srgb,
+        177 => Astc10x8Unorm,
+        178 => Astc10x8Srgb,
+        179 => Astc10x10Unorm,
+        180 => Astc10x10Srgb,
+        181 => Astc12x10Unorm,
+        182 => Astc12x10Srgb,
+        183 => Astc12x12Unorm,
+        184 => Astc12x12Srgb,
+        0 => {
+            return Err(format_err!(
+                "KTX2 files without a Vulkan format are not supported"
+            ))
+        }
+        _ => return Err(format_err!("Unsupported KTX2 Vulkan format {}", vk_format)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a KTX2 file with the level data placed after the level index.
+    fn ktx2(vk_format: u32, size: u32, layers: u32, faces: u32, levels: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = IDENTIFIER.to_vec();
+        for value in &[
+            vk_format,
+            1,
+            size,
+            size,
+            0,
+            layers,
+            faces,
+            levels.len() as u32,
+            0,
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.resize(LEVEL_INDEX, 0);
+        let mut offset = (LEVEL_INDEX + levels.len().max(1) * 24) as u64;
+        for level in levels {
+            let len = level.len() as u64;
+            for value in &[offset, len, len] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            offset += len;
+        }
+        for level in levels {
+            bytes.extend_from_slice(level);
+        }
+        bytes
+    }
+
+    #[test]
+    fn compressed_mip_chain() {
+        // 8x8 BC1 has 2x2, 1x1 and 1x1 blocks of 8 bytes
+        let levels = vec![vec![1; 32], vec![2; 8], vec![3; 8], vec![4; 8]];
+        let texture = parse_ktx2(&ktx2(133, 8, 0, 1, &levels)).unwrap();
+        assert_eq!(
+            texture,
+            ContainerTexture {
+                format: hal::format::Format::Bc1RgbaUnorm,
+                width: 8,
+                height: 8,
+                layers: 1,
+                cubemap: false,
+                array: false,
+                levels,
+                generate_mips: false,
+            }
+        );
+    }
+
+    #[test]
+    fn cubemap_faces() {
+        let levels = vec![vec![0; 6 * 2 * 2 * 4], vec![0; 6 * 4]];
+        let texture = parse_ktx2(&ktx2(43, 2, 0, 6, &levels)).unwrap();
+        assert_eq!(texture.format, hal::format::Format::Rgba8Srgb);
+        assert_eq!(texture.layers, 6);
+        assert!(texture.cubemap);
+        assert!(!texture.array);
+        assert_eq!(texture.levels.len(), 2);
+    }
+
+    #[test]
+    fn uncompressed_file_without_levels_generates_mips() {
+        let levels = vec![vec![0; 3 * 4 * 4 * 4]];
+        let mut bytes = ktx2(37, 4, 3, 1, &levels);
+        // Level count of 0
+        bytes[12 + 7 * 4] = 0;
+        let texture = parse_ktx2(&bytes).unwrap();
+        assert_eq!(texture.layers, 3);
+        assert!(texture.array);
+        assert!(texture.generate_mips);
+    }
+
+    #[test]
+    fn truncated_level_is_an_error() {
+        let mut bytes = ktx2(37, 2, 0, 1, &[vec![0; 16]]);
+        bytes.pop();
+        assert!(parse_ktx2(&bytes).is_err());
+    }
+
+    #[test]
+    fn oversized_header_is_an_error() {
+        let bytes = ktx2(133, u32::max_value(), 0, 1, &[vec![0; 8]]);
+        assert!(parse_ktx2(&bytes).is_err());
+        let mut bytes = ktx2(37, 2, 0, 6, &[vec![0; 16]]);
+        bytes[12 + 5 * 4..12 + 6 * 4].copy_from_slice(&u32::max_value().to_le_bytes());
+        assert!(parse_ktx2(&bytes).is_err());
+    }
+
+    #[test]
+    fn supercompressed_file_is_an_error() {
+        let mut bytes = ktx2(37, 2, 0, 1, &[vec![0; 16]]);
+        bytes[12 + 8 * 4] = 1;
+        assert!(parse_ktx2(&bytes).is_err());
+    }
+}
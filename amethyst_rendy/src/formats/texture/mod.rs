@@ -1,4 +1,6 @@
 //! Texture formats implementation.
+pub use self::{dds::DdsFormat, ktx2::Ktx2Format};
+
 use amethyst_assets::Format;
 use amethyst_error::Error;
 use rendy::{
@@ -17,13 +19,17 @@ use type_uuid::TypeUuid;
 
 use crate::types::TextureData;
 
+mod container;
+mod dds;
+mod ktx2;
+
 /// Image format description newtype wrapper for `ImageTextureConfig` from rendy.
 ///
 /// # Example Usage
 /// ```ignore
 ///
-///    let loader = res.fetch_mut::<DefaultLoader>();
-///    let texture_storage = res.fetch_mut::<AssetStorage<Texture>>();
+///    let loader = resources.get::<DefaultLoader>().unwrap();
+///    let texture_queue = resources.get::<ProcessingQueue<TextureData>>().unwrap();
 ///
 ///    let texture_builder = TextureBuilder::new()
 ///        .with_data_width(handle.width)
@@ -46,7 +52,7 @@ use crate::types::TextureData;
 ///        })
 ///        .with_raw_data(handle.pixels, Format::Rgba8Unorm);
 ///
-///    let tex: Handle<Texture> = loader.load_from_data(TextureData(texture_builder), (), &texture_storage);
+///    let tex: Handle<Texture> = loader.load_from_data(TextureData::from(texture_builder), (), &texture_queue);
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize, TypeUuid)]
 #[serde(transparent)]
@@ -85,6 +91,16 @@ impl Default for ImageFormat {
     }
 }
 
+impl ImageFormat {
+    /// Generates the mip levels of the image when it is loaded, and filters between them when
+    /// the texture is sampled.
+    pub fn with_mipmaps(mut self) -> Self {
+        self.0.generate_mips = true;
+        self.0.sampler_info.mip_filter = Filter::Linear;
+        self
+    }
+}
+
 amethyst_assets::register_importer!(".jpg", ImageFormat);
 amethyst_assets::register_importer!(".png", ImageFormat);
 amethyst_assets::register_importer!(".tga", ImageFormat);
@@ -102,7 +102,7 @@ pub use rendy::{
 pub use crate::{
     bundle::{RenderPlugin, RenderingBundle},
     camera::{ActiveCamera, Camera},
-    formats::texture::ImageFormat,
+    formats::texture::{DdsFormat, ImageFormat, Ktx2Format},
     mtl::{Material, MaterialDefaults},
     plugins::*,
     sprite::{Sprite, SpriteRender, SpriteSheet},
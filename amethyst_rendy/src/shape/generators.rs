@@ -0,0 +1,219 @@
+//! Generators of the shapes `genmesh` does not provide.
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+use amethyst_core::math::Vector3;
+
+use super::{internal_vertex, InternalVertexData};
+
+/// Triangulates a grid of quads, with the corners of the cells returned by `vertex`, indexed
+/// along the first and second axis of the grid.
+///
+/// The triangles are counter-clockwise when the first axis points right and the second axis
+/// points up.
+pub(super) fn quads<F>(first: usize, second: usize, vertex: F) -> Vec<InternalVertexData>
+where
+    F: Fn(usize, usize) -> InternalVertexData,
+{
+    let mut vertices = Vec::with_capacity(first * second * 6);
+    for i in 0..first {
+        for j in 0..second {
+            vertices.extend_from_slice(&[
+                vertex(i, j),
+                vertex(i + 1, j),
+                vertex(i + 1, j + 1),
+                vertex(i, j),
+                vertex(i + 1, j + 1),
+                vertex(i, j + 1),
+            ]);
+        }
+    }
+    vertices
+}
+
+/// Point of the profile of a surface of revolution around the Z axis.
+struct ProfilePoint {
+    radius: f32,
+    z: f32,
+    /// Normal in the plane of the profile, outwards from the axis and along the Z axis.
+    normal: (f32, f32),
+    /// Texture coordinate along the profile.
+    v: f32,
+}
+
+/// Revolves a profile, ordered along the Z axis, around the Z axis.
+///
+/// Consecutive points at the same place start a hard edge, and points on the axis close the
+/// surface.
+fn revolve(
+    profile: &[ProfilePoint],
+    segments: usize,
+    scale: Option<(f32, f32, f32)>,
+) -> Vec<InternalVertexData> {
+    let segments = segments.max(3);
+    let vertex = |point: &ProfilePoint, i: usize| {
+        let u = i as f32 / segments as f32;
+        let (sin, cos) = (2. * PI * u).sin_cos();
+        internal_vertex(
+            Vector3::new(point.radius * cos, point.radius * sin, point.z),
+            Vector3::new(point.normal.0 * cos, point.normal.0 * sin, point.normal.1),
+            [u, point.v],
+            scale,
+        )
+    };
+
+    let mut vertices = Vec::new();
+    for ring in profile.windows(2) {
+        let (lower, upper) = (&ring[0], &ring[1]);
+        #[allow(clippy::float_cmp)]
+        let hard_edge = lower.radius == upper.radius && lower.z == upper.z;
+        if hard_edge {
+            continue;
+        }
+        for i in 0..segments {
+            if lower.radius > 0. {
+                vertices.extend_from_slice(&[
+                    vertex(lower, i),
+                    vertex(lower, i + 1),
+                    vertex(upper, i + 1),
+                ]);
+            }
+            if upper.radius > 0. {
+                vertices.extend_from_slice(&[
+                    vertex(lower, i),
+                    vertex(upper, i + 1),
+                    vertex(upper, i),
+                ]);
+            }
+        }
+    }
+    vertices
+}
+
+pub(super) fn capsule(
+    radius: f32,
+    length: f32,
+    segments: usize,
+    rings: usize,
+    scale: Option<(f32, f32, f32)>,
+) -> Vec<InternalVertexData> {
+    let rings = rings.max(1);
+    let half_length = length / 2.;
+    let total_length = length + 2. * radius;
+    let mut profile = Vec::with_capacity(2 * (rings + 1));
+    for &(center, start_angle) in &[(-half_length, -FRAC_PI_2), (half_length, 0.)] {
+        for i in 0..=rings {
+            let (sin, cos) = (start_angle + FRAC_PI_2 * i as f32 / rings as f32).sin_cos();
+            let z = center + radius * sin;
+            profile.push(ProfilePoint {
+                radius: (radius * cos).max(0.),
+                z,
+                normal: (cos, sin),
+                v: (z + half_length + radius) / total_length,
+            });
+        }
+    }
+    revolve(&profile, segments, scale)
+}
+
+pub(super) fn arrow(
+    shaft_radius: f32,
+    head_radius: f32,
+    head_length: f32,
+    segments: usize,
+    scale: Option<(f32, f32, f32)>,
+) -> Vec<InternalVertexData> {
+    let shaft_length = 1. - head_length;
+    let slope = (head_length * head_length + head_radius * head_radius).sqrt();
+    let head_normal = (head_length / slope, head_radius / slope);
+    let point = |radius, z, normal| ProfilePoint {
+        radius,
+        z,
+        normal,
+        v: z,
+    };
+    revolve(
+        &[
+            point(0., 0., (0., -1.)),
+            point(shaft_radius, 0., (0., -1.)),
+            point(shaft_radius, 0., (1., 0.)),
+            point(shaft_radius, shaft_length, (1., 0.)),
+            point(shaft_radius, shaft_length, (0., -1.)),
+            point(head_radius, shaft_length, (0., -1.)),
+            point(head_radius, shaft_length, head_normal),
+            point(0., 1., head_normal),
+        ],
+        segments,
+        scale,
+    )
+}
+
+pub(super) fn rounded_box(
+    radius: f32,
+    segments: usize,
+    scale: Option<(f32, f32, f32)>,
+) -> Vec<InternalVertexData> {
+    let radius = radius.max(0.).min(1.);
+    let segments = segments.max(1);
+    let inner = 1. - radius;
+
+    // Each face holds half of the arc of its edges, with the points spaced evenly along the arc
+    let mut coords: Vec<f32> = Vec::with_capacity(2 * (segments + 1));
+    let arc = |i: usize| radius * (FRAC_PI_4 * i as f32 / segments as f32).tan();
+    let corner = (0..=segments).rev().map(|i| -inner - arc(i));
+    let far_corner = (0..=segments).map(|i| inner + arc(i));
+    for coord in corner.chain(far_corner) {
+        if coords.last().map_or(true, |last| coord > *last) {
+            coords.push(coord);
+        }
+    }
+
+    let faces = [
+        (Vector3::x(), Vector3::y(), Vector3::z()),
+        (-Vector3::x(), Vector3::z(), Vector3::y()),
+        (Vector3::y(), Vector3::z(), Vector3::x()),
+        (-Vector3::y(), Vector3::x(), Vector3::z()),
+        (Vector3::z(), Vector3::x(), Vector3::y()),
+        (-Vector3::z(), Vector3::y(), Vector3::x()),
+    ];
+    let cells = coords.len() - 1;
+    let mut vertices = Vec::with_capacity(faces.len() * cells * cells * 6);
+    for (face_normal, u, v) in faces.iter() {
+        vertices.extend(quads(cells, cells, |i, j| {
+            let (a, b) = (coords[i], coords[j]);
+            let point: Vector3<f32> = face_normal + u * a + v * b;
+            let center = point.map(|c| c.max(-inner).min(inner));
+            let offset = point - center;
+            let normal = if offset.norm_squared() > 0. {
+                offset.normalize()
+            } else {
+                *face_normal
+            };
+            internal_vertex(
+                center + normal * radius,
+                normal,
+                [(a + 1.) / 2., (b + 1.) / 2.],
+                scale,
+            )
+        }));
+    }
+    vertices
+}
+
+pub(super) fn grid(
+    x: usize,
+    y: usize,
+    tile_x: f32,
+    tile_y: f32,
+    scale: Option<(f32, f32, f32)>,
+) -> Vec<InternalVertexData> {
+    let (x, y) = (x.max(1), y.max(1));
+    quads(x, y, |i, j| {
+        let (u, v) = (i as f32 / x as f32, j as f32 / y as f32);
+        internal_vertex(
+            Vector3::new(u * 2. - 1., v * 2. - 1., 0.),
+            Vector3::z(),
+            [u * tile_x, v * tile_y],
+            scale,
+        )
+    })
+}
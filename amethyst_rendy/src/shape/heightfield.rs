@@ -0,0 +1,269 @@
+use std::convert::TryFrom;
+
+use amethyst_core::math::Vector3;
+use amethyst_error::{format_err, Error};
+use rendy::mesh::Position;
+use serde::{Deserialize, Serialize};
+
+use super::{generators::quads, InternalVertexData};
+use crate::formats::mesh::calculate_normals;
+
+/// Heights of a terrain sampled on a regular grid, generated into a mesh by `Shape::Heightfield`.
+///
+/// The samples are spread over [-1, +1] along the X and Z axes, with the first row at -1 along
+/// the Z axis, and the heights are the Y coordinates of the vertices. The texture spans the
+/// whole terrain, and the tangents of the mesh point along its U axis, which is the X axis.
+///
+/// # Example
+/// ```ignore
+/// let heightfield = Heightfield::from_image(&std::fs::read("terrain.png")?)?.with_lod_step(4);
+/// let mesh = Shape::Heightfield(heightfield)
+///     .generate::<Vec<PosNormTangTex>>(Some((100.0, 20.0, 100.0)));
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "HeightfieldData")]
+pub struct Heightfield {
+    columns: usize,
+    rows: usize,
+    heights: Vec<f32>,
+    lod_step: usize,
+}
+
+/// Deserialized fields of a `Heightfield`, which are checked like the arguments of `new` and
+/// `with_lod_step`.
+#[derive(Deserialize)]
+struct HeightfieldData {
+    columns: usize,
+    rows: usize,
+    heights: Vec<f32>,
+    lod_step: usize,
+}
+
+impl TryFrom<HeightfieldData> for Heightfield {
+    type Error = Error;
+
+    fn try_from(data: HeightfieldData) -> Result<Self, Error> {
+        Ok(Heightfield::new(data.columns, data.rows, data.heights)?.with_lod_step(data.lod_step))
+    }
+}
+
+impl Heightfield {
+    /// Creates a heightfield of `columns` by `rows` samples, from the heights of the rows one
+    /// after the other.
+    pub fn new(columns: usize, rows: usize, heights: Vec<f32>) -> Result<Self, Error> {
+        if columns < 2 || rows < 2 {
+            return Err(format_err!(
+                "Heightfield of {}x{} samples has no cells",
+                columns,
+                rows
+            ));
+        }
+        if columns.checked_mul(rows) != Some(heights.len()) {
+            return Err(format_err!(
+                "Heightfield of {}x{} samples has {} heights",
+                columns,
+                rows,
+                heights.len()
+            ));
+        }
+        Ok(Heightfield {
+            columns,
+            rows,
+            heights,
+            lod_step: 1,
+        })
+    }
+
+    /// Creates a heightfield from the brightness of an image in one of the formats of the
+    /// `ImageFormat`, with heights from 0 for black to 1 for white.
+    ///
+    /// 16 bit grayscale images keep their precision, other images are converted to 8 bit
+    /// grayscale.
+    pub fn from_image(bytes: &[u8]) -> Result<Self, Error> {
+        use image::DynamicImage;
+
+        let image = image::load_from_memory(bytes)
+            .map_err(|e| format_err!("Failed to decode heightfield image: {}", e))?;
+        let (columns, rows, heights) = match image {
+            DynamicImage::ImageLuma16(image) => (
+                image.width(),
+                image.height(),
+                image
+                    .pixels()
+                    .map(|pixel| f32::from(pixel.0[0]) / 65535.)
+                    .collect(),
+            ),
+            image => {
+                let image = image.to_luma();
+                (
+                    image.width(),
+                    image.height(),
+                    image
+                        .pixels()
+                        .map(|pixel| f32::from(pixel.0[0]) / 255.)
+                        .collect(),
+                )
+            }
+        };
+        Self::new(columns as usize, rows as usize, heights)
+    }
+
+    /// Builds the mesh from every `lod_step`th sample along both axes, and the samples of the
+    /// last row and column, for a coarser level of detail. A step of 0 is treated as 1.
+    pub fn with_lod_step(mut self, lod_step: usize) -> Self {
+        self.lod_step = lod_step.max(1);
+        self
+    }
+
+    /// Number of samples along the X axis.
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// Number of samples along the Z axis.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Number of samples between the vertices of the mesh.
+    pub fn lod_step(&self) -> usize {
+        self.lod_step
+    }
+
+    /// Height of a sample.
+    pub fn height(&self, column: usize, row: usize) -> f32 {
+        self.heights[row * self.columns + column]
+    }
+
+    pub(super) fn generate(&self, scale: Option<(f32, f32, f32)>) -> Vec<InternalVertexData> {
+        let columns = lod_samples(self.columns, self.lod_step);
+        let rows = lod_samples(self.rows, self.lod_step);
+        let (scale_x, scale_y, scale_z) = scale.unwrap_or((1., 1., 1.));
+        let coord = |sample: usize, count: usize| sample as f32 / (count - 1) as f32;
+
+        let positions = rows
+            .iter()
+            .flat_map(|&row| {
+                columns.iter().map(move |&column| {
+                    Position([
+                        (coord(column, self.columns) * 2. - 1.) * scale_x,
+                        self.height(column, row) * scale_y,
+                        (coord(row, self.rows) * 2. - 1.) * scale_z,
+                    ])
+                })
+            })
+            .collect::<Vec<_>>();
+        let index = |row: usize, column: usize| row * columns.len() + column;
+        let mut indices = Vec::with_capacity((rows.len() - 1) * (columns.len() - 1) * 6);
+        for row in 0..rows.len() - 1 {
+            for column in 0..columns.len() - 1 {
+                // The same triangles as `quads`
+                indices.extend(
+                    [
+                        index(row, column),
+                        index(row + 1, column),
+                        index(row + 1, column + 1),
+                        index(row, column),
+                        index(row + 1, column + 1),
+                        index(row, column + 1),
+                    ]
+                    .iter()
+                    .map(|&i| i as u32),
+                );
+            }
+        }
+        let normals = calculate_normals(&positions, Some(&indices));
+
+        quads(rows.len() - 1, columns.len() - 1, |row, column| {
+            let index = index(row, column);
+            let normal = Vector3::from(normals[index].0);
+            let tangent = (Vector3::x() - normal * normal.x).normalize();
+            (
+                positions[index].0,
+                normals[index].0,
+                [
+                    coord(columns[column], self.columns),
+                    coord(rows[row], self.rows),
+                ],
+                tangent.into(),
+            )
+        })
+    }
+}
+
+/// Returns the samples used as vertices along an axis.
+fn lod_samples(count: usize, lod_step: usize) -> Vec<usize> {
+    let mut samples = (0..count).step_by(lod_step).collect::<Vec<_>>();
+    if samples.last() != Some(&(count - 1)) {
+        samples.push(count - 1);
+    }
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lod_keeps_the_edges() {
+        assert_eq!(lod_samples(5, 1), vec![0, 1, 2, 3, 4]);
+        assert_eq!(lod_samples(5, 2), vec![0, 2, 4]);
+        assert_eq!(lod_samples(6, 4), vec![0, 4, 5]);
+        assert_eq!(lod_samples(3, 8), vec![0, 2]);
+    }
+
+    #[test]
+    fn slope_normals_and_tangents() {
+        // Rises by 2 along the X axis, which spans 2 units
+        let heightfield = Heightfield::new(3, 2, vec![0., 1., 2., 0., 1., 2.]).unwrap();
+        let vertices = heightfield.generate(None);
+        assert_eq!(vertices.len(), 2 * 6);
+        let expected_normal = Vector3::new(-1., 1., 0.).normalize();
+        let expected_tangent = Vector3::new(1., 1., 0.).normalize();
+        for (_, normal, _, tangent) in &vertices {
+            assert!((Vector3::from(*normal) - expected_normal).norm() < 1e-5);
+            assert!((Vector3::from(*tangent) - expected_tangent).norm() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn lod_step_reduces_the_cells() {
+        let heightfield = Heightfield::new(5, 5, vec![0.; 25])
+            .unwrap()
+            .with_lod_step(2);
+        let vertices = heightfield.generate(Some((10., 1., 10.)));
+        assert_eq!(vertices.len(), 2 * 2 * 6);
+        assert!(vertices
+            .iter()
+            .any(|(position, ..)| *position == [10., 0., 10.]));
+    }
+
+    #[test]
+    fn mismatched_heights_are_an_error() {
+        assert!(Heightfield::new(3, 3, vec![0.; 8]).is_err());
+        assert!(Heightfield::new(1, 3, vec![0.; 3]).is_err());
+    }
+
+    #[test]
+    fn deserialized_heightfields_are_checked() {
+        let heightfield = Heightfield::new(2, 2, vec![0., 1., 2., 3.])
+            .unwrap()
+            .with_lod_step(2);
+        let serialized = ron::ser::to_string(&heightfield).unwrap();
+        assert_eq!(heightfield, ron::de::from_str(&serialized).unwrap());
+
+        let unit_step: Heightfield =
+            ron::de::from_str("(columns: 2, rows: 2, heights: [0., 1., 2., 3.], lod_step: 0)")
+                .unwrap();
+        assert_eq!(1, unit_step.lod_step());
+
+        let invalid = [
+            "(columns: 1, rows: 2, heights: [0., 1.], lod_step: 1)",
+            "(columns: 2, rows: 2, heights: [0., 1., 2.], lod_step: 1)",
+            "(columns: 18446744073709551615, rows: 2, heights: [0., 1.], lod_step: 1)",
+        ];
+        for invalid in &invalid {
+            assert!(ron::de::from_str::<Heightfield>(invalid).is_err());
+        }
+    }
+}
@@ -1,4 +1,6 @@
 //! 3D Shape Primitives
+pub use self::heightfield::Heightfield;
+
 use amethyst_assets::{DefaultLoader, Handle, Loader, ProcessingQueue, Progress};
 use amethyst_core::math::Vector3;
 use genmesh::{
@@ -13,6 +15,10 @@ use rendy::mesh::{
 };
 
 use crate::types::{Mesh, MeshData};
+
+mod generators;
+mod heightfield;
+
 fn option_none<T>() -> Option<T> {
     None
 }
@@ -38,6 +44,21 @@ pub enum Shape {
     Plane(Option<(usize, usize)>),
     /// Circle, located in the XY plane, number of points around the circle
     Circle(usize),
+    /// Capsule along the Z axis, radius of the hemispheres, length of the cylinder between the
+    /// centers of the hemispheres, number of points around the radius, number of rings of each
+    /// hemisphere
+    Capsule(f32, f32, usize, usize),
+    /// Cube with vertices in [-1, +1] range and rounded edges, radius of the edges in (0, 1],
+    /// number of subdivisions of each edge
+    RoundedBox(f32, usize),
+    /// Arrow from the origin to (0, 0, 1), radius of the shaft, radius of the head, length of
+    /// the head, number of points around the radius
+    Arrow(f32, f32, f32, usize),
+    /// Grid, located in the XY plane, number of cells along x and y axis, number of times the
+    /// texture repeats along x and y axis
+    Grid(usize, usize, f32, f32),
+    /// Terrain in the XZ plane with vertices in [-1, +1] range, and heights along the Y axis
+    Heightfield(Heightfield),
 }
 
 /// Required resource access to upload shape
@@ -188,6 +209,15 @@ impl Shape {
                 )
             }
             Shape::Circle(u) => generate_vertices(Circle::new(u), scale),
+            Shape::Capsule(radius, length, segments, rings) => {
+                generators::capsule(radius, length, segments, rings, scale)
+            }
+            Shape::RoundedBox(radius, segments) => generators::rounded_box(radius, segments, scale),
+            Shape::Arrow(shaft_radius, head_radius, head_length, segments) => {
+                generators::arrow(shaft_radius, head_radius, head_length, segments, scale)
+            }
+            Shape::Grid(x, y, tile_x, tile_y) => generators::grid(x, y, tile_x, tile_y, scale),
+            Shape::Heightfield(ref heightfield) => heightfield.generate(scale),
         };
         InternalShape(vertices)
     }
@@ -210,28 +240,11 @@ where
         .map(|f| {
             f.map_vertex(|u| {
                 let v = vertices[u];
-                let pos = scale
-                    .map(|(x, y, z)| Vector3::new(v.pos.x * x, v.pos.y * y, v.pos.z * z))
-                    .unwrap_or_else(|| Vector3::new(v.pos.x, v.pos.y, v.pos.z));
-                let normal = scale
-                    .map(|(x, y, z)| {
-                        Vector3::new(v.normal.x * x, v.normal.y * y, v.normal.z * z).normalize()
-                    })
-                    .unwrap_or_else(|| Vector3::new(v.normal.x, v.normal.y, v.normal.z));
-                let tangent1 = normal.cross(&Vector3::x());
-                let tangent2 = normal.cross(&Vector3::y());
-                let tangent = if tangent1.norm_squared() > tangent2.norm_squared() {
-                    tangent1
-                } else {
-                    tangent2
-                }
-                .cross(&normal);
-
-                (
-                    pos.into(),
-                    normal.into(),
+                internal_vertex(
+                    Vector3::new(v.pos.x, v.pos.y, v.pos.z),
+                    Vector3::new(v.normal.x, v.normal.y, v.normal.z),
                     [(v.pos.x + 1.) / 2., (v.pos.y + 1.) / 2.],
-                    tangent.into(),
+                    scale,
                 )
             })
         })
@@ -239,6 +252,32 @@ where
         .collect::<Vec<_>>()
 }
 
+/// Scales the position and normal of a vertex, and picks a tangent perpendicular to its normal.
+fn internal_vertex(
+    pos: Vector3<f32>,
+    normal: Vector3<f32>,
+    tex_coord: [f32; 2],
+    scale: Option<(f32, f32, f32)>,
+) -> InternalVertexData {
+    let (pos, normal) = match scale {
+        Some((x, y, z)) => (
+            Vector3::new(pos.x * x, pos.y * y, pos.z * z),
+            Vector3::new(normal.x * x, normal.y * y, normal.z * z).normalize(),
+        ),
+        None => (pos, normal),
+    };
+    let tangent1 = normal.cross(&Vector3::x());
+    let tangent2 = normal.cross(&Vector3::y());
+    let tangent = if tangent1.norm_squared() > tangent2.norm_squared() {
+        tangent1
+    } else {
+        tangent2
+    }
+    .cross(&normal);
+
+    (pos.into(), normal.into(), tex_coord, tangent.into())
+}
+
 impl FromInternalVertex for Position {
     fn from_internal(v: &InternalVertexData) -> Self {
         Position([v.0[0], v.0[1], v.0[2]])
@@ -322,4 +361,62 @@ mod tests {
             Shape::Plane(None).generate::<Vec<PosNormTangTex>>(None)
         );
     }
+
+    /// Checks that the triangles are counter-clockwise seen from outside of a shape around the
+    /// origin, and that the normals point outwards.
+    fn assert_outwards(shape: Shape) {
+        let vertices = shape.generate_vertices::<Vec<PosNormTangTex>>(None);
+        assert!(!vertices.is_empty());
+        for triangle in vertices.chunks(3) {
+            let position = |i: usize| Vector3::from(triangle[i].position.0);
+            let (a, b, c) = (position(0), position(1), position(2));
+            let face_normal = (b - a).cross(&(c - a));
+            assert!(face_normal.dot(&(a + b + c)) > 0.0, "{:?}", triangle);
+            for vertex in triangle {
+                let normal = Vector3::from(vertex.normal.0);
+                assert!(normal.dot(&face_normal) >= 0.0, "{:?}", triangle);
+            }
+        }
+    }
+
+    #[test]
+    fn test_capsule() {
+        assert_outwards(Shape::Capsule(0.5, 1.0, 8, 4));
+    }
+
+    #[test]
+    fn test_rounded_box() {
+        assert_outwards(Shape::RoundedBox(0.25, 3));
+        assert_outwards(Shape::RoundedBox(1.0, 3));
+        // Without rounding, it is a cube of 6 quads
+        assert_eq!(
+            Shape::RoundedBox(0.0, 3)
+                .generate_vertices::<Vec<Position>>(None)
+                .len(),
+            6 * 6
+        );
+    }
+
+    #[test]
+    fn test_arrow() {
+        let vertices = Shape::Arrow(0.1, 0.2, 0.3, 8).generate_vertices::<Vec<PosNormTex>>(None);
+        let max_z = vertices
+            .iter()
+            .map(|vertex| vertex.position.0[2])
+            .fold(0.0, f32::max);
+        assert!((max_z - 1.0).abs() < 1e-6);
+        for vertex in &vertices {
+            let [x, y, _] = vertex.position.0;
+            assert!((x * x + y * y).sqrt() <= 0.2 + 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_grid() {
+        let vertices = Shape::Grid(4, 2, 8.0, 2.0).generate_vertices::<Vec<PosTex>>(None);
+        assert_eq!(vertices.len(), 4 * 2 * 6);
+        assert!(vertices
+            .iter()
+            .any(|vertex| vertex.position.0 == [1.0, 1.0, 0.0] && vertex.tex_coord.0 == [8.0, 2.0]));
+    }
 }
@@ -24,6 +24,11 @@ impl Asset for SpriteSheet {
         "renderer::SpriteSheet"
     }
     type Data = Self;
+
+    /// The texture and sprites of the sheet are separate assets, so only the handles count.
+    fn byte_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+    }
 }
 
 /// Dimensions and texture coordinates of each sprite in a sprite sheet.
@@ -358,6 +363,13 @@ impl Asset for Sprites {
         "renderer::Sprites"
     }
     type Data = Self;
+
+    fn byte_size(&self) -> usize {
+        match self {
+            Sprites::List(list) => list.sprites.len() * std::mem::size_of::<SpritePosition>(),
+            Sprites::Grid(_) => std::mem::size_of::<SpriteGrid>(),
+        }
+    }
 }
 
 register_asset_type!(Sprites => Sprites; AssetProcessorSystem<Sprites>);
@@ -15,7 +15,7 @@ use thread_profiler::profile_scope;
 
 use crate::{
     mtl::Material,
-    types::{Backend, Mesh, MeshData, Texture, TextureData},
+    types::{Backend, Mesh, MeshData, Texture, TextureData, TextureMipLevel},
 };
 
 /// Auxiliary data for render graph.
@@ -213,19 +213,16 @@ impl<B: Backend> System<'_> for TextureProcessorSystem<B> {
                             #[cfg(feature = "profiler")]
                             profile_scope!("process_texture");
 
-                            b.0.build(
-                                ImageState {
-                                    queue: **queue_id,
-                                    stage: rendy::hal::pso::PipelineStage::VERTEX_SHADER
-                                        | rendy::hal::pso::PipelineStage::FRAGMENT_SHADER,
-                                    access: rendy::hal::image::Access::SHADER_READ,
-                                    layout: rendy::hal::image::Layout::ShaderReadOnlyOptimal,
-                                },
-                                &mut *factory,
-                            )
-                            .map(B::wrap_texture)
-                            .map(ProcessingState::Loaded)
-                            .map_err(|e| e.into())
+                            let state = ImageState {
+                                queue: **queue_id,
+                                stage: rendy::hal::pso::PipelineStage::VERTEX_SHADER
+                                    | rendy::hal::pso::PipelineStage::FRAGMENT_SHADER,
+                                access: rendy::hal::image::Access::SHADER_READ,
+                                layout: rendy::hal::image::Layout::ShaderReadOnlyOptimal,
+                            };
+                            let texture = b.0.build(state, &mut *factory)?;
+                            upload_mip_levels(&*factory, &texture, &b.1, state)?;
+                            Ok(ProcessingState::Loaded(B::wrap_texture(texture)))
                         });
                         texture_storage.process_custom_drop(|_| {});
                     },
@@ -234,6 +231,45 @@ impl<B: Backend> System<'_> for TextureProcessorSystem<B> {
     }
 }
 
+/// Uploads the precomputed mip levels of a texture after the first one, which is uploaded by
+/// the `TextureBuilder`.
+fn upload_mip_levels<B: Backend>(
+    factory: &Factory<B>,
+    texture: &rendy::texture::Texture<B>,
+    levels: &[TextureMipLevel],
+    state: ImageState,
+) -> Result<(), amethyst_error::Error> {
+    use rendy::hal::image::{Extent, Layout, Offset, SubresourceLayers};
+
+    let image = texture.image();
+    let info = image.info();
+    for level in levels {
+        // The builder created the levels without data, so they are in the undefined layout
+        unsafe {
+            factory.upload_image(
+                image.clone(),
+                level.data_width,
+                level.data_height,
+                SubresourceLayers {
+                    aspects: info.format.surface_desc().aspects,
+                    level: level.level,
+                    layers: 0..info.kind.num_layers(),
+                },
+                Offset::ZERO,
+                Extent {
+                    width: level.width,
+                    height: level.height,
+                    depth: 1,
+                },
+                &level.data,
+                Layout::Undefined,
+                state,
+            )?;
+        }
+    }
+    Ok(())
+}
+
 pub(crate) fn create_default_mat<B: Backend>(resources: &Resources) -> Material {
     use crate::mtl::TextureOffset;
 
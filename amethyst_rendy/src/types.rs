@@ -166,6 +166,17 @@ impl Asset for Mesh {
         "Mesh"
     }
     type Data = MeshData;
+
+    fn byte_size(&self) -> usize {
+        match self {
+            #[cfg(target_os = "macos")]
+            Mesh::Metal(mesh) => mesh_byte_size(mesh),
+            #[cfg(all(not(target_os = "macos"), not(feature = "empty")))]
+            Mesh::Vulkan(mesh) => mesh_byte_size(mesh),
+            #[cfg(feature = "empty")]
+            Mesh::Empty(mesh) => mesh_byte_size(mesh),
+        }
+    }
 }
 
 impl Asset for Texture {
@@ -173,6 +184,49 @@ impl Asset for Texture {
         "Texture"
     }
     type Data = TextureData;
+
+    fn byte_size(&self) -> usize {
+        match self {
+            #[cfg(target_os = "macos")]
+            Texture::Metal(texture) => texture_byte_size(texture),
+            #[cfg(all(not(target_os = "macos"), not(feature = "empty")))]
+            Texture::Vulkan(texture) => texture_byte_size(texture),
+            #[cfg(feature = "empty")]
+            Texture::Empty(texture) => texture_byte_size(texture),
+        }
+    }
+}
+
+/// Approximates the size of the buffers of a mesh, as one vertex of every vertex buffer and one
+/// index per drawn vertex.
+fn mesh_byte_size<B: rendy::hal::Backend>(mesh: &rendy::mesh::Mesh<B>) -> usize {
+    let stride: u32 = mesh.formats().map(|format| format.stride).sum();
+    let index_size = match mesh.index_type() {
+        Some(rendy::hal::IndexType::U16) => 2,
+        Some(rendy::hal::IndexType::U32) => 4,
+        None => 0,
+    };
+    mesh.len() as usize * (stride as usize + index_size)
+}
+
+/// Returns the size of the image of a texture, with all its mip levels and layers.
+fn texture_byte_size<B: rendy::hal::Backend>(texture: &rendy::texture::Texture<B>) -> usize {
+    let image = texture.image();
+    let extent = image.kind().extent();
+    let desc = image.format().surface_desc();
+    let (block_width, block_height) = (u32::from(desc.dim.0), u32::from(desc.dim.1));
+    let layer_size: usize = (0..u32::from(image.levels()))
+        .map(|level| {
+            let width = (extent.width >> level).max(1);
+            let height = (extent.height >> level).max(1);
+            let depth = (extent.depth >> level).max(1);
+            let blocks = ((width + block_width - 1) / block_width)
+                * ((height + block_height - 1) / block_height)
+                * depth;
+            blocks as usize * usize::from(desc.bits / 8)
+        })
+        .sum();
+    layer_size * usize::from(image.layers())
 }
 
 /// Newtype for MeshBuilder prefab usage.
@@ -183,9 +237,34 @@ pub struct MeshData(
 );
 
 /// Newtype for TextureBuilder prefab usage.
+///
+/// The builder holds the data of the first mip level. The precomputed mip levels after it, read
+/// from texture containers such as KTX2 and DDS files, are uploaded once the texture is built;
+/// the builder must create the texture with `MipLevels::Levels` for them.
 #[derive(Debug, Clone, Serialize, Deserialize, TypeUuid)]
 #[uuid = "25063afd-6cc0-487e-982f-a63fed7d7393"]
-pub struct TextureData(pub rendy::texture::TextureBuilder<'static>);
+pub struct TextureData(
+    pub rendy::texture::TextureBuilder<'static>,
+    pub Vec<TextureMipLevel>,
+);
+
+/// Precomputed mip level of a texture.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextureMipLevel {
+    /// Index of the mip level, starting at 1 for the level after the one in the builder.
+    pub level: u8,
+    /// Width of the level in texels.
+    pub width: u32,
+    /// Height of the level in texels.
+    pub height: u32,
+    /// Width of the rows of `data` in texels, a multiple of the block width of compressed formats.
+    pub data_width: u32,
+    /// Height of the images of `data` in texels, a multiple of the block height of compressed
+    /// formats.
+    pub data_height: u32,
+    /// Texels of every array layer or cubemap face of the level, one image after the other.
+    pub data: Vec<u8>,
+}
 
 impl From<rendy::mesh::MeshBuilder<'static>> for MeshData {
     fn from(builder: rendy::mesh::MeshBuilder<'static>) -> Self {
@@ -195,7 +274,7 @@ impl From<rendy::mesh::MeshBuilder<'static>> for MeshData {
 
 impl From<rendy::texture::TextureBuilder<'static>> for TextureData {
     fn from(builder: rendy::texture::TextureBuilder<'static>) -> Self {
-        Self(builder)
+        Self(builder, Vec::new())
     }
 }
 
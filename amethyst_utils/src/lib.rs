@@ -21,5 +21,6 @@ pub mod circular_buffer;
 pub mod fps_counter;
 pub mod ortho_camera;
 pub mod removal;
+pub mod save;
 pub mod tag;
 pub mod time_destroy;
@@ -0,0 +1,501 @@
+//! Save slots stored in the per-user data directory.
+//!
+//! Every slot is a single file holding the slot's `SaveMetadata` followed by the game's data and
+//! a checksum over both. Slots are written to a temporary file first, which is then renamed over
+//! the previous save, so a crash while saving never leaves a half-written slot behind.
+
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use amethyst_error::{format_err, Error, ResultExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::application_dir;
+
+const MAGIC: &[u8; 8] = b"AMSAVE01";
+const EXTENSION: &str = "sav";
+const BACKUP_DIR: &str = "backups";
+
+/// Identifies a save slot.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SlotId {
+    /// A numbered slot, such as the slots of a "load game" menu.
+    Numbered(u32),
+    /// A named slot, such as `"autosave"` or `"quicksave"`.
+    ///
+    /// The name may only contain ASCII alphanumerics, `-` and `_`.
+    Named(String),
+}
+
+impl SlotId {
+    fn file_name(&self) -> Result<String, Error> {
+        match self {
+            SlotId::Numbered(n) => Ok(format!("slot_{}.{}", n, EXTENSION)),
+            SlotId::Named(name) => {
+                let valid = !name.is_empty()
+                    && !name.starts_with("slot_")
+                    && name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+                if valid {
+                    Ok(format!("{}.{}", name, EXTENSION))
+                } else {
+                    Err(format_err!("Invalid save slot name {:?}", name))
+                }
+            }
+        }
+    }
+
+    fn from_file_name(path: &Path) -> Option<SlotId> {
+        if path.extension()? != EXTENSION {
+            return None;
+        }
+        let stem = path.file_stem()?.to_str()?;
+        let slot = if let Some(number) = stem.strip_prefix("slot_") {
+            SlotId::Numbered(number.parse().ok()?)
+        } else {
+            SlotId::Named(stem.to_string())
+        };
+        // Files the slot is not saved to, such as `a.b.sav` or `slot_01.sav`, are not slots
+        if slot.file_name().ok()? == path.file_name()?.to_str()? {
+            Some(slot)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for SlotId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SlotId::Numbered(n) => write!(f, "slot {}", n),
+            SlotId::Named(name) => f.write_str(name),
+        }
+    }
+}
+
+/// Information about a save, readable without loading the save data itself.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SaveMetadata {
+    /// When the save was written, in seconds since `UNIX_EPOCH`.
+    pub timestamp: u64,
+    /// Total play time at the moment the save was written.
+    pub play_time: Duration,
+    /// Version of the game that wrote the save.
+    pub game_version: String,
+    /// Encoded thumbnail image, such as a PNG, empty if there is none.
+    pub thumbnail: Vec<u8>,
+}
+
+impl SaveMetadata {
+    /// Creates metadata for a save written now.
+    pub fn new<V: Into<String>>(game_version: V, play_time: Duration) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        SaveMetadata {
+            timestamp,
+            play_time,
+            game_version: game_version.into(),
+            thumbnail: Vec::new(),
+        }
+    }
+
+    /// Attaches an encoded thumbnail image.
+    pub fn with_thumbnail(mut self, thumbnail: Vec<u8>) -> Self {
+        self.thumbnail = thumbnail;
+        self
+    }
+}
+
+/// A save slot found by `SaveManager::list`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SaveSlot {
+    /// Identifier of the slot.
+    pub id: SlotId,
+    /// Metadata of the save in the slot.
+    pub metadata: SaveMetadata,
+}
+
+/// Manages the save slots in a directory.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use amethyst_utils::save::{SaveManager, SaveMetadata, SlotId};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Progress {
+///     level: u32,
+/// }
+///
+/// # fn main() -> Result<(), amethyst_error::Error> {
+/// let saves = SaveManager::in_user_data_dir("my_game")?;
+/// let metadata = SaveMetadata::new("1.0.0", Duration::from_secs(3600));
+/// saves.save(&SlotId::Numbered(1), &metadata, &Progress { level: 3 })?;
+///
+/// for slot in saves.list()? {
+///     println!("{}: played {:?}", slot.id, slot.metadata.play_time);
+/// }
+/// let (_, progress): (_, Progress) = saves.load(&SlotId::Numbered(1))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SaveManager {
+    directory: PathBuf,
+}
+
+impl SaveManager {
+    /// Creates a manager for the save slots in the given directory.
+    ///
+    /// The directory is created when the first slot is written.
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Self {
+        SaveManager {
+            directory: directory.into(),
+        }
+    }
+
+    /// Creates a manager for the save slots in the given directory relative to the application
+    /// root, see `application_dir`. Useful for portable installs and during development.
+    pub fn in_application_dir<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Ok(SaveManager::new(application_dir(path)?))
+    }
+
+    /// Creates a manager for the save slots in `<data dir>/<game_name>/saves`, where
+    /// `<data dir>` is the per-user local data directory of the platform.
+    pub fn in_user_data_dir<N: AsRef<Path>>(game_name: N) -> Result<Self, Error> {
+        let data_dir = dirs::data_local_dir()
+            .ok_or_else(|| format_err!("Could not determine the user data directory"))?;
+        Ok(SaveManager::new(
+            data_dir.join(game_name.as_ref()).join("saves"),
+        ))
+    }
+
+    /// Returns the directory the slots are stored in.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Returns the path of the file for the given slot.
+    pub fn slot_path(&self, slot: &SlotId) -> Result<PathBuf, Error> {
+        Ok(self.directory.join(slot.file_name()?))
+    }
+
+    /// Returns `true` if the given slot contains a save.
+    pub fn exists(&self, slot: &SlotId) -> bool {
+        self.slot_path(slot).map(|p| p.is_file()).unwrap_or(false)
+    }
+
+    /// Serializes `data` and atomically writes it with the metadata to the given slot.
+    pub fn save<T: Serialize>(
+        &self,
+        slot: &SlotId,
+        metadata: &SaveMetadata,
+        data: &T,
+    ) -> Result<(), Error> {
+        let bytes = ron::ser::to_string(data)
+            .with_context(|_| format_err!("Failed to serialize save data for {}", slot))?;
+        self.save_bytes(slot, metadata, bytes.as_bytes())
+    }
+
+    /// Atomically writes raw save data with the metadata to the given slot.
+    pub fn save_bytes(
+        &self,
+        slot: &SlotId,
+        metadata: &SaveMetadata,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let path = self.slot_path(slot)?;
+        let metadata = ron::ser::to_string(metadata)
+            .with_context(|_| format_err!("Failed to serialize save metadata for {}", slot))?;
+
+        let mut contents = Vec::with_capacity(MAGIC.len() + metadata.len() + data.len() + 16);
+        contents.extend_from_slice(MAGIC);
+        contents.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+        contents.extend_from_slice(metadata.as_bytes());
+        contents.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        contents.extend_from_slice(data);
+        let checksum = crc32fast::hash(&contents);
+        contents.extend_from_slice(&checksum.to_le_bytes());
+
+        fs::create_dir_all(&self.directory).with_context(|_| {
+            format_err!("Failed to create save directory {:?}", self.directory)
+        })?;
+        write_atomic(&path, &contents)
+            .with_context(|_| format_err!("Failed to write save file {:?}", path))
+    }
+
+    /// Loads and deserializes the save data of the given slot.
+    pub fn load<T: DeserializeOwned>(&self, slot: &SlotId) -> Result<(SaveMetadata, T), Error> {
+        let (metadata, bytes) = self.load_bytes(slot)?;
+        let data = ron::de::from_bytes(&bytes)
+            .with_context(|_| format_err!("Failed to deserialize save data for {}", slot))?;
+        Ok((metadata, data))
+    }
+
+    /// Loads the raw save data of the given slot, verifying its checksum.
+    pub fn load_bytes(&self, slot: &SlotId) -> Result<(SaveMetadata, Vec<u8>), Error> {
+        let path = self.slot_path(slot)?;
+        let contents =
+            fs::read(&path).with_context(|_| format_err!("Failed to read save file {:?}", path))?;
+
+        if contents.len() < MAGIC.len() + 4 {
+            return Err(format_err!("Save file {:?} is truncated", path));
+        }
+        let (body, checksum) = contents.split_at(contents.len() - 4);
+        if crc32fast::hash(body).to_le_bytes() != checksum {
+            return Err(format_err!("Save file {:?} is corrupted", path));
+        }
+
+        let mut reader = body;
+        let metadata = read_metadata(&mut reader, body.len() as u64)
+            .with_context(|_| format_err!("Failed to read save file {:?}", path))?;
+        let mut len = [0; 8];
+        reader
+            .read_exact(&mut len)
+            .with_context(|_| format_err!("Save file {:?} is truncated", path))?;
+        if reader.len() as u64 != u64::from_le_bytes(len) {
+            return Err(format_err!("Save file {:?} is truncated", path));
+        }
+
+        Ok((metadata, reader.to_vec()))
+    }
+
+    /// Returns the metadata of the given slot, without loading or verifying the save data.
+    pub fn metadata(&self, slot: &SlotId) -> Result<SaveMetadata, Error> {
+        let path = self.slot_path(slot)?;
+        let mut file = File::open(&path)
+            .with_context(|_| format_err!("Failed to open save file {:?}", path))?;
+        let file_len = file
+            .metadata()
+            .with_context(|_| format_err!("Failed to read save file {:?}", path))?
+            .len();
+        read_metadata(&mut file, file_len)
+            .with_context(|_| format_err!("Failed to read save file {:?}", path))
+    }
+
+    /// Lists all slots in the directory, sorted by slot id.
+    ///
+    /// Slots whose metadata can not be read are skipped with a warning.
+    pub fn list(&self) -> Result<Vec<SaveSlot>, Error> {
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e).with_context(|_| {
+                    format_err!("Failed to read save directory {:?}", self.directory)
+                })
+            }
+        };
+
+        let mut slots = Vec::new();
+        for entry in entries {
+            let path = entry
+                .with_context(|_| format_err!("Failed to read save directory entry"))?
+                .path();
+            if let Some(id) = SlotId::from_file_name(&path) {
+                match self.metadata(&id) {
+                    Ok(metadata) => slots.push(SaveSlot { id, metadata }),
+                    Err(e) => log::warn!("Skipping unreadable save slot {:?}: {}", path, e),
+                }
+            }
+        }
+        slots.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(slots)
+    }
+
+    /// Deletes the save in the given slot, if any.
+    pub fn delete(&self, slot: &SlotId) -> Result<(), Error> {
+        let path = self.slot_path(slot)?;
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(e).with_context(|_| format_err!("Failed to delete save file {:?}", path))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Copies the save in the given slot to the `backups` subdirectory, and returns the path of
+    /// the backup. The backup's file name contains the timestamp of the save.
+    pub fn backup(&self, slot: &SlotId) -> Result<PathBuf, Error> {
+        let path = self.slot_path(slot)?;
+        let metadata = self.metadata(slot)?;
+        let backup_dir = self.directory.join(BACKUP_DIR);
+        fs::create_dir_all(&backup_dir)
+            .with_context(|_| format_err!("Failed to create backup directory {:?}", backup_dir))?;
+
+        let backup = backup_dir.join(format!(
+            "{}.{}.{}",
+            path.file_stem().and_then(|s| s.to_str()).unwrap_or("save"),
+            metadata.timestamp,
+            EXTENSION
+        ));
+        let contents =
+            fs::read(&path).with_context(|_| format_err!("Failed to read save file {:?}", path))?;
+        write_atomic(&backup, &contents)
+            .with_context(|_| format_err!("Failed to write backup {:?}", backup))?;
+        Ok(backup)
+    }
+}
+
+/// Reads the metadata at the start of a save of `save_len` bytes.
+fn read_metadata<R: Read>(reader: &mut R, save_len: u64) -> Result<SaveMetadata, Error> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(format_err!("Not a save file"));
+    }
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len);
+    // The length is not covered by a checksum yet, so it must not allocate more than the file
+    if u64::from(len) > save_len.saturating_sub((MAGIC.len() + 4) as u64) {
+        return Err(format_err!("Save metadata is larger than the save file"));
+    }
+    let mut metadata = vec![0; len as usize];
+    reader.read_exact(&mut metadata)?;
+    Ok(ron::de::from_bytes(&metadata)?)
+}
+
+/// Writes to a temporary file next to `path`, then renames it over `path`.
+fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    {
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp, path).map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        e
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, time::Duration};
+
+    use super::*;
+
+    fn manager(name: &str) -> SaveManager {
+        let dir = env::temp_dir().join(format!("amethyst_save_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        SaveManager::new(dir)
+    }
+
+    #[test]
+    fn saves_lists_and_loads_slots() {
+        let saves = manager("round_trip");
+        let metadata = SaveMetadata::new("1.0.0", Duration::from_secs(60)).with_thumbnail(vec![1]);
+        saves
+            .save(&SlotId::Numbered(2), &metadata, &vec![1u32, 2, 3])
+            .expect("Failed to save slot 2");
+        saves
+            .save(&SlotId::Named("autosave".into()), &metadata, &7u32)
+            .expect("Failed to save autosave");
+
+        let slots = saves.list().expect("Failed to list slots");
+        assert_eq!(2, slots.len());
+        assert_eq!(SlotId::Numbered(2), slots[0].id);
+        assert_eq!(metadata, slots[1].metadata);
+
+        let (loaded_metadata, data): (_, Vec<u32>) =
+            saves.load(&SlotId::Numbered(2)).expect("Failed to load");
+        assert_eq!(metadata, loaded_metadata);
+        assert_eq!(vec![1, 2, 3], data);
+
+        saves
+            .delete(&SlotId::Numbered(2))
+            .expect("Failed to delete");
+        assert!(!saves.exists(&SlotId::Numbered(2)));
+        let _ = fs::remove_dir_all(saves.directory());
+    }
+
+    #[test]
+    fn detects_corrupted_saves() {
+        let saves = manager("corrupted");
+        let slot = SlotId::Numbered(1);
+        saves
+            .save_bytes(
+                &slot,
+                &SaveMetadata::new("1.0.0", Duration::default()),
+                b"data",
+            )
+            .expect("Failed to save");
+
+        let path = saves.slot_path(&slot).unwrap();
+        let mut contents = fs::read(&path).unwrap();
+        let len = contents.len();
+        contents[len - 5] ^= 0xff;
+        fs::write(&path, contents).unwrap();
+
+        assert!(saves.load_bytes(&slot).is_err());
+        let _ = fs::remove_dir_all(saves.directory());
+    }
+
+    #[test]
+    fn rejects_metadata_longer_than_the_file() {
+        let saves = manager("metadata_len");
+        let slot = SlotId::Numbered(1);
+        fs::create_dir_all(saves.directory()).unwrap();
+        let mut contents = MAGIC.to_vec();
+        contents.extend_from_slice(&u32::max_value().to_le_bytes());
+        contents.extend_from_slice(b"(game_version");
+        fs::write(saves.slot_path(&slot).unwrap(), contents).unwrap();
+
+        assert!(saves.metadata(&slot).is_err());
+        assert!(saves.load_bytes(&slot).is_err());
+        assert!(saves.list().unwrap().is_empty());
+        let _ = fs::remove_dir_all(saves.directory());
+    }
+
+    #[test]
+    fn backs_up_slots() {
+        let saves = manager("backup");
+        let slot = SlotId::Named("quicksave".into());
+        saves
+            .save_bytes(
+                &slot,
+                &SaveMetadata::new("1.0.0", Duration::default()),
+                b"data",
+            )
+            .expect("Failed to save");
+
+        let backup = saves.backup(&slot).expect("Failed to back up");
+        assert_eq!(
+            fs::read(saves.slot_path(&slot).unwrap()).unwrap(),
+            fs::read(backup).unwrap()
+        );
+        let _ = fs::remove_dir_all(saves.directory());
+    }
+
+    #[test]
+    fn rejects_invalid_slot_names() {
+        assert!(SlotId::Named("../escape".into()).file_name().is_err());
+        assert!(SlotId::Named("slot_1".into()).file_name().is_err());
+        assert!(SlotId::Named(String::new()).file_name().is_err());
+    }
+
+    #[test]
+    fn skips_files_with_invalid_slot_names() {
+        let slot = |name: &str| SlotId::from_file_name(Path::new(name));
+        assert_eq!(Some(SlotId::Numbered(2)), slot("saves/slot_2.sav"));
+        assert_eq!(
+            Some(SlotId::Named("quicksave".into())),
+            slot("quicksave.sav")
+        );
+        for invalid in &["a.b.sav", "my save.sav", "slot_01.sav", "slot_.sav", ".sav"] {
+            assert_eq!(None, slot(invalid));
+        }
+    }
+}
@@ -0,0 +1,39 @@
+//! Validates asset directories without starting the game, for use in CI.
+
+use std::path::PathBuf;
+
+use amethyst::{
+    assets::{AssetDaemonConfig, AssetValidator, Format},
+    utils::application_root_dir,
+    Error,
+};
+
+// This is required because rustc does not recognize .ctor segments when considering which symbols
+// to include when linking static libraries, so we need to reference a symbol in each module that
+// registers an importer since it uses inventory::submit and the .ctor linkage hack.
+fn init_modules() {
+    let _ = amethyst::renderer::formats::texture::ImageFormat::default().name();
+    let _ = amethyst::renderer::formats::sprite_sheet::SpriteSheetImporter;
+    let _ = amethyst::audio::WavFormat.name();
+    let _ = amethyst::locale::FTLFormat.name();
+    let _ = amethyst::ui::TtfFormat.name();
+}
+
+fn main() -> Result<(), Error> {
+    amethyst::start_logger(Default::default());
+    init_modules();
+
+    let mut asset_dirs: Vec<PathBuf> = std::env::args().skip(1).map(PathBuf::from).collect();
+    if asset_dirs.is_empty() {
+        asset_dirs.push(application_root_dir()?.join("assets"));
+    }
+
+    let report = AssetValidator::new(AssetDaemonConfig::default().with_asset_dirs(asset_dirs))
+        .with_ignored_extension("md")
+        .validate()?;
+    println!("{}", report);
+    if !report.is_ok() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -1,137 +1,93 @@
-//! Displays a 2D GLTF scene
+//! Displays a GLTF scene and plays its animations
 
 use amethyst::{
     animation::{
-        get_animation_set, AnimationBundle, AnimationCommand, AnimationControlSet, AnimationSet,
-        EndControl, VertexSkinningBundle,
+        get_animation_set, AnimationBundle, AnimationCommand, AnimationSet, EndControl,
+        VertexSkinningBundle,
     },
-    assets::{
-        AssetPrefab, Completion, Handle, Prefab, PrefabData, PrefabLoader, PrefabLoaderSystemDesc,
-        ProgressCounter, RonFormat,
-    },
-    controls::{ControlTagPrefab, FlyControlBundle},
+    assets::{prefab::Prefab, DefaultLoader, Handle, Loader, LoaderBundle},
+    controls::{FlyControl, FlyControlBundle, HideCursor},
     core::transform::{Transform, TransformBundle},
-    derive::PrefabData,
-    ecs::{Entity, ReadStorage, Write, WriteStorage},
-    gltf::{GltfSceneAsset, GltfSceneFormat, GltfSceneLoaderSystemDesc},
-    input::{is_close_requested, is_key_down, StringBindings, VirtualKeyCode},
+    gltf::GltfBundle,
+    input::{is_close_requested, is_key_down, is_mouse_button_down, InputBundle},
     prelude::*,
     renderer::{
-        camera::CameraPrefab,
-        light::LightPrefab,
+        camera::Camera,
+        light::{Light, PointLight},
+        palette::Srgb,
         plugins::{RenderPbr3D, RenderSkybox, RenderToWindow},
         types::DefaultBackend,
         RenderingBundle,
     },
-    utils::{
-        application_root_dir,
-        auto_fov::{AutoFov, AutoFovSystem},
-        tag::{Tag, TagFinder},
-    },
+    utils::application_root_dir,
+    window::ScreenDimensions,
+    winit::event::{MouseButton, VirtualKeyCode},
     Error,
 };
-use serde::{Deserialize, Serialize};
 
 #[derive(Default)]
 struct Example {
-    entity: Option<Entity>,
-    initialised: bool,
-    progress: Option<ProgressCounter>,
-}
-
-#[derive(Clone, Serialize, Deserialize)]
-struct AnimationMarker;
-
-#[derive(Default)]
-struct Scene {
-    handle: Option<Handle<Prefab<ScenePrefabData>>>,
+    scene: Option<Entity>,
     animation_index: usize,
 }
 
-#[derive(Default, Deserialize, Serialize, PrefabData)]
-#[serde(default)]
-struct ScenePrefabData {
-    transform: Option<Transform>,
-    gltf: Option<AssetPrefab<GltfSceneAsset, GltfSceneFormat>>,
-    camera: Option<CameraPrefab>,
-    auto_fov: Option<AutoFov>,
-    light: Option<LightPrefab>,
-    tag: Option<Tag<AnimationMarker>>,
-    fly_tag: Option<ControlTagPrefab>,
-}
-
 impl SimpleState for Example {
     fn on_start(&mut self, data: StateData<'_, GameData>) {
-        let StateData { world, .. } = data;
-
-        self.progress = Some(ProgressCounter::default());
-
-        world.exec(
-            |(loader, mut scene): (PrefabLoader<'_, ScenePrefabData>, Write<'_, Scene>)| {
-                scene.handle = Some(loader.load(
-                    "prefab/puffy_scene.ron",
-                    RonFormat,
-                    self.progress.as_mut().unwrap(),
-                ));
-            },
+        let StateData {
+            world, resources, ..
+        } = data;
+
+        // The scene is spawned on this entity once the file and its assets are loaded
+        let loader = resources.get::<DefaultLoader>().unwrap();
+        let scene: Handle<Prefab> = loader.load("mesh/puffy.gltf");
+        self.scene = Some(world.push((scene, Transform::default())));
+
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(0.0, 0.0, 10.0);
+        let (width, height) = {
+            let dim = resources.get::<ScreenDimensions>().unwrap();
+            (dim.width(), dim.height())
+        };
+        world.push((Camera::standard_3d(width, height), transform, FlyControl));
+
+        let lights = vec![
+            ([6.0, 6.0, -6.0], 6.0, Srgb::new(0.8, 0.0, 0.0)),
+            ([0.0, 4.0, 4.0], 6.0, Srgb::new(0.0, 0.3, 0.7)),
+        ];
+        world.extend(
+            lights
+                .into_iter()
+                .map(|([x, y, z], intensity, color)| {
+                    let mut transform = Transform::default();
+                    transform.set_translation_xyz(x, y, z);
+                    let light: Light = PointLight {
+                        intensity,
+                        color,
+                        ..PointLight::default()
+                    }
+                    .into();
+                    (light, transform)
+                })
+                .collect::<Vec<_>>(),
         );
     }
 
     fn handle_event(&mut self, data: StateData<'_, GameData>, event: StateEvent) -> SimpleTrans {
-        let StateData { world, .. } = data;
+        let StateData {
+            world, resources, ..
+        } = data;
         if let StateEvent::Window(event) = &event {
-            if is_close_requested(&event) || is_key_down(&event, VirtualKeyCode::Escape) {
-                Trans::Quit
-            } else if is_key_down(&event, VirtualKeyCode::Space) {
-                toggle_or_cycle_animation(
-                    self.entity,
-                    &mut world.write_resource(),
-                    &world.read_storage(),
-                    &mut world.write_storage(),
-                );
-                Trans::None
-            } else {
-                Trans::None
+            if is_close_requested(&event) {
+                return Trans::Quit;
             }
-        } else {
-            Trans::None
-        }
-    }
-
-    fn update(&mut self, data: &mut StateData<'_, GameData>) -> SimpleTrans {
-        if !self.initialised {
-            let remove = match self.progress.as_ref().map(|p| p.complete()) {
-                None | Some(Completion::Loading) => false,
-
-                Some(Completion::Complete) => {
-                    let scene_handle = data
-                        .world
-                        .read_resource::<Scene>()
-                        .handle
-                        .as_ref()
-                        .unwrap()
-                        .clone();
-
-                    data.world.create_entity().with(scene_handle).build();
-
-                    true
-                }
-
-                Some(Completion::Failed) => {
-                    println!("Error: {:?}", self.progress.as_ref().unwrap().errors());
-                    return Trans::Quit;
-                }
-            };
-            if remove {
-                self.progress = None;
-            }
-            if self.entity.is_none() {
-                if let Some(entity) = data
-                    .world
-                    .exec(|finder: TagFinder<'_, AnimationMarker>| finder.find())
-                {
-                    self.entity = Some(entity);
-                    self.initialised = true;
+            let mut hide_cursor = resources.get_mut::<HideCursor>().unwrap();
+            if is_key_down(&event, VirtualKeyCode::Escape) {
+                hide_cursor.hide = false;
+            } else if is_mouse_button_down(&event, MouseButton::Left) {
+                hide_cursor.hide = true;
+            } else if is_key_down(&event, VirtualKeyCode::Space) {
+                if let Some(scene) = self.scene {
+                    toggle_or_cycle_animation(world, scene, &mut self.animation_index);
                 }
             }
         }
@@ -139,88 +95,74 @@ impl SimpleState for Example {
     }
 }
 
-fn toggle_or_cycle_animation(
-    entity: Option<Entity>,
-    scene: &mut Scene,
-    sets: &ReadStorage<'_, AnimationSet<usize, Transform>>,
-    controls: &mut WriteStorage<'_, AnimationControlSet<usize, Transform>>,
-) {
-    if let Some((entity, Some(animations))) = entity.map(|entity| (entity, sets.get(entity))) {
-        if animations.animations.len() > scene.animation_index {
-            let animation = animations.animations.get(&scene.animation_index).unwrap();
-            let set = get_animation_set::<usize, Transform>(controls, entity).unwrap();
-            if set.has_animation(scene.animation_index) {
-                set.toggle(scene.animation_index);
-            } else {
-                println!("Running animation {}", scene.animation_index);
-                set.add_animation(
-                    scene.animation_index,
-                    animation,
-                    EndControl::Normal,
-                    1.0,
-                    AnimationCommand::Start,
-                );
-            }
-            scene.animation_index += 1;
-            if scene.animation_index >= animations.animations.len() {
-                scene.animation_index = 0;
-            }
+fn toggle_or_cycle_animation(world: &mut World, entity: Entity, animation_index: &mut usize) {
+    // The `AnimationSet` is added when the scene is spawned
+    let (animation, count) = match world.entry_ref(entity).ok().and_then(|entry| {
+        entry
+            .get_component::<AnimationSet<usize, Transform>>()
+            .ok()
+            .map(|set| (set.get(animation_index).cloned(), set.animations.len()))
+    }) {
+        Some((Some(animation), count)) => (animation, count),
+        _ => return,
+    };
+
+    let mut buffer = CommandBuffer::new(world);
+    if let Some(control_set) =
+        get_animation_set::<usize, Transform, World>(world, &mut buffer, entity)
+    {
+        if control_set.has_animation(*animation_index) {
+            control_set.toggle(*animation_index);
+        } else {
+            println!("Running animation {}", animation_index);
+            control_set.add_animation(
+                *animation_index,
+                &animation,
+                EndControl::Normal,
+                1.0,
+                AnimationCommand::Start,
+            );
         }
+        *animation_index = (*animation_index + 1) % count;
     }
+    buffer.flush(world);
 }
 
-fn main() -> Result<(), amethyst::Error> {
+fn main() -> Result<(), Error> {
     amethyst::start_logger(Default::default());
 
     let app_root = application_root_dir()?;
 
     let display_config_path = app_root.join("config/display.ron");
+    let key_bindings_path = app_root.join("config/input.ron");
     let assets_dir = app_root.join("assets/");
 
-    let mut game_data = DispatcherBuilder::default()
-        .with(AutoFovSystem::default(), "auto_fov", &[])
-        .with_system_desc(
-            PrefabLoaderSystemDesc::<ScenePrefabData>::default(),
-            "scene_loader",
-            &[],
-        )
-        .with_system_desc(
-            GltfSceneLoaderSystemDesc::default(),
-            "gltf_loader",
-            &["scene_loader"], // This is important so that entity instantiation is performed in a single frame.
-        )
+    let mut builder = DispatcherBuilder::default();
+    builder
+        .add_bundle(LoaderBundle)
+        // Sets up the scenes spawned by the `LoaderBundle`
+        .add_bundle(GltfBundle)
+        .add_bundle(InputBundle::new().with_bindings_from_file(&key_bindings_path)?)
         .add_bundle(
-            AnimationBundle::<usize, Transform>::new("animation_control", "sampler_interpolation")
-                .with_dep(&["gltf_loader"]),
-        )?
-        .add_bundle(
-            FlyControlBundle::<StringBindings>::new(None, None, None)
-                .with_sensitivity(0.1, 0.1)
-                .with_speed(5.),
-        )?
-        .add_bundle(TransformBundle::new().with_dep(&[
-            "animation_control",
-            "sampler_interpolation",
-            "fly_movement",
-        ]))?
-        .add_bundle(VertexSkinningBundle::new().with_dep(&[
-            "transform_system",
-            "animation_control",
-            "sampler_interpolation",
-        ]))?
-        // `VisibilitySortingSystem` (part of `RenderPbr3D`) should depend on:
-        // &["fly_movement", "transform_system", "auto_fov"]
-        //
-        // There is currently no way to pass the dependencies to that system. However, since that
-        // system is thread local as part of rendering, it runs after all of the systems anyway.
+            FlyControlBundle::new(
+                Some("move_x".into()),
+                Some("move_y".into()),
+                Some("move_z".into()),
+            )
+            .with_sensitivity(0.1, 0.1)
+            .with_speed(5.),
+        )
+        .add_bundle(AnimationBundle::<usize, Transform>::default())
+        .add_bundle(TransformBundle)
+        .add_bundle(VertexSkinningBundle::new())
         .add_bundle(
             RenderingBundle::<DefaultBackend>::new()
                 .with_plugin(RenderToWindow::from_config_path(display_config_path)?)
                 .with_plugin(RenderPbr3D::default().with_skinning())
                 .with_plugin(RenderSkybox::default()),
-        )?;
+        );
 
-    let game = Application::build(assets_dir, Example::default())?.build(game_data)?;
+    let game = Application::build(assets_dir, Example::default())?.build(builder)?;
     game.run();
     Ok(())
 }
@@ -1,4 +1,5 @@
-//! Example showing how to load a Locale file as an Asset using the Loader.
+//! Example showing how to load a Locale file as an Asset using the Loader, and how to switch
+//! to the file of another language.
 
 use amethyst::{
     assets::{AssetStorage, DefaultLoader, Handle, Loader, LoaderBundle},
@@ -11,15 +12,15 @@ use amethyst::{
 };
 
 struct Example {
-    handle_en: Option<Handle<Locale>>,
-    handle_fr: Option<Handle<Locale>>,
+    handle: Option<Handle<Locale>>,
+    printed: Option<String>,
 }
 
 impl Example {
     pub fn new() -> Self {
         Example {
-            handle_en: None,
-            handle_fr: None,
+            handle: None,
+            printed: None,
         }
     }
 }
@@ -27,33 +28,40 @@ impl Example {
 impl SimpleState for Example {
     fn on_start(&mut self, data: StateData<'_, GameData>) {
         let loader = data.resources.get::<DefaultLoader>().unwrap();
-        self.handle_en = Some(loader.load("locale/locale_en.ftl"));
-        self.handle_fr = Some(loader.load("locale/locale_fr.ftl"));
+        // `{lang}` is replaced by the `ActiveLocale`, so this loads `locale/locale_en.ftl`.
+        self.handle = Some(loader.load("locale/locale_{lang}.ftl"));
     }
 
     fn update(&mut self, data: &mut StateData<'_, GameData>) -> SimpleTrans {
-        let _loader = data.resources.get::<DefaultLoader>().unwrap();
-
         // Check if the locale has been loaded.
         let store = data.resources.get::<AssetStorage<Locale>>().unwrap();
+        let locale = match self.handle.as_ref().and_then(|h| store.get(h)) {
+            Some(locale) => locale,
+            None => return Trans::None,
+        };
+
+        let bundle = &locale.bundle;
+        let msg_hello = bundle
+            .get_message("hello")
+            .expect("Failed to load message for hello");
+        let msg_bye = bundle
+            .get_message("bye")
+            .expect("Failed to load message for bye");
+        let hello_value = msg_hello.value.expect("Hello message has no value");
+        let bye_value = msg_bye.value.expect("Bye message has no value");
 
-        for h in [self.handle_en.as_ref(), self.handle_fr.as_ref()].iter() {
-            if let Some(locale) = h.and_then(|h| store.get(h)) {
-                let bundle = &locale.bundle;
-                let msg_hello = bundle
-                    .get_message("hello")
-                    .expect("Failed to load message for hello");
-                let msg_bye = bundle
-                    .get_message("bye")
-                    .expect("Failed to load message for bye");
-                let hello_value = msg_hello.value.expect("Hello message has no value");
-                let bye_value = msg_bye.value.expect("Bye message has no value");
+        let mut errors = vec![];
+        let hello = bundle.format_pattern(hello_value, None, &mut errors);
+        if self.printed.as_deref() == Some(&*hello) {
+            return Trans::None;
+        }
+        println!("{}", hello);
+        println!("{}", bundle.format_pattern(bye_value, None, &mut errors));
+        assert_eq!(errors.len(), 0);
 
-                let mut errors = vec![];
-                println!("{}", bundle.format_pattern(hello_value, None, &mut errors));
-                println!("{}", bundle.format_pattern(bye_value, None, &mut errors));
-                assert_eq!(errors.len(), 0);
-            }
+        if self.printed.replace(hello.into_owned()).is_none() {
+            // Switching the locale reloads the handle with `locale/locale_fr.ftl`.
+            data.resources.get_mut::<ActiveLocale>().unwrap().0 = "fr".parse().unwrap();
         }
 
         Trans::None
@@ -70,6 +78,7 @@ fn main() -> Result<(), Error> {
 
     builder
         .add_bundle(LoaderBundle)
+        .add_bundle(LocaleBundle::default())
         .add_bundle(RenderingBundle::<DefaultBackend>::new());
 
     let game = Application::new(assets_dir, Example::new(), builder)?;
@@ -36,7 +36,9 @@ fn main() -> amethyst::Result<()> {
         game_data.add_bundle(WindowBundle::from_config_path(display_config_path)?);
         game = Application::build(assets_dir, ExampleState)?.build(game_data)?;
     } else {
-        game = Application::build(assets_dir, EmptyState)?.build(game_data)?;
+        game = Application::build(assets_dir, EmptyState)?
+            .headless(true)
+            .build(game_data)?;
     }
 
     game.run();
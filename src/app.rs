@@ -1,9 +1,18 @@
 //! The core engine framework.
 
-use std::{env, marker::PhantomData, path::Path, sync::Arc, time::Duration};
+use std::{
+    env,
+    marker::PhantomData,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use derivative::Derivative;
-use log::{debug, info, log_enabled, trace, Level};
+use log::{debug, info, log_enabled, trace, warn, Level};
 use rayon::ThreadPoolBuilder;
 #[cfg(feature = "sentry")]
 use sentry::integrations::panic::register_panic_handler;
@@ -12,20 +21,27 @@ use thread_profiler::{profile_scope, register_thread_with_profiler, write_profil
 use winit::event::{Event, WindowEvent};
 
 use crate::{
-    assets::{start_asset_daemon, DefaultLoader, Source},
+    args::EngineArgs,
+    assets::{AssetDaemonConfig, AssetSources, PackfileIO, Source},
     core::{
         frame_limiter::{FrameLimiter, FrameRateLimitConfig, FrameRateLimitStrategy},
         shrev::{EventChannel, ReaderId},
+        tasks::{apply_completed_tasks, Tasks},
         timing::{Stopwatch, Time},
         ArcThreadPool, EventReader,
     },
+    crash_report::{self, CrashReporter},
     ecs::*,
     error::Error,
     game_data::{DataDispose, DataInit},
     state::{State, StateData, StateMachine, TransEvent},
     state_event::{StateEvent, StateEventReader},
+    window::{ScreenDimensions, Window},
 };
 
+/// Screen dimensions provided to the states of a headless application.
+const HEADLESS_DIMENSIONS: (u32, u32) = (800, 600);
+
 /// `CoreApplication` is the application implementation for the game engine. This is fully generic
 /// over the state type and event type.
 ///
@@ -59,6 +75,12 @@ where
     trans_reader_id: ReaderId<TransEvent<T, E>>,
     states: StateMachine<'a, T, E>,
     ignore_window_close: bool,
+    /// Set by the signal handler of a headless application.
+    #[derivative(Debug = "ignore")]
+    shutdown_requested: Option<Arc<AtomicBool>>,
+    #[derivative(Debug = "ignore")]
+    crash_reporter: Option<CrashReporter>,
+    max_frames: Option<u64>,
     data: T,
 }
 
@@ -231,11 +253,21 @@ where
             None
         };
 
+        let report_crashes = if let Some(crash_reporter) = self.crash_reporter.take() {
+            crash_reporter.install();
+            true
+        } else {
+            false
+        };
+
         self.initialize();
 
         self.resources.get_mut::<Stopwatch>().unwrap().start();
 
         while self.states.is_running() {
+            if report_crashes {
+                crash_report::set_state_stack(self.states.state_names());
+            }
             self.advance_frame();
             {
                 #[cfg(feature = "profiler")]
@@ -248,9 +280,23 @@ where
                 let mut time = self.resources.get_mut::<Time>().unwrap();
                 time.increment_frame_number();
                 time.set_delta_time(elapsed);
+                if report_crashes {
+                    crash_report::set_frame_number(time.frame_number());
+                }
                 stopwatch.stop();
                 stopwatch.restart();
             }
+            if let Some(max_frames) = self.max_frames {
+                let frame_number = self.resources.get::<Time>().unwrap().frame_number();
+                if frame_number >= max_frames {
+                    info!("Stopping after {} frames", frame_number);
+                    self.states.stop(StateData::new(
+                        &mut self.world,
+                        &mut self.resources,
+                        &mut self.data,
+                    ));
+                }
+            }
         }
         self.shutdown();
     }
@@ -268,9 +314,11 @@ where
             .expect("Tried to start state machine without any states present");
     }
 
-    // React to window close events
+    // React to window close events, or to termination signals in headless mode
     fn should_close(&mut self) -> bool {
-        if self.ignore_window_close {
+        if let Some(shutdown_requested) = &self.shutdown_requested {
+            shutdown_requested.load(Ordering::Relaxed)
+        } else if self.ignore_window_close {
             false
         } else {
             let reader_id = &mut self.event_reader_id;
@@ -310,6 +358,8 @@ where
             states.stop(StateData::new(world, resources, &mut self.data));
         }
 
+        apply_completed_tasks(&mut self.resources);
+
         // Read the Trans queue and apply changes.
 
         let world = &mut self.world;
@@ -418,6 +468,10 @@ pub struct ApplicationBuilder<S, T, E, R> {
     /// Used by bundles to initialize any resources in the world
     pub resources: Resources,
     ignore_window_close: bool,
+    headless: bool,
+    crash_reporter: Option<CrashReporter>,
+    max_frames: Option<u64>,
+    asset_daemon: Option<AssetDaemonConfig>,
     phantom: PhantomData<(T, E, R)>,
 }
 
@@ -511,9 +565,6 @@ where
             info!("Rustc git commit: {}", hash);
         }
 
-        let asset_dirs = vec![path.as_ref().to_path_buf()];
-        start_asset_daemon(asset_dirs);
-
         let thread_count: Option<usize> = env::var("AMETHYST_NUM_THREADS")
             .as_ref()
             .map(|s| {
@@ -543,6 +594,7 @@ where
         }
         // FIXME check that the loader is added to the resources
         // resources.insert(Loader::new(path.as_ref().to_owned(), pool.clone()));
+        resources.insert(Tasks::new(pool.clone()));
         resources.insert(pool);
         resources.insert(EventChannel::<Event<'static, ()>>::with_capacity(2000));
         //resources.insert(EventChannel::<UiEvent>::with_capacity(40));
@@ -555,6 +607,10 @@ where
             world,
             resources,
             ignore_window_close: false,
+            headless: false,
+            crash_reporter: None,
+            max_frames: None,
+            asset_daemon: Some(AssetDaemonConfig::default().with_asset_dir(path.as_ref())),
             phantom: PhantomData,
         })
     }
@@ -617,6 +673,10 @@ where
     /// effect will be a replacement of the older store with the new one.
     /// No warning or panic will result from this action.
     ///
+    /// Files of the store are loaded by prefixing their path with the name of the store and `://`.
+    /// They are imported with the importers registered with `register_importer!` when they are
+    /// requested, see [`AssetSources`](../amethyst_assets/struct.AssetSources.html).
+    ///
     /// # Parameters
     ///
     /// - `name`: A unique name or key to identify the asset storage location. `name`
@@ -636,13 +696,13 @@ where
     ///
     /// ~~~no_run
     /// use amethyst::prelude::*;
-    /// use amethyst::assets::{Directory,  DefaultLoader, Loader, Handle};
-    /// use amethyst::renderer::{Mesh, formats::mesh::ObjFormat};
+    /// use amethyst::assets::{Directory, DefaultLoader, Loader, Handle};
+    /// use amethyst::renderer::Mesh;
     ///
     /// # fn main() -> amethyst::Result<()> {
     /// let assets_dir = "assets/";
     /// let game = Application::build(assets_dir, LoadingState)?
-    ///     // Register the directory "custom_directory" under the name "resources".
+    ///     // Register the directory "custom_directory" under the name "custom_store".
     ///     .with_source("custom_store", Directory::new("custom_directory"))
     ///     .build(DispatcherBuilder::default())?
     ///     .run();
@@ -653,29 +713,28 @@ where
     /// impl SimpleState for LoadingState {
     ///     fn on_start(&mut self, data: StateData<'_, GameData>) {
     ///         let loader = data.resources.get::<DefaultLoader>().unwrap();
-    ///         let storage = data.resources.get().unwrap();
     ///
     ///         // Load a teapot mesh from the directory that registered above.
-    ///         let mesh: Handle<Mesh> =
-    ///             loader.load_from("teapot", ObjFormat, "custom_directory", (), &storage);
+    ///         let mesh: Handle<Mesh> = loader.load("custom_store://teapot.obj");
     ///     }
     /// }
     /// ~~~
-    pub fn with_source<I, O>(self, _name: I, _store: O) -> Self
+    pub fn with_source<I, O>(mut self, name: I, store: O) -> Self
     where
         I: Into<String>,
         O: Source,
     {
-        {
-            let _loader = self.resources.get_mut::<DefaultLoader>().unwrap();
-            // FIXME Update the source on the loader
-            // loader.add_source(name, store);
-        }
+        self.resources
+            .get_or_insert_with(AssetSources::default)
+            .add_source(name, store);
         self
     }
 
     /// Registers the default asset store with the loader logic of the Application.
     ///
+    /// Paths without a store prefix are looked up in the default store first, and are loaded
+    /// through the asset daemon if the default store does not have them.
+    ///
     /// # Parameters
     ///
     /// - `store`: The asset store being registered.
@@ -692,8 +751,8 @@ where
     ///
     /// ~~~no_run
     /// use amethyst::prelude::*;
-    /// use amethyst::assets::{Directory,  DefaultLoader, Loader, Handle};
-    /// use amethyst::renderer::{Mesh, formats::mesh::ObjFormat};
+    /// use amethyst::assets::{Directory, DefaultLoader, Loader, Handle};
+    /// use amethyst::renderer::Mesh;
     ///
     /// # fn main() -> amethyst::Result<()> {
     /// let assets_dir = "assets/";
@@ -709,21 +768,18 @@ where
     /// impl SimpleState for LoadingState {
     ///     fn on_start(&mut self, data: StateData<'_, GameData>) {
     ///         let loader = data.resources.get::<DefaultLoader>().unwrap();
-    ///         let storage = data.resources.get().unwrap();
     ///         // Load a teapot mesh from the directory that registered above.
-    ///         let mesh: Handle<Mesh> = loader.load("teapot", ObjFormat, (), &storage);
+    ///         let mesh: Handle<Mesh> = loader.load("teapot.obj");
     ///     }
     /// }
     /// ~~~
-    pub fn with_default_source<O>(self, _store: O) -> Self
+    pub fn with_default_source<O>(mut self, store: O) -> Self
     where
         O: Source,
     {
-        {
-            let _loader = self.resources.get_mut::<DefaultLoader>().unwrap();
-            // FIXME Update the location on the loader
-            // loader.set_default_source(store);
-        }
+        self.resources
+            .get_or_insert_with(AssetSources::default)
+            .set_default_source(store);
         self
     }
 
@@ -773,6 +829,182 @@ where
         self
     }
 
+    /// Stops the application after the given number of frames.
+    ///
+    /// This is mostly useful for automated runs, such as benchmarks or CI smoke tests.
+    ///
+    /// # Parameters
+    ///
+    /// `frames`: The number of frames to run before the state machine is stopped.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    pub fn with_max_frames(mut self, frames: u64) -> Self {
+        self.max_frames = Some(frames);
+        self
+    }
+
+    /// Applies the standard engine command-line arguments.
+    ///
+    /// `--fps`, `--fixed-rate` and `--frames` are applied to the `FrameLimiter`, `Time` and the
+    /// application respectively. `--fps` keeps the strategy of the frame limiter, so it should
+    /// be applied after `with_frame_limit`. The arguments are then inserted as a resource, so
+    /// that game-specific arguments can be read from systems and states.
+    ///
+    /// `--assets`, `--log-level`, `--window-size` and `--config` need to be known before the
+    /// builder is created, see [`EngineArgs`](struct.EngineArgs.html) for how to apply them.
+    ///
+    /// # Parameters
+    ///
+    /// `args`: The parsed command-line arguments.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    pub fn with_engine_args(mut self, args: EngineArgs) -> Self {
+        if let Some(fps) = args.fps() {
+            let mut limiter = self
+                .resources
+                .get_mut::<FrameLimiter>()
+                .expect("`FrameLimiter` is inserted by `ApplicationBuilder::new`");
+            // A limit of 0 fps switches to `Unlimited`, which does not apply to a new limit
+            let strategy = match limiter.strategy() {
+                FrameRateLimitStrategy::Unlimited => FrameRateLimitStrategy::default(),
+                strategy => strategy.clone(),
+            };
+            limiter.set_rate(strategy, fps);
+        }
+        if let Some(rate) = args.fixed_rate().filter(|rate| *rate > 0) {
+            self = self.with_fixed_step_length(Duration::from_secs(1) / rate);
+        }
+        if let Some(frames) = args.frames() {
+            self = self.with_max_frames(frames);
+        }
+        if args.headless() {
+            self = self.headless(true);
+        }
+        self.with_resource(args)
+    }
+
+    /// Runs the application without a window or renderer, such as for a dedicated server or a
+    /// simulation on CI.
+    ///
+    /// A headless application:
+    ///
+    /// * must not have a `WindowBundle` or `RenderingBundle` added to its game data, `build`
+    ///   returns an error if a window was created.
+    /// * provides a stub `ScreenDimensions` resource of 800x600, unless one was inserted already,
+    ///   so that states shared with the client can still read it.
+    /// * stops cleanly on `SIGINT` (`ctrl-c`) and `SIGTERM`, instead of on window close events.
+    ///   States are stopped the same way as for `Trans::Quit`.
+    ///
+    /// # Parameters
+    ///
+    /// `headless`: Whether the application runs without a window. False by default.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    ///
+    /// # Examples
+    ///
+    /// ~~~no_run
+    /// use amethyst::{prelude::*, window::WindowBundle};
+    ///
+    /// struct ServerState;
+    /// impl SimpleState for ServerState {}
+    ///
+    /// # fn main() -> amethyst::Result<()> {
+    /// let headless = std::env::args().any(|arg| arg == "--headless");
+    /// let mut game_data = DispatcherBuilder::default();
+    /// if !headless {
+    ///     game_data.add_bundle(WindowBundle::from_config_path("config/display.ron")?);
+    /// }
+    /// let game = Application::build("assets/", ServerState)?
+    ///     .headless(headless)
+    ///     .build(game_data)?;
+    /// game.run();
+    /// # Ok(())
+    /// # }
+    /// ~~~
+    pub fn headless(mut self, headless: bool) -> Self {
+        self.headless = headless;
+        self
+    }
+
+    /// Configures the asset daemon started by `build`.
+    ///
+    /// By default the daemon watches the assets directory passed to the builder, listens on
+    /// `127.0.0.1:9999` and keeps its database in `.assets_db`. The daemon is shut down when the
+    /// application is dropped.
+    ///
+    /// # Parameters
+    ///
+    /// `config`: The configuration of the asset daemon.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    ///
+    /// # Examples
+    ///
+    /// ~~~no_run
+    /// use amethyst::{assets::AssetDaemonConfig, prelude::*};
+    ///
+    /// struct NullState;
+    /// impl EmptyState for NullState {}
+    ///
+    /// # fn main() -> amethyst::Result<()> {
+    /// let game = Application::build("assets/", NullState)?
+    ///     .with_asset_daemon(
+    ///         AssetDaemonConfig::default()
+    ///             .with_asset_dir("assets/")
+    ///             .with_address("127.0.0.1:9998".parse().unwrap())
+    ///             .with_db_dir(std::env::temp_dir().join("my_game_assets_db")),
+    ///     )
+    ///     .build(())?;
+    /// # Ok(())
+    /// # }
+    /// ~~~
+    pub fn with_asset_daemon(mut self, config: AssetDaemonConfig) -> Self {
+        self.asset_daemon = Some(config);
+        self
+    }
+
+    /// Loads assets from a pack file written by `AssetPacker` instead of the asset daemon, as
+    /// shipped games do. No daemon is started and no socket is opened.
+    ///
+    /// # Parameters
+    ///
+    /// `path`: Path of the pack file.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it, or an error if the pack
+    /// file could not be opened.
+    ///
+    /// # Examples
+    ///
+    /// ~~~no_run
+    /// use amethyst::prelude::*;
+    ///
+    /// struct NullState;
+    /// impl EmptyState for NullState {}
+    ///
+    /// # fn main() -> amethyst::Result<()> {
+    /// let game = Application::build("assets/", NullState)?
+    ///     .with_asset_pack("assets.pack")?
+    ///     .build(())?;
+    /// # Ok(())
+    /// # }
+    /// ~~~
+    pub fn with_asset_pack<P: AsRef<Path>>(mut self, path: P) -> Result<Self, Error> {
+        self.asset_daemon = None;
+        self.resources.insert(PackfileIO::open(path)?);
+        Ok(self)
+    }
+
     /// Tells the resulting application window to ignore close events if ignore is true.
     /// This will make your game window unresponsive to operating system close commands.
     /// Use with caution.
@@ -789,6 +1021,24 @@ where
         self
     }
 
+    /// Writes a local crash report when the application panics.
+    ///
+    /// See [`CrashReporter`](struct.CrashReporter.html) for what the report contains and where
+    /// it is written.
+    ///
+    /// # Parameters
+    ///
+    /// `crash_reporter`: The crash reporter to install when the application starts running.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    pub fn with_crash_reporter(mut self, crash_reporter: CrashReporter) -> Self {
+        crash_report::record_log_lines();
+        self.crash_reporter = Some(crash_reporter);
+        self
+    }
+
     /// Build an `Application` object using the `ApplicationBuilder` as configured.
     ///
     /// # Returns
@@ -823,8 +1073,27 @@ where
         #[cfg(feature = "profiler")]
         profile_scope!("new");
 
+        let shutdown_requested = if self.headless {
+            self.resources.get_or_insert_with(|| {
+                ScreenDimensions::new(HEADLESS_DIMENSIONS.0, HEADLESS_DIMENSIONS.1)
+            });
+            Some(register_shutdown_signals())
+        } else {
+            None
+        };
+
+        if let Some(asset_daemon) = self.asset_daemon.take() {
+            self.resources.insert(asset_daemon.start());
+        }
+
         let data = init.build(&mut self.world, &mut self.resources)?;
 
+        if self.headless && self.resources.contains::<Window>() {
+            return Err(Error::from_string(
+                "A window was created by a headless application, remove the `WindowBundle` and `RenderingBundle` from the game data",
+            ));
+        }
+
         let event_reader_id = self
             .resources
             .get_mut::<EventChannel<Event<'static, ()>>>()
@@ -845,9 +1114,25 @@ where
             reader,
             events: Vec::new(),
             ignore_window_close: self.ignore_window_close,
+            shutdown_requested,
+            crash_reporter: self.crash_reporter,
+            max_frames: self.max_frames,
             data,
             event_reader_id,
             trans_reader_id,
         })
     }
 }
+
+/// Installs a handler for `SIGINT` and `SIGTERM`, returning the flag it sets.
+fn register_shutdown_signals() -> Arc<AtomicBool> {
+    let shutdown_requested = Arc::new(AtomicBool::new(false));
+    let flag = shutdown_requested.clone();
+    if let Err(e) = ctrlc::set_handler(move || {
+        info!("Received termination signal, shutting down");
+        flag.store(true, Ordering::Relaxed);
+    }) {
+        warn!("Failed to install termination signal handler: {}", e);
+    }
+    shutdown_requested
+}
@@ -0,0 +1,442 @@
+//! Standard engine command-line arguments.
+
+use std::{collections::HashMap, env, fmt::Write as _, path::PathBuf, str::FromStr};
+
+use crate::{
+    config::{Config, ConfigError},
+    error::{format_err, Error},
+    logger::{LevelFilter, LoggerConfig},
+    window::DisplayConfig,
+};
+
+/// A game-specific argument, registered with [`EngineArgsParser`].
+///
+/// [`EngineArgsParser`]: struct.EngineArgsParser.html
+#[derive(Clone, Debug)]
+struct CustomArg {
+    name: String,
+    takes_value: bool,
+    help: String,
+}
+
+/// Parses [`EngineArgs`], optionally extended with game-specific flags and options.
+///
+/// # Examples
+///
+/// ```
+/// use amethyst::EngineArgs;
+///
+/// # fn main() -> amethyst::Result<()> {
+/// let args = EngineArgs::parser()
+///     .flag("--god-mode", "Makes the player invincible")
+///     .option("--level", "Level to start in")
+///     .parse(vec!["--fps", "30", "--level", "forest", "--god-mode"])?;
+///
+/// assert_eq!(Some(30), args.fps());
+/// assert!(args.has_flag("--god-mode"));
+/// assert_eq!(Some("forest"), args.value("--level"));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`EngineArgs`]: struct.EngineArgs.html
+#[derive(Clone, Debug, Default)]
+pub struct EngineArgsParser {
+    custom: Vec<CustomArg>,
+}
+
+impl EngineArgsParser {
+    /// Registers a flag that does not take a value, such as `--god-mode`.
+    pub fn flag<N: Into<String>, H: Into<String>>(mut self, name: N, help: H) -> Self {
+        self.custom.push(CustomArg {
+            name: name.into(),
+            takes_value: false,
+            help: help.into(),
+        });
+        self
+    }
+
+    /// Registers an option that takes a value, such as `--level forest` or `--level=forest`.
+    pub fn option<N: Into<String>, H: Into<String>>(mut self, name: N, help: H) -> Self {
+        self.custom.push(CustomArg {
+            name: name.into(),
+            takes_value: true,
+            help: help.into(),
+        });
+        self
+    }
+
+    /// Parses the arguments the process was started with, skipping the executable name.
+    ///
+    /// If `--help` was given, the usage is printed and the process exits.
+    pub fn parse_env(self) -> Result<EngineArgs, Error> {
+        let args = self.parse(env::args().skip(1))?;
+        if args.help_requested() {
+            print!("{}", args.usage());
+            std::process::exit(0);
+        }
+        Ok(args)
+    }
+
+    /// Parses the given arguments, which must not include the executable name.
+    pub fn parse<I, S>(self, args: I) -> Result<EngineArgs, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut parsed = EngineArgs::default();
+        let mut args = args.into_iter().map(Into::into);
+
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                parsed.free.push(arg);
+                continue;
+            }
+
+            let (name, inline_value) = match arg.find('=') {
+                Some(index) => (arg[..index].to_string(), Some(arg[index + 1..].to_string())),
+                None => (arg, None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format_err!("Missing value for argument `{}`", name))
+            };
+            let no_value = || match &inline_value {
+                Some(_) => Err(format_err!("Argument `{}` takes no value", name)),
+                None => Ok(true),
+            };
+
+            match name.as_str() {
+                "--help" => parsed.help = no_value()?,
+                "--headless" => parsed.headless = no_value()?,
+                "--assets" => parsed.assets = Some(PathBuf::from(value()?)),
+                "--config" => parsed.config = Some(PathBuf::from(value()?)),
+                "--log-level" => parsed.log_level = Some(parse_value(&name, &value()?)?),
+                "--fps" => parsed.fps = Some(parse_value(&name, &value()?)?),
+                "--fixed-rate" => parsed.fixed_rate = Some(parse_value(&name, &value()?)?),
+                "--frames" => parsed.frames = Some(parse_value(&name, &value()?)?),
+                "--window-size" => parsed.window_size = Some(parse_window_size(&value()?)?),
+                _ => {
+                    let custom = self
+                        .custom
+                        .iter()
+                        .find(|custom| custom.name == name)
+                        .ok_or_else(|| {
+                            format_err!("Unknown argument `{}`\n\n{}", name, self.usage())
+                        })?;
+                    let custom_value = if custom.takes_value {
+                        Some(value()?)
+                    } else {
+                        no_value()?;
+                        None
+                    };
+                    parsed.custom.insert(name.clone(), custom_value);
+                }
+            }
+        }
+
+        parsed.usage = self.usage();
+        Ok(parsed)
+    }
+
+    /// Returns a description of all supported arguments.
+    pub fn usage(&self) -> String {
+        let mut usage = String::from("Options:\n");
+        let engine_args = [
+            ("--assets <DIR>", "Directory to load assets from"),
+            ("--config <PATH>", "Path of the configuration file"),
+            (
+                "--log-level <LEVEL>",
+                "off, error, warn, info, debug or trace",
+            ),
+            ("--fps <FPS>", "Maximum frames per second, 0 for unlimited"),
+            ("--fixed-rate <HZ>", "Number of fixed updates per second"),
+            ("--frames <N>", "Quit after running N frames"),
+            (
+                "--window-size <WxH>",
+                "Initial window size, such as 1280x720",
+            ),
+            ("--headless", "Run without a window or renderer"),
+            ("--help", "Print this message"),
+        ];
+        let custom_args = self.custom.iter().map(|custom| {
+            let name = if custom.takes_value {
+                format!("{} <VALUE>", custom.name)
+            } else {
+                custom.name.clone()
+            };
+            (name, custom.help.as_str())
+        });
+        for (name, help) in engine_args
+            .iter()
+            .map(|(name, help)| (name.to_string(), *help))
+            .chain(custom_args)
+        {
+            // Writing to a `String` can not fail.
+            let _ = writeln!(usage, "    {:<24}{}", name, help);
+        }
+        usage
+    }
+}
+
+fn parse_value<T: FromStr>(name: &str, value: &str) -> Result<T, Error> {
+    value
+        .parse()
+        .map_err(|_| format_err!("Invalid value `{}` for argument `{}`", value, name))
+}
+
+fn parse_window_size(value: &str) -> Result<(u32, u32), Error> {
+    let mut parts = value.splitn(2, |c| c == 'x' || c == 'X');
+    match (
+        parts.next().and_then(|w| w.parse().ok()),
+        parts.next().and_then(|h| h.parse().ok()),
+    ) {
+        (Some(width), Some(height)) => Ok((width, height)),
+        _ => Err(format_err!(
+            "Invalid window size `{}`, expected the form `1280x720`",
+            value
+        )),
+    }
+}
+
+/// The standard set of command-line arguments understood by Amethyst applications.
+///
+/// * `--assets <DIR>`: directory to load assets from.
+/// * `--config <PATH>`: path of the game's configuration file, see `load_config`.
+/// * `--log-level <LEVEL>`: overrides `LoggerConfig::level_filter`.
+/// * `--fps <FPS>`: overrides the `FrameLimiter` rate, 0 disables frame limiting.
+/// * `--fixed-rate <HZ>`: overrides the fixed update rate of `Time`.
+/// * `--frames <N>`: stops the application after `N` frames.
+/// * `--window-size <WxH>`: overrides `DisplayConfig::dimensions`.
+/// * `--headless`: requests running without a window or renderer.
+/// * `--help`: prints the supported arguments and exits, when parsed with `from_env`.
+///
+/// Arguments that should be applied before the application is built (assets, logging, display)
+/// are exposed as helpers, while the remaining ones are applied by
+/// [`ApplicationBuilder::with_engine_args`]. The arguments are also inserted as a resource, so
+/// systems and states can read the game-specific ones.
+///
+/// # Examples
+///
+/// ```no_run
+/// use amethyst::{prelude::*, EngineArgs, LoggerConfig};
+///
+/// struct NullState;
+/// impl EmptyState for NullState {}
+///
+/// fn main() -> amethyst::Result<()> {
+///     let args = EngineArgs::from_env()?;
+///     amethyst::start_logger(args.logger_config(LoggerConfig::default()));
+///
+///     let game = Application::build(args.assets_dir_or("assets/"), NullState)?
+///         .with_engine_args(args)
+///         .build(())?;
+///     game.run();
+///     Ok(())
+/// }
+/// ```
+///
+/// [`ApplicationBuilder::with_engine_args`]: struct.ApplicationBuilder.html#method.with_engine_args
+#[derive(Clone, Debug, Default)]
+pub struct EngineArgs {
+    assets: Option<PathBuf>,
+    config: Option<PathBuf>,
+    log_level: Option<LevelFilter>,
+    fps: Option<u32>,
+    fixed_rate: Option<u32>,
+    frames: Option<u64>,
+    window_size: Option<(u32, u32)>,
+    headless: bool,
+    help: bool,
+    custom: HashMap<String, Option<String>>,
+    free: Vec<String>,
+    usage: String,
+}
+
+impl EngineArgs {
+    /// Returns a parser which can be extended with game-specific arguments.
+    pub fn parser() -> EngineArgsParser {
+        EngineArgsParser::default()
+    }
+
+    /// Parses the standard arguments the process was started with.
+    ///
+    /// If `--help` was given, the usage is printed and the process exits.
+    pub fn from_env() -> Result<Self, Error> {
+        Self::parser().parse_env()
+    }
+
+    /// Returns the `--assets` directory if given, otherwise `default`.
+    pub fn assets_dir_or<P: Into<PathBuf>>(&self, default: P) -> PathBuf {
+        self.assets.clone().unwrap_or_else(|| default.into())
+    }
+
+    /// Returns the `--config` path, if given.
+    pub fn config(&self) -> Option<&PathBuf> {
+        self.config.as_ref()
+    }
+
+    /// Loads the game's configuration from the `--config` path if given, otherwise from
+    /// `default`.
+    pub fn load_config<T: Config, P: Into<PathBuf>>(&self, default: P) -> Result<T, ConfigError> {
+        T::load(self.config.clone().unwrap_or_else(|| default.into()))
+    }
+
+    /// Returns the `--log-level`, if given.
+    pub fn log_level(&self) -> Option<LevelFilter> {
+        self.log_level
+    }
+
+    /// Returns the `--fps` limit, if given.
+    pub fn fps(&self) -> Option<u32> {
+        self.fps
+    }
+
+    /// Returns the `--fixed-rate`, if given.
+    pub fn fixed_rate(&self) -> Option<u32> {
+        self.fixed_rate
+    }
+
+    /// Returns the number of `--frames` to run, if given.
+    pub fn frames(&self) -> Option<u64> {
+        self.frames
+    }
+
+    /// Returns the `--window-size`, if given.
+    pub fn window_size(&self) -> Option<(u32, u32)> {
+        self.window_size
+    }
+
+    /// Returns `true` if `--headless` was given.
+    pub fn headless(&self) -> bool {
+        self.headless
+    }
+
+    /// Returns `true` if `--help` was given. The message can be obtained from `usage`.
+    pub fn help_requested(&self) -> bool {
+        self.help
+    }
+
+    /// Returns a description of all supported arguments.
+    pub fn usage(&self) -> &str {
+        &self.usage
+    }
+
+    /// Returns `true` if the given game-specific flag or option was given.
+    pub fn has_flag(&self, name: &str) -> bool {
+        self.custom.contains_key(name)
+    }
+
+    /// Returns the value of the given game-specific option, if given.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.custom.get(name).and_then(|v| v.as_deref())
+    }
+
+    /// Returns the arguments that did not start with `--`.
+    pub fn free(&self) -> &[String] {
+        &self.free
+    }
+
+    /// Applies `--log-level` to the given logger configuration.
+    pub fn logger_config(&self, mut config: LoggerConfig) -> LoggerConfig {
+        if let Some(level) = self.log_level {
+            config.level_filter = level;
+        }
+        config
+    }
+
+    /// Applies `--window-size` to the given display configuration.
+    pub fn display_config(&self, mut config: DisplayConfig) -> DisplayConfig {
+        if let Some(dimensions) = self.window_size {
+            config.dimensions = Some(dimensions);
+        }
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_engine_args() {
+        let args = EngineArgs::parser()
+            .parse(vec![
+                "--assets",
+                "data",
+                "--log-level=debug",
+                "--fps",
+                "0",
+                "--fixed-rate",
+                "30",
+                "--frames",
+                "100",
+                "--window-size",
+                "800x600",
+                "--headless",
+                "save.ron",
+            ])
+            .expect("Failed to parse arguments");
+
+        assert_eq!(PathBuf::from("data"), args.assets_dir_or("assets"));
+        assert_eq!(Some(LevelFilter::Debug), args.log_level());
+        assert_eq!(Some(0), args.fps());
+        assert_eq!(Some(30), args.fixed_rate());
+        assert_eq!(Some(100), args.frames());
+        assert_eq!(Some((800, 600)), args.window_size());
+        assert!(args.headless());
+        assert_eq!(&["save.ron".to_string()], args.free());
+    }
+
+    #[test]
+    fn rejects_unknown_and_invalid_args() {
+        assert!(EngineArgs::parser().parse(vec!["--unknown"]).is_err());
+        assert!(EngineArgs::parser().parse(vec!["--fps", "fast"]).is_err());
+        assert!(EngineArgs::parser().parse(vec!["--frames"]).is_err());
+        assert!(EngineArgs::parser()
+            .parse(vec!["--window-size", "800"])
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_values_of_flags() {
+        assert!(EngineArgs::parser().parse(vec!["--headless=no"]).is_err());
+        assert!(EngineArgs::parser().parse(vec!["--help=yes"]).is_err());
+        let parser = || EngineArgs::parser().flag("--god-mode", "Makes the player invincible");
+        assert!(parser().parse(vec!["--god-mode=false"]).is_err());
+        assert!(parser().parse(vec!["--god-mode"]).is_ok());
+    }
+
+    #[test]
+    fn loads_config_from_the_config_path() {
+        let path = env::temp_dir().join(format!("amethyst_args_{}.ron", std::process::id()));
+        std::fs::write(&path, "(level: 3)").expect("Failed to write config");
+        let args = EngineArgs::parser()
+            .parse(vec!["--config".into(), path.to_string_lossy().into_owned()])
+            .expect("Failed to parse arguments");
+
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct GameConfig {
+            level: u32,
+        }
+        let config: GameConfig = args
+            .load_config("missing.ron")
+            .expect("Failed to load config");
+        assert_eq!(3, config.level);
+        assert!(EngineArgs::default()
+            .load_config::<GameConfig, _>("missing.ron")
+            .is_err());
+        std::fs::remove_file(&path).expect("Failed to remove config");
+    }
+
+    #[test]
+    fn applies_log_level_to_logger_config() {
+        let args = EngineArgs::parser()
+            .parse(vec!["--log-level", "warn"])
+            .expect("Failed to parse arguments");
+        let config = args.logger_config(LoggerConfig::default());
+        assert_eq!(LevelFilter::Warn, config.level_filter);
+    }
+}
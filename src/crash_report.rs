@@ -0,0 +1,412 @@
+//! Local crash reporting.
+//!
+//! Unlike the `sentry` integration, this does not need any remote service: when the application
+//! panics, a plain text report is written to the per-user data directory. The report contains the
+//! panic message, a backtrace, the state stack, the current frame number, the last lines that were
+//! logged and the engine version and features.
+
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    fs,
+    io::{self, Write},
+    panic::{self, PanicInfo},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex, MutexGuard, TryLockError,
+    },
+    thread::{self, ThreadId},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use lazy_static::lazy_static;
+use log::{error, info, warn};
+
+use crate::error::Backtrace;
+
+const LAST_CRASH_FILE: &str = "last_crash";
+
+lazy_static! {
+    static ref LOG_LINES: Mutex<LogLines> = Mutex::new(LogLines::default());
+    static ref STATE_STACK: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+}
+
+static FRAME_NUMBER: AtomicU64 = AtomicU64::new(0);
+/// Whether log lines are kept, which is only the case once a crash reporter is used.
+static RECORD_LOG_LINES: AtomicBool = AtomicBool::new(false);
+
+#[derive(Default)]
+struct LogLines {
+    capacity: usize,
+    lines: VecDeque<String>,
+}
+
+/// Writes a crash report to disk when the application panics.
+///
+/// Pass it to [`ApplicationBuilder::with_crash_reporter`] to install it. The panic hook is
+/// registered when the application starts running, and chains to the previously installed hook,
+/// so panics are still printed to the terminal.
+///
+/// Only panics of the thread running the application are reported. Panics of `Tasks` and of
+/// asset processing on the thread pool are caught and fail the task or the load, so they do not
+/// count as crashes, and panics of other threads are not reported.
+///
+/// The last log lines are only available when the logger was started with a non-zero
+/// [`Logger::crash_log_lines`], the default, and are recorded from the moment the crash reporter
+/// is passed to the application builder.
+///
+/// # Examples
+///
+/// ```no_run
+/// use amethyst::{prelude::*, CrashReporter};
+///
+/// struct NullState;
+/// impl EmptyState for NullState {}
+///
+/// # fn main() -> amethyst::Result<()> {
+/// let game = Application::build("assets/", NullState)?
+///     .with_crash_reporter(CrashReporter::new("my_game").on_previous_crash(|report| {
+///         eprintln!("The game crashed last time, see {:?}", report);
+///     }))
+///     .build(())?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`ApplicationBuilder::with_crash_reporter`]: struct.ApplicationBuilder.html#method.with_crash_reporter
+/// [`Logger::crash_log_lines`]: struct.Logger.html#method.crash_log_lines
+#[allow(missing_debug_implementations)]
+pub struct CrashReporter {
+    directory: Option<PathBuf>,
+    on_previous_crash: Option<Box<dyn FnOnce(&Path) + Send>>,
+}
+
+impl CrashReporter {
+    /// Creates a crash reporter that writes reports to `<data dir>/<app_name>/crash_reports`,
+    /// where `<data dir>` is the per-user local data directory of the platform.
+    pub fn new<N: AsRef<Path>>(app_name: N) -> Self {
+        let directory =
+            dirs::data_local_dir().map(|dir| dir.join(app_name.as_ref()).join("crash_reports"));
+        CrashReporter {
+            directory,
+            on_previous_crash: None,
+        }
+    }
+
+    /// Writes the crash reports to the given directory instead.
+    pub fn with_directory<P: Into<PathBuf>>(mut self, directory: P) -> Self {
+        self.directory = Some(directory.into());
+        self
+    }
+
+    /// Registers a callback that is invoked once on the next launch after a crash, with the path
+    /// of the crash report. This can be used to show a dialog or offer to send the report.
+    pub fn on_previous_crash<F>(mut self, callback: F) -> Self
+    where
+        F: FnOnce(&Path) + Send + 'static,
+    {
+        self.on_previous_crash = Some(Box::new(callback));
+        self
+    }
+
+    /// Returns the directory crash reports are written to, if any could be determined.
+    pub fn directory(&self) -> Option<&Path> {
+        self.directory.as_deref()
+    }
+
+    /// Reports a previous crash, if there was one, and installs the panic hook.
+    pub(crate) fn install(self) {
+        let directory = match self.directory {
+            Some(directory) => directory,
+            None => {
+                warn!("Could not determine a crash report directory, crash reporting is disabled");
+                return;
+            }
+        };
+
+        let marker = directory.join(LAST_CRASH_FILE);
+        if let Ok(report) = fs::read_to_string(&marker) {
+            let _ = fs::remove_file(&marker);
+            if let Some(callback) = self.on_previous_crash {
+                callback(Path::new(report.trim()));
+            }
+        }
+
+        info!("Crash reports will be written to {:?}", directory);
+        let main_thread = thread::current().id();
+        let previous_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |panic_info| {
+            if is_reported(main_thread) {
+                match write_report(&directory, &panic_message(panic_info)) {
+                    Ok(path) => error!("Crash report written to {:?}", path),
+                    Err(e) => error!("Failed to write crash report: {}", e),
+                }
+            }
+            previous_hook(panic_info);
+        }));
+    }
+}
+
+/// Starts keeping the log lines for crash reports.
+pub(crate) fn record_log_lines() {
+    RECORD_LOG_LINES.store(true, Ordering::Relaxed);
+}
+
+/// Sets how many of the most recent log lines are kept for crash reports.
+pub(crate) fn set_log_capacity(capacity: usize) {
+    let mut log = lock(&LOG_LINES);
+    log.capacity = capacity;
+    while log.lines.len() > capacity {
+        log.lines.pop_front();
+    }
+}
+
+/// Records a formatted log line, dropping the oldest one when the buffer is full.
+pub(crate) fn push_log_line(record: &log::Record<'_>) {
+    if !RECORD_LOG_LINES.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut log = lock(&LOG_LINES);
+    if log.capacity == 0 {
+        return;
+    }
+    if log.lines.len() == log.capacity {
+        log.lines.pop_front();
+    }
+    log.lines.push_back(format!(
+        "[{}][{}] {}",
+        record.level(),
+        record.target(),
+        record.args()
+    ));
+}
+
+/// Updates the frame number included in crash reports.
+pub(crate) fn set_frame_number(frame_number: u64) {
+    FRAME_NUMBER.store(frame_number, Ordering::Relaxed);
+}
+
+/// Updates the state stack included in crash reports, from bottom to top.
+pub(crate) fn set_state_stack<I>(states: I)
+where
+    I: Iterator<Item = &'static str> + Clone,
+{
+    let mut stack = lock(&STATE_STACK);
+    if !stack.iter().copied().eq(states.clone()) {
+        stack.clear();
+        stack.extend(states);
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Like `lock`, but does not block in case the panicking thread already holds the lock.
+fn try_lock<T>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+    match mutex.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+/// Whether a panic of the current thread is a crash, which is the case for the thread that runs
+/// the application.
+fn is_reported(main_thread: ThreadId) -> bool {
+    thread::current().id() == main_thread
+}
+
+fn write_report(directory: &Path, panic: &str) -> io::Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    fs::create_dir_all(directory)?;
+    let (mut file, path) = create_report_file(directory, timestamp)?;
+    file.write_all(format_report(panic, timestamp).as_bytes())?;
+    fs::write(
+        directory.join(LAST_CRASH_FILE),
+        path.to_string_lossy().as_bytes(),
+    )?;
+
+    Ok(path)
+}
+
+/// Creates a new report file, numbering the reports written within the same second.
+fn create_report_file(directory: &Path, timestamp: u64) -> io::Result<(fs::File, PathBuf)> {
+    let mut number = 0;
+    loop {
+        let name = if number == 0 {
+            format!("crash-{}.txt", timestamp)
+        } else {
+            format!("crash-{}-{}.txt", timestamp, number)
+        };
+        let path = directory.join(name);
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(file) => return Ok((file, path)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => number += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Formats the message and location of a panic.
+fn panic_message(panic_info: &PanicInfo<'_>) -> String {
+    let message = if let Some(s) = panic_info.payload().downcast_ref::<&str>() {
+        *s
+    } else if let Some(s) = panic_info.payload().downcast_ref::<String>() {
+        s.as_str()
+    } else {
+        "Box<Any>"
+    };
+    match panic_info.location() {
+        Some(location) => format!("{} at {}:{}", message, location.file(), location.line()),
+        None => message.to_string(),
+    }
+}
+
+fn format_report(panic: &str, timestamp: u64) -> String {
+    let mut report = String::new();
+    // Writing to a `String` can not fail.
+    let _ = writeln!(report, "Amethyst crash report");
+    let _ = writeln!(report, "Time: {} seconds since UNIX epoch", timestamp);
+    let _ = writeln!(report, "Version: {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(report, "Amethyst git commit: {}", env!("VERGEN_SHA"));
+    let _ = writeln!(report, "Platform: {}", env!("VERGEN_TARGET_TRIPLE"));
+    let _ = writeln!(report, "Features: {}", enabled_features().join(", "));
+    let _ = writeln!(report, "Frame: {}", FRAME_NUMBER.load(Ordering::Relaxed));
+    let _ = writeln!(
+        report,
+        "Thread: {}",
+        thread::current().name().unwrap_or("<unnamed>")
+    );
+    let _ = writeln!(report, "Panic: {}", panic);
+
+    let _ = writeln!(report, "\nState stack (bottom to top):");
+    match try_lock(&STATE_STACK) {
+        Some(stack) => {
+            for state in stack.iter() {
+                let _ = writeln!(report, "    {}", state);
+            }
+        }
+        None => {
+            let _ = writeln!(report, "    <unavailable>");
+        }
+    }
+
+    let _ = writeln!(report, "\nBacktrace:\n{:?}", Backtrace::new());
+
+    let _ = writeln!(report, "Last log lines:");
+    match try_lock(&LOG_LINES) {
+        Some(log) => {
+            for line in log.lines.iter() {
+                let _ = writeln!(report, "    {}", line);
+            }
+        }
+        None => {
+            let _ = writeln!(report, "    <unavailable>");
+        }
+    }
+
+    report
+}
+
+fn enabled_features() -> Vec<&'static str> {
+    let features = [
+        ("animation", cfg!(feature = "animation")),
+        ("audio", cfg!(feature = "audio")),
+        ("locale", cfg!(feature = "locale")),
+        ("network", cfg!(feature = "network")),
+        ("renderer", cfg!(feature = "renderer")),
+        ("tiles", cfg!(feature = "tiles")),
+        ("ui", cfg!(feature = "ui")),
+        ("utils", cfg!(feature = "utils")),
+        ("json", cfg!(feature = "json")),
+        ("parallel", cfg!(feature = "parallel")),
+        ("profiler", cfg!(feature = "profiler")),
+        ("sentry", cfg!(feature = "sentry")),
+        ("empty", cfg!(feature = "empty")),
+        ("vulkan", cfg!(feature = "vulkan")),
+        ("metal", cfg!(feature = "metal")),
+    ];
+    features
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!(
+            "amethyst_crash_report_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    #[test]
+    fn reports_in_the_same_second_get_distinct_files() {
+        let directory = temp_dir("distinct");
+        let first = write_report(&directory, "first").unwrap();
+        let second = write_report(&directory, "second").unwrap();
+        assert_ne!(first, second);
+        assert!(fs::read_to_string(&first).unwrap().contains("Panic: first"));
+        assert!(fs::read_to_string(&second)
+            .unwrap()
+            .contains("Panic: second"));
+        assert_eq!(
+            fs::read_to_string(directory.join(LAST_CRASH_FILE)).unwrap(),
+            second.to_string_lossy()
+        );
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn only_the_application_thread_is_reported() {
+        let main_thread = thread::current().id();
+        assert!(is_reported(main_thread));
+        assert!(!thread::spawn(move || is_reported(main_thread))
+            .join()
+            .unwrap());
+    }
+
+    #[test]
+    fn log_lines_keep_the_level_and_target_of_the_most_recent_records() {
+        let push = |message: &str| {
+            push_log_line(
+                &log::Record::builder()
+                    .args(format_args!("{}", message))
+                    .level(log::Level::Warn)
+                    .target("game::level")
+                    .build(),
+            )
+        };
+
+        set_log_capacity(2);
+        push("ignored before a crash reporter is used");
+        assert!(lock(&LOG_LINES).lines.is_empty());
+
+        record_log_lines();
+        push("first");
+        push("second");
+        push("third");
+        assert_eq!(
+            lock(&LOG_LINES).lines.iter().collect::<Vec<_>>(),
+            vec!["[WARN][game::level] second", "[WARN][game::level] third"]
+        );
+        assert!(format_report("test", 0).contains("    [WARN][game::level] third"));
+    }
+}
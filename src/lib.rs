@@ -96,8 +96,11 @@ pub use winit;
 
 pub use self::{
     app::{Application, ApplicationBuilder, CoreApplication},
+    args::{EngineArgs, EngineArgsParser},
+    crash_report::CrashReporter,
     error::Error,
     game_data::{DataDispose, DataInit, GameData},
+    loading_state::LoadingState,
     logger::{start_logger, LevelFilter as LogLevelFilter, Logger, LoggerConfig, StdoutLog},
     state::{
         EmptyState, EmptyTrans, SimpleState, SimpleTrans, State, StateData, StateMachine, Trans,
@@ -115,7 +118,10 @@ pub type Result<T> = std::result::Result<T, error::Error>;
 pub mod prelude;
 
 mod app;
+mod args;
+mod crash_report;
 mod game_data;
+mod loading_state;
 mod logger;
 mod state;
 mod state_event;
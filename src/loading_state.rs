@@ -0,0 +1,129 @@
+//! A ready-made loading screen state.
+
+use log::{error, warn};
+
+use crate::{
+    assets::{Completion, DefaultLoader, LoadingTracker},
+    GameData, SimpleState, SimpleTrans, State, StateData, StateEvent, Trans,
+};
+
+type LoadFn = Box<dyn FnOnce(&mut StateData<'_, GameData>, &mut LoadingTracker)>;
+
+/// State that loads a set of assets, and switches to the next state once all of them have loaded.
+///
+/// The assets are loaded when the state starts, by the function passed to `with_loads`, which
+/// adds the handles to wait for to the [`LoadingTracker`]. If any asset fails to load, the errors
+/// are logged and the state stays active; use [`tracker`](#method.tracker) to display the
+/// progress and errors.
+///
+/// The tracker is dropped with the state, so the handles of assets that should stay loaded must
+/// also be stored elsewhere, such as in a resource.
+///
+/// # Examples
+///
+/// ```no_run
+/// use amethyst::{
+///     assets::{DefaultLoader, Handle, Loader},
+///     prelude::*,
+///     renderer::Texture,
+///     LoadingState,
+/// };
+///
+/// struct Textures {
+///     logo: Handle<Texture>,
+/// }
+///
+/// struct MainMenu;
+/// impl SimpleState for MainMenu {}
+///
+/// let loading = LoadingState::new(MainMenu).with_loads(|data, tracker| {
+///     let logo = {
+///         let loader = data.resources.get::<DefaultLoader>().unwrap();
+///         loader.load("texture/logo.png")
+///     };
+///     tracker.track_named(&logo, "texture/logo.png");
+///     data.resources.insert(Textures { logo });
+/// });
+/// ```
+///
+/// [`LoadingTracker`]: assets/struct.LoadingTracker.html
+pub struct LoadingState {
+    tracker: LoadingTracker,
+    load: Option<LoadFn>,
+    next: Option<Box<dyn State<GameData, StateEvent>>>,
+    errors_logged: usize,
+}
+
+impl std::fmt::Debug for LoadingState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoadingState")
+            .field("tracker", &self.tracker)
+            .finish()
+    }
+}
+
+impl LoadingState {
+    /// Creates a loading state switching to `next` once all assets have loaded.
+    pub fn new<S>(next: S) -> Self
+    where
+        S: State<GameData, StateEvent> + 'static,
+    {
+        LoadingState {
+            tracker: LoadingTracker::new(),
+            load: None,
+            next: Some(Box::new(next)),
+            errors_logged: 0,
+        }
+    }
+
+    /// Sets the function that loads the assets when the state starts, and adds their handles to
+    /// the tracker.
+    pub fn with_loads<F>(mut self, load: F) -> Self
+    where
+        F: FnOnce(&mut StateData<'_, GameData>, &mut LoadingTracker) + 'static,
+    {
+        self.load = Some(Box::new(load));
+        self
+    }
+
+    /// Returns the tracker of the assets that are loaded.
+    pub fn tracker(&self) -> &LoadingTracker {
+        &self.tracker
+    }
+}
+
+impl SimpleState for LoadingState {
+    fn on_start(&mut self, mut data: StateData<'_, GameData>) {
+        if let Some(load) = self.load.take() {
+            load(&mut data, &mut self.tracker);
+        }
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData>) -> SimpleTrans {
+        match data.resources.get::<DefaultLoader>() {
+            Some(loader) => self.tracker.update(&*loader),
+            None => {
+                warn!("`LoadingState` requires the `LoaderBundle`");
+                return Trans::None;
+            }
+        }
+
+        match self.tracker.complete() {
+            Completion::Complete => match self.next.take() {
+                Some(next) => Trans::Switch(next),
+                None => Trans::None,
+            },
+            Completion::Failed => {
+                for e in &self.tracker.errors()[self.errors_logged..] {
+                    error!(
+                        "Failed to load {} ({}): {}",
+                        e.asset_name, e.asset_type_name, e.error
+                    );
+                }
+                self.errors_logged = self.tracker.errors().len();
+                Trans::None
+            }
+            Completion::Loading => Trans::None,
+        }
+    }
+}
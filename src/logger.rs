@@ -4,6 +4,8 @@ use log::debug;
 pub use log::LevelFilter;
 use serde::{Deserialize, Serialize};
 
+use crate::crash_report;
+
 /// An enum that contains options for logging to the terminal.
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum StdoutLog {
@@ -71,8 +73,12 @@ impl Default for LoggerConfig {
 #[allow(missing_debug_implementations)]
 pub struct Logger {
     dispatch: fern::Dispatch,
+    crash_log_lines: usize,
 }
 
+/// Number of log lines kept for crash reports by default.
+const DEFAULT_CRASH_LOG_LINES: usize = 100;
+
 impl Logger {
     fn new() -> Self {
         let dispatch = fern::Dispatch::new().format(|out, message, record| {
@@ -83,7 +89,10 @@ impl Logger {
                 message = message,
             ))
         });
-        Self { dispatch }
+        Self {
+            dispatch,
+            crash_log_lines: DEFAULT_CRASH_LOG_LINES,
+        }
     }
 
     /// Create a new Logger with a passed in formatter callback
@@ -95,7 +104,10 @@ impl Logger {
             + 'static,
     {
         let dispatch = fern::Dispatch::new().format(formatter);
-        Self { dispatch }
+        Self {
+            dispatch,
+            crash_log_lines: DEFAULT_CRASH_LOG_LINES,
+        }
     }
 
     /// Create a new logger from [`LoggerConfig`] and the Logger it will be added to
@@ -189,8 +201,21 @@ impl Logger {
         self
     }
 
+    /// Sets the number of most recent log lines kept in memory for crash reports, 0 disables it.
+    /// The lines are only recorded when the application uses a `CrashReporter`.
+    pub fn crash_log_lines(mut self, lines: usize) -> Self {
+        self.crash_log_lines = lines;
+        self
+    }
+
     /// Starts [`Logger`] by consuming it.
-    pub fn start(self) {
+    pub fn start(mut self) {
+        if self.crash_log_lines > 0 {
+            crash_report::set_log_capacity(self.crash_log_lines);
+            self.dispatch = self
+                .dispatch
+                .chain(fern::Output::call(crash_report::push_log_line));
+        }
         self.dispatch.apply().unwrap_or_else(|_| {
             debug!("Global logger already set, default Amethyst logger will not be used")
         });
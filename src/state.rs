@@ -149,6 +149,11 @@ pub trait State<T, E: Send + Sync + 'static> {
     /// even when this is not the active state,
     /// as long as this state is on the [StateMachine](struct.StateMachine.html)'s state-stack.
     fn shadow_update(&mut self, _data: StateData<'_, T>) {}
+
+    /// Returns the type name of this state, used to describe the state stack in crash reports.
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
 }
 
 /// An empty `State` trait. It contains no `StateData` or custom `StateEvent`.
@@ -382,6 +387,11 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
         self.running
     }
 
+    /// Returns the type names of the states on the stack, from bottom to top.
+    pub fn state_names(&self) -> impl Iterator<Item = &'static str> + Clone + '_ {
+        self.state_stack.iter().map(|state| state.type_name())
+    }
+
     /// Initializes the state machine.
     pub fn start(&mut self, data: StateData<'_, T>) -> Result<(), StateError> {
         if !self.running {